@@ -140,8 +140,9 @@ pub async fn run(
     transport: Option<&str>,
     reconnect_delay_secs: u64,
     capabilities: &[String],
+    name: Option<&str>,
 ) -> Result<()> {
-    let options = reverse_host_options(capabilities)?;
+    let options = reverse_host_options(capabilities)?.with_name(name)?;
     let fingerprint = load_fingerprint(identity)?;
     let state = AgentState::new(
         identity,
@@ -156,8 +157,8 @@ pub async fn run(
     let keystore = KeyStore::default_location()
         .map_err(|e| anyhow::anyhow!("{e}"))
         .context("failed to initialize keystore")?;
-    let (_signing_key, verifying_key) = keystore
-        .load(identity)
+    let verifying_key = keystore
+        .load_public(identity)
         .map_err(|e| anyhow::anyhow!("{e}"))
         .with_context(|| format!("failed to load key '{identity}'"))?;
     let public_key = auth::public_key_bytes(&verifying_key);
@@ -564,8 +565,8 @@ fn load_fingerprint(identity: &str) -> Result<String> {
     let keystore = KeyStore::default_location()
         .map_err(|e| anyhow::anyhow!("{e}"))
         .context("failed to initialize keystore")?;
-    let (_signing_key, verifying_key) = keystore
-        .load(identity)
+    let verifying_key = keystore
+        .load_public(identity)
         .map_err(|e| anyhow::anyhow!("{e}"))
         .with_context(|| format!("failed to load key '{identity}'"))?;
     let public_bytes = auth::public_key_bytes(&verifying_key);
@@ -170,8 +170,8 @@ fn load_identity_status(identity: &str) -> Result<String> {
     let keystore = wsh_client::KeyStore::default_location()
         .map_err(|e| anyhow::anyhow!("{e}"))
         .context("failed to initialize keystore")?;
-    let (_, verifying_key) = keystore
-        .load(identity)
+    let verifying_key = keystore
+        .load_public(identity)
         .map_err(|e| anyhow::anyhow!("{e}"))
         .with_context(|| format!("failed to load key '{identity}'"))?;
     let fingerprint = wsh_core::fingerprint(&wsh_client::auth::public_key_bytes(&verifying_key));
@@ -3,9 +3,13 @@
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
-use wsh_client::{ConnectConfig, WshClient};
+use std::sync::Arc;
+use std::time::Duration;
+use wsh_client::transport::profiles::DEFAULT_ORDER;
+use wsh_client::{ConnectConfig, TransportKind, TransportProfiles, WshClient};
 
-use crate::config::parse_target;
+use crate::commands::scp::format_size;
+use crate::config::{parse_target, Config};
 
 /// Resolved connection details for a target.
 #[derive(Debug, Clone)]
@@ -16,6 +20,20 @@ pub struct ResolvedTarget {
     pub url: String,
     pub fallback_urls: Vec<String>,
     pub transport: Option<String>,
+    /// Seconds to remember which transport connected when several are
+    /// tried (0 = never).
+    pub transport_ttl: u64,
+    /// `[user@]host[:port]` jump hosts to tunnel through, in order.
+    pub jumps: Vec<String>,
+    /// Identity from the target's `[[host]]` blocks, unless `-i` was given.
+    pub identity: Option<String>,
+}
+
+impl ResolvedTarget {
+    /// The key to authenticate with: the host's configured identity, else `fallback`.
+    pub fn identity_or<'a>(&'a self, fallback: &'a str) -> &'a str {
+        self.identity.as_deref().unwrap_or(fallback)
+    }
 }
 
 /// Persisted "last session" metadata used by session-oriented commands.
@@ -39,8 +57,46 @@ pub struct LastReversePeer {
 }
 
 /// Resolve `[user@]host` + transport into a concrete connection URL.
+///
+/// The target's `[[host]]` config blocks may supply the real host name, user,
+/// port, identity, transport and jump hosts; CLI flags (including `-J`) and
+/// an explicit `user@` take precedence, and `port`/`transport` are the
+/// fallbacks when neither sets a value.
 pub fn resolve_target(target: &str, port: u16, transport: Option<&str>) -> Result<ResolvedTarget> {
-    let (user, host) = parse_target(target)?;
+    let config = Config::active();
+    let (user, alias) = parse_target(target)?;
+    let settings = config.host_settings(&alias);
+
+    let user = match settings.user {
+        Some(block_user) if !target.contains('@') => block_user,
+        _ => user,
+    };
+    let host = settings.hostname.unwrap_or_else(|| alias.clone());
+    let port = config.overrides.port.or(settings.port).unwrap_or(port);
+    let transport = match (&config.overrides.transport, &settings.transport) {
+        (Some(flag), _) => Some(flag.as_str()),
+        (None, Some(block)) => Some(block.as_str()).filter(|t| *t != "auto"),
+        (None, None) => transport,
+    };
+    let jumps = config.jump_hosts_for(&alias);
+
+    let mut resolved = resolve_direct(user, host, port, transport, jumps)?;
+    if config.overrides.identity.is_none() {
+        resolved.identity = settings.identity;
+    }
+    if let Some(ttl) = settings.transport_ttl {
+        resolved.transport_ttl = ttl;
+    }
+    Ok(resolved)
+}
+
+fn resolve_direct(
+    user: String,
+    host: String,
+    port: u16,
+    transport: Option<&str>,
+    jumps: Vec<String>,
+) -> Result<ResolvedTarget> {
     let transport = transport.map(ToString::to_string);
     let mut urls = connection_urls(&host, port, transport.as_deref())?;
     let url = urls.remove(0);
@@ -51,6 +107,9 @@ pub fn resolve_target(target: &str, port: u16, transport: Option<&str>) -> Resul
         url,
         fallback_urls: urls,
         transport,
+        transport_ttl: Config::active().default.transport_ttl,
+        jumps,
+        identity: None,
     })
 }
 
@@ -58,10 +117,47 @@ pub fn resolve_target(target: &str, port: u16, transport: Option<&str>) -> Resul
 pub async fn connect_client(resolved: &ResolvedTarget, identity: &str) -> Result<WshClient> {
     let config = ConnectConfig {
         username: resolved.user.clone(),
-        key_name: Some(identity.to_string()),
+        key_name: Some(resolved.identity_or(identity).to_string()),
+        totp_prompt: Some(prompt_totp),
+        passphrase_prompt: Some(prompt_passphrase),
+        host_key_checking: Config::active().host_key_checking(),
+        host_key_prompt: Some(crate::known_hosts::prompt_new_host),
+        host_key_change_prompt: Some(crate::known_hosts::prompt_changed_host),
         ..Default::default()
     };
+    connect_client_with(resolved, config).await
+}
+
+/// Ask for a TOTP code on the terminal when the server requires one.
+pub fn prompt_totp(user: &str, host: &str) -> Option<String> {
+    dialoguer::Input::<String>::new()
+        .with_prompt(format!("Verification code for {user}@{host}"))
+        .interact_text()
+        .ok()
+}
+
+/// Ask for the passphrase of an encrypted key on the terminal.
+pub fn prompt_passphrase(key_name: &str) -> Option<String> {
+    dialoguer::Password::new()
+        .with_prompt(format!("Passphrase for key '{key_name}'"))
+        .interact()
+        .ok()
+}
+
+/// Connect with an explicit client configuration, trying each transport in turn.
+pub async fn connect_client_with(
+    resolved: &ResolvedTarget,
+    mut config: ConnectConfig,
+) -> Result<WshClient> {
+    config.compression |= Config::active().default.compression;
+    if resolved.jumps.is_empty() {
+        connect_direct(resolved, config).await
+    } else {
+        connect_through_jumps(resolved, config).await
+    }
+}
 
+async fn connect_direct(resolved: &ResolvedTarget, config: ConnectConfig) -> Result<WshClient> {
     let mut attempts = Vec::with_capacity(1 + resolved.fallback_urls.len());
     attempts.push((transport_label(&resolved.url), resolved.url.clone()));
     attempts.extend(
@@ -72,10 +168,36 @@ pub async fn connect_client(resolved: &ResolvedTarget, identity: &str) -> Result
             .map(|url| (transport_label(&url), url)),
     );
 
+    // With a choice of transports, start with the one that last worked.
+    // The entry is only rewritten when the winner changes, so it expires
+    // after the TTL and the preferred order is probed again.
+    let profiles = (attempts.len() > 1 && resolved.transport_ttl > 0)
+        .then(TransportProfiles::default_location)
+        .and_then(Result::ok);
+    let remembered = profiles.as_ref().and_then(|profiles| {
+        let ttl = Duration::from_secs(resolved.transport_ttl);
+        profiles.remembered(&resolved.host, resolved.port, ttl)
+    });
+    if let Some(kind) = remembered {
+        tracing::debug!(
+            transport = kind.label(),
+            "trying remembered transport first"
+        );
+        attempts.sort_by_key(|(label, _)| *label != kind.label());
+    }
+
     let mut errors = Vec::new();
     for (label, url) in attempts {
         match WshClient::connect(&url, config.clone()).await {
-            Ok(client) => return Ok(client),
+            Ok(client) => {
+                let kind = TransportKind::from_label(label).filter(|k| remembered != Some(*k));
+                if let (Some(profiles), Some(kind)) = (&profiles, kind) {
+                    if let Err(e) = profiles.remember(&resolved.host, resolved.port, kind) {
+                        tracing::debug!("failed to remember transport: {e}");
+                    }
+                }
+                return Ok(client);
+            }
             Err(err) => errors.push(format!("{label}: {err}")),
         }
     }
@@ -92,13 +214,124 @@ pub async fn connect_client(resolved: &ResolvedTarget, identity: &str) -> Result
     )
 }
 
+/// Connect to the first jump host directly, then tunnel each following hop
+/// (and finally the target) through the one before it.
+///
+/// Tunneled hops always use WebSocket. Every hop verifies its own host key
+/// and authenticates with the same identity; agent forwarding only applies
+/// to the target.
+async fn connect_through_jumps(
+    resolved: &ResolvedTarget,
+    config: ConnectConfig,
+) -> Result<WshClient> {
+    let mut hops = resolved
+        .jumps
+        .iter()
+        .map(|spec| parse_hop(spec, resolved.port))
+        .collect::<Result<Vec<_>>>()?;
+    let (user, host, port) = hops.remove(0);
+    let hop_config = |user: String| ConnectConfig {
+        username: user,
+        forward_agent: false,
+        ..config.clone()
+    };
+
+    let first = resolve_direct(
+        user.clone(),
+        host.clone(),
+        port,
+        resolved.transport.as_deref(),
+        Vec::new(),
+    )?;
+    let mut via = Arc::new(
+        connect_direct(&first, hop_config(user))
+            .await
+            .with_context(|| format!("failed to connect to jump host {host}:{port}"))?,
+    );
+    let mut via_label = format!("{host}:{port}");
+
+    for (user, host, port) in hops {
+        let tunnel = wsh_client::jump::open_tunnel(via, &host, port)
+            .await
+            .map_err(|e| anyhow::anyhow!("{e}"))
+            .with_context(|| format!("cannot reach {host}:{port} through {via_label}"))?;
+        let url = format!("wss://{host}:{port}");
+        let client = WshClient::connect_over(&url, tunnel, hop_config(user))
+            .await
+            .map_err(|e| anyhow::anyhow!("{e}"))
+            .with_context(|| format!("failed to connect to jump host {host}:{port}"))?;
+        via = Arc::new(client);
+        via_label = format!("{host}:{port}");
+    }
+
+    let tunnel = wsh_client::jump::open_tunnel(via, &resolved.host, resolved.port)
+        .await
+        .map_err(|e| anyhow::anyhow!("{e}"))
+        .with_context(|| {
+            format!(
+                "cannot reach {}:{} through {via_label}",
+                resolved.host, resolved.port
+            )
+        })?;
+    let url = format!("wss://{}:{}", resolved.host, resolved.port);
+    WshClient::connect_over(&url, tunnel, config)
+        .await
+        .map_err(|e| anyhow::anyhow!("{e}"))
+        .with_context(|| format!("failed to connect to {url} via {via_label}"))
+}
+
+/// Parse a `[user@]host[:port]` jump host, defaulting the port.
+fn parse_hop(spec: &str, default_port: u16) -> Result<(String, String, u16)> {
+    let (user, authority) = parse_target(spec)?;
+    let (host, port) = if let Some(rest) = authority.strip_prefix('[') {
+        let (host, tail) = rest
+            .split_once(']')
+            .with_context(|| format!("unterminated IPv6 address in jump host '{spec}'"))?;
+        (host.to_string(), tail.strip_prefix(':'))
+    } else {
+        match authority.rsplit_once(':') {
+            Some((host, port)) if !host.contains(':') => (host.to_string(), Some(port)),
+            _ => (authority.clone(), None),
+        }
+    };
+    let port = match port {
+        Some(port) => port
+            .parse()
+            .with_context(|| format!("invalid port in jump host '{spec}'"))?,
+        None => default_port,
+    };
+    if host.is_empty() {
+        anyhow::bail!("empty host in jump host '{spec}'");
+    }
+    Ok((user, host, port))
+}
+
+/// Log what compression saved on `client`'s connection (shown with `-v`).
+pub fn log_compression(client: &WshClient) {
+    let Some(codec) = client.compression() else {
+        return;
+    };
+    let stats = client.compression_stats();
+    if stats.frames() == 0 {
+        return;
+    }
+    tracing::info!(
+        "compression: {}, {} -> {} ({:.1}x), {} ms cpu",
+        codec.name(),
+        format_size(stats.raw_bytes()),
+        format_size(stats.wire_bytes()),
+        stats.ratio(),
+        stats.cpu_micros() / 1000,
+    );
+}
+
 /// Save the most recent successful connection for follow-up commands.
-pub fn save_last_session(resolved: &ResolvedTarget, port: u16, identity: &str) -> Result<()> {
+pub fn save_last_session(resolved: &ResolvedTarget, identity: &str) -> Result<()> {
     let entry = LastSession {
         user: resolved.user.clone(),
         host: resolved.host.clone(),
-        port,
-        identity: identity.to_string(),
+        port: resolved.port,
+        identity: resolved.identity_or(identity).to_string(),
         transport: resolved.transport.clone(),
     };
 
@@ -205,13 +438,14 @@ fn last_reverse_peer_path() -> Result<PathBuf> {
 
 fn connection_urls(host: &str, port: u16, transport: Option<&str>) -> Result<Vec<String>> {
     match transport {
-        Some("wt") => Ok(vec![format!("https://{host}:{port}")]),
-        Some("ws") => Ok(vec![format!("wss://{host}:{port}")]),
-        None => Ok(vec![
-            format!("https://{host}:{port}"),
-            format!("wss://{host}:{port}"),
-        ]),
-        Some(other) => anyhow::bail!("unknown transport: {other}"),
+        None => Ok(DEFAULT_ORDER
+            .iter()
+            .map(|kind| kind.url(host, port))
+            .collect()),
+        Some(label) => match TransportKind::from_label(label) {
+            Some(kind) => Ok(vec![kind.url(host, port)]),
+            None => anyhow::bail!("unknown transport: {label}"),
+        },
     }
 }
 
@@ -235,6 +469,7 @@ mod tests {
         assert_eq!(resolved.port, 4422);
         assert_eq!(resolved.url, "https://example.com:4422");
         assert_eq!(resolved.fallback_urls, vec!["wss://example.com:4422"]);
+        assert!(resolved.jumps.is_empty());
     }
 
     #[test]
@@ -252,4 +487,21 @@ mod tests {
         assert!(resolved.fallback_urls.is_empty());
         assert_eq!(resolved.transport.as_deref(), Some("ws"));
     }
+
+    #[test]
+    fn parse_hop_handles_users_ports_and_ipv6() {
+        let (user, host, port) = parse_hop("ops@bastion:4423", 4422).unwrap();
+        assert_eq!(
+            (user.as_str(), host.as_str(), port),
+            ("ops", "bastion", 4423)
+        );
+
+        let (_, host, port) = parse_hop("gw.example.com", 4422).unwrap();
+        assert_eq!((host.as_str(), port), ("gw.example.com", 4422));
+
+        let (_, host, port) = parse_hop("[fd00::1]:9000", 4422).unwrap();
+        assert_eq!((host.as_str(), port), ("fd00::1", 9000));
+
+        assert!(parse_hop("bastion:http", 4422).is_err());
+    }
 }
@@ -0,0 +1,58 @@
+//! `wsh config test <host>` — show the effective settings for a target.
+//!
+//! Resolves `[user@]host` exactly as a connection would (CLI flags,
+//! `[[host]]` blocks, then `[default]`) and prints the result without
+//! connecting.
+
+use anyhow::Result;
+
+use crate::commands::common::resolve_target;
+use crate::config::{parse_target, Config};
+
+/// Print the settings `wsh` would use to connect to `target`.
+pub fn run_test(target: &str, port: u16, identity: &str, transport: Option<&str>) -> Result<()> {
+    let config = Config::active();
+    let (_, alias) = parse_target(target)?;
+    let settings = config.host_settings(&alias);
+    let resolved = resolve_target(target, port, transport)?;
+
+    let list = |items: &[String]| {
+        if items.is_empty() {
+            "(none)".to_string()
+        } else {
+            items.join(", ")
+        }
+    };
+
+    println!("host              {alias}");
+    println!("matched blocks    {}", list(&settings.patterns));
+    println!("hostname          {}", resolved.host);
+    println!("user              {}", resolved.user);
+    println!("port              {}", resolved.port);
+    println!("identity          {}", resolved.identity_or(identity));
+    println!(
+        "transport         {}",
+        resolved.transport.as_deref().unwrap_or("auto")
+    );
+    println!("transport ttl     {}s", resolved.transport_ttl);
+    if !resolved.fallback_urls.is_empty() {
+        let ttl = std::time::Duration::from_secs(resolved.transport_ttl);
+        let remembered = wsh_client::TransportProfiles::default_location()
+            .ok()
+            .and_then(|p| p.remembered(&resolved.host, resolved.port, ttl));
+        println!(
+            "last transport    {}",
+            remembered.map_or("(none)", |kind| kind.label())
+        );
+    }
+    println!("url               {}", resolved.url);
+    for url in &resolved.fallback_urls {
+        println!("fallback url      {url}");
+    }
+    println!("proxy jump        {}", list(&resolved.jumps));
+    println!("local forward     {}", list(&settings.local_forward));
+    println!("remote forward    {}", list(&settings.remote_forward));
+    println!("dynamic forward   {}", list(&settings.dynamic_forward));
+    println!("host key checking {}", config.host_key_checking());
+    Ok(())
+}
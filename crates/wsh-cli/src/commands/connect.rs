@@ -3,20 +3,44 @@
 //! Parses the target, loads the identity key from the keystore, connects via
 //! WshClient, opens a PTY channel, and enters raw terminal mode to pipe
 //! stdin/stdout between the local terminal and the remote PTY. Terminal
-//! resize events are forwarded to the server.
+//! resize events are forwarded to the server. With `--record file.cast` the
+//! session output is also saved as an asciicast v2 recording that
+//! `wsh play` can replay. With `-A` the local key agent is forwarded so the
+//! remote shell can authenticate onward without copying keys there.
+//!
+//! The connection is probed with keepalive pings (`--keepalive`, or
+//! `keepalive` under `[default]` in the config). When it drops, the session
+//! keeps running on the server and is resumed on a new connection.
 
 use anyhow::{Context, Result};
+use std::path::Path;
 use tracing::{debug, info};
 use wsh_client::session::SessionOpts;
+use wsh_client::ConnectConfig;
+use wsh_core::cast::{CastHeader, CastWriter};
 use wsh_core::messages::ChannelKind;
 
-use crate::commands::common::{connect_client, resolve_target, save_last_session};
+use crate::commands::common::{
+    connect_client_with, log_compression, prompt_passphrase, prompt_totp, resolve_target,
+    save_last_session,
+};
 use crate::commands::interactive;
+use crate::config::Config;
 use crate::terminal as term;
 
 /// Run an interactive PTY session against `target` ([user@]host).
-pub async fn run(target: &str, port: u16, identity: &str, transport: Option<&str>) -> Result<()> {
+pub async fn run(
+    target: &str,
+    port: u16,
+    identity: &str,
+    transport: Option<&str>,
+    record: Option<&Path>,
+    forward_agent: bool,
+    keepalive: Option<u64>,
+) -> Result<()> {
     let resolved = resolve_target(target, port, transport)?;
+    let identity = resolved.identity_or(identity);
+    let port = resolved.port;
     info!(user = %resolved.user, host = %resolved.host, port, "connecting");
     debug!(url = %resolved.url, "transport URL");
 
@@ -24,7 +48,38 @@ pub async fn run(target: &str, port: u16, identity: &str, transport: Option<&str
     let (cols, rows) = term::get_terminal_size();
     info!(cols, rows, "terminal size");
 
-    let client = connect_client(&resolved, identity).await?;
+    // Create the recording before connecting so a bad path fails fast.
+    let recorder = match record {
+        Some(path) => {
+            let file = std::fs::File::create(path)
+                .with_context(|| format!("cannot create recording {}", path.display()))?;
+            let mut header = CastHeader::new(cols, rows);
+            header.title = Some(format!("{}@{}", resolved.user, resolved.host));
+            let writer = CastWriter::new(file, &header)
+                .map_err(|e| anyhow::anyhow!("{e}"))
+                .context("failed to start recording")?;
+            info!(path = %path.display(), "recording session");
+            Some(writer)
+        }
+        None => None,
+    };
+
+    if forward_agent && std::env::var_os("WSH_AUTH_SOCK").is_none() {
+        eprintln!("wsh: WSH_AUTH_SOCK is not set; not forwarding the key agent");
+    }
+    let config = ConnectConfig {
+        username: resolved.user.clone(),
+        key_name: Some(identity.to_string()),
+        forward_agent,
+        totp_prompt: Some(prompt_totp),
+        passphrase_prompt: Some(prompt_passphrase),
+        host_key_checking: Config::active().host_key_checking(),
+        host_key_prompt: Some(crate::known_hosts::prompt_new_host),
+        host_key_change_prompt: Some(crate::known_hosts::prompt_changed_host),
+        ping_interval_secs: keepalive.unwrap_or(Config::active().default.keepalive),
+        ..Default::default()
+    };
+    let client = connect_client_with(&resolved, config.clone()).await?;
     let session = client
         .open_session(SessionOpts {
             kind: ChannelKind::Pty,
@@ -37,8 +92,14 @@ pub async fn run(target: &str, port: u16, identity: &str, transport: Option<&str
         .map_err(|e| anyhow::anyhow!("{e}"))
         .context("failed to open PTY session")?;
 
-    save_last_session(&resolved, port, identity)?;
-    interactive::run_session(session, &resolved.host).await?;
+    save_last_session(&resolved, identity)?;
+    let reconnect = interactive::Reconnect {
+        target: &resolved,
+        config,
+        client: &client,
+    };
+    interactive::run_session(session, &resolved.host, recorder, Some(reconnect)).await?;
+    log_compression(&client);
     let _ = client.disconnect().await;
     info!("disconnected from {}", resolved.host);
 
@@ -2,19 +2,47 @@
 //!
 //! Reads the local public key from the keystore, connects to the remote host
 //! using password authentication, and installs the key into
-//! `~/.wsh/authorized_keys` via a remote shell command.
+//! `~/.wsh/authorized_keys`. Servers that advertise `authorized-keys` are
+//! asked over the protocol, which skips keys already authorized under any
+//! comment or options; older servers get a remote shell command instead.
+//!
+//! `--expiry`, `--command` and `--option` prefix the installed line with
+//! authorized_keys options, and `--list` shows the remote keys instead of
+//! installing one. `wsh keys revoke-remote` removes a key again.
 
 use anyhow::{Context, Result};
 use dialoguer::Password;
 use std::io::Write as _;
 use tracing::{debug, info};
 use wsh_client::{ConnectConfig, WshClient};
-use wsh_core::messages::ChannelKind;
+use wsh_core::messages::{AuthorizedKeyInfo, ChannelKind};
+
+use crate::commands::common::{prompt_totp, resolve_target};
+use crate::config::Config;
 
-use crate::commands::common::resolve_target;
+/// Server feature for protocol-level authorized_keys management.
+pub(crate) const AUTHORIZED_KEYS_FEATURE: &str = "authorized-keys";
+
+/// What `wsh copy-id` should do on the remote host.
+pub struct CopyIdOptions {
+    /// List the remote keys instead of installing.
+    pub list: bool,
+    /// `expiry-time` for the installed key (`YYYYMMDD[HHMM[SS]]`, UTC).
+    pub expiry: Option<String>,
+    /// Forced command for the installed key.
+    pub command: Option<String>,
+    /// Further raw options, such as `no-pty`.
+    pub options: Vec<String>,
+}
 
 /// Copy the local public key to the remote host's authorized_keys.
-pub async fn run(target: &str, port: u16, identity: &str, transport: Option<&str>) -> Result<()> {
+pub async fn run(
+    target: &str,
+    port: u16,
+    identity: &str,
+    transport: Option<&str>,
+    opts: CopyIdOptions,
+) -> Result<()> {
     let resolved = resolve_target(target, port, transport)?;
     info!(user = %resolved.user, host = %resolved.host, "copy-id");
 
@@ -31,6 +59,10 @@ pub async fn run(target: &str, port: u16, identity: &str, transport: Option<&str
     if pub_key_ssh.is_empty() {
         anyhow::bail!("public key for '{identity}' is empty");
     }
+    let key_options = build_key_options(&opts);
+    // Validate locally so a bad option never reaches either install path.
+    let local_key = wsh_core::keys::parse_public_key(&pub_key_ssh, key_options.as_deref())
+        .map_err(|e| anyhow::anyhow!("{e}"))?;
 
     let password = load_password(&resolved.user, &resolved.host)?;
     debug!(url = %resolved.url, "transport URL");
@@ -41,6 +73,10 @@ pub async fn run(target: &str, port: u16, identity: &str, transport: Option<&str
             username: resolved.user.clone(),
             key_name: None,
             password: Some(password),
+            totp_prompt: Some(prompt_totp),
+            host_key_checking: Config::active().host_key_checking(),
+            host_key_prompt: Some(crate::known_hosts::prompt_new_host),
+            host_key_change_prompt: Some(crate::known_hosts::prompt_changed_host),
             ..Default::default()
         },
     )
@@ -48,8 +84,29 @@ pub async fn run(target: &str, port: u16, identity: &str, transport: Option<&str
     .map_err(|e| anyhow::anyhow!("{e}"))
     .with_context(|| format!("failed to connect to {}", resolved.url))?;
 
+    let managed = client
+        .server_features()
+        .iter()
+        .any(|f| f == AUTHORIZED_KEYS_FEATURE);
+    if opts.list || managed {
+        let result = if opts.list {
+            list_remote(&client, &local_key.fingerprint).await
+        } else {
+            add_remote(
+                &client,
+                &local_key,
+                identity,
+                &resolved.user,
+                &resolved.host,
+            )
+            .await
+        };
+        let _ = client.disconnect().await;
+        return result;
+    }
+
     let result = async {
-        let install_command = build_install_command(&pub_key_ssh);
+        let install_command = build_install_command(&local_key.to_line());
         let session = client
             .open_session(wsh_client::SessionOpts {
                 kind: ChannelKind::Exec,
@@ -106,6 +163,82 @@ pub async fn run(target: &str, port: u16, identity: &str, transport: Option<&str
     Ok(())
 }
 
+/// Install a key over the protocol, reporting keys that were already there.
+async fn add_remote(
+    client: &WshClient,
+    key: &wsh_core::keys::AuthorizedKey,
+    identity: &str,
+    user: &str,
+    host: &str,
+) -> Result<()> {
+    let bare = wsh_core::keys::AuthorizedKey {
+        options: None,
+        ..key.clone()
+    };
+    let added = client
+        .add_authorized_key(&bare.to_line(), key.options.as_deref())
+        .await
+        .map_err(|e| anyhow::anyhow!("{e}"))
+        .context("remote key installation failed")?;
+    if added {
+        println!("Installed public key '{identity}' on {user}@{host}");
+    } else {
+        println!("Public key '{identity}' is already authorized on {user}@{host}");
+    }
+    Ok(())
+}
+
+/// Print the remote authorized keys, marking the local identity's key.
+async fn list_remote(client: &WshClient, local_fingerprint: &str) -> Result<()> {
+    let keys = client
+        .authorized_keys()
+        .await
+        .map_err(|e| anyhow::anyhow!("{e}"))
+        .context("failed to list remote keys (server may not support it)")?;
+    print!("{}", format_key_list(&keys, local_fingerprint));
+    Ok(())
+}
+
+/// Render remote keys as a table; `*` marks `local_fingerprint`.
+pub(crate) fn format_key_list(keys: &[AuthorizedKeyInfo], local_fingerprint: &str) -> String {
+    if keys.is_empty() {
+        return "No authorized keys on the remote host.\n".to_string();
+    }
+    let all_fps: Vec<&str> = keys.iter().map(|k| k.fingerprint.as_str()).collect();
+    let mut out = format!(
+        "  {:<14} {:<6} {:<24} {}\n",
+        "FINGERPRINT", "FILE", "COMMENT", "OPTIONS"
+    );
+    for key in keys {
+        let marker = if key.fingerprint == local_fingerprint {
+            '*'
+        } else {
+            ' '
+        };
+        out.push_str(&format!(
+            "{marker} {:<14} {:<6} {:<24} {}\n",
+            wsh_core::short_fingerprint(&key.fingerprint, &all_fps, 8),
+            key.source,
+            key.comment,
+            key.options.as_deref().unwrap_or("-"),
+        ));
+    }
+    out
+}
+
+/// Join the requested key options into an authorized_keys options field.
+fn build_key_options(opts: &CopyIdOptions) -> Option<String> {
+    let mut options = Vec::new();
+    if let Some(command) = &opts.command {
+        options.push(format!("command=\"{}\"", command.replace('"', "\\\"")));
+    }
+    if let Some(expiry) = &opts.expiry {
+        options.push(format!("expiry-time=\"{expiry}\""));
+    }
+    options.extend(opts.options.iter().cloned());
+    (!options.is_empty()).then(|| options.join(","))
+}
+
 fn load_password(user: &str, host: &str) -> Result<String> {
     if let Ok(password) = std::env::var("WSH_PASSWORD") {
         if !password.is_empty() {
@@ -148,7 +281,10 @@ fn shell_single_quote(value: &str) -> String {
 
 #[cfg(test)]
 mod tests {
-    use super::{build_install_command, build_install_script, shell_single_quote};
+    use super::{
+        build_install_command, build_install_script, build_key_options, shell_single_quote,
+        CopyIdOptions,
+    };
 
     #[test]
     fn shell_single_quote_escapes_embedded_quotes() {
@@ -169,4 +305,25 @@ mod tests {
         assert!(command.starts_with("sh -lc 'set -eu"));
         assert!(command.contains("\"$HOME/.wsh/authorized_keys\""));
     }
+
+    #[test]
+    fn key_options_combine_command_expiry_and_extras() {
+        let opts = CopyIdOptions {
+            list: false,
+            expiry: Some("20301231".into()),
+            command: Some("backup \"nightly\"".into()),
+            options: vec!["no-pty".into()],
+        };
+        assert_eq!(
+            build_key_options(&opts).unwrap(),
+            "command=\"backup \\\"nightly\\\"\",expiry-time=\"20301231\",no-pty"
+        );
+        let none = CopyIdOptions {
+            list: false,
+            expiry: None,
+            command: None,
+            options: Vec::new(),
+        };
+        assert!(build_key_options(&none).is_none());
+    }
 }
@@ -2,6 +2,8 @@
 //!
 //! Connects to the remote host, opens an exec channel with the given command,
 //! pipes stdout to the local terminal, and exits with the remote exit code.
+//! If a `wsh mux` master is running for the target, the command runs over
+//! its shared connection instead.
 
 use anyhow::{Context, Result};
 use std::io::Write as _;
@@ -23,6 +25,21 @@ pub async fn run(
     info!(user = %resolved.user, host = %resolved.host, command = %command, "exec");
     debug!(url = %resolved.url, "transport URL");
 
+    #[cfg(unix)]
+    if let Some(master) = crate::commands::mux::find(&resolved).await {
+        let exit_code = master
+            .exec(command, |data| {
+                let mut stdout = std::io::stdout().lock();
+                let _ = stdout.write_all(data);
+                let _ = stdout.flush();
+            })
+            .await
+            .map_err(|e| anyhow::anyhow!("{e}"))
+            .context("exec through mux master failed")?;
+        save_last_session(&resolved, identity)?;
+        return exit_with(exit_code);
+    }
+
     let client = connect_client(&resolved, identity).await?;
     let session = client
         .open_session(SessionOpts {
@@ -35,7 +52,7 @@ pub async fn run(
         .await
         .map_err(|e| anyhow::anyhow!("{e}"))
         .context("failed to open exec session")?;
-    save_last_session(&resolved, port, identity)?;
+    save_last_session(&resolved, identity)?;
 
     let mut stdout = std::io::stdout().lock();
     let mut buf = vec![0u8; 8192];
@@ -57,10 +74,14 @@ pub async fn run(
     let exit_code = session.exit_code().await.unwrap_or(0);
     let _ = session.close().await;
     let _ = client.disconnect().await;
+    exit_with(exit_code)
+}
+
+/// Exit with the remote command's status if it failed.
+fn exit_with(exit_code: i32) -> Result<()> {
     if exit_code != 0 {
         eprintln!("wsh: remote command exited with code {exit_code}");
         std::process::exit(exit_code);
     }
-
     Ok(())
 }
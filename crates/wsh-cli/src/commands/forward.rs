@@ -0,0 +1,975 @@
+//! `wsh forward` — local (`-L`), remote (`-R`) and dynamic SOCKS5 (`-D`)
+//! port forwarding.
+//!
+//! Each forwarded TCP connection is a `tcpforward` channel multiplexed over
+//! the existing control channel, so every forward shares the one
+//! authenticated session: `-L` opens one with OPEN (destination in `command`
+//! as `host:port`) and `-R` accepts one with INBOUND_ACCEPT (no
+//! `gateway_id`) after LISTEN_REQUEST / INBOUND_OPEN; data then flows as
+//! SESSION_DATA / CLOSE. Dynamic forwards are local forwards whose
+//! destination comes from a SOCKS5 request, opened with OPEN_TCP so a failure
+//! maps onto a SOCKS5 reply code. The server's gateway allowlist decides
+//! which destinations may be reached.
+//!
+//! Either end of a `-L`/`-R` forward may be a unix-domain socket instead of
+//! a TCP port. Those ride the gateway messages (OPEN_UNIX / LISTEN_UNIX,
+//! GATEWAY_DATA / GATEWAY_CLOSE), so a remote docker socket or language
+//! server can be reached locally and vice versa.
+
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::{bail, Context, Result};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio::time::{timeout, Duration};
+use tracing::{debug, info, warn};
+use wsh_client::socks::{self, SocksReply};
+use wsh_client::WshClient;
+use wsh_core::messages::*;
+
+use crate::commands::common::{connect_client, resolve_target};
+use crate::config::{parse_target, Config};
+
+/// How long a local application has to complete the SOCKS5 handshake.
+const SOCKS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// One end of a forward: a TCP address or a unix-domain socket path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Endpoint {
+    /// A host and port (for a listening end, the bind address and port).
+    Tcp { host: String, port: u16 },
+    /// A unix-domain socket path.
+    Unix(String),
+}
+
+impl std::fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Endpoint::Tcp { host, port } => write!(f, "{host}:{port}"),
+            Endpoint::Unix(path) => f.write_str(path),
+        }
+    }
+}
+
+/// A parsed forward specification: connections accepted at `listen` are
+/// forwarded to `connect`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForwardSpec {
+    /// Where the listening side accepts connections (port 0 lets the OS
+    /// pick for `-R`).
+    pub listen: Endpoint,
+    /// What the connecting side dials.
+    pub connect: Endpoint,
+}
+
+/// Parse a forward spec in OpenSSH `-L`/`-R` syntax:
+/// `[bind_addr:]port:host:hostport`, where either side may instead be a
+/// unix socket path (anything containing `/`), e.g. `2375:/var/run/docker.sock`
+/// or `/tmp/lsp.sock:127.0.0.1:9257`.
+///
+/// IPv6 addresses may be wrapped in brackets, e.g. `8080:[::1]:80`.
+pub fn parse_forward_spec(spec: &str, default_bind: &str) -> Result<ForwardSpec> {
+    let parts = split_spec(spec);
+    let (listen, connect) = match parts.split_last() {
+        Some((path, listen)) if is_socket_path(path) => (listen, Endpoint::Unix(path.clone())),
+        _ if parts.len() >= 3 => {
+            let (listen, destination) = parts.split_at(parts.len() - 2);
+            let port = destination[1]
+                .parse::<u16>()
+                .with_context(|| format!("invalid destination port in '{spec}'"))?;
+            if destination[0].is_empty() {
+                bail!("missing destination host in '{spec}'");
+            }
+            let host = destination[0].clone();
+            (listen, Endpoint::Tcp { host, port })
+        }
+        _ => bail!("invalid forward spec '{spec}' (expected [bind_addr:]port:host:hostport)"),
+    };
+    let (bind_addr, listen_port) = match listen {
+        [path] if is_socket_path(path) => {
+            return Ok(ForwardSpec {
+                listen: Endpoint::Unix(path.clone()),
+                connect,
+            });
+        }
+        [listen_port] => (default_bind.to_string(), listen_port),
+        [bind_addr, listen_port] => (bind_addr.clone(), listen_port),
+        _ => bail!("invalid forward spec '{spec}' (expected [bind_addr:]port:host:hostport)"),
+    };
+    let listen_port = listen_port
+        .parse::<u16>()
+        .with_context(|| format!("invalid listen port in '{spec}'"))?;
+    Ok(ForwardSpec {
+        listen: Endpoint::Tcp {
+            host: bind_addr,
+            port: listen_port,
+        },
+        connect,
+    })
+}
+
+fn is_socket_path(part: &str) -> bool {
+    part.contains('/')
+}
+
+/// Parse a dynamic forward spec: `[bind_addr:]port`.
+pub fn parse_dynamic_spec(spec: &str, default_bind: &str) -> Result<(String, u16)> {
+    let parts = split_spec(spec);
+    let (bind_addr, port) = match parts.as_slice() {
+        [port] => (default_bind.to_string(), port),
+        [bind_addr, port] => (bind_addr.clone(), port),
+        _ => bail!("invalid dynamic forward spec '{spec}' (expected [bind_addr:]port)"),
+    };
+    let port = port
+        .parse::<u16>()
+        .with_context(|| format!("invalid listen port in '{spec}'"))?;
+    Ok((bind_addr, port))
+}
+
+/// Split on `:` while keeping bracketed IPv6 literals intact (brackets removed).
+fn split_spec(spec: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut in_brackets = false;
+    for ch in spec.chars() {
+        match ch {
+            '[' if current.is_empty() => in_brackets = true,
+            ']' if in_brackets => in_brackets = false,
+            ':' if !in_brackets => parts.push(std::mem::take(&mut current)),
+            _ => current.push(ch),
+        }
+    }
+    parts.push(current);
+    parts
+}
+
+/// Run the requested forwards until interrupted or the connection drops.
+pub async fn run(
+    target: &str,
+    local: &[String],
+    remote: &[String],
+    dynamic: &[String],
+    port: u16,
+    identity: &str,
+    transport: Option<&str>,
+) -> Result<()> {
+    // Forwards from the target's `[[host]]` blocks are opened alongside
+    // those given on the command line.
+    let (_, alias) = parse_target(target)?;
+    let settings = Config::active().host_settings(&alias);
+    let local = [local, &settings.local_forward].concat();
+    let remote = [remote, &settings.remote_forward].concat();
+    let dynamic = [dynamic, &settings.dynamic_forward].concat();
+    if local.is_empty() && remote.is_empty() && dynamic.is_empty() {
+        bail!("no forwards requested (use -L, -R and/or -D, or set them in a [[host]] block)");
+    }
+    let local_specs = local
+        .iter()
+        .map(|spec| parse_forward_spec(spec, "127.0.0.1"))
+        .collect::<Result<Vec<_>>>()?;
+    let remote_specs = remote
+        .iter()
+        .map(|spec| parse_forward_spec(spec, "127.0.0.1"))
+        .collect::<Result<Vec<_>>>()?;
+    let dynamic_specs = dynamic
+        .iter()
+        .map(|spec| parse_dynamic_spec(spec, "127.0.0.1"))
+        .collect::<Result<Vec<_>>>()?;
+
+    let resolved = resolve_target(target, port, transport)?;
+    let client = Arc::new(connect_client(&resolved, identity).await?);
+    let mut message_rx = client
+        .take_relay_message_rx()
+        .await
+        .context("gateway message channel unavailable")?;
+
+    let (event_tx, mut event_rx) = mpsc::channel::<ForwardEvent>(64);
+    let mut forwarder = Forwarder::new(client.clone());
+    // Socket files created for local unix listeners, removed on exit.
+    let mut local_sockets = Vec::new();
+
+    for spec in &local_specs {
+        let listener = LocalListener::bind(&spec.listen).await?;
+        eprintln!(
+            "wsh: forwarding {} -> {} (via {})",
+            listener.describe(),
+            spec.connect,
+            resolved.host
+        );
+        if let Endpoint::Unix(path) = &spec.listen {
+            local_sockets.push(PathBuf::from(path));
+        }
+        tokio::spawn(accept_local(
+            listener,
+            spec.connect.clone(),
+            event_tx.clone(),
+        ));
+    }
+
+    for (bind_addr, listen_port) in &dynamic_specs {
+        let listener = TcpListener::bind((bind_addr.as_str(), *listen_port))
+            .await
+            .with_context(|| format!("failed to bind {bind_addr}:{listen_port}"))?;
+        eprintln!(
+            "wsh: SOCKS5 proxy on {} (via {})",
+            listener.local_addr()?,
+            resolved.host
+        );
+        tokio::spawn(accept_dynamic(listener, event_tx.clone()));
+    }
+
+    for (index, spec) in remote_specs.iter().enumerate() {
+        let listener_id = index as u32 + 1;
+        let request = match &spec.listen {
+            Endpoint::Tcp { host, port } => Envelope {
+                msg_type: MsgType::ListenRequest,
+                payload: Payload::ListenRequest(ListenRequestPayload {
+                    listener_id,
+                    port: *port,
+                    bind_addr: host.clone(),
+                }),
+            },
+            Endpoint::Unix(path) => Envelope {
+                msg_type: MsgType::ListenUnix,
+                payload: Payload::ListenUnix(ListenUnixPayload {
+                    listener_id,
+                    path: path.clone(),
+                }),
+            },
+        };
+        let response = client
+            .send_and_wait_public(request, MsgType::ListenOk)
+            .await
+            .map_err(|e| anyhow::anyhow!("{e}"))
+            .context("remote forward request failed")?;
+        match response.payload {
+            Payload::ListenOk(ok) => {
+                let listening = match &spec.listen {
+                    Endpoint::Tcp { host, .. } => format!("{host}:{}", ok.actual_port),
+                    Endpoint::Unix(path) => path.clone(),
+                };
+                eprintln!(
+                    "wsh: forwarding {}:{} -> {}",
+                    resolved.host, listening, spec.connect
+                );
+                forwarder.remote_listeners.insert(listener_id, spec.clone());
+            }
+            Payload::ListenFail(fail) => {
+                bail!("remote forward {} rejected: {}", spec.listen, fail.reason);
+            }
+            other => bail!("unexpected response to listen request: {other:?}"),
+        }
+    }
+
+    let result = loop {
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => break Ok(()),
+            Some(event) = event_rx.recv() => forwarder.handle_event(event).await?,
+            message = message_rx.recv() => match message {
+                Some(envelope) => forwarder.handle_message(envelope, &event_tx).await?,
+                None => break Err(anyhow::anyhow!("connection to {} closed", resolved.host)),
+            },
+        }
+    };
+
+    for listener_id in forwarder.remote_listeners.keys() {
+        let _ = client
+            .send_fire_and_forget(Envelope {
+                msg_type: MsgType::ListenClose,
+                payload: Payload::ListenClose(ListenClosePayload {
+                    listener_id: *listener_id,
+                }),
+            })
+            .await;
+    }
+    forwarder.close_all();
+    for path in &local_sockets {
+        let _ = std::fs::remove_file(path);
+    }
+    let _ = client.disconnect().await;
+    result
+}
+
+/// A local TCP or unix socket stream carried over a forward.
+trait ForwardStream: AsyncRead + AsyncWrite + Send + Unpin {}
+
+impl<S: AsyncRead + AsyncWrite + Send + Unpin> ForwardStream for S {}
+
+type BoxedStream = Box<dyn ForwardStream>;
+
+/// A listener for the local end of a `-L` forward.
+enum LocalListener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(tokio::net::UnixListener, String),
+}
+
+impl LocalListener {
+    async fn bind(endpoint: &Endpoint) -> Result<Self> {
+        match endpoint {
+            Endpoint::Tcp { host, port } => {
+                let listener = TcpListener::bind((host.as_str(), *port))
+                    .await
+                    .with_context(|| format!("failed to bind {host}:{port}"))?;
+                Ok(Self::Tcp(listener))
+            }
+            #[cfg(unix)]
+            Endpoint::Unix(path) => {
+                let listener = tokio::net::UnixListener::bind(path)
+                    .with_context(|| format!("failed to bind {path}"))?;
+                Ok(Self::Unix(listener, path.clone()))
+            }
+            #[cfg(not(unix))]
+            Endpoint::Unix(path) => {
+                bail!("cannot listen on {path}: unix sockets are not supported")
+            }
+        }
+    }
+
+    /// The bound address, for messages.
+    fn describe(&self) -> String {
+        match self {
+            Self::Tcp(listener) => listener
+                .local_addr()
+                .map(|addr| addr.to_string())
+                .unwrap_or_default(),
+            #[cfg(unix)]
+            Self::Unix(_, path) => path.clone(),
+        }
+    }
+
+    async fn accept(&self) -> std::io::Result<(BoxedStream, String)> {
+        match self {
+            Self::Tcp(listener) => {
+                let (stream, peer) = listener.accept().await?;
+                Ok((Box::new(stream), peer.to_string()))
+            }
+            #[cfg(unix)]
+            Self::Unix(listener, path) => {
+                let (stream, _) = listener.accept().await?;
+                Ok((Box::new(stream), path.clone()))
+            }
+        }
+    }
+}
+
+/// Dial the local end of a `-R` forward.
+async fn connect_local(endpoint: &Endpoint) -> std::io::Result<BoxedStream> {
+    match endpoint {
+        Endpoint::Tcp { host, port } => {
+            Ok(Box::new(TcpStream::connect((host.as_str(), *port)).await?))
+        }
+        #[cfg(unix)]
+        Endpoint::Unix(path) => Ok(Box::new(tokio::net::UnixStream::connect(path).await?)),
+        #[cfg(not(unix))]
+        Endpoint::Unix(_) => Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "unix sockets are not supported",
+        )),
+    }
+}
+
+enum ForwardEvent {
+    /// A local listener accepted a connection that should be opened remotely.
+    LocalAccepted {
+        stream: BoxedStream,
+        connect: Endpoint,
+        /// Whether the stream is waiting for a SOCKS5 reply.
+        socks: bool,
+    },
+    /// A remote-forward connection reached its local destination. Without a
+    /// `gateway_id` it is accepted as a `tcpforward` channel.
+    RemoteConnected {
+        channel_id: u32,
+        gateway_id: Option<u32>,
+        stream: BoxedStream,
+    },
+}
+
+/// How a forwarded connection is addressed on the wire.
+#[derive(Debug, Clone, Copy)]
+enum ForwardId {
+    /// A gateway connection (GATEWAY_DATA / GATEWAY_CLOSE).
+    Gateway(u32),
+    /// A `tcpforward` channel (SESSION_DATA / CLOSE).
+    Channel(u32),
+}
+
+impl ForwardId {
+    fn data(self, data: Vec<u8>) -> Envelope {
+        match self {
+            ForwardId::Gateway(gateway_id) => Envelope {
+                msg_type: MsgType::GatewayData,
+                payload: Payload::GatewayData(GatewayDataPayload { gateway_id, data }),
+            },
+            ForwardId::Channel(channel_id) => Envelope {
+                msg_type: MsgType::SessionData,
+                payload: Payload::SessionData(SessionDataPayload {
+                    channel_id,
+                    data,
+                    compression: None,
+                }),
+            },
+        }
+    }
+
+    fn close(self, reason: &str) -> Envelope {
+        match self {
+            ForwardId::Gateway(gateway_id) => Envelope {
+                msg_type: MsgType::GatewayClose,
+                payload: Payload::GatewayClose(GatewayClosePayload {
+                    gateway_id,
+                    reason: Some(reason.to_string()),
+                }),
+            },
+            ForwardId::Channel(channel_id) => Envelope {
+                msg_type: MsgType::Close,
+                payload: Payload::Close(ClosePayload { channel_id }),
+            },
+        }
+    }
+}
+
+struct ForwardConnection {
+    write_tx: mpsc::Sender<Vec<u8>>,
+    task: tokio::task::JoinHandle<()>,
+}
+
+/// A local stream waiting for GATEWAY_OK / GATEWAY_FAIL (or OPEN_OK /
+/// OPEN_FAIL for a `tcpforward` channel).
+struct PendingOpen {
+    stream: BoxedStream,
+    connect: Endpoint,
+    socks: bool,
+}
+
+struct Forwarder {
+    client: Arc<WshClient>,
+    next_gateway_id: u32,
+    pending: HashMap<u32, PendingOpen>,
+    connections: HashMap<u32, ForwardConnection>,
+    /// Channel opens awaiting OPEN_OK / OPEN_FAIL, which the server answers
+    /// in the order the opens were sent.
+    pending_channels: VecDeque<PendingOpen>,
+    /// Open `tcpforward` channels, by `channel_id`.
+    channels: HashMap<u32, ForwardConnection>,
+    remote_listeners: HashMap<u32, ForwardSpec>,
+}
+
+impl Forwarder {
+    fn new(client: Arc<WshClient>) -> Self {
+        Self {
+            client,
+            next_gateway_id: 1,
+            pending: HashMap::new(),
+            connections: HashMap::new(),
+            pending_channels: VecDeque::new(),
+            channels: HashMap::new(),
+            remote_listeners: HashMap::new(),
+        }
+    }
+
+    fn alloc_gateway_id(&mut self) -> u32 {
+        let id = self.next_gateway_id;
+        self.next_gateway_id = self.next_gateway_id.wrapping_add(1).max(1);
+        id
+    }
+
+    async fn handle_event(&mut self, event: ForwardEvent) -> Result<()> {
+        match event {
+            ForwardEvent::LocalAccepted {
+                stream,
+                connect: Endpoint::Tcp { host, port },
+                socks: false,
+            } => {
+                let destination = if host.contains(':') {
+                    format!("[{host}]:{port}")
+                } else {
+                    format!("{host}:{port}")
+                };
+                self.pending_channels.push_back(PendingOpen {
+                    stream,
+                    connect: Endpoint::Tcp { host, port },
+                    socks: false,
+                });
+                self.send(Envelope {
+                    msg_type: MsgType::Open,
+                    payload: Payload::Open(OpenPayload {
+                        kind: ChannelKind::TcpForward,
+                        command: Some(destination),
+                        cols: None,
+                        rows: None,
+                        env: None,
+                    }),
+                })
+                .await?;
+            }
+            ForwardEvent::LocalAccepted {
+                stream,
+                connect,
+                socks,
+            } => {
+                let gateway_id = self.alloc_gateway_id();
+                let envelope = match &connect {
+                    Endpoint::Tcp { host, port } => Envelope {
+                        msg_type: MsgType::OpenTcp,
+                        payload: Payload::OpenTcp(OpenTcpPayload {
+                            gateway_id,
+                            host: host.clone(),
+                            port: *port,
+                        }),
+                    },
+                    Endpoint::Unix(path) => Envelope {
+                        msg_type: MsgType::OpenUnix,
+                        payload: Payload::OpenUnix(OpenUnixPayload {
+                            gateway_id,
+                            path: path.clone(),
+                        }),
+                    },
+                };
+                self.pending.insert(
+                    gateway_id,
+                    PendingOpen {
+                        stream,
+                        connect,
+                        socks,
+                    },
+                );
+                self.send(envelope).await?;
+            }
+            ForwardEvent::RemoteConnected {
+                channel_id,
+                gateway_id,
+                stream,
+            } => {
+                match gateway_id {
+                    Some(gateway_id) => self.connections.insert(
+                        gateway_id,
+                        spawn_forward_connection(
+                            self.client.clone(),
+                            ForwardId::Gateway(gateway_id),
+                            stream,
+                        ),
+                    ),
+                    None => self.channels.insert(
+                        channel_id,
+                        spawn_forward_connection(
+                            self.client.clone(),
+                            ForwardId::Channel(channel_id),
+                            stream,
+                        ),
+                    ),
+                };
+                self.send(Envelope {
+                    msg_type: MsgType::InboundAccept,
+                    payload: Payload::InboundAccept(InboundAcceptPayload {
+                        channel_id,
+                        gateway_id,
+                    }),
+                })
+                .await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn handle_message(
+        &mut self,
+        envelope: Envelope,
+        event_tx: &mpsc::Sender<ForwardEvent>,
+    ) -> Result<()> {
+        match envelope.payload {
+            Payload::GatewayOk(ok) => {
+                if let Some(mut pending) = self.pending.remove(&ok.gateway_id) {
+                    if pending.socks
+                        && socks::send_reply(&mut pending.stream, SocksReply::Succeeded)
+                            .await
+                            .is_err()
+                    {
+                        self.send(Envelope {
+                            msg_type: MsgType::GatewayClose,
+                            payload: Payload::GatewayClose(GatewayClosePayload {
+                                gateway_id: ok.gateway_id,
+                                reason: Some("SOCKS client went away".to_string()),
+                            }),
+                        })
+                        .await?;
+                        return Ok(());
+                    }
+                    self.connections.insert(
+                        ok.gateway_id,
+                        spawn_forward_connection(
+                            self.client.clone(),
+                            ForwardId::Gateway(ok.gateway_id),
+                            pending.stream,
+                        ),
+                    );
+                }
+            }
+            Payload::GatewayFail(fail) => {
+                if let Some(mut pending) = self.pending.remove(&fail.gateway_id) {
+                    warn!(
+                        gateway_id = fail.gateway_id,
+                        code = fail.code,
+                        "forward to {} failed: {}",
+                        pending.connect,
+                        fail.message
+                    );
+                    if pending.socks {
+                        let reply = SocksReply::from_gateway_code(fail.code);
+                        let _ = socks::send_reply(&mut pending.stream, reply).await;
+                    } else {
+                        eprintln!(
+                            "wsh: forward to {} failed: {}",
+                            pending.connect, fail.message
+                        );
+                    }
+                }
+            }
+            Payload::GatewayData(data) => {
+                let Some(connection) = self.connections.get(&data.gateway_id) else {
+                    return Ok(());
+                };
+                if connection.write_tx.send(data.data).await.is_err() {
+                    self.connections.remove(&data.gateway_id);
+                    self.send(Envelope {
+                        msg_type: MsgType::GatewayClose,
+                        payload: Payload::GatewayClose(GatewayClosePayload {
+                            gateway_id: data.gateway_id,
+                            reason: Some("local stream closed".to_string()),
+                        }),
+                    })
+                    .await?;
+                }
+            }
+            Payload::GatewayClose(close) => {
+                self.pending.remove(&close.gateway_id);
+                if let Some(connection) = self.connections.remove(&close.gateway_id) {
+                    // Dropping the writer lets the task flush and exit on its own.
+                    drop(connection.write_tx);
+                    debug!(
+                        gateway_id = close.gateway_id,
+                        "forwarded connection closed by peer"
+                    );
+                }
+            }
+            Payload::OpenOk(ok) => {
+                if let Some(pending) = self.pending_channels.pop_front() {
+                    self.channels.insert(
+                        ok.channel_id,
+                        spawn_forward_connection(
+                            self.client.clone(),
+                            ForwardId::Channel(ok.channel_id),
+                            pending.stream,
+                        ),
+                    );
+                }
+            }
+            Payload::OpenFail(fail) => {
+                if let Some(pending) = self.pending_channels.pop_front() {
+                    warn!("forward to {} failed: {}", pending.connect, fail.reason);
+                    eprintln!(
+                        "wsh: forward to {} failed: {}",
+                        pending.connect, fail.reason
+                    );
+                }
+            }
+            Payload::SessionData(data) => {
+                let Some(connection) = self.channels.get(&data.channel_id) else {
+                    return Ok(());
+                };
+                if connection.write_tx.send(data.data).await.is_err() {
+                    self.channels.remove(&data.channel_id);
+                    self.send(ForwardId::Channel(data.channel_id).close("local stream closed"))
+                        .await?;
+                }
+            }
+            Payload::Close(close) => {
+                if let Some(connection) = self.channels.remove(&close.channel_id) {
+                    // Dropping the writer lets the task flush and exit on its own.
+                    drop(connection.write_tx);
+                    debug!(
+                        channel_id = close.channel_id,
+                        "forwarded channel closed by peer"
+                    );
+                }
+            }
+            Payload::InboundOpen(open) => {
+                let Some(spec) = self.remote_listeners.get(&open.listener_id).cloned() else {
+                    self.reject_inbound(open.channel_id, "unknown listener")
+                        .await?;
+                    return Ok(());
+                };
+                info!(
+                    listener_id = open.listener_id,
+                    peer = %format!("{}:{}", open.peer_addr, open.peer_port),
+                    "inbound forwarded connection"
+                );
+                // TCP listeners hand their connections over as channels.
+                let gateway_id = match spec.listen {
+                    Endpoint::Tcp { .. } => None,
+                    Endpoint::Unix(_) => Some(self.alloc_gateway_id()),
+                };
+                let client = self.client.clone();
+                let event_tx = event_tx.clone();
+                tokio::spawn(async move {
+                    match connect_local(&spec.connect).await {
+                        Ok(stream) => {
+                            let _ = event_tx
+                                .send(ForwardEvent::RemoteConnected {
+                                    channel_id: open.channel_id,
+                                    gateway_id,
+                                    stream,
+                                })
+                                .await;
+                        }
+                        Err(err) => {
+                            warn!("remote forward to {} failed: {err}", spec.connect);
+                            let _ = client
+                                .send_fire_and_forget(Envelope {
+                                    msg_type: MsgType::InboundReject,
+                                    payload: Payload::InboundReject(InboundRejectPayload {
+                                        channel_id: open.channel_id,
+                                        reason: Some(err.to_string()),
+                                    }),
+                                })
+                                .await;
+                        }
+                    }
+                });
+            }
+            other => {
+                debug!("ignoring message during forward: {other:?}");
+            }
+        }
+        Ok(())
+    }
+
+    async fn reject_inbound(&self, channel_id: u32, reason: &str) -> Result<()> {
+        self.send(Envelope {
+            msg_type: MsgType::InboundReject,
+            payload: Payload::InboundReject(InboundRejectPayload {
+                channel_id,
+                reason: Some(reason.to_string()),
+            }),
+        })
+        .await
+    }
+
+    async fn send(&self, envelope: Envelope) -> Result<()> {
+        self.client
+            .send_fire_and_forget(envelope)
+            .await
+            .map_err(|e| anyhow::anyhow!("{e}"))
+    }
+
+    fn close_all(&mut self) {
+        for (_, connection) in self.connections.drain().chain(self.channels.drain()) {
+            connection.task.abort();
+        }
+        self.pending.clear();
+        self.pending_channels.clear();
+    }
+}
+
+/// Accept connections on a local forward listener and hand them to the forwarder.
+async fn accept_local(
+    listener: LocalListener,
+    connect: Endpoint,
+    event_tx: mpsc::Sender<ForwardEvent>,
+) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, peer)) => {
+                debug!(%peer, "local forward connection accepted");
+                let event = ForwardEvent::LocalAccepted {
+                    stream,
+                    connect: connect.clone(),
+                    socks: false,
+                };
+                if event_tx.send(event).await.is_err() {
+                    break;
+                }
+            }
+            Err(err) => {
+                warn!("local forward accept failed: {err}");
+                break;
+            }
+        }
+    }
+}
+
+/// Accept SOCKS5 clients, complete the handshake, and hand the requested
+/// destination to the forwarder.
+async fn accept_dynamic(listener: TcpListener, event_tx: mpsc::Sender<ForwardEvent>) {
+    loop {
+        let (mut stream, peer) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(err) => {
+                warn!("SOCKS5 accept failed: {err}");
+                break;
+            }
+        };
+        let event_tx = event_tx.clone();
+        tokio::spawn(async move {
+            let target = match timeout(SOCKS_HANDSHAKE_TIMEOUT, socks::accept(&mut stream)).await {
+                Ok(Ok(target)) => target,
+                Ok(Err(err)) => {
+                    debug!(%peer, "SOCKS5 handshake failed: {err}");
+                    return;
+                }
+                Err(_) => {
+                    debug!(%peer, "SOCKS5 handshake timed out");
+                    return;
+                }
+            };
+            debug!(%peer, host = %target.host, port = target.port, "SOCKS5 connect");
+            let _ = event_tx
+                .send(ForwardEvent::LocalAccepted {
+                    stream: Box::new(stream),
+                    connect: Endpoint::Tcp {
+                        host: target.host,
+                        port: target.port,
+                    },
+                    socks: true,
+                })
+                .await;
+        });
+    }
+}
+
+/// Pump one forwarded stream to and from GATEWAY_DATA (or SESSION_DATA)
+/// frames.
+fn spawn_forward_connection(
+    client: Arc<WshClient>,
+    id: ForwardId,
+    stream: BoxedStream,
+) -> ForwardConnection {
+    let (write_tx, mut write_rx) = mpsc::channel::<Vec<u8>>(64);
+    let task = tokio::spawn(async move {
+        let (mut reader, mut writer) = tokio::io::split(stream);
+        let mut buf = vec![0_u8; 8192];
+        loop {
+            tokio::select! {
+                read = reader.read(&mut buf) => {
+                    match read {
+                        Ok(0) => break,
+                        Ok(n) => {
+                            let _ = client.send_fire_and_forget(id.data(buf[..n].to_vec())).await;
+                        }
+                        Err(err) => {
+                            debug!(?id, "forward read failed: {err}");
+                            break;
+                        }
+                    }
+                }
+                payload = write_rx.recv() => {
+                    match payload {
+                        Some(payload) => {
+                            if let Err(err) = writer.write_all(&payload).await {
+                                debug!(?id, "forward write failed: {err}");
+                                break;
+                            }
+                        }
+                        None => {
+                            let _ = writer.shutdown().await;
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+
+        let _ = client
+            .send_fire_and_forget(id.close("forwarded stream closed"))
+            .await;
+    });
+
+    ForwardConnection { write_tx, task }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_three_part_spec_with_default_bind() {
+        let spec = parse_forward_spec("8080:localhost:80", "127.0.0.1").unwrap();
+        assert_eq!(
+            spec,
+            ForwardSpec {
+                listen: Endpoint::Tcp {
+                    host: "127.0.0.1".into(),
+                    port: 8080,
+                },
+                connect: Endpoint::Tcp {
+                    host: "localhost".into(),
+                    port: 80,
+                },
+            }
+        );
+    }
+
+    #[test]
+    fn parses_bind_address_and_ipv6_destination() {
+        let spec = parse_forward_spec("0.0.0.0:5433:[::1]:5432", "127.0.0.1").unwrap();
+        assert_eq!(
+            spec.listen,
+            Endpoint::Tcp {
+                host: "0.0.0.0".into(),
+                port: 5433,
+            }
+        );
+        assert_eq!(
+            spec.connect,
+            Endpoint::Tcp {
+                host: "::1".into(),
+                port: 5432,
+            }
+        );
+    }
+
+    #[test]
+    fn parses_unix_socket_endpoints() {
+        let spec = parse_forward_spec("2375:/var/run/docker.sock", "127.0.0.1").unwrap();
+        assert_eq!(
+            spec.listen,
+            Endpoint::Tcp {
+                host: "127.0.0.1".into(),
+                port: 2375,
+            }
+        );
+        assert_eq!(spec.connect, Endpoint::Unix("/var/run/docker.sock".into()));
+
+        let spec = parse_forward_spec("/tmp/lsp.sock:127.0.0.1:9257", "127.0.0.1").unwrap();
+        assert_eq!(spec.listen, Endpoint::Unix("/tmp/lsp.sock".into()));
+        assert_eq!(spec.connect.to_string(), "127.0.0.1:9257");
+
+        let spec = parse_forward_spec("./docker.sock:/var/run/docker.sock", "").unwrap();
+        assert_eq!(spec.listen, Endpoint::Unix("./docker.sock".into()));
+        assert!(parse_forward_spec("/tmp/a.sock", "127.0.0.1").is_err());
+    }
+
+    #[test]
+    fn parses_dynamic_spec() {
+        assert_eq!(
+            parse_dynamic_spec("1080", "127.0.0.1").unwrap(),
+            ("127.0.0.1".to_string(), 1080)
+        );
+        assert_eq!(
+            parse_dynamic_spec("0.0.0.0:1080", "127.0.0.1").unwrap(),
+            ("0.0.0.0".to_string(), 1080)
+        );
+        assert!(parse_dynamic_spec("a:b:c", "127.0.0.1").is_err());
+    }
+
+    #[test]
+    fn rejects_malformed_specs() {
+        assert!(parse_forward_spec("8080", "127.0.0.1").is_err());
+        assert!(parse_forward_spec("8080:host:notaport", "127.0.0.1").is_err());
+        assert!(parse_forward_spec("8080::80", "127.0.0.1").is_err());
+    }
+}
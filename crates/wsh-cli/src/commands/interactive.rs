@@ -1,24 +1,65 @@
 //! Shared interactive PTY loop for direct and reverse connections.
 
+use std::fs::File;
 use std::io::Write as _;
 use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::{Context, Result};
 use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
 use tokio::sync::mpsc;
 use tracing::{debug, info, warn};
-use wsh_client::WshSession;
+use wsh_client::{ConnectConfig, ResumeHandle, WshClient, WshError, WshSession};
+use wsh_core::cast::CastWriter;
 
+use crate::commands::common::{connect_client_with, ResolvedTarget};
+use crate::config::Config;
 use crate::terminal as term;
 
+/// Longest wait between reconnect attempts.
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(30);
+
+/// How to get back to the server when the connection drops mid-session.
+pub struct Reconnect<'a> {
+    /// Target to reconnect to.
+    pub target: &'a ResolvedTarget,
+    /// Configuration for the new connection.
+    pub config: ConnectConfig,
+    /// Connection the session was opened on.
+    pub client: &'a WshClient,
+}
+
 /// Run the interactive terminal loop for an already-open session.
-pub async fn run_session(session: Arc<WshSession>, label: &str) -> Result<()> {
+///
+/// When `recorder` is set, PTY output and resizes are also written to it.
+/// OSC sequences in the output are filtered by the `[terminal]` config
+/// before reaching the local terminal. With `reconnect`, a lost connection is re-established and the session
+/// resumed instead of ending the loop.
+pub async fn run_session(
+    mut session: Arc<WshSession>,
+    label: &str,
+    mut recorder: Option<CastWriter<File>>,
+    reconnect: Option<Reconnect<'_>>,
+) -> Result<()> {
     let _guard = term::RawModeGuard::enter().context("failed to enter raw terminal mode")?;
 
     let (tx_input, mut rx_input) = mpsc::channel::<Vec<u8>>(64);
     let (tx_resize, mut rx_resize) = mpsc::channel::<(u16, u16)>(8);
     let (tx_quit, mut rx_quit) = mpsc::channel::<()>(1);
 
+    #[cfg(unix)]
+    let resize_task = {
+        let mut watcher = term::ResizeWatcher::new()?;
+        let tx_resize = tx_resize.clone();
+        tokio::spawn(async move {
+            while let Some(size) = watcher.changed().await {
+                if tx_resize.send(size).await.is_err() {
+                    break;
+                }
+            }
+        })
+    };
+
     let input_handle = tokio::task::spawn_blocking(move || loop {
         match event::read() {
             Ok(Event::Key(key_event)) => {
@@ -35,6 +76,8 @@ pub async fn run_session(session: Arc<WshSession>, label: &str) -> Result<()> {
                     }
                 }
             }
+            // On unix, SIGWINCH is watched directly (see `ResizeWatcher`).
+            #[cfg(not(unix))]
             Ok(Event::Resize(new_cols, new_rows)) => {
                 let _ = tx_resize.blocking_send((new_cols, new_rows));
             }
@@ -48,34 +91,71 @@ pub async fn run_session(session: Arc<WshSession>, label: &str) -> Result<()> {
 
     eprintln!("Connected to {label}. Press Ctrl+] to exit.\r");
 
+    let terminal = &Config::active().terminal;
+    let mut osc = term::OscFilter::new(term::OscPolicy {
+        title: terminal.title,
+        clipboard: terminal.clipboard,
+    });
     let mut stdout = std::io::stdout();
     let mut read_buf = vec![0_u8; 8192];
+    // Connection made by the latest reconnect, if any.
+    let mut replacement: Option<WshClient> = None;
 
     loop {
         tokio::select! {
             result = session.read(&mut read_buf) => {
                 let n = result.map_err(|e| anyhow::anyhow!("{e}"))?;
                 if n == 0 {
-                    break;
+                    let Some(reconnect) = &reconnect else {
+                        break;
+                    };
+                    let client = replacement.as_ref().unwrap_or(reconnect.client);
+                    let lost = !client.is_connected().await && session.exit_code().await.is_none();
+                    let Some(handle) = session.resume_handle().filter(|_| lost) else {
+                        break;
+                    };
+                    match resume(reconnect, &handle, label, &mut rx_quit).await {
+                        Some((client, resumed)) => {
+                            session = resumed;
+                            replacement = Some(client);
+                            continue;
+                        }
+                        None => break,
+                    }
                 }
                 stdout
-                    .write_all(&read_buf[..n])
+                    .write_all(&osc.filter(&read_buf[..n]))
                     .context("failed to write PTY output to stdout")?;
                 stdout.flush().context("failed to flush stdout")?;
+                if let Some(writer) = recorder.as_mut() {
+                    if let Err(e) = writer.output(&read_buf[..n]) {
+                        warn!("recording stopped: {e}");
+                        recorder = None;
+                    }
+                }
             }
             Some(bytes) = rx_input.recv() => {
-                session
-                    .write(&bytes)
-                    .await
-                    .map_err(|e| anyhow::anyhow!("{e}"))
-                    .context("failed to send input to PTY session")?;
+                // While disconnected, input is dropped; the read side notices
+                // the closed session and reconnects.
+                if let Err(e) = session.write(&bytes).await {
+                    if reconnect.is_none() {
+                        return Err(anyhow::anyhow!("{e}"))
+                            .context("failed to send input to PTY session");
+                    }
+                    debug!("dropping input: {e}");
+                }
             }
             Some((cols, rows)) = rx_resize.recv() => {
-                session
-                    .resize(cols, rows)
-                    .await
-                    .map_err(|e| anyhow::anyhow!("{e}"))
-                    .context("failed to resize PTY session")?;
+                if let Err(e) = session.resize(cols, rows).await {
+                    if reconnect.is_none() {
+                        return Err(anyhow::anyhow!("{e}"))
+                            .context("failed to resize PTY session");
+                    }
+                    debug!("dropping resize: {e}");
+                }
+                if let Some(writer) = recorder.as_mut() {
+                    let _ = writer.resize(cols, rows);
+                }
                 debug!(cols, rows, "terminal resized");
             }
             _ = rx_quit.recv() => {
@@ -86,12 +166,59 @@ pub async fn run_session(session: Arc<WshSession>, label: &str) -> Result<()> {
     }
 
     input_handle.abort();
+    #[cfg(unix)]
+    resize_task.abort();
     let _ = session.close().await;
+    if let Some(client) = replacement {
+        let _ = client.disconnect().await;
+    }
     eprintln!("\r\nConnection to {label} closed.");
 
     Ok(())
 }
 
+/// Reconnect with exponential backoff and resume the session described by
+/// `handle`. Gives up when the server no longer has the session or the user
+/// presses Ctrl+].
+async fn resume(
+    reconnect: &Reconnect<'_>,
+    handle: &ResumeHandle,
+    label: &str,
+    rx_quit: &mut mpsc::Receiver<()>,
+) -> Option<(WshClient, Arc<WshSession>)> {
+    eprint!("\r\nConnection to {label} lost; reconnecting (Ctrl+] to give up)...\r\n");
+    let mut delay = Duration::from_secs(1);
+    loop {
+        let attempt = async {
+            tokio::time::sleep(delay).await;
+            let client = connect_client_with(reconnect.target, reconnect.config.clone()).await?;
+            let session = client.resume_session(handle).await?;
+            anyhow::Ok((client, session))
+        };
+        let result = tokio::select! {
+            result = attempt => result,
+            _ = rx_quit.recv() => return None,
+        };
+
+        match result {
+            Ok((client, session)) => {
+                let (cols, rows) = term::get_terminal_size();
+                let _ = session.resize(cols, rows).await;
+                eprint!("Reconnected to {label}.\r\n");
+                return Some((client, session));
+            }
+            Err(e) if matches!(e.downcast_ref(), Some(WshError::Channel(_))) => {
+                eprint!("Cannot resume session on {label}: {e}\r\n");
+                return None;
+            }
+            Err(e) => {
+                debug!("reconnect failed: {e:#}");
+                delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+            }
+        }
+    }
+}
+
 /// Convert a crossterm key event to raw bytes suitable for a PTY.
 pub(crate) fn key_event_to_bytes(event: &KeyEvent) -> Option<Vec<u8>> {
     match event.code {
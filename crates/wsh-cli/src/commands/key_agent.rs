@@ -0,0 +1,136 @@
+//! `wsh key-agent` — hold keys in memory and sign for other wsh commands.
+//!
+//! `wsh key-agent start` listens on a unix socket (default
+//! `~/.wsh/agent.sock`) and prints a shell snippet that exports its path as
+//! `WSH_AUTH_SOCK`. `wsh key-agent add` loads a keystore key into it, after
+//! which `wsh connect` can authenticate without the key on disk and
+//! `wsh connect -A` forwards the agent to the remote host.
+
+use anyhow::{Context, Result};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::net::UnixListener;
+use wsh_client::key_agent::{self, AgentClient, KeyAgent, AUTH_SOCK_ENV};
+
+use crate::commands::common::prompt_passphrase;
+
+/// Run an agent in the foreground until interrupted.
+pub async fn run_start(socket: Option<PathBuf>, keys: &[String]) -> Result<()> {
+    let socket = match socket {
+        Some(path) => path,
+        None => default_socket_path()?,
+    };
+    if socket.exists() {
+        if AgentClient::connect(&socket).await.is_ok() {
+            anyhow::bail!("an agent is already listening on {}", socket.display());
+        }
+        // Left behind by an agent that did not shut down cleanly.
+        std::fs::remove_file(&socket)
+            .with_context(|| format!("cannot remove stale socket {}", socket.display()))?;
+    }
+    if let Some(parent) = socket.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("cannot create {}", parent.display()))?;
+    }
+
+    let agent = Arc::new(KeyAgent::new());
+    if !keys.is_empty() {
+        let keystore = open_keystore()?;
+        for name in keys {
+            let (signing_key, _) = keystore
+                .unlock(name, prompt_passphrase)
+                .map_err(|e| anyhow::anyhow!("{e}"))
+                .with_context(|| format!("failed to load key '{name}'"))?;
+            agent.add(name, signing_key);
+        }
+    }
+
+    let listener = UnixListener::bind(&socket)
+        .with_context(|| format!("cannot listen on {}", socket.display()))?;
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&socket, std::fs::Permissions::from_mode(0o600))
+            .with_context(|| format!("cannot restrict {}", socket.display()))?;
+    }
+
+    println!(
+        "{AUTH_SOCK_ENV}={}; export {AUTH_SOCK_ENV};",
+        socket.display()
+    );
+    eprintln!("wsh: key agent listening (Ctrl+C to stop)");
+
+    let result = tokio::select! {
+        result = key_agent::serve(listener, agent) => result.map_err(|e| anyhow::anyhow!("{e}")),
+        _ = tokio::signal::ctrl_c() => Ok(()),
+    };
+    let _ = std::fs::remove_file(&socket);
+    result
+}
+
+/// Load a keystore key into the running agent.
+pub async fn run_add(name: &str) -> Result<()> {
+    let (signing_key, _) = open_keystore()?
+        .unlock(name, prompt_passphrase)
+        .map_err(|e| anyhow::anyhow!("{e}"))
+        .with_context(|| format!("failed to load key '{name}'"))?;
+    let mut agent = connect_agent().await?;
+    agent
+        .add(name, &signing_key)
+        .await
+        .map_err(|e| anyhow::anyhow!("{e}"))
+        .context("agent refused the key")?;
+    println!("Added key '{name}' to the agent");
+    Ok(())
+}
+
+/// List the keys held by the running agent.
+pub async fn run_list() -> Result<()> {
+    let keys = connect_agent()
+        .await?
+        .identities()
+        .await
+        .map_err(|e| anyhow::anyhow!("{e}"))?;
+    if keys.is_empty() {
+        println!("The agent holds no keys. Run `wsh key-agent add [name]`.");
+        return Ok(());
+    }
+    for key in &keys {
+        println!("{:<16} {}", key.name, key.fingerprint());
+    }
+    Ok(())
+}
+
+/// Remove every key from the running agent.
+pub async fn run_clear() -> Result<()> {
+    connect_agent()
+        .await?
+        .remove_all()
+        .await
+        .map_err(|e| anyhow::anyhow!("{e}"))?;
+    println!("All keys removed from the agent");
+    Ok(())
+}
+
+/// Connect to the agent named by `WSH_AUTH_SOCK`, or the default socket.
+async fn connect_agent() -> Result<AgentClient> {
+    let socket = match key_agent::socket_from_env() {
+        Some(path) => path,
+        None => default_socket_path()?,
+    };
+    AgentClient::connect(&socket)
+        .await
+        .map_err(|e| anyhow::anyhow!("{e}"))
+        .context("is `wsh key-agent start` running?")
+}
+
+fn open_keystore() -> Result<wsh_client::KeyStore> {
+    wsh_client::KeyStore::default_location()
+        .map_err(|e| anyhow::anyhow!("{e}"))
+        .context("failed to initialize keystore")
+}
+
+/// `~/.wsh/agent.sock`.
+fn default_socket_path() -> Result<PathBuf> {
+    let home = dirs::home_dir().context("cannot determine home directory")?;
+    Ok(home.join(".wsh").join("agent.sock"))
+}
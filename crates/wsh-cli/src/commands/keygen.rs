@@ -2,28 +2,219 @@
 //!
 //! Uses the wsh-client `KeyStore` to generate and persist a new Ed25519
 //! key pair. Prints the fingerprint and file paths on success.
+//!
+//! `wsh keygen --sign <ca> --principals ... <name>` instead issues a
+//! certificate for an existing key, signed by the stored key `<ca>`.
+//!
+//! `wsh keygen --passphrase` encrypts the new private key with a prompted
+//! passphrase and `wsh keygen --change-passphrase <name>` adds, changes or
+//! removes one later; `--keychain` also saves it in the OS keychain.
 
 use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
 use tracing::info;
+use wsh_core::keys::{CertKind, Certificate};
+
+use crate::commands::common::prompt_passphrase;
 
 /// Generate a new Ed25519 key pair and store it in the keystore.
-pub async fn run(name: &str) -> Result<()> {
+pub async fn run(name: &str, passphrase: bool, keychain: bool) -> Result<()> {
     let keystore = wsh_client::KeyStore::default_location()
         .map_err(|e| anyhow::anyhow!("{e}"))
         .context("failed to initialize keystore")?;
 
-    let (fingerprint, ssh_pub) = keystore
-        .generate(name)
-        .map_err(|e| anyhow::anyhow!("{e}"))
-        .with_context(|| format!("failed to generate key '{name}'"))?;
+    let passphrase = if passphrase {
+        let passphrase = read_new_passphrase(name)?;
+        if passphrase.is_none() {
+            eprintln!("wsh: empty passphrase, the key will not be encrypted");
+        }
+        passphrase
+    } else {
+        None
+    };
+    if keychain && passphrase.is_none() {
+        anyhow::bail!("--keychain needs an encrypted key (use --passphrase)");
+    }
+
+    let (fingerprint, ssh_pub) = match &passphrase {
+        Some(passphrase) => keystore.generate_encrypted(name, passphrase),
+        None => keystore.generate(name),
+    }
+    .map_err(|e| anyhow::anyhow!("{e}"))
+    .with_context(|| format!("failed to generate key '{name}'"))?;
 
     let short_fp = &fingerprint[..fingerprint.len().min(12)];
 
-    info!(name, fingerprint = %fingerprint, "key generated");
+    info!(name, fingerprint = %fingerprint, encrypted = passphrase.is_some(), "key generated");
 
     println!("Generated Ed25519 key pair '{name}'");
     println!("  Fingerprint: {short_fp}");
     println!("  Public key:  {ssh_pub}");
+    if let Some(passphrase) = &passphrase {
+        println!("  Encrypted:   yes");
+        if keychain {
+            save_to_keychain(name, passphrase)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Add, change or remove the passphrase of a stored key.
+pub async fn run_change_passphrase(name: &str, keychain: bool) -> Result<()> {
+    let keystore = wsh_client::KeyStore::default_location()
+        .map_err(|e| anyhow::anyhow!("{e}"))
+        .context("failed to initialize keystore")?;
+
+    let encrypted = keystore
+        .is_encrypted(name)
+        .map_err(|e| anyhow::anyhow!("{e}"))
+        .with_context(|| format!("failed to read key '{name}'"))?;
+    let current = if encrypted {
+        Some(prompt_passphrase(name).context("no passphrase given")?)
+    } else {
+        None
+    };
+    let new = read_new_passphrase(name)?;
+    if keychain && new.is_none() {
+        anyhow::bail!("--keychain needs a non-empty passphrase");
+    }
+
+    keystore
+        .set_passphrase(name, current.as_deref(), new.as_deref())
+        .map_err(|e| anyhow::anyhow!("{e}"))
+        .with_context(|| format!("failed to update key '{name}'"))?;
+
+    match &new {
+        Some(passphrase) => {
+            println!("Passphrase for key '{name}' updated");
+            if keychain {
+                save_to_keychain(name, passphrase)?;
+            } else {
+                // A stale keychain entry would only cause a failed attempt.
+                let _ = wsh_client::keychain::delete_passphrase(name);
+            }
+        }
+        None => {
+            println!("Passphrase removed; key '{name}' is stored unencrypted");
+            let _ = wsh_client::keychain::delete_passphrase(name);
+        }
+    }
+    Ok(())
+}
+
+/// Prompt twice for a new passphrase; an empty one means "no passphrase".
+fn read_new_passphrase(name: &str) -> Result<Option<String>> {
+    let passphrase = dialoguer::Password::new()
+        .with_prompt(format!("New passphrase for key '{name}' (empty for none)"))
+        .with_confirmation("Confirm passphrase", "Passphrases do not match")
+        .allow_empty_password(true)
+        .interact()
+        .context("failed to read passphrase")?;
+    Ok(Some(passphrase).filter(|p| !p.is_empty()))
+}
+
+fn save_to_keychain(name: &str, passphrase: &str) -> Result<()> {
+    wsh_client::keychain::store_passphrase(name, passphrase).map_err(|e| anyhow::anyhow!("{e}"))?;
+    println!("  Passphrase saved to the OS keychain");
+    Ok(())
+}
+
+/// Options for `wsh keygen --sign`.
+pub struct SignRequest {
+    /// Stored key name, or path to an `ssh-ed25519` public key file.
+    pub subject: String,
+    /// Stored key name of the signing CA.
+    pub ca: String,
+    pub principals: Vec<String>,
+    pub host: bool,
+    pub valid_days: u64,
+    pub key_id: Option<String>,
+}
+
+/// Issue a CA-signed certificate for a stored key or a public key file.
+///
+/// Certificates for stored keys are saved as `~/.wsh/keys/<name>-cert.pub`
+/// and presented automatically when authenticating with that key; for a
+/// `<name>.pub` file the certificate is written beside it as
+/// `<name>-cert.pub`.
+pub async fn run_sign(request: SignRequest) -> Result<()> {
+    if request.principals.is_empty() {
+        anyhow::bail!("--principals is required when signing");
+    }
+    let keystore = wsh_client::KeyStore::default_location()
+        .map_err(|e| anyhow::anyhow!("{e}"))
+        .context("failed to initialize keystore")?;
+    let (ca_key, _) = keystore
+        .unlock(&request.ca, prompt_passphrase)
+        .map_err(|e| anyhow::anyhow!("{e}"))
+        .with_context(|| format!("failed to load CA key '{}'", request.ca))?;
+
+    let subject_file = Path::new(&request.subject);
+    let public_key = if subject_file.is_file() {
+        let content = std::fs::read_to_string(subject_file)
+            .with_context(|| format!("cannot read {}", subject_file.display()))?;
+        wsh_core::keys::parse_authorized_keys(&content)
+            .first()
+            .and_then(|key| key.public_key())
+            .with_context(|| format!("{} has no ssh-ed25519 key", subject_file.display()))?
+    } else {
+        let verifying_key = keystore
+            .load_public(&request.subject)
+            .map_err(|e| anyhow::anyhow!("{e}"))
+            .with_context(|| format!("failed to load key '{}'", request.subject))?;
+        verifying_key.to_bytes().to_vec()
+    };
+
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let mut cert = Certificate {
+        kind: if request.host {
+            CertKind::Host
+        } else {
+            CertKind::User
+        },
+        public_key,
+        key_id: request.key_id.unwrap_or_else(|| request.subject.clone()),
+        serial: now,
+        principals: request.principals,
+        // Allow for clock skew between the CA and the verifier.
+        valid_after: now.saturating_sub(300),
+        valid_before: now.saturating_add(request.valid_days.saturating_mul(86_400)),
+        ca_key: Vec::new(),
+        signature: Vec::new(),
+    };
+    wsh_client::auth::sign_certificate(&ca_key, &mut cert).map_err(|e| anyhow::anyhow!("{e}"))?;
+
+    let path = if subject_file.is_file() {
+        let stem = request
+            .subject
+            .strip_suffix(".pub")
+            .unwrap_or(&request.subject);
+        let path = PathBuf::from(format!("{stem}-cert.pub"));
+        let line = cert
+            .to_line(&cert.key_id)
+            .map_err(|e| anyhow::anyhow!("{e}"))?;
+        std::fs::write(&path, line + "\n")
+            .with_context(|| format!("failed to write {}", path.display()))?;
+        path
+    } else {
+        keystore
+            .save_certificate(&request.subject, &cert)
+            .map_err(|e| anyhow::anyhow!("{e}"))
+            .context("failed to save certificate")?
+    };
 
+    info!(key_id = %cert.key_id, serial = cert.serial, "certificate issued");
+    println!(
+        "Signed {} certificate '{}' for {}",
+        if request.host { "host" } else { "user" },
+        cert.key_id,
+        cert.principals.join(", ")
+    );
+    println!("  Valid for:   {} days", request.valid_days);
+    println!("  Written to:  {}", path.display());
     Ok(())
 }
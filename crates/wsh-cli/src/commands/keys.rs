@@ -2,9 +2,16 @@
 //!
 //! Uses the wsh-client `KeyStore` to enumerate all stored keys and prints
 //! a table showing the key name, short fingerprint, and SSH public key.
+//!
+//! `wsh keys revoke-remote` removes a key from a remote host's
+//! `~/.wsh/authorized_keys` over the protocol, the counterpart of
+//! `wsh copy-id`.
 
 use anyhow::{Context, Result};
 
+use crate::commands::common::{connect_client, resolve_target};
+use crate::commands::copy_id::{format_key_list, AUTHORIZED_KEYS_FEATURE};
+
 /// List all stored key pairs.
 pub async fn run() -> Result<()> {
     let keystore = wsh_client::KeyStore::default_location()
@@ -48,3 +55,50 @@ pub async fn run() -> Result<()> {
 
     Ok(())
 }
+
+/// Remove a key, named by fingerprint prefix or local key name, from the
+/// remote host's authorized_keys.
+pub async fn run_revoke_remote(
+    target: &str,
+    key: &str,
+    port: u16,
+    identity: &str,
+    transport: Option<&str>,
+) -> Result<()> {
+    let fingerprint = local_fingerprint(key).unwrap_or_else(|| key.to_string());
+    let resolved = resolve_target(target, port, transport)?;
+    let client = connect_client(&resolved, identity).await?;
+
+    let result = async {
+        if !client
+            .server_features()
+            .iter()
+            .any(|f| f == AUTHORIZED_KEYS_FEATURE)
+        {
+            anyhow::bail!("{} does not support remote key management", resolved.host);
+        }
+        let remaining = client
+            .remove_authorized_key(&fingerprint)
+            .await
+            .map_err(|e| anyhow::anyhow!("{e}"))
+            .context("failed to revoke remote key")?;
+        println!("Revoked {key} on {}@{}", resolved.user, resolved.host);
+        print!("{}", format_key_list(&remaining, ""));
+        Ok(())
+    }
+    .await;
+
+    let _ = client.disconnect().await;
+    result
+}
+
+/// Fingerprint of the local key called `name`, if there is one.
+fn local_fingerprint(name: &str) -> Option<String> {
+    let keystore = wsh_client::KeyStore::default_location().ok()?;
+    keystore
+        .list()
+        .ok()?
+        .into_iter()
+        .find(|k| k.name == name)
+        .map(|k| k.fingerprint)
+}
@@ -3,14 +3,22 @@
 pub mod agent;
 pub mod check;
 pub mod common;
+pub mod config;
 pub mod connect;
 pub mod copy_id;
 pub mod exec;
+pub mod forward;
 pub mod interactive;
+#[cfg(unix)]
+pub mod key_agent;
 pub mod keygen;
 pub mod keys;
+#[cfg(unix)]
+pub mod mux;
+pub mod play;
 pub mod relay;
 pub mod reverse_host;
 pub mod scp;
 pub mod sessions;
+pub mod sync;
 pub mod tools;
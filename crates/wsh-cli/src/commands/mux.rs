@@ -0,0 +1,102 @@
+//! `wsh mux` — share one authenticated connection between commands.
+//!
+//! `wsh mux start [user@]host` connects once and listens on
+//! `~/.wsh/mux/<user>@<host>-<port>.sock` until stopped. While it runs,
+//! `wsh [user@]host command` and `wsh scp` for the same target use the
+//! shared connection instead of opening their own (see
+//! [`wsh_client::mux`]).
+
+use anyhow::{Context, Result};
+use std::sync::Arc;
+use tokio::net::UnixListener;
+use tracing::info;
+use wsh_client::mux::{self, MuxClient};
+
+use crate::commands::common::{connect_client, resolve_target, ResolvedTarget};
+
+/// Connect to `target` and serve the mux socket in the foreground.
+pub async fn run_start(
+    target: &str,
+    port: u16,
+    identity: &str,
+    transport: Option<&str>,
+) -> Result<()> {
+    let resolved = resolve_target(target, port, transport)?;
+    let socket = socket_for(&resolved)?;
+    if MuxClient::find(&resolved.user, &resolved.host, resolved.port)
+        .await
+        .is_some()
+    {
+        anyhow::bail!("a mux master for {} is already running", label(&resolved));
+    }
+    if let Some(parent) = socket.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("cannot create {}", parent.display()))?;
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(parent, std::fs::Permissions::from_mode(0o700))
+            .with_context(|| format!("cannot restrict {}", parent.display()))?;
+    }
+    // A socket left behind by a master that died is no longer reachable.
+    let _ = std::fs::remove_file(&socket);
+
+    let client = Arc::new(connect_client(&resolved, identity).await?);
+    let listener = UnixListener::bind(&socket)
+        .with_context(|| format!("cannot listen on {}", socket.display()))?;
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&socket, std::fs::Permissions::from_mode(0o600))
+            .with_context(|| format!("cannot restrict {}", socket.display()))?;
+    }
+    info!(socket = %socket.display(), "mux master listening");
+    eprintln!(
+        "wsh: sharing connection to {} (Ctrl+C or `wsh mux stop` to end)",
+        label(&resolved)
+    );
+
+    let result = tokio::select! {
+        result = mux::serve(listener, client.clone()) => result.map_err(|e| anyhow::anyhow!("{e}")),
+        _ = tokio::signal::ctrl_c() => Ok(()),
+    };
+    let _ = std::fs::remove_file(&socket);
+    let _ = client.disconnect().await;
+    result
+}
+
+/// Report whether a mux master is running for `target`.
+pub async fn run_check(target: &str, port: u16, transport: Option<&str>) -> Result<()> {
+    let resolved = resolve_target(target, port, transport)?;
+    match MuxClient::find(&resolved.user, &resolved.host, resolved.port).await {
+        Some(_) => {
+            println!("mux master for {} is running", label(&resolved));
+            Ok(())
+        }
+        None => anyhow::bail!("no mux master running for {}", label(&resolved)),
+    }
+}
+
+/// Ask the mux master for `target` to disconnect and exit.
+pub async fn run_stop(target: &str, port: u16, transport: Option<&str>) -> Result<()> {
+    let resolved = resolve_target(target, port, transport)?;
+    let master = MuxClient::find(&resolved.user, &resolved.host, resolved.port)
+        .await
+        .with_context(|| format!("no mux master running for {}", label(&resolved)))?;
+    master.stop().await.map_err(|e| anyhow::anyhow!("{e}"))?;
+    println!("Stopped mux master for {}", label(&resolved));
+    Ok(())
+}
+
+/// The running master for a resolved target, if any.
+pub async fn find(resolved: &ResolvedTarget) -> Option<MuxClient> {
+    let master = MuxClient::find(&resolved.user, &resolved.host, resolved.port).await?;
+    tracing::debug!("using mux master for {}", label(resolved));
+    Some(master)
+}
+
+fn socket_for(resolved: &ResolvedTarget) -> Result<std::path::PathBuf> {
+    mux::socket_path(&resolved.user, &resolved.host, resolved.port)
+        .map_err(|e| anyhow::anyhow!("{e}"))
+}
+
+fn label(resolved: &ResolvedTarget) -> String {
+    format!("{}@{}:{}", resolved.user, resolved.host, resolved.port)
+}
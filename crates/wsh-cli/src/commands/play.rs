@@ -0,0 +1,93 @@
+//! `wsh play <file.cast>` — replay an asciicast v2 recording in the terminal.
+//!
+//! Output events are written to stdout with their original timing, scaled by
+//! `--speed`. Long pauses can be shortened with `--idle-limit`. Recordings
+//! come from `wsh connect --record` or a server-side recording exported in
+//! asciicast format.
+
+use anyhow::{Context, Result};
+use std::io::Write as _;
+use std::path::Path;
+use std::time::Duration;
+use wsh_core::cast::{self, CastEvent};
+
+/// Play a recording to stdout.
+pub async fn run(path: &Path, speed: f64, idle_limit: Option<f64>) -> Result<()> {
+    if !(speed > 0.0 && speed.is_finite()) {
+        anyhow::bail!("--speed must be a positive number");
+    }
+    let content =
+        std::fs::read_to_string(path).with_context(|| format!("cannot read {}", path.display()))?;
+    let (header, events) = cast::parse(&content)
+        .map_err(|e| anyhow::anyhow!("{e}"))
+        .with_context(|| format!("{} is not an asciicast v2 file", path.display()))?;
+
+    if let Some(title) = &header.title {
+        eprintln!("wsh: playing {title} ({}x{})", header.width, header.height);
+    }
+
+    let mut stdout = std::io::stdout();
+    for (delay, event) in playback_schedule(&events, speed, idle_limit) {
+        if !delay.is_zero() {
+            tokio::time::sleep(delay).await;
+        }
+        stdout
+            .write_all(event.data().as_bytes())
+            .context("failed to write to stdout")?;
+        stdout.flush().context("failed to flush stdout")?;
+    }
+    Ok(())
+}
+
+/// Pair each output event with the delay to wait before writing it.
+fn playback_schedule(
+    events: &[CastEvent],
+    speed: f64,
+    idle_limit: Option<f64>,
+) -> Vec<(Duration, &CastEvent)> {
+    let mut last = 0.0_f64;
+    events
+        .iter()
+        .filter(|e| e.code() == "o")
+        .map(|event| {
+            let mut gap = (event.time() - last).max(0.0);
+            last = event.time();
+            if let Some(limit) = idle_limit {
+                gap = gap.min(limit);
+            }
+            (Duration::from_secs_f64(gap / speed), event)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn output(time: f64, data: &str) -> CastEvent {
+        CastEvent(time, "o".into(), data.into())
+    }
+
+    #[test]
+    fn schedule_scales_and_caps_gaps() {
+        let events = [
+            output(0.5, "a"),
+            CastEvent(1.0, "r".into(), "100x30".into()),
+            output(2.5, "b"),
+            output(12.5, "c"),
+        ];
+
+        let delays: Vec<_> = playback_schedule(&events, 2.0, Some(3.0))
+            .into_iter()
+            .map(|(d, _)| d)
+            .collect();
+        assert_eq!(
+            delays,
+            [
+                Duration::from_millis(250),
+                Duration::from_secs(1),
+                Duration::from_millis(1500),
+            ]
+        );
+    }
+}
@@ -24,6 +24,8 @@ pub struct PeerQueryOptions {
     pub peer_type: Option<String>,
     pub shell_backend: Option<String>,
     pub capability: Option<String>,
+    /// Keep the connection open and print join/leave events.
+    pub watch: bool,
 }
 
 fn reverse_accept_summary(accept: &ReverseAcceptPayload) -> String {
@@ -73,29 +75,24 @@ fn peer_session_features(
 
 fn filter_peers(peers: Vec<PeerInfo>, options: &PeerQueryOptions) -> Vec<PeerInfo> {
     peers.into_iter()
-        .filter(|peer| {
-            options
-                .peer_type
-                .as_ref()
-                .map_or(true, |peer_type| &peer.peer_type == peer_type)
-        })
-        .filter(|peer| {
-            options
-                .shell_backend
-                .as_ref()
-                .map_or(true, |backend| &peer.shell_backend == backend)
-        })
-        .filter(|peer| {
-            options
-                .capability
-                .as_ref()
-                .map_or(true, |capability| {
-                    peer.capabilities.iter().any(|cap| cap == capability)
-                })
-        })
+        .filter(|peer| peer_matches_filters(peer, options))
         .collect()
 }
 
+fn peer_matches_filters(peer: &PeerInfo, options: &PeerQueryOptions) -> bool {
+    options
+        .peer_type
+        .as_ref()
+        .map_or(true, |peer_type| &peer.peer_type == peer_type)
+        && options
+            .shell_backend
+            .as_ref()
+            .map_or(true, |backend| &peer.shell_backend == backend)
+        && options.capability.as_ref().map_or(true, |capability| {
+            peer.capabilities.iter().any(|cap| cap == capability)
+        })
+}
+
 fn reverse_connect_label(accept: &ReverseAcceptPayload) -> String {
     if accept.capabilities.is_empty() {
         format!("{} {}", accept.peer_type, accept.shell_backend)
@@ -145,7 +142,7 @@ fn parse_reverse_connect_target(input: &str) -> ReverseConnectTarget<'_> {
 
 fn peer_matches_name(peer: &PeerInfo, selector: &str) -> bool {
     let name = selector.trim_start_matches('@');
-    peer.username == name || peer.fingerprint_short == name
+    peer.name.as_deref() == Some(name) || peer.username == name || peer.fingerprint_short == name
 }
 
 fn resolve_peer_selector<'a>(peers: &'a [PeerInfo], selector: &str) -> Result<&'a PeerInfo> {
@@ -168,7 +165,11 @@ fn resolve_peer_selector<'a>(peers: &'a [PeerInfo], selector: &str) -> Result<&'
         return Ok(peer);
     }
 
-    if selector.starts_with('@') {
+    if let Some(name) = selector.strip_prefix('@') {
+        // Registered names are unique on the relay, so they win outright.
+        if let Some(peer) = peers.iter().find(|peer| peer.name.as_deref() == Some(name)) {
+            return Ok(peer);
+        }
         let matches = peers
             .iter()
             .filter(|peer| peer_matches_name(peer, selector))
@@ -248,6 +249,7 @@ pub async fn run_reverse(
     identity: &str,
     transport: Option<&str>,
     capabilities: &[String],
+    name: Option<&str>,
 ) -> Result<()> {
     info!(relay = %relay_host, "registering as reverse peer");
 
@@ -255,15 +257,15 @@ pub async fn run_reverse(
     let keystore = wsh_client::KeyStore::default_location()
         .map_err(|e| anyhow::anyhow!("{e}"))
         .context("failed to initialize keystore")?;
-    let (_signing_key, verifying_key) = keystore
-        .load(identity)
+    let verifying_key = keystore
+        .load_public(identity)
         .map_err(|e| anyhow::anyhow!("{e}"))
         .with_context(|| format!("failed to load key '{identity}'"))?;
 
     let public_bytes = wsh_client::auth::public_key_bytes(&verifying_key);
     let fingerprint = wsh_core::fingerprint(&public_bytes);
     let short_fp = &fingerprint[..fingerprint.len().min(12)];
-    let reverse_options = reverse_options(capabilities)?;
+    let reverse_options = reverse_options(capabilities)?.with_name(name)?;
 
     let resolved = resolve_target(relay_host, port, transport)?;
     debug!(url = %resolved.url, fallback_urls = ?resolved.fallback_urls, "relay URL");
@@ -299,7 +301,10 @@ pub async fn run_reverse(
         .map_err(|e| anyhow::anyhow!("{e}"))
         .context("failed to send ReverseRegister")?;
 
-    println!("Registered as peer {short_fp} on {relay_host}:{port}");
+    match name {
+        Some(name) => println!("Registered as peer {short_fp} (@{name}) on {relay_host}:{port}"),
+        None => println!("Registered as peer {short_fp} on {relay_host}:{port}"),
+    }
     println!("Waiting for connections... (Ctrl+C to stop)");
     reverse_host::run_with_options(client.clone(), rc_rx, relay_rx, reverse_options, None)
         .await?;
@@ -362,67 +367,58 @@ pub async fn run_peers(
         .await
         .context("failed to connect to relay")?;
 
-    let peers = fetch_peers(&client).await?;
-
-    {
-        let peers = filter_peers(peers.peers, options);
+    // Subscribe before listing so no join or leave falls between the two.
+    let events = if options.watch {
+        Some(
+            client
+                .watch_peers()
+                .await
+                .map_err(|e| anyhow::anyhow!("{e}"))
+                .context("failed to subscribe to peer events")?,
+        )
+    } else {
+        None
+    };
+    let peers = filter_peers(fetch_peers(&client).await?.peers, options);
 
-        if options.json {
-            let descriptors: Vec<RemotePeerDescriptor> = peers
-                .iter()
-                .map(|peer| RemotePeerDescriptor::from_wsh_peer_info(peer, relay_host, port))
-                .collect();
-            println!(
-                "{}",
-                serde_json::to_string_pretty(&descriptors).context("failed to serialize peers")?
-            );
-        } else {
-            println!(
-                "{:<14} {:<16} {:<14} {:<16} {:<18} {:<20} {}",
-                "FINGERPRINT",
-                "USERNAME",
-                "TYPE",
-                "BACKEND",
-                "SESSION",
-                "CAPABILITIES",
-                "LAST SEEN"
-            );
-            println!(
-                "{:<14} {:<16} {:<14} {:<16} {:<18} {:<20} {}",
-                "───────────",
-                "────────",
-                "────",
-                "───────",
-                "───────",
-                "────────────",
-                "─────────"
-            );
+    if options.json {
+        let descriptors: Vec<RemotePeerDescriptor> = peers
+            .iter()
+            .map(|peer| RemotePeerDescriptor::from_wsh_peer_info(peer, relay_host, port))
+            .collect();
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&descriptors).context("failed to serialize peers")?
+        );
+    } else {
+        print_peer_table(&peers);
+    }
 
-            if peers.is_empty() {
-                println!("(no peers online)");
+    if let Some(mut events) = events {
+        if !options.json {
+            println!("\nWatching for peers joining and leaving... (Ctrl+C to stop)");
+        }
+        loop {
+            let event = tokio::select! {
+                event = events.recv() => event,
+                _ = tokio::signal::ctrl_c() => break,
+            };
+            let Some(event) = event else {
+                eprintln!("wsh: relay connection closed");
+                break;
+            };
+            if !peer_matches_filters(&event.peer, options) {
+                continue;
+            }
+            if options.json {
+                let line = serde_json::json!({
+                    "event": event.event,
+                    "peer": RemotePeerDescriptor::from_wsh_peer_info(&event.peer, relay_host, port),
+                });
+                println!("{line}");
             } else {
-                for peer in &peers {
-                    println!(
-                        "{:<14} {:<16} {:<14} {:<16} {:<18} {:<20} {}",
-                        peer.fingerprint_short,
-                        peer.username,
-                        peer.peer_type,
-                        peer.shell_backend,
-                        peer_session_features(
-                            peer.supports_attach,
-                            peer.supports_replay,
-                            peer.supports_echo,
-                            peer.supports_term_sync,
-                        ),
-                        peer.capabilities.join(", "),
-                        peer.last_seen
-                            .map(|t| format!("{t}s ago"))
-                            .unwrap_or_else(|| "—".to_string()),
-                    );
-                }
+                println!("{}", peer_event_line(&event));
             }
-
-            println!("\n{} peer(s).", peers.len());
         }
     }
 
@@ -434,6 +430,79 @@ pub async fn run_peers(
     Ok(())
 }
 
+fn print_peer_table(peers: &[PeerInfo]) {
+    println!(
+        "{:<14} {:<16} {:<16} {:<14} {:<16} {:<18} {:<20} {}",
+        "FINGERPRINT",
+        "NAME",
+        "USERNAME",
+        "TYPE",
+        "BACKEND",
+        "SESSION",
+        "CAPABILITIES",
+        "LAST SEEN"
+    );
+    println!(
+        "{:<14} {:<16} {:<16} {:<14} {:<16} {:<18} {:<20} {}",
+        "───────────",
+        "────",
+        "────────",
+        "────",
+        "───────",
+        "───────",
+        "────────────",
+        "─────────"
+    );
+
+    if peers.is_empty() {
+        println!("(no peers online)");
+    } else {
+        for peer in peers {
+            println!(
+                "{:<14} {:<16} {:<16} {:<14} {:<16} {:<18} {:<20} {}",
+                peer.fingerprint_short,
+                peer.name
+                    .as_deref()
+                    .map_or_else(|| "—".to_string(), |name| format!("@{name}")),
+                peer.username,
+                peer.peer_type,
+                peer.shell_backend,
+                peer_session_features(
+                    peer.supports_attach,
+                    peer.supports_replay,
+                    peer.supports_echo,
+                    peer.supports_term_sync,
+                ),
+                peer.capabilities.join(", "),
+                peer.last_seen
+                    .map(|t| format!("{t}s ago"))
+                    .unwrap_or_else(|| "—".to_string()),
+            );
+        }
+    }
+
+    println!("\n{} peer(s).", peers.len());
+}
+
+/// One line per `--watch` event, e.g. `+ 1a2b3c4d @laptop alice host/pty [shell, exec]`.
+fn peer_event_line(event: &ReversePeerEventPayload) -> String {
+    let peer = &event.peer;
+    let sign = if event.event == "leave" { '-' } else { '+' };
+    let name = peer
+        .name
+        .as_deref()
+        .map(|name| format!(" @{name}"))
+        .unwrap_or_default();
+    format!(
+        "{sign} {}{name} {} {}/{} [{}]",
+        peer.fingerprint_short,
+        peer.username,
+        peer.peer_type,
+        peer.shell_backend,
+        peer.capabilities.join(", ")
+    )
+}
+
 /// Reverse connect to a browser peer through a relay.
 ///
 /// Sends `ReverseConnect` to the relay server targeting the given peer
@@ -522,6 +591,8 @@ pub async fn run_connect(
             "peer {target_fingerprint} ({})",
             reverse_connect_label(&accept)
         ),
+        None,
+        None,
     )
     .await?;
     save_last_reverse_peer(&LastReversePeer {
@@ -561,10 +632,12 @@ pub async fn fetch_peers(client: &wsh_client::WshClient) -> Result<ReversePeersP
 mod tests {
     use super::{
         filter_peers, parse_reverse_connect_response, parse_reverse_connect_target,
-        peer_session_features, resolve_peer_selector, resolve_saved_last_peer,
+        peer_event_line, peer_session_features, resolve_peer_selector, resolve_saved_last_peer,
         reverse_accept_summary, reverse_options, LastReversePeer, PeerQueryOptions,
     };
-    use wsh_core::messages::{Payload, PeerInfo, ReverseAcceptPayload, ReverseRejectPayload};
+    use wsh_core::messages::{
+        Payload, PeerInfo, ReverseAcceptPayload, ReversePeerEventPayload, ReverseRejectPayload,
+    };
 
     #[test]
     fn reverse_accept_summary_includes_backend_and_capabilities() {
@@ -596,6 +669,7 @@ mod tests {
                 fingerprint: "host".into(),
                 fingerprint_short: "host".into(),
                 username: "host".into(),
+                name: None,
                 capabilities: vec!["shell".into(), "fs".into()],
                 peer_type: "host".into(),
                 shell_backend: "pty".into(),
@@ -610,6 +684,7 @@ mod tests {
                 fingerprint: "browser".into(),
                 fingerprint_short: "browser".into(),
                 username: "browser".into(),
+                name: None,
                 capabilities: vec!["shell".into()],
                 peer_type: "browser-shell".into(),
                 shell_backend: "virtual-shell".into(),
@@ -629,6 +704,7 @@ mod tests {
                 peer_type: Some("browser-shell".into()),
                 shell_backend: Some("virtual-shell".into()),
                 capability: Some("shell".into()),
+                watch: false,
             },
         );
 
@@ -687,6 +763,7 @@ mod tests {
                 fingerprint: "abcdef123456".into(),
                 fingerprint_short: "abcdef12".into(),
                 username: "builder".into(),
+                name: None,
                 capabilities: vec!["shell".into()],
                 peer_type: "host".into(),
                 shell_backend: "pty".into(),
@@ -701,6 +778,7 @@ mod tests {
                 fingerprint: "999999123456".into(),
                 fingerprint_short: "99999912".into(),
                 username: "browser".into(),
+                name: None,
                 capabilities: vec!["shell".into()],
                 peer_type: "browser-shell".into(),
                 shell_backend: "virtual-shell".into(),
@@ -726,6 +804,7 @@ mod tests {
                 fingerprint: "abcdef123456".into(),
                 fingerprint_short: "abcdef12".into(),
                 username: "builder".into(),
+                name: None,
                 capabilities: vec!["shell".into()],
                 peer_type: "host".into(),
                 shell_backend: "pty".into(),
@@ -740,6 +819,7 @@ mod tests {
                 fingerprint: "abcdef654321".into(),
                 fingerprint_short: "abcdef65".into(),
                 username: "builder".into(),
+                name: None,
                 capabilities: vec!["shell".into()],
                 peer_type: "browser-shell".into(),
                 shell_backend: "virtual-shell".into(),
@@ -756,12 +836,46 @@ mod tests {
         assert!(err.to_string().contains("matched multiple peers"));
     }
 
+    #[test]
+    fn resolve_peer_selector_prefers_registered_name_over_usernames() {
+        let peer = |fingerprint: &str, username: &str, name: Option<&str>| PeerInfo {
+            fingerprint: fingerprint.into(),
+            fingerprint_short: fingerprint[..8].into(),
+            username: username.into(),
+            name: name.map(Into::into),
+            capabilities: vec!["shell".into()],
+            peer_type: "host".into(),
+            shell_backend: "pty".into(),
+            source: "wsh-relay".into(),
+            supports_attach: false,
+            supports_replay: false,
+            supports_echo: false,
+            supports_term_sync: false,
+            last_seen: None,
+        };
+        let peers = vec![
+            peer("abcdef123456", "laptop", None),
+            peer("999999123456", "alice", Some("laptop")),
+        ];
+
+        let resolved = resolve_peer_selector(&peers, "@laptop").unwrap();
+        assert_eq!(resolved.fingerprint, "999999123456");
+        assert_eq!(
+            peer_event_line(&ReversePeerEventPayload {
+                event: "leave".into(),
+                peer: peers[1].clone(),
+            }),
+            "- 99999912 @laptop alice host/pty [shell]"
+        );
+    }
+
     #[test]
     fn resolve_peer_selector_supports_only_when_one_peer_is_online() {
         let peers = vec![PeerInfo {
             fingerprint: "abcdef123456".into(),
             fingerprint_short: "abcdef12".into(),
             username: "builder".into(),
+            name: None,
             capabilities: vec!["shell".into()],
             peer_type: "host".into(),
             shell_backend: "pty".into(),
@@ -784,6 +898,7 @@ mod tests {
                 fingerprint: "abcdef123456".into(),
                 fingerprint_short: "abcdef12".into(),
                 username: "builder".into(),
+                name: None,
                 capabilities: vec!["shell".into()],
                 peer_type: "host".into(),
                 shell_backend: "pty".into(),
@@ -798,6 +913,7 @@ mod tests {
                 fingerprint: "999999123456".into(),
                 fingerprint_short: "99999912".into(),
                 username: "browser".into(),
+                name: None,
                 capabilities: vec!["shell".into()],
                 peer_type: "browser-shell".into(),
                 shell_backend: "virtual-shell".into(),
@@ -822,6 +938,7 @@ mod tests {
             fingerprint: "abcdef123456".into(),
             fingerprint_short: "abcdef12".into(),
             username: "builder".into(),
+            name: None,
             capabilities: vec!["shell".into()],
             peer_type: "host".into(),
             shell_backend: "pty".into(),
@@ -851,6 +968,7 @@ mod tests {
             fingerprint: "999999123456".into(),
             fingerprint_short: "99999912".into(),
             username: "browser".into(),
+            name: None,
             capabilities: vec!["shell".into()],
             peer_type: "browser-shell".into(),
             shell_backend: "virtual-shell".into(),
@@ -26,6 +26,8 @@ const DEFAULT_PTY_REPLAY_LIMIT: usize = 256 * 1024;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ReverseHostOptions {
+    /// Human-readable name to register on the relay.
+    pub name: Option<String>,
     pub capabilities: Vec<String>,
     pub peer_type: String,
     pub shell_backend: String,
@@ -38,6 +40,7 @@ pub struct ReverseHostOptions {
 impl Default for ReverseHostOptions {
     fn default() -> Self {
         Self {
+            name: None,
             capabilities: vec!["shell".to_string(), "exec".to_string()],
             peer_type: "host".to_string(),
             shell_backend: "pty".to_string(),
@@ -50,6 +53,20 @@ impl Default for ReverseHostOptions {
 }
 
 impl ReverseHostOptions {
+    /// Set the relay peer name, rejecting names the relay would ignore.
+    pub fn with_name(mut self, name: Option<&str>) -> anyhow::Result<Self> {
+        if let Some(name) = name {
+            if !wsh_core::valid_peer_name(name) {
+                anyhow::bail!(
+                    "invalid peer name `{name}`; use up to {} letters, digits, `-`, `_` or `.`",
+                    wsh_core::remote_runtime::MAX_PEER_NAME_LEN
+                );
+            }
+        }
+        self.name = name.map(str::to_string);
+        Ok(self)
+    }
+
     pub fn reverse_register_payload(
         &self,
         username: String,
@@ -57,6 +74,7 @@ impl ReverseHostOptions {
     ) -> ReverseRegisterPayload {
         ReverseRegisterPayload {
             username,
+            name: self.name.clone(),
             capabilities: self.capabilities.clone(),
             peer_type: self.peer_type.clone(),
             shell_backend: self.shell_backend.clone(),
@@ -374,7 +392,11 @@ impl ReverseHostRuntime {
                     self.client()?
                         .send_fire_and_forget(Envelope {
                             msg_type: MsgType::SessionData,
-                            payload: Payload::SessionData(SessionDataPayload { channel_id, data }),
+                            payload: Payload::SessionData(SessionDataPayload {
+                                channel_id,
+                                data,
+                                compression: None,
+                            }),
                         })
                         .await
                         .map_err(|err| anyhow::anyhow!("{err}"))?;
@@ -496,6 +518,8 @@ impl ReverseHostRuntime {
                     stream_ids: vec![],
                     data_mode: SessionDataMode::Virtual,
                     capabilities,
+                    session_id: None,
+                    resume_token: None,
                 }),
             })
             .await
@@ -533,6 +557,7 @@ impl ReverseHostRuntime {
                             payload: Payload::SessionData(SessionDataPayload {
                                 channel_id: data.channel_id,
                                 data: response,
+                                compression: None,
                             }),
                         })
                         .await
@@ -913,6 +938,8 @@ impl ReverseHostRuntime {
                     stream_ids: vec![],
                     data_mode: SessionDataMode::Virtual,
                     capabilities,
+                    session_id: None,
+                    resume_token: None,
                 }),
             })
             .await
@@ -992,6 +1019,7 @@ impl ReverseHostSession {
                         payload: Payload::SessionData(SessionDataPayload {
                             channel_id,
                             data: replay,
+                            compression: None,
                         }),
                     })
                     .await
@@ -2056,6 +2084,7 @@ mod tests {
             .call(&wsh_core::messages::McpCallPayload {
                 tool: "shell.exec".to_string(),
                 arguments: json!({ "command": "printf hello" }),
+                progress: None,
             })
             .await;
         assert_eq!(result.result["stdout"], "hello");
@@ -2,7 +2,10 @@
 //!
 //! Supports both upload (local -> remote) and download (remote -> local)
 //! based on which argument contains the host:path syntax. Shows a terminal
-//! progress bar during transfer.
+//! progress bar during transfer. Files are streamed in hashed chunks, so an
+//! interrupted copy resumes where it left off when re-run. `--limit-rate`
+//! caps the transfer's bandwidth. A running `wsh mux` master for the host
+//! carries the transfer over its shared connection.
 
 use anyhow::{Context, Result};
 use std::fs;
@@ -11,12 +14,14 @@ use std::path::{Path, PathBuf};
 use tracing::{debug, info};
 use wsh_client::file_transfer;
 
-use crate::commands::common::{connect_client, resolve_target, save_last_session};
+use crate::commands::common::{
+    connect_client, log_compression, resolve_target, save_last_session, ResolvedTarget,
+};
 use crate::config::parse_target;
 
 /// A parsed SCP endpoint — either local or remote.
 #[derive(Debug)]
-enum Endpoint {
+pub(crate) enum Endpoint {
     Local(PathBuf),
     Remote {
         user: String,
@@ -29,6 +34,7 @@ enum Endpoint {
 pub async fn run(
     src: &str,
     dst: &str,
+    limit_rate: Option<u64>,
     port: u16,
     identity: &str,
     transport: Option<&str>,
@@ -39,11 +45,17 @@ pub async fn run(
     match (&src_ep, &dst_ep) {
         (Endpoint::Local(local_path), Endpoint::Remote { user, host, path }) => {
             info!(local = %local_path.display(), remote = %format!("{user}@{host}:{path}"), "upload");
-            upload(local_path, user, host, path, port, identity, transport).await
+            upload(
+                local_path, user, host, path, limit_rate, port, identity, transport,
+            )
+            .await
         }
         (Endpoint::Remote { user, host, path }, Endpoint::Local(local_path)) => {
             info!(remote = %format!("{user}@{host}:{path}"), local = %local_path.display(), "download");
-            download(user, host, path, local_path, port, identity, transport).await
+            download(
+                user, host, path, local_path, limit_rate, port, identity, transport,
+            )
+            .await
         }
         (Endpoint::Local(_), Endpoint::Local(_)) => {
             anyhow::bail!("both source and destination are local — use cp instead")
@@ -55,11 +67,13 @@ pub async fn run(
 }
 
 /// Upload a local file to a remote host.
+#[allow(clippy::too_many_arguments)]
 async fn upload(
     local_path: &Path,
     user: &str,
     host: &str,
     remote_path: &str,
+    limit_rate: Option<u64>,
     port: u16,
     identity: &str,
     transport: Option<&str>,
@@ -68,51 +82,53 @@ async fn upload(
     let metadata = fs::metadata(local_path)
         .with_context(|| format!("cannot stat {}", local_path.display()))?;
     let file_size = metadata.len();
-    let data =
-        fs::read(local_path).with_context(|| format!("cannot read {}", local_path.display()))?;
 
     let target = format!("{user}@{host}");
     let resolved = resolve_target(&target, port, transport)?;
-    let client = connect_client(&resolved, identity).await?;
-    debug!(url = %resolved.url, file_size, "upload transport URL");
-    save_last_session(&resolved, port, identity)?;
+    let via_mux = via_mux(&resolved, true, local_path, remote_path, limit_rate).await?;
+    if via_mux.is_none() {
+        let client = connect_client(&resolved, identity).await?;
+        debug!(url = %resolved.url, file_size, "upload transport URL");
 
-    file_transfer::upload(&client, &data, remote_path, |sent, total| {
-        print_progress(sent, total);
-    })
-    .await
-    .map_err(|e| anyhow::anyhow!("{e}"))
-    .context("upload failed")?;
+        file_transfer::upload_file(
+            &client,
+            local_path,
+            remote_path,
+            limit_rate,
+            |sent, total| {
+                print_progress(sent, total);
+            },
+        )
+        .await
+        .map_err(|e| anyhow::anyhow!("{e}"))
+        .context("upload failed")?;
+        log_compression(&client);
+        let _ = client.disconnect().await;
+    }
+    save_last_session(&resolved, identity)?;
 
     println!(
         "wsh: uploaded {} to {user}@{host}:{remote_path}",
         format_size(file_size),
     );
-    let _ = client.disconnect().await;
 
     Ok(())
 }
 
 /// Download a remote file to a local path.
+#[allow(clippy::too_many_arguments)]
 async fn download(
     user: &str,
     host: &str,
     remote_path: &str,
     local_path: &Path,
+    limit_rate: Option<u64>,
     port: u16,
     identity: &str,
     transport: Option<&str>,
 ) -> Result<()> {
     let target = format!("{user}@{host}");
     let resolved = resolve_target(&target, port, transport)?;
-    let client = connect_client(&resolved, identity).await?;
-    debug!(url = %resolved.url, "download transport URL");
-    save_last_session(&resolved, port, identity)?;
-
-    let data = file_transfer::download(&client, remote_path)
-        .await
-        .map_err(|e| anyhow::anyhow!("{e}"))
-        .context("download failed")?;
 
     if let Some(parent) = local_path.parent() {
         if !parent.as_os_str().is_empty() {
@@ -120,21 +136,92 @@ async fn download(
                 .with_context(|| format!("cannot create {}", parent.display()))?;
         }
     }
-    std::fs::write(local_path, &data)
-        .with_context(|| format!("cannot write {}", local_path.display()))?;
-    print_progress(data.len() as u64, data.len() as u64);
+    let size = match via_mux(&resolved, false, local_path, remote_path, limit_rate).await? {
+        Some(size) => size,
+        None => {
+            let client = connect_client(&resolved, identity).await?;
+            debug!(url = %resolved.url, "download transport URL");
+            let size = file_transfer::download_file(
+                &client,
+                remote_path,
+                local_path,
+                limit_rate,
+                |received, total| {
+                    print_progress(received, total);
+                },
+            )
+            .await
+            .map_err(|e| anyhow::anyhow!("{e}"))
+            .context("download failed")?;
+            log_compression(&client);
+            let _ = client.disconnect().await;
+            size
+        }
+    };
+    save_last_session(&resolved, identity)?;
+
     println!(
         "wsh: downloaded {} to {}",
-        format_size(data.len() as u64),
+        format_size(size),
         local_path.display(),
     );
-    let _ = client.disconnect().await;
 
     Ok(())
 }
 
+/// Run the transfer through a `wsh mux` master for the target, returning
+/// the bytes transferred, or `None` if no master is running.
+#[cfg(unix)]
+async fn via_mux(
+    resolved: &ResolvedTarget,
+    upload: bool,
+    local_path: &Path,
+    remote_path: &str,
+    limit_rate: Option<u64>,
+) -> Result<Option<u64>> {
+    use wsh_client::mux::MuxRequest;
+
+    let Some(master) = crate::commands::mux::find(resolved).await else {
+        return Ok(None);
+    };
+    // The master may run in another directory.
+    let local_path = std::path::absolute(local_path)
+        .with_context(|| format!("cannot resolve {}", local_path.display()))?;
+    let remote_path = remote_path.to_string();
+    let request = if upload {
+        MuxRequest::Upload {
+            local_path,
+            remote_path,
+            limit_rate,
+        }
+    } else {
+        MuxRequest::Download {
+            remote_path,
+            local_path,
+            limit_rate,
+        }
+    };
+    let size = master
+        .transfer(&request, print_progress)
+        .await
+        .map_err(|e| anyhow::anyhow!("{e}"))
+        .context("transfer through mux master failed")?;
+    Ok(Some(size))
+}
+
+#[cfg(not(unix))]
+async fn via_mux(
+    _resolved: &ResolvedTarget,
+    _upload: bool,
+    _local_path: &Path,
+    _remote_path: &str,
+    _limit_rate: Option<u64>,
+) -> Result<Option<u64>> {
+    Ok(None)
+}
+
 /// Parse an SCP endpoint string. Remote endpoints use `[user@]host:path` syntax.
-fn parse_endpoint(s: &str) -> Result<Endpoint> {
+pub(crate) fn parse_endpoint(s: &str) -> Result<Endpoint> {
     // Look for the colon that separates host from path, but skip Windows drive letters
     // (e.g., C:\path) by requiring that the part before the colon contains no path separators.
     if let Some(colon_pos) = s.find(':') {
@@ -171,8 +258,7 @@ fn whoami() -> Option<String> {
 }
 
 /// Print a progress bar to stderr.
-#[allow(dead_code)]
-fn print_progress(transferred: u64, total: u64) {
+pub(crate) fn print_progress(transferred: u64, total: u64) {
     if total == 0 {
         return;
     }
@@ -197,7 +283,7 @@ fn print_progress(transferred: u64, total: u64) {
 }
 
 /// Format a byte count as a human-readable string.
-fn format_size(bytes: u64) -> String {
+pub(crate) fn format_size(bytes: u64) -> String {
     const KB: u64 = 1024;
     const MB: u64 = 1024 * KB;
     const GB: u64 = 1024 * MB;
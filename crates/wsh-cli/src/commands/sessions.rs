@@ -6,6 +6,7 @@
 
 use anyhow::{Context, Result};
 use tracing::info;
+use wsh_client::RemoteSessionInfo;
 
 use crate::commands::common::{
     clear_active_attachment, connect_client, load_active_attachment, load_last_session,
@@ -15,8 +16,9 @@ use crate::commands::common::{
 /// List active sessions on the most recently connected host.
 ///
 /// Reads the last-connected host from `~/.wsh/last_session` and queries
-/// the server for active sessions.
-pub async fn run_list() -> Result<()> {
+/// the server for active sessions. With `json`, prints the full metadata
+/// as a JSON array instead of a table.
+pub async fn run_list(json: bool) -> Result<()> {
     let last = load_last_session()?
         .context("no previous session found (connect once before using `wsh sessions`)")?;
     let target = format!("{}@{}", last.user, last.host);
@@ -27,35 +29,81 @@ pub async fn run_list() -> Result<()> {
         .await
         .map_err(|e| anyhow::anyhow!("{e}"))
         .context("failed to fetch sessions from server")?;
+    let _ = client.disconnect().await;
+
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&sessions).context("failed to serialize sessions")?
+        );
+        return Ok(());
+    }
 
     println!(
-        "{:<24} {:<12} {:<10} {:<8} {:<8} {}",
-        "SESSION_ID", "OWNER", "ATTACHED", "IDLE", "CREATED", "NAME"
+        "{:<24} {:<12} {:<10} {:<8} {:<9} {:<20} {:<10} {}",
+        "SESSION_ID", "OWNER", "STATE", "IDLE", "SIZE", "COMMAND", "KEY", "NAME"
     );
     println!(
-        "{:<24} {:<12} {:<10} {:<8} {:<8} {}",
-        "----------", "-----", "--------", "----", "-------", "----"
+        "{:<24} {:<12} {:<10} {:<8} {:<9} {:<20} {:<10} {}",
+        "----------", "-----", "-----", "----", "----", "-------", "---", "----"
     );
     if sessions.is_empty() {
         println!("(no visible sessions)");
     } else {
         for s in &sessions {
             println!(
-                "{:<24} {:<12} {:<10} {:<8} {:<8} {}",
+                "{:<24} {:<12} {:<10} {:<8} {:<9} {:<20} {:<10} {}",
                 s.session_id,
                 s.username,
-                s.attached_count,
-                s.idle_secs,
-                s.created_at_secs,
+                state_label(s),
+                format_idle(s.idle_secs),
+                size_label(s),
+                truncate(s.command.as_deref().unwrap_or("(shell)"), 20),
+                s.fingerprint_short,
                 s.name.as_deref().unwrap_or("-"),
             );
         }
     }
-    let _ = client.disconnect().await;
 
     Ok(())
 }
 
+/// State column; older servers only report the attached count.
+fn state_label(s: &RemoteSessionInfo) -> String {
+    match &s.state {
+        Some(state) if state == "attached" => format!("attached({})", s.attached_count),
+        Some(state) => state.clone(),
+        None if s.attached_count > 0 => format!("attached({})", s.attached_count),
+        None => "detached".to_string(),
+    }
+}
+
+fn size_label(s: &RemoteSessionInfo) -> String {
+    match (s.cols, s.rows) {
+        (Some(cols), Some(rows)) => format!("{cols}x{rows}"),
+        _ => "-".to_string(),
+    }
+}
+
+/// Compact idle time: `42s`, `7m`, `3h`, `2d`.
+fn format_idle(secs: u64) -> String {
+    match secs {
+        0..=59 => format!("{secs}s"),
+        60..=3599 => format!("{}m", secs / 60),
+        3600..=86_399 => format!("{}h", secs / 3600),
+        _ => format!("{}d", secs / 86_400),
+    }
+}
+
+fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        text.to_string()
+    } else {
+        let head: String = text.chars().take(max - 1).collect();
+        format!("{head}…")
+    }
+}
+
 /// Reattach to a session by name or ID.
 pub async fn run_attach(
     session: &str,
@@ -111,3 +159,44 @@ pub async fn run_detach() -> Result<()> {
     let _ = client.disconnect().await;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(state: Option<&str>, attached_count: u32) -> RemoteSessionInfo {
+        RemoteSessionInfo {
+            session_id: "s1".into(),
+            name: None,
+            username: "alice".into(),
+            fingerprint_short: "abcd1234".into(),
+            created_at_secs: 0,
+            idle_secs: 0,
+            attached_count,
+            state: state.map(str::to_string),
+            last_activity_secs: None,
+            command: None,
+            cols: None,
+            rows: None,
+            fingerprint: None,
+        }
+    }
+
+    #[test]
+    fn state_label_falls_back_to_attached_count() {
+        assert_eq!(state_label(&info(Some("attached"), 2)), "attached(2)");
+        assert_eq!(state_label(&info(Some("dormant"), 0)), "dormant");
+        assert_eq!(state_label(&info(None, 1)), "attached(1)");
+        assert_eq!(state_label(&info(None, 0)), "detached");
+    }
+
+    #[test]
+    fn formats_idle_and_truncates_commands() {
+        assert_eq!(format_idle(42), "42s");
+        assert_eq!(format_idle(7 * 60), "7m");
+        assert_eq!(format_idle(3 * 3600 + 5), "3h");
+        assert_eq!(format_idle(2 * 86_400), "2d");
+        assert_eq!(truncate("top", 20), "top");
+        assert_eq!(truncate("abcdef", 4), "abc…");
+    }
+}
@@ -0,0 +1,252 @@
+//! `wsh sync <src> <dst>` — rsync-like directory sync.
+//!
+//! Both trees are listed as manifests of relative path, size and BLAKE3
+//! hash. Only files that are missing or differ on the destination are
+//! transferred, using the resumable chunked protocol. `--delete` removes
+//! destination files that no longer exist in the source, `--dry-run`
+//! prints the plan without changing anything, and `--limit-rate` caps the
+//! bandwidth of each file transfer.
+//!
+//! Either side may be remote (`[user@]host:path`), but not both.
+
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::path::Path;
+use tracing::{debug, info};
+use wsh_client::file_transfer;
+use wsh_client::WshClient;
+use wsh_core::messages::SyncEntry;
+use wsh_core::transfer::{build_manifest, join_relative};
+
+use crate::commands::common::{connect_client, log_compression, resolve_target, save_last_session};
+use crate::commands::scp::{format_size, parse_endpoint, Endpoint};
+
+/// What a sync run will do.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct SyncPlan {
+    /// Source entries that are new or changed on the destination.
+    transfer: Vec<SyncEntry>,
+    /// Destination paths absent from the source (only with `--delete`).
+    delete: Vec<String>,
+}
+
+/// Which way files flow.
+enum Direction {
+    Push,
+    Pull,
+}
+
+/// Run a directory sync between src and dst.
+#[allow(clippy::too_many_arguments)]
+pub async fn run(
+    src: &str,
+    dst: &str,
+    delete: bool,
+    dry_run: bool,
+    limit_rate: Option<u64>,
+    port: u16,
+    identity: &str,
+    transport: Option<&str>,
+) -> Result<()> {
+    let (direction, local_root, user, host, remote_root) =
+        match (parse_endpoint(src)?, parse_endpoint(dst)?) {
+            (Endpoint::Local(local), Endpoint::Remote { user, host, path }) => {
+                (Direction::Push, local, user, host, path)
+            }
+            (Endpoint::Remote { user, host, path }, Endpoint::Local(local)) => {
+                (Direction::Pull, local, user, host, path)
+            }
+            (Endpoint::Local(_), Endpoint::Local(_)) => {
+                anyhow::bail!("both source and destination are local — use rsync instead")
+            }
+            (Endpoint::Remote { .. }, Endpoint::Remote { .. }) => {
+                anyhow::bail!("remote-to-remote sync is not supported")
+            }
+        };
+
+    if matches!(direction, Direction::Push) && !local_root.is_dir() {
+        anyhow::bail!("{} is not a directory", local_root.display());
+    }
+
+    let target = format!("{user}@{host}");
+    let resolved = resolve_target(&target, port, transport)?;
+    let client = connect_client(&resolved, identity).await?;
+    debug!(url = %resolved.url, "sync transport URL");
+    save_last_session(&resolved, identity)?;
+
+    let local_manifest = {
+        let root = local_root.clone();
+        tokio::task::spawn_blocking(move || build_manifest(&root))
+            .await?
+            .map_err(|e| anyhow::anyhow!("{e}"))
+            .with_context(|| format!("cannot scan {}", local_root.display()))?
+    };
+    let remote_manifest = file_transfer::remote_manifest(&client, &remote_root)
+        .await
+        .map_err(|e| anyhow::anyhow!("{e}"))
+        .with_context(|| format!("cannot scan {target}:{remote_root}"))?;
+    info!(
+        local = local_manifest.len(),
+        remote = remote_manifest.len(),
+        "manifests exchanged"
+    );
+
+    let plan = match direction {
+        Direction::Push => plan_sync(&local_manifest, &remote_manifest, delete),
+        Direction::Pull => plan_sync(&remote_manifest, &local_manifest, delete),
+    };
+
+    let prefix = if dry_run { "would " } else { "" };
+    for entry in &plan.transfer {
+        println!("{prefix}send {} ({})", entry.path, format_size(entry.size));
+    }
+    for path in &plan.delete {
+        println!("{prefix}delete {path}");
+    }
+
+    let result = match direction {
+        _ if dry_run => Ok(()),
+        Direction::Push => push(&client, &local_root, &remote_root, &plan, limit_rate).await,
+        Direction::Pull => pull(&client, &remote_root, &local_root, &plan, limit_rate).await,
+    };
+    log_compression(&client);
+    let _ = client.disconnect().await;
+    result?;
+
+    let bytes: u64 = plan.transfer.iter().map(|e| e.size).sum();
+    println!(
+        "wsh: {}{} file(s) sent ({}), {} deleted",
+        if dry_run { "dry run: " } else { "" },
+        plan.transfer.len(),
+        format_size(bytes),
+        plan.delete.len(),
+    );
+    Ok(())
+}
+
+/// Upload changed files and delete stale remote files.
+async fn push(
+    client: &WshClient,
+    local_root: &Path,
+    remote_root: &str,
+    plan: &SyncPlan,
+    limit_rate: Option<u64>,
+) -> Result<()> {
+    for entry in &plan.transfer {
+        let local = join_relative(local_root, &entry.path).map_err(|e| anyhow::anyhow!("{e}"))?;
+        let remote = remote_join(remote_root, &entry.path);
+        file_transfer::upload_file(client, &local, &remote, limit_rate, |_, _| {})
+            .await
+            .map_err(|e| anyhow::anyhow!("{e}"))
+            .with_context(|| format!("upload of {} failed", entry.path))?;
+    }
+    if !plan.delete.is_empty() {
+        file_transfer::remote_delete(client, remote_root, plan.delete.clone())
+            .await
+            .map_err(|e| anyhow::anyhow!("{e}"))
+            .context("remote delete failed")?;
+    }
+    Ok(())
+}
+
+/// Download changed files and delete stale local files.
+///
+/// Paths come from the remote manifest, so each one is checked with
+/// [`join_relative`] before it touches the local filesystem.
+async fn pull(
+    client: &WshClient,
+    remote_root: &str,
+    local_root: &Path,
+    plan: &SyncPlan,
+    limit_rate: Option<u64>,
+) -> Result<()> {
+    for entry in &plan.transfer {
+        let local = join_relative(local_root, &entry.path).map_err(|e| anyhow::anyhow!("{e}"))?;
+        if let Some(parent) = local.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("cannot create {}", parent.display()))?;
+        }
+        let remote = remote_join(remote_root, &entry.path);
+        file_transfer::download_file(client, &remote, &local, limit_rate, |_, _| {})
+            .await
+            .map_err(|e| anyhow::anyhow!("{e}"))
+            .with_context(|| format!("download of {} failed", entry.path))?;
+    }
+    for rel in &plan.delete {
+        let local = join_relative(local_root, rel).map_err(|e| anyhow::anyhow!("{e}"))?;
+        std::fs::remove_file(&local)
+            .with_context(|| format!("cannot delete {}", local.display()))?;
+    }
+    Ok(())
+}
+
+/// Compare a source manifest against a destination manifest.
+fn plan_sync(source: &[SyncEntry], dest: &[SyncEntry], delete: bool) -> SyncPlan {
+    let dest_by_path: HashMap<&str, &SyncEntry> =
+        dest.iter().map(|e| (e.path.as_str(), e)).collect();
+    let transfer = source
+        .iter()
+        .filter(|e| {
+            !matches!(dest_by_path.get(e.path.as_str()),
+                Some(d) if d.hash == e.hash && d.size == e.size)
+        })
+        .cloned()
+        .collect();
+
+    let delete = if delete {
+        let source_paths: std::collections::HashSet<&str> =
+            source.iter().map(|e| e.path.as_str()).collect();
+        dest.iter()
+            .filter(|e| !source_paths.contains(e.path.as_str()))
+            .map(|e| e.path.clone())
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    SyncPlan { transfer, delete }
+}
+
+/// Append a manifest-relative path to a remote directory.
+fn remote_join(root: &str, rel: &str) -> String {
+    let root = root.trim_end_matches('/');
+    if root.is_empty() {
+        format!("/{rel}")
+    } else {
+        format!("{root}/{rel}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(path: &str, hash: &str) -> SyncEntry {
+        SyncEntry {
+            path: path.into(),
+            size: hash.len() as u64,
+            hash: hash.into(),
+        }
+    }
+
+    #[test]
+    fn plan_transfers_only_changed_files() {
+        let source = [entry("a", "1"), entry("b", "2"), entry("c", "3")];
+        let dest = [entry("a", "1"), entry("b", "x"), entry("stale", "9")];
+
+        let plan = plan_sync(&source, &dest, false);
+        let paths: Vec<_> = plan.transfer.iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(paths, ["b", "c"]);
+        assert!(plan.delete.is_empty());
+
+        let plan = plan_sync(&source, &dest, true);
+        assert_eq!(plan.delete, ["stale"]);
+    }
+
+    #[test]
+    fn remote_join_handles_trailing_slash() {
+        assert_eq!(remote_join("~/site/", "a/b.txt"), "~/site/a/b.txt");
+        assert_eq!(remote_join("data", "x"), "data/x");
+        assert_eq!(remote_join("/", "x"), "/x");
+    }
+}
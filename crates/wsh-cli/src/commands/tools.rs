@@ -2,8 +2,17 @@
 //!
 //! Connects to the specified (or configured default) host, sends an
 //! McpDiscover message, and prints the available tools in a table.
+//!
+//! `wsh tools invoke <host> <tool> --args '{...}'` calls one tool. While it
+//! runs, the elapsed time is shown on stderr (when the server reports
+//! progress and stderr is a terminal); the result is then printed as text,
+//! or as raw JSON with `--json`. A result carrying an `error` fails the
+//! command.
+
+use std::io::{IsTerminal, Write as _};
 
 use anyhow::{Context, Result};
+use serde_json::Value;
 use tracing::{debug, info};
 
 use crate::commands::common::{connect_client, resolve_target, save_last_session};
@@ -19,20 +28,11 @@ pub async fn run(
     let host = host.unwrap_or("localhost");
     info!(host = %host, "discovering MCP tools");
 
-    // Parse target (may include user@).
-    let (user, resolved_host) = if host.contains('@') {
-        parse_target(host)?
-    } else {
-        let user = std::env::var("USER")
-            .or_else(|_| std::env::var("USERNAME"))
-            .unwrap_or_else(|_| "root".into());
-        (user, host.to_string())
-    };
-    let target = format!("{user}@{resolved_host}");
+    let (target, resolved_host) = tools_target(host)?;
     let resolved = resolve_target(&target, port, transport)?;
     debug!(url = %resolved.url, user = %resolved.user, "transport URL");
     let client = connect_client(&resolved, identity).await?;
-    save_last_session(&resolved, port, identity)?;
+    save_last_session(&resolved, identity)?;
 
     let tools = wsh_client::mcp::discover_tools(&client)
         .await
@@ -55,3 +55,138 @@ pub async fn run(
 
     Ok(())
 }
+
+/// Call an MCP tool on a remote host and print its result.
+pub async fn run_invoke(
+    host: &str,
+    tool: &str,
+    args: &str,
+    json: bool,
+    port: u16,
+    identity: &str,
+    transport: Option<&str>,
+) -> Result<()> {
+    let args: Value = serde_json::from_str(args).context("--args is not valid JSON")?;
+    if !args.is_object() {
+        anyhow::bail!("--args must be a JSON object");
+    }
+    info!(host = %host, tool = %tool, "invoking MCP tool");
+
+    let (target, _) = tools_target(host)?;
+    let resolved = resolve_target(&target, port, transport)?;
+    debug!(url = %resolved.url, user = %resolved.user, "transport URL");
+    let client = connect_client(&resolved, identity).await?;
+    save_last_session(&resolved, identity)?;
+
+    let show_progress = std::io::stderr().is_terminal();
+    let mut reported = false;
+    let result = wsh_client::mcp::call_tool_with_progress(&client, tool, args, |progress| {
+        if show_progress {
+            eprint!(
+                "\r{}: running ({}s)",
+                progress.tool,
+                progress.elapsed_ms / 1000
+            );
+            let _ = std::io::stderr().flush();
+            reported = true;
+        }
+    })
+    .await;
+    if reported {
+        eprint!("\r\x1b[K");
+    }
+    let _ = client.disconnect().await;
+    let result = result
+        .map_err(|e| anyhow::anyhow!("{e}"))
+        .with_context(|| format!("failed to call MCP tool '{tool}'"))?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&result)?);
+    } else {
+        print!("{}", format_result(&result));
+    }
+    if let Some(error) = result.get("error") {
+        let error = error
+            .as_str()
+            .map_or_else(|| error.to_string(), str::to_string);
+        anyhow::bail!("{tool}: {error}");
+    }
+    Ok(())
+}
+
+/// `user@host` for a tools target, defaulting the user to the local one.
+fn tools_target(host: &str) -> Result<(String, String)> {
+    let (user, resolved_host) = if host.contains('@') {
+        parse_target(host)?
+    } else {
+        let user = std::env::var("USER")
+            .or_else(|_| std::env::var("USERNAME"))
+            .unwrap_or_else(|_| "root".into());
+        (user, host.to_string())
+    };
+    Ok((format!("{user}@{resolved_host}"), resolved_host))
+}
+
+/// Render a tool result for a terminal.
+///
+/// MCP `content` blocks print their text, `exec`-style results their
+/// output streams and exit code, plain strings as-is; anything else (and
+/// errors, which are reported separately) as pretty JSON.
+fn format_result(result: &Value) -> String {
+    if result.get("error").is_some() {
+        return String::new();
+    }
+    let mut out = String::new();
+    if let Some(blocks) = result.get("content").and_then(Value::as_array) {
+        for block in blocks {
+            match block.get("text").and_then(Value::as_str) {
+                Some(text) => out.push_str(text),
+                None => out.push_str(&block.to_string()),
+            }
+            if !out.ends_with('\n') {
+                out.push('\n');
+            }
+        }
+        return out;
+    }
+    if let Some(code) = result.get("exit_code").and_then(Value::as_i64) {
+        for stream in ["stdout", "stderr"] {
+            if let Some(text) = result.get(stream).and_then(Value::as_str) {
+                out.push_str(text);
+                if !text.is_empty() && !text.ends_with('\n') {
+                    out.push('\n');
+                }
+            }
+        }
+        if code != 0 {
+            out.push_str(&format!("[exit code {code}]\n"));
+        }
+        return out;
+    }
+    match result {
+        Value::String(text) => format!("{text}\n"),
+        other => format!(
+            "{}\n",
+            serde_json::to_string_pretty(other).unwrap_or_default()
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::format_result;
+    use serde_json::json;
+
+    #[test]
+    fn formats_content_exec_and_plain_results() {
+        let content = json!({ "content": [{ "type": "text", "text": "hello" }] });
+        assert_eq!(format_result(&content), "hello\n");
+
+        let exec = json!({ "stdout": "out\n", "stderr": "", "exit_code": 2, "truncated": false });
+        assert_eq!(format_result(&exec), "out\n[exit code 2]\n");
+
+        assert_eq!(format_result(&json!("done")), "done\n");
+        assert_eq!(format_result(&json!({ "n": 1 })), "{\n  \"n\": 1\n}\n");
+        assert_eq!(format_result(&json!({ "error": "nope" })), "");
+    }
+}
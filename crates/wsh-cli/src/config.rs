@@ -1,12 +1,46 @@
 //! Client configuration at `~/.wsh/config.toml`.
 //!
-//! Provides default host, port, identity, and transport settings.
-//! CLI flags always override config file values.
+//! Provides default host, port, identity, and transport settings, plus
+//! SSH-config-style `[[host]]` blocks matched against the host name given on
+//! the command line. Each setting is taken from the first matching block that
+//! sets it, so put specific patterns before catch-alls. CLI flags (and an
+//! explicit `user@`) always override config file values; `wsh config test
+//! <host>` prints the result.
+//!
+//! ```toml
+//! [default]
+//! host_key_checking = "accept-new"   # strict | ask | accept-new | off
+//! compression = true                 # same as -C
+//! transport_ttl = 86400              # seconds to remember a working transport (0 = never)
+//!
+//! [terminal]
+//! title = true        # let remote programs set the window title
+//! clipboard = false   # let remote programs write the clipboard (OSC 52)
+//!
+//! [[host]]
+//! pattern = "db"
+//! hostname = "db1.internal"
+//! user = "postgres"
+//! local_forward = ["5432:localhost:5432"]
+//!
+//! [[host]]
+//! pattern = "*.internal 10.0.*"
+//! port = 4423
+//! identity = "work"
+//! transport = "ws"
+//! transport_ttl = 3600
+//! proxy_jump = "ops@bastion.example.com"
+//! ```
 
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use std::path::Path;
-use tracing::debug;
+use std::sync::OnceLock;
+use tracing::{debug, warn};
+use wsh_client::HostKeyChecking;
+
+/// Configuration consulted when resolving targets, set once at startup.
+static ACTIVE: OnceLock<Config> = OnceLock::new();
 
 /// Top-level config file structure.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -14,16 +48,122 @@ pub struct Config {
     /// Default connection settings.
     #[serde(default)]
     pub default: DefaultConfig,
+
+    /// Interactive terminal settings.
+    #[serde(default)]
+    pub terminal: TerminalConfig,
+
+    /// Per-host settings; the first block whose pattern matches wins.
+    #[serde(default, rename = "host", skip_serializing_if = "Vec::is_empty")]
+    pub hosts: Vec<HostConfig>,
+
+    /// Jump hosts given with `-J`, taking precedence over any `proxy_jump`.
+    #[serde(skip)]
+    pub jump: Option<String>,
+
+    /// Settings given on the command line, taking precedence over `[[host]]`.
+    #[serde(skip)]
+    pub overrides: Overrides,
+}
+
+/// Connection settings passed as CLI flags.
+#[derive(Debug, Clone, Default)]
+pub struct Overrides {
+    /// `-p`
+    pub port: Option<u16>,
+    /// `-i`
+    pub identity: Option<String>,
+    /// `-t`
+    pub transport: Option<String>,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
             default: DefaultConfig::default(),
+            terminal: TerminalConfig::default(),
+            hosts: Vec::new(),
+            jump: None,
+            overrides: Overrides::default(),
         }
     }
 }
 
+/// Settings applied to targets matching a host pattern.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HostConfig {
+    /// Whitespace-separated glob patterns (`*` and `?`) matched against the host.
+    pub pattern: String,
+
+    /// Real host name to connect to, if the target is an alias.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+
+    /// Remote user when the target has no `user@`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+
+    /// Server port.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+
+    /// Identity (key name).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub identity: Option<String>,
+
+    /// Transport preference: "auto", "ws", or "wt".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transport: Option<String>,
+
+    /// Seconds an automatically chosen transport is remembered (0 = never).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transport_ttl: Option<u64>,
+
+    /// Comma-separated `[user@]host[:port]` jump hosts, or `"none"`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy_jump: Option<String>,
+
+    /// `wsh forward -L` specs opened for this host.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub local_forward: Vec<String>,
+
+    /// `wsh forward -R` specs opened for this host.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub remote_forward: Vec<String>,
+
+    /// `wsh forward -D` specs opened for this host.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dynamic_forward: Vec<String>,
+}
+
+impl HostConfig {
+    /// Whether any of this block's patterns matches `host`.
+    pub fn matches(&self, host: &str) -> bool {
+        self.pattern
+            .split_whitespace()
+            .any(|pattern| glob_match(pattern.as_bytes(), host.as_bytes()))
+    }
+}
+
+/// The `[[host]]` settings in effect for one host, merged across every
+/// matching block.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HostSettings {
+    /// Patterns of the blocks that matched, in file order.
+    pub patterns: Vec<String>,
+    pub hostname: Option<String>,
+    pub user: Option<String>,
+    pub port: Option<u16>,
+    pub identity: Option<String>,
+    pub transport: Option<String>,
+    pub transport_ttl: Option<u64>,
+    pub proxy_jump: Option<String>,
+    /// Forwards accumulate across blocks, like OpenSSH's `LocalForward`.
+    pub local_forward: Vec<String>,
+    pub remote_forward: Vec<String>,
+    pub dynamic_forward: Vec<String>,
+}
+
 /// Default connection settings.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DefaultConfig {
@@ -42,6 +182,23 @@ pub struct DefaultConfig {
     /// Transport preference: "auto", "ws", or "wt".
     #[serde(default = "default_transport")]
     pub transport: String,
+
+    /// Seconds to keep trying first the transport that last reached a host
+    /// under `auto`, before probing WebTransport again (0 = never remember).
+    #[serde(default = "default_transport_ttl")]
+    pub transport_ttl: u64,
+
+    /// Seconds between keepalive pings on interactive sessions (0 = off).
+    #[serde(default = "default_keepalive")]
+    pub keepalive: u64,
+
+    /// Host key checking mode: "strict", "ask", "accept-new", or "off".
+    #[serde(default = "default_host_key_checking")]
+    pub host_key_checking: String,
+
+    /// Ask the server to compress PTY output and file transfers.
+    #[serde(default)]
+    pub compression: bool,
 }
 
 impl Default for DefaultConfig {
@@ -51,10 +208,42 @@ impl Default for DefaultConfig {
             port: default_port(),
             identity: default_identity(),
             transport: default_transport(),
+            transport_ttl: default_transport_ttl(),
+            keepalive: default_keepalive(),
+            host_key_checking: default_host_key_checking(),
+            compression: false,
         }
     }
 }
 
+/// Which OSC sequences from remote programs reach the local terminal in
+/// interactive sessions.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TerminalConfig {
+    /// Pass window title changes (OSC 0/1/2) through.
+    #[serde(default = "default_true")]
+    pub title: bool,
+
+    /// Pass clipboard writes (OSC 52) through. Off by default, since any
+    /// program on the remote host could replace the local clipboard;
+    /// clipboard reads are never passed through.
+    #[serde(default)]
+    pub clipboard: bool,
+}
+
+impl Default for TerminalConfig {
+    fn default() -> Self {
+        Self {
+            title: true,
+            clipboard: false,
+        }
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
 fn default_port() -> u16 {
     4422
 }
@@ -67,6 +256,18 @@ fn default_transport() -> String {
     "auto".to_string()
 }
 
+fn default_transport_ttl() -> u64 {
+    wsh_client::transport::profiles::DEFAULT_PROFILE_TTL.as_secs()
+}
+
+fn default_keepalive() -> u64 {
+    30
+}
+
+fn default_host_key_checking() -> String {
+    HostKeyChecking::default().to_string()
+}
+
 impl Config {
     /// Load configuration from a TOML file, returning defaults if the file
     /// does not exist.
@@ -100,6 +301,79 @@ impl Config {
 
         Ok(())
     }
+
+    /// Make this the configuration used by [`Config::active`].
+    pub fn install(self) {
+        let _ = ACTIVE.set(self);
+    }
+
+    /// The installed configuration, or defaults if none was installed.
+    pub fn active() -> &'static Config {
+        ACTIVE.get_or_init(Config::default)
+    }
+
+    /// The configured host key checking mode; an unrecognised value falls
+    /// back to `strict` rather than silently trusting hosts.
+    pub fn host_key_checking(&self) -> HostKeyChecking {
+        self.default.host_key_checking.parse().unwrap_or_else(|e| {
+            warn!("{e}; using strict host key checking");
+            HostKeyChecking::Strict
+        })
+    }
+
+    /// Settings from every `[[host]]` block matching `host`; each one comes
+    /// from the first block that sets it.
+    pub fn host_settings(&self, host: &str) -> HostSettings {
+        let mut settings = HostSettings::default();
+        for block in self.hosts.iter().filter(|block| block.matches(host)) {
+            settings.patterns.push(block.pattern.clone());
+            settings.hostname = settings.hostname.or_else(|| block.hostname.clone());
+            settings.user = settings.user.or_else(|| block.user.clone());
+            settings.port = settings.port.or(block.port);
+            settings.identity = settings.identity.or_else(|| block.identity.clone());
+            settings.transport = settings.transport.or_else(|| block.transport.clone());
+            settings.transport_ttl = settings.transport_ttl.or(block.transport_ttl);
+            settings.proxy_jump = settings.proxy_jump.or_else(|| block.proxy_jump.clone());
+            settings
+                .local_forward
+                .extend(block.local_forward.iter().cloned());
+            settings
+                .remote_forward
+                .extend(block.remote_forward.iter().cloned());
+            settings
+                .dynamic_forward
+                .extend(block.dynamic_forward.iter().cloned());
+        }
+        settings
+    }
+
+    /// Jump hosts to pass through on the way to `host`, in connection order.
+    pub fn jump_hosts_for(&self, host: &str) -> Vec<String> {
+        let block_spec = self.host_settings(host).proxy_jump;
+        let spec = self.jump.as_deref().or(block_spec.as_deref());
+        match spec {
+            None => Vec::new(),
+            Some(spec) if spec.trim().eq_ignore_ascii_case("none") => Vec::new(),
+            Some(spec) => spec
+                .split(',')
+                .map(str::trim)
+                .filter(|hop| !hop.is_empty())
+                .map(str::to_string)
+                .collect(),
+        }
+    }
+}
+
+/// Case-insensitive glob match supporting `*` and `?`.
+fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some((b'*', rest)) => (0..=text.len()).any(|skip| glob_match(rest, &text[skip..])),
+        Some((b'?', rest)) => !text.is_empty() && glob_match(rest, &text[1..]),
+        Some((c, rest)) => text
+            .split_first()
+            .is_some_and(|(t, tail)| c.eq_ignore_ascii_case(t) && glob_match(rest, tail)),
+    }
 }
 
 /// Parse a `[user@]host` string into `(user, host)`.
@@ -166,6 +440,7 @@ mod tests {
         assert_eq!(cfg.default.port, 4422);
         assert_eq!(cfg.default.identity, "default");
         assert_eq!(cfg.default.transport, "auto");
+        assert_eq!(cfg.default.keepalive, 30);
         assert!(cfg.default.host.is_empty());
     }
 
@@ -196,4 +471,71 @@ host = "example.com"
         assert_eq!(cfg.default.port, 4422); // default
         assert_eq!(cfg.default.identity, "default"); // default
     }
+
+    #[test]
+    fn host_blocks_supply_proxy_jump() {
+        let toml_str = r#"
+[[host]]
+pattern = "db.internal"
+proxy_jump = "none"
+
+[[host]]
+pattern = "*.internal 10.0.?.*"
+proxy_jump = "ops@bastion:4423, gw2"
+"#;
+        let mut cfg: Config = toml::from_str(toml_str).unwrap();
+        assert!(cfg.jump_hosts_for("db.internal").is_empty());
+        assert_eq!(
+            cfg.jump_hosts_for("API.Internal"),
+            vec!["ops@bastion:4423", "gw2"]
+        );
+        assert_eq!(
+            cfg.jump_hosts_for("10.0.3.7"),
+            vec!["ops@bastion:4423", "gw2"]
+        );
+        assert!(cfg.jump_hosts_for("example.com").is_empty());
+
+        cfg.jump = Some("edge".into());
+        assert_eq!(cfg.jump_hosts_for("db.internal"), vec!["edge"]);
+    }
+
+    #[test]
+    fn host_settings_merge_first_match_per_field() {
+        let toml_str = r#"
+[[host]]
+pattern = "db"
+hostname = "db1.internal"
+user = "postgres"
+local_forward = ["5432:localhost:5432"]
+
+[[host]]
+pattern = "db *.internal"
+user = "ops"
+port = 4423
+identity = "work"
+local_forward = ["9187:localhost:9187"]
+
+[[host]]
+pattern = "*"
+transport = "ws"
+port = 4422
+"#;
+        let cfg: Config = toml::from_str(toml_str).unwrap();
+        let db = cfg.host_settings("db");
+        assert_eq!(db.patterns, vec!["db", "db *.internal", "*"]);
+        assert_eq!(db.hostname.as_deref(), Some("db1.internal"));
+        assert_eq!(db.user.as_deref(), Some("postgres"));
+        assert_eq!(db.port, Some(4423));
+        assert_eq!(db.identity.as_deref(), Some("work"));
+        assert_eq!(db.transport.as_deref(), Some("ws"));
+        assert_eq!(
+            db.local_forward,
+            vec!["5432:localhost:5432", "9187:localhost:9187"]
+        );
+
+        let other = cfg.host_settings("example.com");
+        assert_eq!(other.patterns, vec!["*"]);
+        assert_eq!(other.hostname, None);
+        assert_eq!(other.port, Some(4422));
+    }
 }
@@ -1,95 +1,73 @@
-//! Known hosts verification for the CLI.
+//! Known hosts prompts for the CLI.
 //!
-//! Wraps `wsh_client::KnownHosts` with interactive user prompts via dialoguer.
-//! On first connection, the user is prompted to accept the server's key.
-//! On subsequent connections, the stored fingerprint is compared and a
-//! warning is shown if it has changed.
+//! `wsh_client` checks host keys against `~/.wsh/known_hosts` according to
+//! the configured [`wsh_client::HostKeyChecking`] mode; in `ask` mode it calls
+//! [`prompt_new_host`] on first connection, which shows the key's fingerprint
+//! and randomart and asks the user to accept it. A changed key the server
+//! proved it rotated is updated without asking; any other changed key is
+//! shown side by side with the old one by [`prompt_changed_host`], which
+//! only replaces it if the user types `yes`. Revoked keys are always
+//! rejected.
 
-use anyhow::{Context, Result};
-use dialoguer::Confirm;
-use tracing::{debug, warn};
+use dialoguer::{Confirm, Input};
+use tracing::debug;
 
-/// Check a server's fingerprint against known_hosts and prompt the user
-/// if the host is unknown or the fingerprint has changed.
+/// Ask whether to trust a host seen for the first time.
 ///
-/// Returns `Ok(())` if the user accepts the host, or an error if they reject it.
-pub fn verify(host: &str, port: u16, server_fingerprint: &str) -> Result<()> {
-    let known_hosts = wsh_client::KnownHosts::default_location()
-        .map_err(|e| anyhow::anyhow!("{e}"))
-        .context("failed to initialize known_hosts")?;
+/// Returns `false` if the user declines or no answer can be read.
+pub fn prompt_new_host(host: &str, fingerprint: &str) -> bool {
+    let short_fp = &fingerprint[..fingerprint.len().min(16)];
+    eprintln!("The authenticity of host '{host}' cannot be established.");
+    eprintln!("Server key fingerprint is {short_fp}.");
+    eprintln!("{}", wsh_client::known_hosts::randomart(fingerprint));
 
-    let host_key = format!("{host}:{port}");
+    let accept = Confirm::new()
+        .with_prompt("Are you sure you want to continue connecting?")
+        .default(false)
+        .interact()
+        .unwrap_or(false);
 
-    let status = known_hosts
-        .verify_host(&host_key, server_fingerprint)
-        .map_err(|e| anyhow::anyhow!("{e}"))?;
-
-    match status {
-        wsh_client::HostStatus::Known => {
-            debug!(host = %host_key, "host verified");
-            Ok(())
-        }
-        wsh_client::HostStatus::Unknown => {
-            let short_fp = &server_fingerprint[..server_fingerprint.len().min(16)];
-            eprintln!("The authenticity of host '{host_key}' cannot be established.");
-            eprintln!("Server key fingerprint is {short_fp}.");
-
-            let accept = Confirm::new()
-                .with_prompt("Are you sure you want to continue connecting?")
-                .default(false)
-                .interact()
-                .context("failed to read user input")?;
-
-            if !accept {
-                anyhow::bail!("host key verification failed — connection aborted by user");
-            }
-
-            // Store the fingerprint.
-            known_hosts
-                .add_host(&host_key, server_fingerprint)
-                .map_err(|e| anyhow::anyhow!("{e}"))?;
-            eprintln!("Warning: Permanently added '{host_key}' to the list of known hosts.");
-
-            Ok(())
-        }
-        wsh_client::HostStatus::Changed { expected } => {
-            let short_expected = &expected[..expected.len().min(16)];
-            let short_new = &server_fingerprint[..server_fingerprint.len().min(16)];
-
-            eprintln!("@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@");
-            eprintln!("@    WARNING: REMOTE HOST IDENTIFICATION HAS CHANGED!    @");
-            eprintln!("@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@");
-            eprintln!("IT IS POSSIBLE THAT SOMEONE IS DOING SOMETHING NASTY!");
-            eprintln!("The server key fingerprint for '{host_key}' has changed.");
-            eprintln!("  Previous: {short_expected}");
-            eprintln!("  Current:  {short_new}");
-
-            warn!(
-                host = %host_key,
-                old_fp = %short_expected,
-                new_fp = %short_new,
-                "host key has changed"
-            );
-
-            let accept = Confirm::new()
-                .with_prompt("Do you want to update the known host and continue?")
-                .default(false)
-                .interact()
-                .context("failed to read user input")?;
-
-            if !accept {
-                anyhow::bail!(
-                    "host key verification failed — fingerprint mismatch for '{host_key}'"
-                );
-            }
+    if accept {
+        eprintln!("Warning: Permanently added '{host}' to the list of known hosts.");
+    } else {
+        debug!(host = %host, "host key rejected by user");
+    }
+    accept
+}
 
-            // Update the stored fingerprint.
-            known_hosts
-                .add_host(&host_key, server_fingerprint)
-                .map_err(|e| anyhow::anyhow!("{e}"))?;
-            eprintln!("Warning: Updated host key for '{host_key}'.");
+/// Ask whether to replace a host key that changed without a rotation proof.
+///
+/// Shows both fingerprints and randomarts; only a typed `yes` accepts, so
+/// the warning cannot be skipped with a stray Enter.
+pub fn prompt_changed_host(host: &str, old_fingerprint: &str, new_fingerprint: &str) -> bool {
+    eprintln!("WARNING: THE HOST KEY FOR '{host}' HAS CHANGED!");
+    eprintln!("The server did not prove it holds the previously trusted key.");
+    eprintln!("Someone could be intercepting this connection (man-in-the-middle).");
+    eprintln!();
+    eprintln!("- old {old_fingerprint}");
+    eprintln!("+ new {new_fingerprint}");
+    eprintln!();
+    let old_art = wsh_client::known_hosts::randomart(old_fingerprint);
+    let new_art = wsh_client::known_hosts::randomart(new_fingerprint);
+    let width = old_art
+        .lines()
+        .map(|l| l.chars().count())
+        .max()
+        .unwrap_or(0);
+    for (old, new) in old_art.lines().zip(new_art.lines()) {
+        eprintln!("{old:<width$}   {new}");
+    }
 
-            Ok(())
-        }
+    let answer: String = Input::new()
+        .with_prompt("Type 'yes' to replace the key in known_hosts")
+        .allow_empty(true)
+        .interact_text()
+        .unwrap_or_default();
+    let accept = answer.trim() == "yes";
+    if accept {
+        eprintln!("Warning: Replaced the host key for '{host}' in the list of known hosts.");
+    } else {
+        debug!(host = %host, "changed host key rejected by user");
     }
+    accept
 }
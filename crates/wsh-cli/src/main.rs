@@ -12,7 +12,9 @@ mod known_hosts;
 mod terminal;
 
 use clap::{Parser, Subcommand};
+use std::path::PathBuf;
 use tracing::error;
+use wsh_core::flow::parse_rate;
 
 /// wsh — Web Shell client
 #[derive(Parser)]
@@ -22,23 +24,30 @@ use tracing::error;
     about = "Web Shell client — SSH-like remote access over WebTransport/WebSocket"
 )]
 struct Cli {
-    /// Server port
-    #[arg(short, long, global = true, default_value_t = 4422)]
-    port: u16,
-
-    /// Key name to use for authentication
-    #[arg(
-        short = 'i',
-        long = "identity",
-        global = true,
-        default_value = "default"
-    )]
-    identity: String,
+    /// Server port [default: 4422, or `port` in the config file]
+    #[arg(short, long, global = true)]
+    port: Option<u16>,
+
+    /// Key name to use for authentication [default: "default", or `identity` in the config file]
+    #[arg(short = 'i', long = "identity", global = true)]
+    identity: Option<String>,
 
     /// Force transport type (ws or wt)
     #[arg(short = 't', long = "transport", global = true)]
     transport: Option<String>,
 
+    /// Connect through jump hosts ([user@]host[:port], comma-separated)
+    #[arg(short = 'J', long = "jump", global = true, value_name = "HOSTS")]
+    jump: Option<String>,
+
+    /// Host key checking: strict, ask, accept-new, or off (overrides config)
+    #[arg(long, global = true, value_name = "MODE")]
+    host_key_checking: Option<wsh_client::HostKeyChecking>,
+
+    /// Compress PTY output and file transfers (overrides config)
+    #[arg(short = 'C', long = "compress", global = true)]
+    compress: bool,
+
     /// Config file path
     #[arg(long = "config", global = true)]
     config: Option<String>,
@@ -64,10 +73,26 @@ enum Command {
     Connect {
         /// Target in [user@]host format
         target: String,
+
+        /// Record the session to an asciicast v2 file
+        #[arg(long, value_name = "FILE")]
+        record: Option<PathBuf>,
+
+        /// Forward the local key agent (`WSH_AUTH_SOCK`) to the remote host
+        #[arg(short = 'A', long)]
+        forward_agent: bool,
+
+        /// Seconds between keepalive pings (0 = off; default from config, else 30)
+        #[arg(long, value_name = "SECS")]
+        keepalive: Option<u64>,
     },
 
     /// List active sessions
-    Sessions,
+    Sessions {
+        /// Emit JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
 
     /// Reattach to a named session
     Attach {
@@ -80,18 +105,81 @@ enum Command {
 
     /// Generate an Ed25519 key pair
     Keygen {
-        /// Key name
+        /// Key name (with --sign: the stored key or `.pub` file to certify)
         #[arg(default_value = "default")]
         name: String,
+
+        /// Issue a certificate for NAME signed by this CA key instead of generating a key
+        #[arg(long, value_name = "CA_KEY")]
+        sign: Option<String>,
+
+        /// Comma-separated user names (host names with --host) the certificate is valid for
+        #[arg(long, value_delimiter = ',', requires = "sign")]
+        principals: Vec<String>,
+
+        /// Issue a host certificate instead of a user certificate
+        #[arg(long, requires = "sign")]
+        host: bool,
+
+        /// Certificate lifetime in days
+        #[arg(long, default_value_t = 365)]
+        valid_days: u64,
+
+        /// Identifier recorded in the certificate (defaults to NAME)
+        #[arg(long, requires = "sign")]
+        key_id: Option<String>,
+
+        /// Encrypt the new private key with a passphrase (prompted)
+        #[arg(long, conflicts_with_all = ["sign", "change_passphrase"])]
+        passphrase: bool,
+
+        /// Change, add or remove (empty input) the passphrase of existing key NAME
+        #[arg(long, conflicts_with = "sign")]
+        change_passphrase: bool,
+
+        /// Save the passphrase in the OS keychain so connecting needs no prompt
+        #[arg(long, conflicts_with = "sign")]
+        keychain: bool,
     },
 
     /// List stored keys with fingerprints
-    Keys,
+    Keys {
+        #[command(subcommand)]
+        command: Option<KeysCommand>,
+    },
+
+    /// Hold keys in memory for signing and agent forwarding
+    KeyAgent {
+        #[command(subcommand)]
+        command: KeyAgentCommand,
+    },
+
+    /// Share one connection between exec and scp commands (control master)
+    Mux {
+        #[command(subcommand)]
+        command: MuxCommand,
+    },
 
     /// Copy public key to a remote host
     CopyId {
         /// Target in [user@]host format
         target: String,
+
+        /// List the remote authorized keys instead of installing
+        #[arg(long, conflicts_with_all = ["expiry", "forced_command", "key_options"])]
+        list: bool,
+
+        /// Expire the installed key (YYYYMMDD[HHMM[SS]], UTC)
+        #[arg(long, value_name = "TIME")]
+        expiry: Option<String>,
+
+        /// Restrict the installed key to a forced command
+        #[arg(long = "command", value_name = "CMD")]
+        forced_command: Option<String>,
+
+        /// Extra authorized_keys option for the installed key (repeatable, e.g. no-pty)
+        #[arg(short = 'o', long = "option", value_name = "OPT")]
+        key_options: Vec<String>,
     },
 
     /// Transfer files (use [user@]host:path syntax)
@@ -100,6 +188,64 @@ enum Command {
         src: String,
         /// Destination path (local or [user@]host:path)
         dst: String,
+
+        /// Cap bandwidth in bytes per second (suffixes K, M, G: `--limit-rate 500K`)
+        #[arg(long, value_name = "RATE", value_parser = parse_rate)]
+        limit_rate: Option<u64>,
+    },
+
+    /// Replay an asciicast recording (from `wsh connect --record`)
+    Play {
+        /// Path to the .cast file
+        file: PathBuf,
+
+        /// Playback speed multiplier
+        #[arg(long, default_value_t = 1.0)]
+        speed: f64,
+
+        /// Cap pauses between output at this many seconds
+        #[arg(long, value_name = "SECS")]
+        idle_limit: Option<f64>,
+    },
+
+    /// Sync a directory tree, sending only changed files (like rsync)
+    Sync {
+        /// Source directory (local or [user@]host:path)
+        src: String,
+        /// Destination directory (local or [user@]host:path)
+        dst: String,
+
+        /// Delete destination files that do not exist in the source
+        #[arg(long)]
+        delete: bool,
+
+        /// Show what would be transferred or deleted without doing it
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+
+        /// Cap bandwidth in bytes per second (suffixes K, M, G: `--limit-rate 500K`)
+        #[arg(long, value_name = "RATE", value_parser = parse_rate)]
+        limit_rate: Option<u64>,
+    },
+
+    /// Forward TCP ports and unix sockets over the session (like ssh -L / -R / -D)
+    Forward {
+        /// Target in [user@]host format
+        target: String,
+
+        /// Local forward: [bind_addr:]port:host:hostport (listen here, dial remotely);
+        /// either side may be a unix socket path instead
+        #[arg(short = 'L', long = "local")]
+        local: Vec<String>,
+
+        /// Remote forward: [bind_addr:]port:host:hostport (listen remotely, dial here);
+        /// either side may be a unix socket path instead
+        #[arg(short = 'R', long = "remote")]
+        remote: Vec<String>,
+
+        /// Dynamic SOCKS5 forward: [bind_addr:]port (destinations chosen per connection)
+        #[arg(short = 'D', long = "dynamic")]
+        dynamic: Vec<String>,
     },
 
     /// Register as a reverse-connectable peer
@@ -110,6 +256,10 @@ enum Command {
         /// Capabilities to expose (`shell`, `exec`, `fs`, `tools`, `gateway`)
         #[arg(long = "capability")]
         capabilities: Vec<String>,
+
+        /// Name to register, so peers can reach this host as `@name`
+        #[arg(long)]
+        name: Option<String>,
     },
 
     /// Run a long-lived reverse-host agent
@@ -138,6 +288,10 @@ enum Command {
         /// Filter peers by required capability
         #[arg(long)]
         capability: Option<String>,
+
+        /// Keep running and report peers as they join and leave
+        #[arg(long)]
+        watch: bool,
     },
 
     /// Reverse connect to a browser peer via relay
@@ -148,6 +302,12 @@ enum Command {
         relay_host: Option<String>,
     },
 
+    /// Inspect the client configuration (~/.wsh/config.toml)
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+
     /// Run self-checks for common relay/bootstrap failures
     Check {
         #[command(subcommand)]
@@ -155,9 +315,30 @@ enum Command {
     },
 
     /// List MCP tools available on a remote host
+    #[command(args_conflicts_with_subcommands = true)]
     Tools {
         /// Target host (optional, uses config default)
         host: Option<String>,
+
+        #[command(subcommand)]
+        command: Option<ToolsCommand>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ToolsCommand {
+    /// Call an MCP tool on a remote host and print its result
+    Invoke {
+        /// Target in [user@]host format
+        host: String,
+        /// Tool name, as listed by `wsh tools`
+        tool: String,
+        /// Tool arguments as a JSON object
+        #[arg(long, value_name = "JSON", default_value = "{}")]
+        args: String,
+        /// Print the raw JSON result instead of formatting it
+        #[arg(long)]
+        json: bool,
     },
 }
 
@@ -175,6 +356,10 @@ enum AgentCommand {
         /// Capabilities to expose (`shell`, `exec`, `fs`, `tools`, `gateway`)
         #[arg(long = "capability")]
         capabilities: Vec<String>,
+
+        /// Name to register, so peers can reach this host as `@name`
+        #[arg(long)]
+        name: Option<String>,
     },
 
     /// Install a user-level startup unit for the reverse-host agent
@@ -216,6 +401,73 @@ enum AgentCommand {
     },
 }
 
+#[derive(Subcommand)]
+enum KeysCommand {
+    /// Remove a key from a remote host's authorized_keys
+    RevokeRemote {
+        /// Target in [user@]host format
+        target: String,
+        /// Fingerprint prefix or local key name
+        key: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum KeyAgentCommand {
+    /// Run the agent in the foreground and print the socket to export
+    Start {
+        /// Socket path (default: ~/.wsh/agent.sock)
+        #[arg(long)]
+        socket: Option<PathBuf>,
+
+        /// Keystore keys to load at startup
+        #[arg(long = "add", value_name = "NAME")]
+        keys: Vec<String>,
+    },
+
+    /// Load a keystore key into the running agent (default: the -i identity)
+    Add {
+        /// Key name
+        name: Option<String>,
+    },
+
+    /// List keys held by the running agent
+    List,
+
+    /// Remove all keys from the running agent
+    Clear,
+}
+
+#[derive(Subcommand)]
+enum MuxCommand {
+    /// Connect and serve the shared connection in the foreground
+    Start {
+        /// Target in [user@]host format
+        target: String,
+    },
+
+    /// Report whether a master is running for a target
+    Check {
+        /// Target in [user@]host format
+        target: String,
+    },
+
+    /// Ask a target's master to disconnect and exit
+    Stop {
+        /// Target in [user@]host format
+        target: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// Show the effective settings for a target without connecting
+    Test {
+        /// Target in [user@]host format
+        target: String,
+    },
+}
+
 #[derive(Subcommand)]
 enum CheckCommand {
     /// Check local key, known_hosts, relay connectivity, and relay auth
@@ -254,11 +506,24 @@ async fn main() {
             .to_string_lossy()
             .to_string()
     });
-    let cfg = config::Config::load(&config_path).unwrap_or_default();
+    let mut cfg = config::Config::load(&config_path).unwrap_or_default();
+    cfg.jump = cli.jump.clone();
+    if let Some(mode) = cli.host_key_checking {
+        cfg.default.host_key_checking = mode.to_string();
+    }
+    if cli.compress {
+        cfg.default.compression = true;
+    }
 
-    // Determine effective port, transport, and identity (CLI overrides config).
-    let port = cli.port;
-    let identity = cli.identity.clone();
+    // Determine effective port, transport, and identity (CLI overrides config;
+    // `[[host]]` blocks are applied per target in `resolve_target`).
+    cfg.overrides = config::Overrides {
+        port: cli.port,
+        identity: cli.identity.clone(),
+        transport: cli.transport.clone(),
+    };
+    let port = cli.port.unwrap_or(cfg.default.port);
+    let identity = cli.identity.clone().unwrap_or(cfg.default.identity.clone());
     let transport = cli.transport.clone().or_else(|| {
         let t = cfg.default.transport.clone();
         if t == "auto" {
@@ -267,27 +532,175 @@ async fn main() {
             Some(t)
         }
     });
+    cfg.install();
 
     let result = match cli.command {
-        Some(Command::Connect { target }) => {
-            commands::connect::run(&target, port, &identity, transport.as_deref()).await
+        Some(Command::Connect {
+            target,
+            record,
+            forward_agent,
+            keepalive,
+        }) => {
+            commands::connect::run(
+                &target,
+                port,
+                &identity,
+                transport.as_deref(),
+                record.as_deref(),
+                forward_agent,
+                keepalive,
+            )
+            .await
         }
-        Some(Command::Sessions) => commands::sessions::run_list().await,
+        Some(Command::Sessions { json }) => commands::sessions::run_list(json).await,
         Some(Command::Attach { session }) => {
             commands::sessions::run_attach(&session, port, &identity, transport.as_deref()).await
         }
         Some(Command::Detach) => commands::sessions::run_detach().await,
-        Some(Command::Keygen { name }) => commands::keygen::run(&name).await,
-        Some(Command::Keys) => commands::keys::run().await,
-        Some(Command::CopyId { target }) => {
-            commands::copy_id::run(&target, port, &identity, transport.as_deref()).await
+        Some(Command::Keygen {
+            name,
+            sign: Some(ca),
+            principals,
+            host,
+            valid_days,
+            key_id,
+            ..
+        }) => {
+            let request = commands::keygen::SignRequest {
+                subject: name,
+                ca,
+                principals,
+                host,
+                valid_days,
+                key_id,
+            };
+            commands::keygen::run_sign(request).await
+        }
+        Some(Command::Keygen {
+            name,
+            change_passphrase: true,
+            keychain,
+            ..
+        }) => commands::keygen::run_change_passphrase(&name, keychain).await,
+        Some(Command::Keygen {
+            name,
+            passphrase,
+            keychain,
+            ..
+        }) => commands::keygen::run(&name, passphrase, keychain).await,
+        Some(Command::Keys { command: None }) => commands::keys::run().await,
+        Some(Command::Keys {
+            command: Some(KeysCommand::RevokeRemote { target, key }),
+        }) => {
+            commands::keys::run_revoke_remote(&target, &key, port, &identity, transport.as_deref())
+                .await
+        }
+        #[cfg(unix)]
+        Some(Command::KeyAgent { command }) => match command {
+            KeyAgentCommand::Start { socket, keys } => {
+                commands::key_agent::run_start(socket, &keys).await
+            }
+            KeyAgentCommand::Add { name } => {
+                commands::key_agent::run_add(name.as_deref().unwrap_or(&identity)).await
+            }
+            KeyAgentCommand::List => commands::key_agent::run_list().await,
+            KeyAgentCommand::Clear => commands::key_agent::run_clear().await,
+        },
+        #[cfg(not(unix))]
+        Some(Command::KeyAgent { .. }) => Err(anyhow::anyhow!(
+            "the key agent requires unix domain sockets"
+        )),
+        #[cfg(unix)]
+        Some(Command::Mux { command }) => match command {
+            MuxCommand::Start { target } => {
+                commands::mux::run_start(&target, port, &identity, transport.as_deref()).await
+            }
+            MuxCommand::Check { target } => {
+                commands::mux::run_check(&target, port, transport.as_deref()).await
+            }
+            MuxCommand::Stop { target } => {
+                commands::mux::run_stop(&target, port, transport.as_deref()).await
+            }
+        },
+        #[cfg(not(unix))]
+        Some(Command::Mux { .. }) => Err(anyhow::anyhow!(
+            "connection sharing requires unix domain sockets"
+        )),
+        Some(Command::CopyId {
+            target,
+            list,
+            expiry,
+            forced_command,
+            key_options,
+        }) => {
+            let opts = commands::copy_id::CopyIdOptions {
+                list,
+                expiry,
+                command: forced_command,
+                options: key_options,
+            };
+            commands::copy_id::run(&target, port, &identity, transport.as_deref(), opts).await
+        }
+        Some(Command::Scp {
+            src,
+            dst,
+            limit_rate,
+        }) => {
+            commands::scp::run(
+                &src,
+                &dst,
+                limit_rate,
+                port,
+                &identity,
+                transport.as_deref(),
+            )
+            .await
+        }
+        Some(Command::Play {
+            file,
+            speed,
+            idle_limit,
+        }) => commands::play::run(&file, speed, idle_limit).await,
+        Some(Command::Sync {
+            src,
+            dst,
+            delete,
+            dry_run,
+            limit_rate,
+        }) => {
+            commands::sync::run(
+                &src,
+                &dst,
+                delete,
+                dry_run,
+                limit_rate,
+                port,
+                &identity,
+                transport.as_deref(),
+            )
+            .await
         }
-        Some(Command::Scp { src, dst }) => {
-            commands::scp::run(&src, &dst, port, &identity, transport.as_deref()).await
+        Some(Command::Forward {
+            target,
+            local,
+            remote,
+            dynamic,
+        }) => {
+            commands::forward::run(
+                &target,
+                &local,
+                &remote,
+                &dynamic,
+                port,
+                &identity,
+                transport.as_deref(),
+            )
+            .await
         }
         Some(Command::Reverse {
             relay_host,
             capabilities,
+            name,
         }) => {
             commands::relay::run_reverse(
                 &relay_host,
@@ -295,6 +708,7 @@ async fn main() {
                 &identity,
                 transport.as_deref(),
                 &capabilities,
+                name.as_deref(),
             )
             .await
         }
@@ -303,6 +717,7 @@ async fn main() {
                 relay_host,
                 reconnect_delay_secs,
                 capabilities,
+                name,
             } => {
                 commands::agent::run(
                     &relay_host,
@@ -311,6 +726,7 @@ async fn main() {
                     transport.as_deref(),
                     reconnect_delay_secs,
                     &capabilities,
+                    name.as_deref(),
                 )
                 .await
             }
@@ -352,12 +768,14 @@ async fn main() {
             peer_type,
             shell_backend,
             capability,
+            watch,
         }) => {
             let options = commands::relay::PeerQueryOptions {
                 json,
                 peer_type,
                 shell_backend,
                 capability,
+                watch,
             };
             commands::relay::run_peers(
                 &relay_host,
@@ -384,9 +802,36 @@ async fn main() {
                     .await
             }
         },
-        Some(Command::Tools { host }) => {
-            commands::tools::run(host.as_deref(), port, &identity, transport.as_deref()).await
+        Some(Command::Config { command }) => match command {
+            ConfigCommand::Test { target } => {
+                commands::config::run_test(&target, port, &identity, transport.as_deref())
+            }
+        },
+        Some(Command::Tools {
+            command:
+                Some(ToolsCommand::Invoke {
+                    host,
+                    tool,
+                    args,
+                    json,
+                }),
+            ..
+        }) => {
+            commands::tools::run_invoke(
+                &host,
+                &tool,
+                &args,
+                json,
+                port,
+                &identity,
+                transport.as_deref(),
+            )
+            .await
         }
+        Some(Command::Tools {
+            host,
+            command: None,
+        }) => commands::tools::run(host.as_deref(), port, &identity, transport.as_deref()).await,
         None => {
             // Positional args mode: wsh [user@]host [command...]
             if cli.args.is_empty() {
@@ -401,7 +846,16 @@ async fn main() {
                 commands::exec::run(target, &command, port, &identity, transport.as_deref()).await
             } else {
                 // Interactive connect: wsh user@host
-                commands::connect::run(target, port, &identity, transport.as_deref()).await
+                commands::connect::run(
+                    target,
+                    port,
+                    &identity,
+                    transport.as_deref(),
+                    None,
+                    false,
+                    None,
+                )
+                .await
             }
         }
     };
@@ -1,4 +1,5 @@
-//! Terminal utilities for raw mode, terminal size, and resize events.
+//! Terminal utilities for raw mode, terminal size, resize events, and
+//! filtering of OSC sequences in remote output.
 //!
 //! Wraps crossterm's terminal operations and provides a RAII guard that
 //! automatically restores the terminal state on drop.
@@ -6,6 +7,10 @@
 use anyhow::{Context, Result};
 use crossterm::terminal;
 
+/// Longest OSC sequence held while waiting for its terminator; longer ones
+/// (e.g. a huge clipboard payload) are dropped.
+const MAX_OSC_LEN: usize = 1 << 20;
+
 /// RAII guard that restores the terminal to its original mode on drop.
 ///
 /// When entered, raw mode is enabled and the alternate screen can optionally
@@ -54,6 +59,151 @@ pub fn get_terminal_size() -> (u16, u16) {
     terminal::size().unwrap_or((80, 24))
 }
 
+/// Watches for `SIGWINCH` and reports each new terminal size once.
+#[cfg(unix)]
+pub struct ResizeWatcher {
+    signal: tokio::signal::unix::Signal,
+    last: (u16, u16),
+}
+
+#[cfg(unix)]
+impl ResizeWatcher {
+    /// Start watching; sizes are reported relative to the current one.
+    pub fn new() -> Result<Self> {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let signal = signal(SignalKind::window_change()).context("failed to watch SIGWINCH")?;
+        Ok(Self {
+            signal,
+            last: get_terminal_size(),
+        })
+    }
+
+    /// Wait for the terminal size to change, returning `(columns, rows)`.
+    /// Signals that leave the size unchanged are skipped.
+    pub async fn changed(&mut self) -> Option<(u16, u16)> {
+        loop {
+            self.signal.recv().await?;
+            let size = get_terminal_size();
+            if size != self.last {
+                self.last = size;
+                return Some(size);
+            }
+        }
+    }
+}
+
+/// Which OSC (Operating System Command) sequences from the remote side may
+/// reach the local terminal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OscPolicy {
+    /// Window and icon title (OSC 0, 1 and 2).
+    pub title: bool,
+    /// Clipboard writes (OSC 52). Clipboard reads are never passed through.
+    pub clipboard: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OscState {
+    /// Copying output through.
+    Text,
+    /// Holding an ESC that may start an OSC.
+    Escape,
+    /// Collecting an OSC body.
+    Osc,
+    /// Collecting an OSC body after an ESC (a possible `ESC \\` terminator).
+    OscEscape,
+}
+
+/// Streaming filter for PTY output that drops the OSC sequences an
+/// [`OscPolicy`] does not allow. Other OSC sequences (hyperlinks, working
+/// directory, colours) pass through unchanged.
+///
+/// Sequences may be split across reads; a partial sequence is held until
+/// its BEL or `ESC \\` terminator arrives.
+pub struct OscFilter {
+    policy: OscPolicy,
+    state: OscState,
+    osc: Vec<u8>,
+    /// The current OSC outgrew [`MAX_OSC_LEN`] and will be dropped.
+    overflow: bool,
+}
+
+impl OscFilter {
+    pub fn new(policy: OscPolicy) -> Self {
+        Self {
+            policy,
+            state: OscState::Text,
+            osc: Vec::new(),
+            overflow: false,
+        }
+    }
+
+    /// Filter one chunk of output, returning the bytes to write.
+    pub fn filter(&mut self, input: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(input.len());
+        for &byte in input {
+            self.step(byte, &mut out);
+        }
+        out
+    }
+
+    fn step(&mut self, byte: u8, out: &mut Vec<u8>) {
+        match self.state {
+            OscState::Text if byte == 0x1b => self.state = OscState::Escape,
+            OscState::Text => out.push(byte),
+            OscState::Escape if byte == b']' => self.state = OscState::Osc,
+            OscState::Escape => {
+                out.push(0x1b);
+                if byte != 0x1b {
+                    out.push(byte);
+                    self.state = OscState::Text;
+                }
+            }
+            OscState::Osc if byte == 0x07 => self.finish(out, b"\x07"),
+            OscState::Osc if byte == 0x1b => self.state = OscState::OscEscape,
+            OscState::Osc => {
+                if !self.overflow {
+                    self.osc.push(byte);
+                }
+                if self.osc.len() > MAX_OSC_LEN {
+                    self.osc = Vec::new();
+                    self.overflow = true;
+                }
+            }
+            OscState::OscEscape if byte == b'\\' => self.finish(out, b"\x1b\\"),
+            OscState::OscEscape => {
+                // An unterminated OSC cut short by another escape sequence.
+                self.finish(out, b"");
+                self.state = OscState::Escape;
+                self.step(byte, out);
+            }
+        }
+    }
+
+    /// Emit the collected OSC with `terminator` if the policy allows it.
+    fn finish(&mut self, out: &mut Vec<u8>, terminator: &[u8]) {
+        if !self.overflow && self.allows(&self.osc) {
+            out.extend_from_slice(b"\x1b]");
+            out.extend_from_slice(&self.osc);
+            out.extend_from_slice(terminator);
+        }
+        self.osc.clear();
+        self.overflow = false;
+        self.state = OscState::Text;
+    }
+
+    fn allows(&self, body: &[u8]) -> bool {
+        let mut fields = body.split(|&b| b == b';');
+        match fields.next().unwrap_or_default() {
+            b"0" | b"1" | b"2" => self.policy.title,
+            // `52;<selection>;<base64>`, where `?` asks for the clipboard.
+            b"52" => self.policy.clipboard && fields.nth(1).is_some_and(|data| data != b"?"),
+            _ => true,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -65,4 +215,44 @@ mod tests {
         assert!(cols > 0);
         assert!(rows > 0);
     }
+
+    const ALL: OscPolicy = OscPolicy {
+        title: true,
+        clipboard: true,
+    };
+
+    #[test]
+    fn osc_filter_applies_policy() {
+        let input = b"a\x1b]0;title\x07b\x1b]52;c;aGk=\x1b\\c\x1b]8;;https://x\x07\x1b[1m";
+        assert_eq!(OscFilter::new(ALL).filter(input), input.to_vec());
+
+        let mut filter = OscFilter::new(OscPolicy {
+            title: false,
+            clipboard: false,
+        });
+        assert_eq!(
+            filter.filter(input),
+            b"abc\x1b]8;;https://x\x07\x1b[1m".to_vec()
+        );
+
+        // Clipboard reads are dropped even when writes are allowed.
+        assert_eq!(
+            OscFilter::new(ALL).filter(b"\x1b]52;c;?\x07x"),
+            b"x".to_vec()
+        );
+    }
+
+    #[test]
+    fn osc_filter_handles_split_sequences() {
+        let mut filter = OscFilter::new(OscPolicy {
+            title: false,
+            clipboard: false,
+        });
+        let mut out = filter.filter(b"x\x1b");
+        out.extend(filter.filter(b"]2;ti"));
+        out.extend(filter.filter(b"tle\x1b"));
+        out.extend(filter.filter(b"\\y\x1b"));
+        out.extend(filter.filter(b"[A"));
+        assert_eq!(out, b"xy\x1b[A".to_vec());
+    }
 }
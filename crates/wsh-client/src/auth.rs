@@ -1,11 +1,13 @@
 //! Ed25519 authentication for the wsh protocol.
 //!
-//! Provides keypair generation, challenge signing, and verification.
+//! Provides keypair generation, challenge signing, verification, and
+//! certificate signing for `wsh keygen --sign`.
 //! The challenge transcript matches the JS implementation:
 //!   `SHA-256("wsh-v1\0" || session_id || nonce)`
 
 use ed25519_dalek::{Signer, SigningKey, Verifier, VerifyingKey};
 use sha2::{Digest, Sha256};
+use wsh_core::keys::Certificate;
 use wsh_core::{WshError, WshResult, PROTOCOL_VERSION};
 
 /// Generate a new Ed25519 keypair.
@@ -21,7 +23,7 @@ pub fn generate_keypair() -> (SigningKey, VerifyingKey) {
 /// Format: `SHA-256("wsh-v1\0" || session_id || nonce)`
 ///
 /// The null byte separator matches the JS implementation exactly.
-fn build_transcript(session_id: &str, nonce: &[u8]) -> Vec<u8> {
+pub fn build_transcript(session_id: &str, nonce: &[u8]) -> Vec<u8> {
     let mut hasher = Sha256::new();
     hasher.update(PROTOCOL_VERSION.as_bytes());
     hasher.update(b"\0");
@@ -78,6 +80,13 @@ pub fn signing_key_from_bytes(bytes: &[u8]) -> WshResult<SigningKey> {
     Ok(SigningKey::from_bytes(&bytes))
 }
 
+/// Sign a certificate as the CA `ca`, filling in `ca_key` and `signature`.
+pub fn sign_certificate(ca: &SigningKey, cert: &mut Certificate) -> WshResult<()> {
+    cert.ca_key = ca.verifying_key().to_bytes().to_vec();
+    cert.signature = ca.sign(&cert.signed_data()?).to_bytes().to_vec();
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -143,4 +152,25 @@ mod tests {
         assert_eq!(t1, t2);
         assert_eq!(t1.len(), 32); // SHA-256 output
     }
+
+    #[test]
+    fn signed_certificate_verifies() {
+        let (ca, _) = generate_keypair();
+        let (_, user) = generate_keypair();
+        let mut cert = Certificate {
+            kind: wsh_core::keys::CertKind::User,
+            public_key: public_key_bytes(&user),
+            key_id: "alice".into(),
+            serial: 7,
+            principals: vec!["alice".into()],
+            valid_after: 0,
+            valid_before: u64::MAX,
+            ca_key: Vec::new(),
+            signature: Vec::new(),
+        };
+        sign_certificate(&ca, &mut cert).unwrap();
+        assert!(cert
+            .verify(wsh_core::keys::CertKind::User, "alice", 100)
+            .is_ok());
+    }
 }
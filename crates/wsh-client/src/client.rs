@@ -4,20 +4,45 @@
 //! authentication, session management, and keepalive.
 
 use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 
+use tokio::net::TcpStream;
 use tokio::sync::{mpsc, oneshot, Mutex};
 use tokio::time;
 
 use wsh_core::codec::{decode_envelope, frame_encode};
+use wsh_core::compress::{Codec, CompressionStats};
 use wsh_core::error::{WshError, WshResult};
 use wsh_core::messages::*;
 
 use crate::auth;
-use crate::known_hosts::{HostStatus, KnownHosts};
-use crate::session::{ControlAction, SessionInfo, SessionOpts, WshSession};
-use crate::transport::{self, AnyTransport};
+use crate::known_hosts::{HostKeyChecking, HostStatus, KnownHosts};
+use crate::session::{ControlAction, ResumeHandle, SessionInfo, SessionOpts, WshSession};
+use crate::transport::{self, AnyTransport, TransportKind, WebSocketSession};
+
+/// Per-transfer receivers for chunked file transfer messages, keyed by transfer ID.
+type TransferRoutes = Arc<Mutex<HashMap<u32, mpsc::Sender<Envelope>>>>;
+
+/// Where the dispatch loop delivers incoming control messages.
+struct IncomingRoutes {
+    /// Waiters for a reply, keyed by message type.
+    response_tx: Arc<Mutex<HashMap<u8, Vec<oneshot::Sender<Envelope>>>>>,
+    /// Open sessions, keyed by channel ID.
+    sessions: Arc<Mutex<HashMap<u32, Arc<WshSession>>>>,
+    /// Chunked file transfers in progress.
+    transfers: TransferRoutes,
+    /// Reverse connect requests from the relay.
+    reverse_connect_tx: Option<mpsc::Sender<Envelope>>,
+    /// Messages relayed from a reverse peer.
+    relay_message_tx: Option<mpsc::Sender<Envelope>>,
+    /// Reverse peer presence events.
+    peer_event_tx: mpsc::Sender<ReversePeerEventPayload>,
+    /// Local key agent that forwarded agent requests are answered from.
+    agent_sock: Option<PathBuf>,
+}
 
 /// Configuration for connecting to a wsh server.
 #[derive(Debug, Clone)]
@@ -28,27 +53,79 @@ pub struct ConnectConfig {
     pub key_name: Option<String>,
     /// Password (for password auth).
     pub password: Option<String>,
-    /// Whether to verify the host key (TOFU).
-    pub verify_host: bool,
+    /// How unknown and changed host keys are handled.
+    pub host_key_checking: HostKeyChecking,
+    /// Asks whether to trust an unknown host in [`HostKeyChecking::Ask`] mode.
+    pub host_key_prompt: Option<HostKeyPrompt>,
+    /// Asks whether to accept a changed host key that the server could not
+    /// prove was rotated, in [`HostKeyChecking::Ask`] mode.
+    pub host_key_change_prompt: Option<HostKeyChangePrompt>,
     /// Ping interval in seconds (0 = disabled).
     pub ping_interval_secs: u64,
+    /// Unanswered pings (each allowed one interval) before the connection is
+    /// considered lost and its sessions are closed.
+    pub keepalive_max_missed: u32,
     /// Connection timeout in seconds.
     pub timeout_secs: u64,
+    /// Let the remote host use the local key agent (`WSH_AUTH_SOCK`).
+    pub forward_agent: bool,
+    /// Asks for a TOTP code when the server requires a second factor.
+    pub totp_prompt: Option<TotpPrompt>,
+    /// Asks for the passphrase of an encrypted key (after the OS keychain).
+    pub passphrase_prompt: Option<PassphrasePrompt>,
+    /// Offer compression of PTY output and file chunks in HELLO.
+    pub compression: bool,
 }
 
+/// Returns a TOTP code for `(username, host)`, or `None` to give up.
+pub type TotpPrompt = fn(&str, &str) -> Option<String>;
+
+/// Returns whether to trust `(host, fingerprint)` on first use.
+pub type HostKeyPrompt = fn(&str, &str) -> bool;
+
+/// Returns whether to replace `(host, old fingerprint)` with the new
+/// fingerprint in known_hosts.
+pub type HostKeyChangePrompt = fn(&str, &str, &str) -> bool;
+
+/// Returns the passphrase for the named key, or `None` to give up.
+pub type PassphrasePrompt = fn(&str) -> Option<String>;
+
 impl Default for ConnectConfig {
     fn default() -> Self {
         Self {
             username: whoami(),
             key_name: None,
             password: None,
-            verify_host: true,
+            host_key_checking: HostKeyChecking::default(),
+            host_key_prompt: None,
+            host_key_change_prompt: None,
             ping_interval_secs: 30,
+            keepalive_max_missed: 3,
             timeout_secs: 10,
+            forward_agent: false,
+            totp_prompt: None,
+            passphrase_prompt: None,
+            compression: false,
         }
     }
 }
 
+/// The local agent socket to expose to the server, if forwarding is on.
+fn forwarded_agent_socket(config: &ConnectConfig) -> Option<PathBuf> {
+    #[cfg(unix)]
+    {
+        config
+            .forward_agent
+            .then(crate::key_agent::socket_from_env)
+            .flatten()
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = config;
+        None
+    }
+}
+
 /// Get the current system username as a default.
 fn whoami() -> String {
     std::env::var("USER")
@@ -82,10 +159,24 @@ pub struct WshClient {
     reverse_connect_rx: Arc<Mutex<Option<mpsc::Receiver<Envelope>>>>,
     /// Receiver for relay-forwarded control/data messages (take-once).
     relay_message_rx: Arc<Mutex<Option<mpsc::Receiver<Envelope>>>>,
+    /// Active chunked file transfers.
+    transfers: TransferRoutes,
+    /// Receiver for relay peer join/leave events (take-once, see [`Self::watch_peers`]).
+    peer_event_rx: Arc<Mutex<Option<mpsc::Receiver<ReversePeerEventPayload>>>>,
+    /// Counter for allocating transfer IDs.
+    next_transfer_id: Arc<AtomicU32>,
+    /// Features listed in SERVER_HELLO.
+    server_features: Vec<String>,
+    /// Codec the server accepted in SERVER_HELLO, used for uploads.
+    compression: Option<Codec>,
+    /// Counters for compressed frames in both directions.
+    compression_stats: Arc<CompressionStats>,
 }
 
 /// Server-provided session summary from `SessionList`.
-#[derive(Debug, Clone)]
+///
+/// Fields after `attached_count` are `None` when the server predates them.
+#[derive(Debug, Clone, serde::Serialize)]
 pub struct RemoteSessionInfo {
     pub session_id: String,
     pub name: Option<String>,
@@ -94,6 +185,15 @@ pub struct RemoteSessionInfo {
     pub created_at_secs: u64,
     pub idle_secs: u64,
     pub attached_count: u32,
+    /// `"attached"`, `"detached"` or `"dormant"`.
+    pub state: Option<String>,
+    /// Unix time of the last activity.
+    pub last_activity_secs: Option<u64>,
+    pub command: Option<String>,
+    pub cols: Option<u16>,
+    pub rows: Option<u16>,
+    /// Full fingerprint of the owner's key.
+    pub fingerprint: Option<String>,
 }
 
 impl WshClient {
@@ -110,6 +210,39 @@ impl WshClient {
             Ok(Err(err)) => return Err(err),
             Err(_) => return Err(WshError::Timeout),
         };
+        Self::establish(transport, &known_host, config).await
+    }
+
+    /// Connect over an already-open TCP stream, such as a tunnel through a
+    /// jump host (see [`crate::jump`]).
+    ///
+    /// `url` must be a WebSocket URL naming the real server: it is used for
+    /// the TLS handshake and for the known_hosts entry the host key is
+    /// checked against.
+    pub async fn connect_over(url: &str, tcp: TcpStream, config: ConnectConfig) -> WshResult<Self> {
+        if transport::detect_transport(url)? != TransportKind::WebSocket {
+            return Err(WshError::Transport(format!(
+                "cannot tunnel {url}: only WebSocket connections can be relayed"
+            )));
+        }
+        let known_host = known_host_label(url)?;
+        let timeout = Duration::from_secs(config.timeout_secs);
+
+        let session = match time::timeout(timeout, WebSocketSession::connect_over(url, tcp)).await {
+            Ok(Ok(session)) => session,
+            Ok(Err(err)) => return Err(err),
+            Err(_) => return Err(WshError::Timeout),
+        };
+        Self::establish(AnyTransport::WebSocket(session), &known_host, config).await
+    }
+
+    /// Handshake, authenticate, and start the background tasks on a connected transport.
+    async fn establish(
+        transport: AnyTransport,
+        known_host: &str,
+        config: ConnectConfig,
+    ) -> WshResult<Self> {
+        let timeout = Duration::from_secs(config.timeout_secs);
         let transport = Arc::new(Mutex::new(transport));
 
         let (control_action_tx, control_action_rx) = mpsc::channel::<ControlAction>(256);
@@ -125,6 +258,10 @@ impl WshClient {
         let reverse_connect_rx = Arc::new(Mutex::new(Some(rc_rx)));
         let (relay_tx, relay_rx) = mpsc::channel::<Envelope>(128);
         let relay_message_rx = Arc::new(Mutex::new(Some(relay_rx)));
+        let (peer_event_tx, peer_event_rx) = mpsc::channel::<ReversePeerEventPayload>(64);
+        let peer_event_rx = Arc::new(Mutex::new(Some(peer_event_rx)));
+        let transfers: TransferRoutes = Arc::new(Mutex::new(HashMap::new()));
+        let agent_sock = forwarded_agent_socket(&config);
 
         let mut client = Self {
             transport: transport.clone(),
@@ -139,10 +276,16 @@ impl WshClient {
             connected: connected.clone(),
             reverse_connect_rx,
             relay_message_rx,
+            transfers: transfers.clone(),
+            peer_event_rx,
+            next_transfer_id: Arc::new(AtomicU32::new(1)),
+            server_features: Vec::new(),
+            compression: None,
+            compression_stats: Arc::new(CompressionStats::default()),
         };
 
         // Perform handshake with timeout
-        let handshake_result = time::timeout(timeout, client.handshake(&config, &known_host)).await;
+        let handshake_result = time::timeout(timeout, client.handshake(&config, known_host)).await;
 
         match handshake_result {
             Ok(Ok(session_id)) => {
@@ -159,18 +302,27 @@ impl WshClient {
             let sessions = sessions.clone();
             let connected = connected.clone();
             let outgoing_tx_clone = outgoing_tx.clone();
+            let compression_stats = client.compression_stats.clone();
+
+            let routes = IncomingRoutes {
+                response_tx,
+                sessions,
+                transfers,
+                reverse_connect_tx: Some(rc_tx),
+                relay_message_tx: Some(relay_tx),
+                peer_event_tx,
+                agent_sock,
+            };
 
             tokio::spawn(async move {
                 Self::dispatch_loop(
                     transport,
                     outgoing_rx,
                     control_action_rx,
-                    response_tx,
-                    sessions,
+                    routes,
                     connected,
                     outgoing_tx_clone,
-                    Some(rc_tx),
-                    Some(relay_tx),
+                    compression_stats,
                 )
                 .await;
             })
@@ -180,12 +332,17 @@ impl WshClient {
         // Spawn keepalive if configured
         if config.ping_interval_secs > 0 {
             let interval = Duration::from_secs(config.ping_interval_secs);
+            let max_missed = config.keepalive_max_missed.max(1);
             let outgoing = outgoing_tx.clone();
             let connected = client.connected.clone();
+            let response_tx = client.response_tx.clone();
+            let sessions = client.sessions.clone();
 
             let keepalive_handle = tokio::spawn(async move {
                 let mut ping_id: u64 = 0;
+                let mut missed = 0;
                 let mut ticker = time::interval(interval);
+                ticker.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
                 ticker.tick().await; // skip first immediate tick
 
                 loop {
@@ -199,6 +356,16 @@ impl WshClient {
                         break;
                     }
 
+                    // Wait for a pong, not just a successful write: a dead
+                    // network path usually accepts writes for a long time.
+                    let (pong_tx, pong_rx) = oneshot::channel();
+                    {
+                        let mut responses = response_tx.lock().await;
+                        let waiters = responses.entry(MsgType::Pong.into()).or_default();
+                        waiters.retain(|waiter| !waiter.is_closed());
+                        waiters.push(pong_tx);
+                    }
+
                     ping_id += 1;
                     let envelope = Envelope {
                         msg_type: MsgType::Ping,
@@ -213,6 +380,24 @@ impl WshClient {
                         }
                         Err(e) => {
                             tracing::warn!("failed to encode ping: {}", e);
+                            continue;
+                        }
+                    }
+
+                    match time::timeout(interval, pong_rx).await {
+                        Ok(Ok(_)) => missed = 0,
+                        _ => {
+                            missed += 1;
+                            tracing::warn!(
+                                "no pong for ping {} ({}/{})",
+                                ping_id,
+                                missed,
+                                max_missed
+                            );
+                            if missed >= max_missed {
+                                Self::mark_connection_lost(&connected, &sessions).await;
+                                break;
+                            }
                         }
                     }
                 }
@@ -235,6 +420,27 @@ impl WshClient {
         self.token.as_deref()
     }
 
+    /// Features the server advertised in SERVER_HELLO.
+    pub fn server_features(&self) -> &[String] {
+        &self.server_features
+    }
+
+    /// The compression codec negotiated with the server, if any.
+    pub fn compression(&self) -> Option<Codec> {
+        self.compression
+    }
+
+    /// Byte and CPU counters for compressed frames on this connection.
+    pub fn compression_stats(&self) -> &CompressionStats {
+        &self.compression_stats
+    }
+
+    /// Compress outgoing chunk data with the negotiated codec, returning the
+    /// bytes to send and the frame's `compression` field.
+    pub(crate) fn compress_chunk(&self, data: Vec<u8>) -> (Vec<u8>, Option<String>) {
+        self.compression_stats.encode(self.compression, data)
+    }
+
     /// Whether the client is currently connected.
     pub async fn is_connected(&self) -> bool {
         *self.connected.lock().await
@@ -255,6 +461,48 @@ impl WshClient {
         self.relay_message_rx.lock().await.take()
     }
 
+    /// Subscribe to relay peer join/leave notifications.
+    ///
+    /// Sends `ReverseSubscribe` and returns a receiver of the relay's
+    /// `ReversePeerEvent`s. Can only be called once per connection.
+    pub async fn watch_peers(&self) -> WshResult<mpsc::Receiver<ReversePeerEventPayload>> {
+        let rx = self
+            .peer_event_rx
+            .lock()
+            .await
+            .take()
+            .ok_or_else(|| WshError::Other("already watching peers".into()))?;
+        self.send_fire_and_forget(Envelope {
+            msg_type: MsgType::ReverseSubscribe,
+            payload: Payload::ReverseSubscribe(ReverseSubscribePayload {}),
+        })
+        .await?;
+        Ok(rx)
+    }
+
+    /// Register a chunked file transfer and return its ID and message receiver.
+    ///
+    /// `FileTransferReady`, `FileChunk` and `FileResult` messages whose
+    /// channel ID matches the transfer are delivered to the receiver until
+    /// [`close_transfer`](Self::close_transfer) is called.
+    pub async fn open_transfer(&self) -> (u32, mpsc::Receiver<Envelope>) {
+        let (tx, rx) = mpsc::channel(64);
+        let mut transfers = self.transfers.lock().await;
+        let transfer_id = loop {
+            let id = self.next_transfer_id.fetch_add(1, Ordering::Relaxed);
+            if id != 0 && !transfers.contains_key(&id) {
+                break id;
+            }
+        };
+        transfers.insert(transfer_id, tx);
+        (transfer_id, rx)
+    }
+
+    /// Stop routing messages for a transfer opened with [`open_transfer`](Self::open_transfer).
+    pub async fn close_transfer(&self, transfer_id: u32) {
+        self.transfers.lock().await.remove(&transfer_id);
+    }
+
     /// Send a control message without waiting for any response (fire-and-forget).
     ///
     /// Unlike `send_and_wait_public`, this does not register a response listener
@@ -275,6 +523,59 @@ impl WshClient {
         self.send_and_wait(envelope, expected_type).await
     }
 
+    /// Send a request and wait for `expected_type`, passing every
+    /// `progress_type` message that arrives meanwhile to `on_progress`.
+    ///
+    /// The response timeout restarts with each progress message, so a long
+    /// request lives as long as the server keeps reporting on it.
+    pub async fn send_and_wait_with_progress(
+        &self,
+        envelope: Envelope,
+        expected_type: MsgType,
+        progress_type: MsgType,
+        mut on_progress: impl FnMut(Envelope),
+    ) -> WshResult<Envelope> {
+        let (tx, mut rx) = oneshot::channel();
+        self.response_tx
+            .lock()
+            .await
+            .entry(expected_type.into())
+            .or_default()
+            .push(tx);
+        let mut progress_rx = self.progress_waiter(progress_type).await;
+
+        self.send_control_message(envelope).await?;
+
+        let timeout_duration = Duration::from_secs(30);
+        loop {
+            tokio::select! {
+                result = &mut rx => {
+                    return result.map_err(|_| WshError::Transport("response channel dropped".into()));
+                }
+                progress = &mut progress_rx => {
+                    progress_rx = self.progress_waiter(progress_type).await;
+                    if let Ok(progress) = progress {
+                        on_progress(progress);
+                    }
+                }
+                _ = time::sleep(timeout_duration) => {
+                    return Err(WshError::Timeout);
+                }
+            }
+        }
+    }
+
+    /// Register for the next `msg_type` message, dropping waiters whose
+    /// requests have finished.
+    async fn progress_waiter(&self, msg_type: MsgType) -> oneshot::Receiver<Envelope> {
+        let (tx, rx) = oneshot::channel();
+        let mut responses = self.response_tx.lock().await;
+        let waiters = responses.entry(msg_type.into()).or_default();
+        waiters.retain(|waiter| !waiter.is_closed());
+        waiters.push(tx);
+        rx
+    }
+
     /// Open a new session (pty, exec, etc.).
     pub async fn open_session(&self, opts: SessionOpts) -> WshResult<Arc<WshSession>> {
         let SessionOpts {
@@ -317,12 +618,20 @@ impl WshClient {
                             ok.capabilities.clone(),
                         ))
                     }
-                    SessionDataMode::Virtual => Arc::new(WshSession::new_virtual(
-                        ok.channel_id,
-                        kind,
-                        self.control_action_tx.clone(),
-                        ok.capabilities.clone(),
-                    )),
+                    SessionDataMode::Virtual => {
+                        let session = WshSession::new_virtual(
+                            ok.channel_id,
+                            kind,
+                            self.control_action_tx.clone(),
+                            ok.capabilities.clone(),
+                        );
+                        Arc::new(match (ok.session_id, ok.resume_token) {
+                            (Some(session_id), Some(token)) => {
+                                session.with_resume(session_id, token, 0)
+                            }
+                            _ => session,
+                        })
+                    }
                 };
 
                 {
@@ -391,6 +700,12 @@ impl WshClient {
                     created_at_secs: s.created_at_secs,
                     idle_secs: s.idle_secs,
                     attached_count: s.attached_count,
+                    state: s.state,
+                    last_activity_secs: s.last_activity_secs,
+                    command: s.command,
+                    cols: s.cols,
+                    rows: s.rows,
+                    fingerprint: s.fingerprint,
                 })
                 .collect()),
             Payload::Error(err) => Err(WshError::Channel(err.message)),
@@ -400,6 +715,66 @@ impl WshClient {
         }
     }
 
+    /// List the keys in the server's authorized_keys files.
+    pub async fn authorized_keys(&self) -> WshResult<Vec<AuthorizedKeyInfo>> {
+        let reply = self
+            .authorized_keys_request(Envelope {
+                msg_type: MsgType::AuthorizedKeysList,
+                payload: Payload::AuthorizedKeysList(AuthorizedKeysListPayload {}),
+            })
+            .await?;
+        Ok(reply.keys)
+    }
+
+    /// Add a public key line to the server's `~/.wsh/authorized_keys`,
+    /// prefixed with `options` when given.
+    ///
+    /// Returns `false` when the key was already authorized.
+    pub async fn add_authorized_key(
+        &self,
+        public_key: &str,
+        options: Option<&str>,
+    ) -> WshResult<bool> {
+        let reply = self
+            .authorized_keys_request(Envelope {
+                msg_type: MsgType::AuthorizedKeyAdd,
+                payload: Payload::AuthorizedKeyAdd(AuthorizedKeyAddPayload {
+                    public_key: public_key.to_string(),
+                    options: options.map(str::to_string),
+                }),
+            })
+            .await?;
+        Ok(reply.changed)
+    }
+
+    /// Remove the key matching a fingerprint (or unique prefix) from the
+    /// server's `~/.wsh/authorized_keys`, returning the remaining keys.
+    pub async fn remove_authorized_key(
+        &self,
+        fingerprint: &str,
+    ) -> WshResult<Vec<AuthorizedKeyInfo>> {
+        let reply = self
+            .authorized_keys_request(Envelope {
+                msg_type: MsgType::AuthorizedKeyRemove,
+                payload: Payload::AuthorizedKeyRemove(AuthorizedKeyRemovePayload {
+                    fingerprint: fingerprint.to_string(),
+                }),
+            })
+            .await?;
+        Ok(reply.keys)
+    }
+
+    async fn authorized_keys_request(&self, request: Envelope) -> WshResult<AuthorizedKeysPayload> {
+        let response = self.send_and_wait(request, MsgType::AuthorizedKeys).await?;
+        match response.payload {
+            Payload::AuthorizedKeys(reply) => Ok(reply),
+            Payload::Error(err) => Err(WshError::Channel(err.message)),
+            _ => Err(WshError::InvalidMessage(
+                "unexpected response to authorized keys request".into(),
+            )),
+        }
+    }
+
     /// Attach to an existing session (read-only or control mode).
     pub async fn attach_session(&self, session_id: &str, read_only: bool) -> WshResult<()> {
         let token = self
@@ -456,6 +831,55 @@ impl WshClient {
         }
     }
 
+    /// Pick up a session from an earlier connection that was lost.
+    ///
+    /// The server replays whatever output it buffered after
+    /// `handle.offset` and from then on streams to the returned session.
+    pub async fn resume_session(&self, handle: &ResumeHandle) -> WshResult<Arc<WshSession>> {
+        let envelope = Envelope {
+            msg_type: MsgType::Resume,
+            payload: Payload::Resume(ResumePayload {
+                session_id: handle.session_id.clone(),
+                token: handle.token.clone(),
+                last_seq: handle.offset,
+            }),
+        };
+
+        // Keep the session table locked until the new channel is registered,
+        // so the replay that follows OPEN_OK cannot arrive before it. Meant
+        // for a fresh connection: other sessions' output waits meanwhile.
+        let mut sessions = self.sessions.lock().await;
+        let response = self.send_and_wait(envelope, MsgType::OpenOk).await?;
+        match response.payload {
+            Payload::OpenOk(ok) => {
+                let session = Arc::new(
+                    WshSession::new_virtual(
+                        ok.channel_id,
+                        ChannelKind::Pty,
+                        self.control_action_tx.clone(),
+                        ok.capabilities,
+                    )
+                    .with_resume(
+                        handle.session_id.clone(),
+                        handle.token.clone(),
+                        handle.offset,
+                    ),
+                );
+                sessions.insert(ok.channel_id, session.clone());
+                tracing::info!(
+                    "resumed session {} on channel {}",
+                    handle.session_id,
+                    ok.channel_id
+                );
+                Ok(session)
+            }
+            Payload::OpenFail(fail) => Err(WshError::Channel(fail.reason)),
+            _ => Err(WshError::InvalidMessage(
+                "unexpected response to RESUME".into(),
+            )),
+        }
+    }
+
     /// Disconnect from the server.
     pub async fn disconnect(&self) -> WshResult<()> {
         {
@@ -482,7 +906,8 @@ impl WshClient {
 
     // ── Internal ─────────────────────────────────────────────────────
 
-    /// Perform the handshake: HELLO -> SERVER_HELLO -> CHALLENGE -> AUTH -> AUTH_OK.
+    /// Perform the handshake: HELLO -> SERVER_HELLO -> CHALLENGE -> AUTH -> AUTH_OK,
+    /// with an AUTH_METHODS -> AUTH round before AUTH_OK when a TOTP code is required.
     async fn handshake(&mut self, config: &ConnectConfig, known_host: &str) -> WshResult<String> {
         // Determine auth method
         let auth_method = if config.key_name.is_some() {
@@ -491,13 +916,21 @@ impl WshClient {
             AuthMethod::Password
         };
 
+        let mut features = vec!["mcp".to_string(), "file-transfer".to_string()];
+        if forwarded_agent_socket(config).is_some() {
+            features.push("agent-forward".to_string());
+        }
+        if config.compression {
+            features.extend(Codec::ALL.map(Codec::feature));
+        }
+
         // Send HELLO
         let hello = Envelope {
             msg_type: MsgType::Hello,
             payload: Payload::Hello(HelloPayload {
                 version: PROTOCOL_VERSION.to_string(),
                 username: config.username.clone(),
-                features: vec!["mcp".to_string(), "file-transfer".to_string()],
+                features,
                 auth_method: Some(auth_method.clone()),
             }),
         };
@@ -508,36 +941,84 @@ impl WshClient {
         let server_hello = decode_envelope(&server_hello_data)?;
 
         let (server_session_id, server_fingerprints) = match &server_hello.payload {
-            Payload::ServerHello(sh) => (sh.session_id.clone(), sh.fingerprints.clone()),
+            Payload::ServerHello(sh) => {
+                self.server_features = sh.features.clone();
+                if config.compression {
+                    self.compression = Codec::negotiate(&sh.features);
+                }
+                (sh.session_id.clone(), sh.fingerprints.clone())
+            }
             _ => return Err(WshError::InvalidMessage("expected SERVER_HELLO".into())),
         };
 
-        // Verify host key (TOFU)
-        if config.verify_host {
-            if let Some(first_fp) = server_fingerprints.first() {
-                self.verify_host_key(known_host, first_fp)?;
-            }
-        }
-
         // Receive CHALLENGE
         let challenge_data = self.recv_raw().await?;
         let challenge = decode_envelope(&challenge_data)?;
 
-        let nonce = match &challenge.payload {
-            Payload::Challenge(c) => c.nonce.clone(),
+        let (nonce, host_proof, rotation) = match challenge.payload {
+            Payload::Challenge(c) => {
+                let host_proof = c.host_certificate.zip(c.host_signature);
+                (c.nonce, host_proof, c.host_key_rotation)
+            }
             _ => return Err(WshError::InvalidMessage("expected CHALLENGE".into())),
         };
 
+        // Verify the host: by certificate when a trusted CA signed one, else TOFU
+        let certified = match &host_proof {
+            Some((cert, signature)) if config.host_key_checking != HostKeyChecking::Off => self
+                .verify_host_certificate(known_host, cert, signature, &server_session_id, &nonce)?,
+            _ => false,
+        };
+        if !certified {
+            if let Some(first_fp) = server_fingerprints.first() {
+                let rotation = rotation
+                    .as_ref()
+                    .map(|proof| (proof, server_session_id.as_str(), nonce.as_slice()));
+                self.verify_host_key(known_host, first_fp, rotation, config)?;
+            }
+        }
+
         // Authenticate
         let auth_envelope = match auth_method {
             AuthMethod::Pubkey => {
                 let key_name = config.key_name.as_deref().unwrap_or("default");
 
                 let keystore = crate::keystore::KeyStore::default_location()?;
-                let (signing_key, verifying_key) = keystore.load(key_name)?;
+                let prompt = |name: &str| config.passphrase_prompt.and_then(|ask| ask(name));
+                let (signature, public_key) = match keystore.unlock(key_name, prompt) {
+                    Ok((signing_key, verifying_key)) => (
+                        auth::sign_challenge(&signing_key, &server_session_id, &nonce),
+                        auth::public_key_bytes(&verifying_key),
+                    ),
+                    // Not on disk: fall back to a running key agent, which is
+                    // how a forwarded agent is used on an intermediate host.
+                    #[cfg(unix)]
+                    Err(err) => match crate::key_agent::socket_from_env() {
+                        Some(socket) => {
+                            tracing::debug!("key '{}' not in keystore, using key agent", key_name);
+                            crate::key_agent::sign_challenge(
+                                &socket,
+                                key_name,
+                                &server_session_id,
+                                &nonce,
+                            )
+                            .await?
+                        }
+                        None => return Err(err),
+                    },
+                    #[cfg(not(unix))]
+                    Err(err) => return Err(err),
+                };
 
-                let signature = auth::sign_challenge(&signing_key, &server_session_id, &nonce);
-                let public_key = auth::public_key_bytes(&verifying_key);
+                // Present a certificate issued for this key, if there is one.
+                let certificate = match keystore.load_certificate(key_name) {
+                    Ok(Some(cert)) if cert.public_key == public_key => Some(cert.to_bytes()?),
+                    Ok(_) => None,
+                    Err(e) => {
+                        tracing::warn!("ignoring unreadable certificate for '{}': {}", key_name, e);
+                        None
+                    }
+                };
 
                 Envelope {
                     msg_type: MsgType::Auth,
@@ -546,6 +1027,8 @@ impl WshClient {
                         signature: Some(signature),
                         public_key: Some(public_key),
                         password: None,
+                        certificate,
+                        code: None,
                     }),
                 }
             }
@@ -562,16 +1045,45 @@ impl WshClient {
                         signature: None,
                         public_key: None,
                         password: Some(password),
+                        certificate: None,
+                        code: None,
                     }),
                 }
             }
+            AuthMethod::Totp => unreachable!("TOTP is only sent as a second factor"),
         };
 
         self.send_raw(&auth_envelope).await?;
 
-        // Receive AUTH_OK or AUTH_FAIL
+        // Receive AUTH_OK or AUTH_FAIL, answering a TOTP request first
         let auth_response_data = self.recv_raw().await?;
-        let auth_response = decode_envelope(&auth_response_data)?;
+        let mut auth_response = decode_envelope(&auth_response_data)?;
+        if let Payload::AuthMethods(requested) = &auth_response.payload {
+            if !requested.methods.contains(&AuthMethod::Totp) {
+                return Err(WshError::AuthFailed(format!(
+                    "server requires unsupported auth methods: {:?}",
+                    requested.methods
+                )));
+            }
+            let code = config
+                .totp_prompt
+                .and_then(|prompt| prompt(&config.username, known_host))
+                .ok_or_else(|| WshError::AuthFailed("server requires a TOTP code".into()))?;
+            self.send_raw(&Envelope {
+                msg_type: MsgType::Auth,
+                payload: Payload::Auth(AuthPayload {
+                    method: AuthMethod::Totp,
+                    signature: None,
+                    public_key: None,
+                    password: None,
+                    certificate: None,
+                    code: Some(code),
+                }),
+            })
+            .await?;
+            let auth_response_data = self.recv_raw().await?;
+            auth_response = decode_envelope(&auth_response_data)?;
+        }
 
         match auth_response.payload {
             Payload::AuthOk(ok) => {
@@ -591,16 +1103,92 @@ impl WshClient {
         }
     }
 
-    /// Verify the server's host key via TOFU.
-    fn verify_host_key(&self, host: &str, fingerprint: &str) -> WshResult<()> {
+    /// Check a host certificate against the `@cert-authority` lines in known_hosts.
+    ///
+    /// Returns `false` when no trusted CA signed it (the caller falls back to
+    /// TOFU) and an error when a trusted CA's certificate does not check out.
+    fn verify_host_certificate(
+        &self,
+        host: &str,
+        certificate: &[u8],
+        signature: &[u8],
+        session_id: &str,
+        nonce: &[u8],
+    ) -> WshResult<bool> {
+        let cert = wsh_core::keys::Certificate::from_bytes(certificate)?;
+        let name = host_name(host);
         let known_hosts = KnownHosts::default_location()?;
+        for key in [&cert.public_key, &cert.ca_key] {
+            if known_hosts.is_revoked(&wsh_core::fingerprint(key))? {
+                return Err(WshError::AuthFailed(format!(
+                    "host certificate for {host} uses a revoked key"
+                )));
+            }
+        }
+        let authorities = known_hosts.cert_authorities_for(name)?;
+        if !authorities.contains(&cert.ca_key) {
+            tracing::debug!("host certificate for {} is not from a trusted CA", host);
+            return Ok(false);
+        }
+
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        cert.verify(wsh_core::keys::CertKind::Host, name, now)
+            .map_err(|e| WshError::AuthFailed(format!("host certificate for {host}: {e}")))?;
+        let data = wsh_core::keys::host_signature_data(session_id, nonce);
+        if !wsh_core::keys::verify_ed25519(&cert.public_key, &data, signature) {
+            return Err(WshError::AuthFailed(format!(
+                "{host} does not hold the key in its host certificate"
+            )));
+        }
+
+        tracing::debug!("host {} verified by certificate '{}'", host, cert.key_id);
+        Ok(true)
+    }
+
+    /// Verify the server's host key against known_hosts, as `config` allows.
+    ///
+    /// A changed key is accepted without asking when `rotation` shows the
+    /// pinned key vouching for the new one in this handshake; otherwise
+    /// only an explicit answer to the change prompt replaces it.
+    fn verify_host_key(
+        &self,
+        host: &str,
+        fingerprint: &str,
+        rotation: Option<(&HostKeyRotation, &str, &[u8])>,
+        config: &ConnectConfig,
+    ) -> WshResult<()> {
+        let known_hosts = KnownHosts::default_location()?;
+        let mode = config.host_key_checking;
 
         match known_hosts.verify_host(host, fingerprint)? {
+            HostStatus::Revoked => Err(WshError::AuthFailed(format!(
+                "host key {fingerprint} for {host} has been REVOKED"
+            ))),
+            _ if mode == HostKeyChecking::Off => {
+                tracing::debug!("host key checking is off; not verifying {}", host);
+                Ok(())
+            }
             HostStatus::Known => {
                 tracing::debug!("host {} verified (known)", host);
                 Ok(())
             }
             HostStatus::Unknown => {
+                match (mode, config.host_key_prompt) {
+                    (HostKeyChecking::Strict, _) => {
+                        return Err(WshError::AuthFailed(format!(
+                            "host {host} is not in known_hosts and strict host key checking is on"
+                        )));
+                    }
+                    (HostKeyChecking::Ask, Some(prompt)) if !prompt(host, fingerprint) => {
+                        return Err(WshError::AuthFailed(format!(
+                            "host key for {host} was not accepted"
+                        )));
+                    }
+                    _ => {}
+                }
                 // TOFU: trust on first use
                 tracing::info!(
                     "new host {} with fingerprint {}, adding to known_hosts",
@@ -610,11 +1198,44 @@ impl WshClient {
                 known_hosts.add_host(host, fingerprint)?;
                 Ok(())
             }
-            HostStatus::Changed { expected } => Err(WshError::AuthFailed(format!(
-                "HOST KEY CHANGED for {}: expected {}, got {}. \
-                 This could indicate a man-in-the-middle attack.",
-                host, expected, fingerprint
-            ))),
+            HostStatus::Changed { expected } => {
+                if let Some((proof, session_id, nonce)) = rotation {
+                    verify_rotation(proof, &expected, fingerprint, session_id, nonce).map_err(
+                        |reason| {
+                            WshError::AuthFailed(format!(
+                                "HOST KEY CHANGED for {host} with an invalid rotation proof: {reason}"
+                            ))
+                        },
+                    )?;
+                    tracing::info!(
+                        "host {} rotated its key from {} to {}, updating known_hosts",
+                        host,
+                        expected,
+                        fingerprint
+                    );
+                    known_hosts.add_host(host, fingerprint)?;
+                    return Ok(());
+                }
+                let confirmed = mode == HostKeyChecking::Ask
+                    && config
+                        .host_key_change_prompt
+                        .is_some_and(|prompt| prompt(host, &expected, fingerprint));
+                if !confirmed {
+                    return Err(WshError::AuthFailed(format!(
+                        "HOST KEY CHANGED for {}: expected {}, got {}. \
+                         This could indicate a man-in-the-middle attack.",
+                        host, expected, fingerprint
+                    )));
+                }
+                tracing::info!(
+                    "replacing host key for {} ({} -> {}) as confirmed",
+                    host,
+                    expected,
+                    fingerprint
+                );
+                known_hosts.add_host(host, fingerprint)?;
+                Ok(())
+            }
         }
     }
 
@@ -663,8 +1284,10 @@ impl WshClient {
             MsgType::AuthOk => Some(MsgType::AuthFail),
             MsgType::DetachOk => Some(MsgType::DetachFail),
             MsgType::ReverseAccept => Some(MsgType::ReverseReject),
+            MsgType::ListenOk => Some(MsgType::ListenFail),
             MsgType::SessionList => Some(MsgType::Error),
             MsgType::Presence => Some(MsgType::Error),
+            MsgType::AuthorizedKeys => Some(MsgType::Error),
             _ => None,
         };
 
@@ -718,12 +1341,10 @@ impl WshClient {
         transport: Arc<Mutex<AnyTransport>>,
         mut outgoing_rx: mpsc::Receiver<Vec<u8>>,
         mut action_rx: mpsc::Receiver<ControlAction>,
-        response_tx: Arc<Mutex<HashMap<u8, Vec<oneshot::Sender<Envelope>>>>>,
-        sessions: Arc<Mutex<HashMap<u32, Arc<WshSession>>>>,
+        routes: IncomingRoutes,
         connected: Arc<Mutex<bool>>,
         outgoing_tx: mpsc::Sender<Vec<u8>>,
-        reverse_connect_tx: Option<mpsc::Sender<Envelope>>,
-        relay_message_tx: Option<mpsc::Sender<Envelope>>,
+        compression_stats: Arc<CompressionStats>,
     ) {
         loop {
             let is_connected = { *connected.lock().await };
@@ -737,8 +1358,8 @@ impl WshClient {
                     let mut t = transport.lock().await;
                     if let Err(e) = t.send_control(&frame).await {
                         tracing::error!("failed to send control message: {}", e);
-                        let mut c = connected.lock().await;
-                        *c = false;
+                        drop(t);
+                        Self::mark_connection_lost(&connected, &routes.sessions).await;
                         break;
                     }
                 }
@@ -748,7 +1369,11 @@ impl WshClient {
                     let envelope = match action {
                         ControlAction::Data { channel_id, data } => Envelope {
                             msg_type: MsgType::SessionData,
-                            payload: Payload::SessionData(SessionDataPayload { channel_id, data }),
+                            payload: Payload::SessionData(SessionDataPayload {
+                                channel_id,
+                                data,
+                                compression: None,
+                            }),
                         },
                         ControlAction::Resize { channel_id, cols, rows } => Envelope {
                             msg_type: MsgType::Resize,
@@ -784,16 +1409,26 @@ impl WshClient {
                 } => {
                     match result {
                         Ok(data) => {
-                            match decode_envelope(&data) {
-                                Ok(envelope) => {
-                                    Self::handle_incoming(
+                            let decoded = decode_envelope(&data)
+                                .and_then(|envelope| decompress_envelope(envelope, &compression_stats));
+                            match decoded {
+                                Ok(envelope) if envelope.msg_type == MsgType::AgentForwardRequest => {
+                                    Self::answer_agent_request(
                                         envelope,
-                                        &response_tx,
-                                        &sessions,
-                                        &outgoing_tx,
-                                        &reverse_connect_tx,
-                                        &relay_message_tx,
-                                    ).await;
+                                        routes.agent_sock.clone(),
+                                        outgoing_tx.clone(),
+                                    );
+                                }
+                                Ok(Envelope {
+                                    payload: Payload::ReversePeerEvent(event),
+                                    ..
+                                }) => {
+                                    if routes.peer_event_tx.try_send(event).is_err() {
+                                        tracing::debug!("peer event dropped (not watching or full)");
+                                    }
+                                }
+                                Ok(envelope) => {
+                                    Self::handle_incoming(envelope, &routes, &outgoing_tx).await;
                                 }
                                 Err(e) => {
                                     tracing::warn!("failed to decode control message: {}", e);
@@ -802,8 +1437,7 @@ impl WshClient {
                         }
                         Err(e) => {
                             tracing::error!("control recv error: {}", e);
-                            let mut c = connected.lock().await;
-                            *c = false;
+                            Self::mark_connection_lost(&connected, &routes.sessions).await;
                             break;
                         }
                     }
@@ -814,17 +1448,81 @@ impl WshClient {
         tracing::debug!("dispatch loop ended");
     }
 
+    /// Mark the connection as gone and end every session on it, so readers
+    /// see EOF instead of waiting forever.
+    async fn mark_connection_lost(
+        connected: &Mutex<bool>,
+        sessions: &Mutex<HashMap<u32, Arc<WshSession>>>,
+    ) {
+        *connected.lock().await = false;
+        let sessions: Vec<_> = sessions.lock().await.drain().map(|(_, s)| s).collect();
+        for session in sessions {
+            session.mark_closed().await;
+        }
+    }
+
+    /// Relay an agent request forwarded by the server to the local key agent
+    /// and send the agent's answer back.
+    fn answer_agent_request(
+        envelope: Envelope,
+        agent_sock: Option<PathBuf>,
+        outgoing_tx: mpsc::Sender<Vec<u8>>,
+    ) {
+        let Payload::AgentForward(request) = envelope.payload else {
+            return;
+        };
+        tokio::spawn(async move {
+            #[cfg(unix)]
+            let data =
+                crate::key_agent::relay_forwarded(agent_sock.as_deref(), &request.data).await;
+            #[cfg(not(unix))]
+            let data = {
+                let _ = agent_sock;
+                Vec::new()
+            };
+            let reply = Envelope {
+                msg_type: MsgType::AgentForwardResponse,
+                payload: Payload::AgentForward(AgentForwardPayload {
+                    request_id: request.request_id,
+                    data,
+                }),
+            };
+            match frame_encode(&reply) {
+                Ok(frame) => {
+                    let _ = outgoing_tx.send(frame).await;
+                }
+                Err(e) => tracing::warn!("failed to encode agent response: {}", e),
+            }
+        });
+    }
+
     /// Handle an incoming control message.
     async fn handle_incoming(
         envelope: Envelope,
-        response_tx: &Arc<Mutex<HashMap<u8, Vec<oneshot::Sender<Envelope>>>>>,
-        sessions: &Arc<Mutex<HashMap<u32, Arc<WshSession>>>>,
+        routes: &IncomingRoutes,
         outgoing_tx: &mpsc::Sender<Vec<u8>>,
-        reverse_connect_tx: &Option<mpsc::Sender<Envelope>>,
-        relay_message_tx: &Option<mpsc::Sender<Envelope>>,
     ) {
+        let IncomingRoutes {
+            response_tx,
+            sessions,
+            transfers,
+            reverse_connect_tx,
+            relay_message_tx,
+            ..
+        } = routes;
         let msg_type_u8: u8 = envelope.msg_type.into();
 
+        // Chunked file transfer traffic goes to the registered transfer.
+        if let Some(transfer_id) = envelope_transfer_id(&envelope) {
+            let route = transfers.lock().await.get(&transfer_id).cloned();
+            if let Some(tx) = route {
+                if let Err(err) = tx.send(envelope).await {
+                    tracing::debug!(transfer_id, "file transfer receiver closed: {err}");
+                }
+                return;
+            }
+        }
+
         match envelope.msg_type {
             // Respond to server pings
             MsgType::Ping => {
@@ -839,11 +1537,6 @@ impl WshClient {
                 }
             }
 
-            // Ignore pong responses (keepalive ack)
-            MsgType::Pong => {
-                tracing::trace!("received pong");
-            }
-
             MsgType::Exit
             | MsgType::Close
             | MsgType::SessionData
@@ -916,6 +1609,17 @@ impl WshClient {
                 }
             }
 
+            // Drain notice: the server is shutting down after a grace period
+            MsgType::Drain => {
+                if let Payload::Drain(drain) = &envelope.payload {
+                    tracing::warn!(
+                        "server draining: {} (closing in {}s)",
+                        drain.reason,
+                        drain.deadline_secs
+                    );
+                }
+            }
+
             // Incoming reverse connection request (unsolicited from relay)
             MsgType::ReverseConnect => {
                 tracing::info!("incoming reverse connect request");
@@ -958,6 +1662,19 @@ impl WshClient {
     }
 }
 
+/// Decompress the data of an incoming `SessionData` or `FileChunk` frame.
+fn decompress_envelope(mut envelope: Envelope, stats: &CompressionStats) -> WshResult<Envelope> {
+    let (compression, data) = match &mut envelope.payload {
+        Payload::SessionData(p) => (&mut p.compression, &mut p.data),
+        Payload::FileChunk(p) => (&mut p.compression, &mut p.data),
+        _ => return Ok(envelope),
+    };
+    if let Some(name) = compression.take() {
+        *data = stats.decode(Some(&name), std::mem::take(data))?;
+    }
+    Ok(envelope)
+}
+
 fn is_relay_forwardable(msg_type: MsgType) -> bool {
     matches!(
         msg_type,
@@ -973,10 +1690,12 @@ fn is_relay_forwardable(msg_type: MsgType) -> bool {
             | MsgType::GatewayOk
             | MsgType::GatewayFail
             | MsgType::GatewayClose
+            | MsgType::InboundOpen
             | MsgType::McpDiscover
             | MsgType::McpTools
             | MsgType::McpCall
             | MsgType::McpResult
+            | MsgType::McpProgress
             | MsgType::EchoAck
             | MsgType::EchoState
             | MsgType::TermSync
@@ -999,6 +1718,17 @@ fn envelope_channel_id(envelope: &Envelope) -> Option<u32> {
     }
 }
 
+fn envelope_transfer_id(envelope: &Envelope) -> Option<u32> {
+    match &envelope.payload {
+        Payload::FileTransferReady(payload) => Some(payload.transfer_id),
+        Payload::SyncManifest(payload) => Some(payload.transfer_id),
+        Payload::FileChunk(payload) => Some(payload.channel_id),
+        Payload::FileResult(payload) => Some(payload.channel_id),
+        Payload::WindowUpdate(payload) => Some(payload.channel_id),
+        _ => None,
+    }
+}
+
 fn known_host_label(url: &str) -> WshResult<String> {
     let (scheme, remainder) = url
         .split_once("://")
@@ -1027,6 +1757,12 @@ fn known_host_label(url: &str) -> WshResult<String> {
     Ok(format!("{authority}:{default_port}"))
 }
 
+/// The host part of a `host:port` known_hosts label, without IPv6 brackets.
+fn host_name(label: &str) -> &str {
+    let host = label.rsplit_once(':').map_or(label, |(host, _)| host);
+    host.trim_start_matches('[').trim_end_matches(']')
+}
+
 fn authority_has_port(authority: &str) -> bool {
     if authority.starts_with('[') {
         authority
@@ -1048,20 +1784,64 @@ impl Drop for WshClient {
     }
 }
 
+/// Check that a rotation proof links the pinned key `expected` to the
+/// presented key `fingerprint` for this handshake.
+fn verify_rotation(
+    proof: &HostKeyRotation,
+    expected: &str,
+    fingerprint: &str,
+    session_id: &str,
+    nonce: &[u8],
+) -> Result<(), String> {
+    if wsh_core::fingerprint(&proof.old_key) != expected {
+        return Err("it is not signed by the key in known_hosts".into());
+    }
+    if wsh_core::fingerprint(&proof.new_key) != fingerprint {
+        return Err("it is for a different key than the one presented".into());
+    }
+    let host_data = wsh_core::keys::host_signature_data(session_id, nonce);
+    if !wsh_core::keys::verify_ed25519(&proof.new_key, &host_data, &proof.new_signature) {
+        return Err("the server does not hold the new key".into());
+    }
+    let rotation_data = wsh_core::keys::rotation_signature_data(&proof.new_key, session_id, nonce);
+    if !wsh_core::keys::verify_ed25519(&proof.old_key, &rotation_data, &proof.old_signature) {
+        return Err("the old key's signature does not verify".into());
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use std::collections::HashMap;
+    use std::sync::atomic::AtomicU32;
     use std::sync::Arc;
 
+    use ed25519_dalek::{Signer, SigningKey};
     use tokio::sync::{mpsc, Mutex};
+    use wsh_core::compress::{Codec, CompressionStats};
     use wsh_core::messages::{
-        ChannelKind, ClosePayload, Envelope, MsgType, OpenOkPayload, Payload, SessionDataMode,
-        SessionDataPayload,
+        ChannelKind, ClosePayload, Envelope, HostKeyRotation, MsgType, OpenOkPayload, Payload,
+        SessionDataMode, SessionDataPayload,
     };
 
-    use super::{known_host_label, SessionOpts, WshClient};
+    use super::{
+        decompress_envelope, host_name, known_host_label, verify_rotation, IncomingRoutes,
+        SessionOpts, WshClient,
+    };
     use crate::session::WshSession;
 
+    fn test_routes(relay_message_tx: Option<mpsc::Sender<Envelope>>) -> IncomingRoutes {
+        IncomingRoutes {
+            response_tx: Arc::new(Mutex::new(HashMap::new())),
+            sessions: Arc::new(Mutex::new(HashMap::new())),
+            transfers: Arc::new(Mutex::new(HashMap::new())),
+            reverse_connect_tx: None,
+            relay_message_tx,
+            peer_event_tx: mpsc::channel(1).0,
+            agent_sock: None,
+        }
+    }
+
     #[test]
     fn known_host_label_preserves_explicit_websocket_port() {
         assert_eq!(
@@ -1070,6 +1850,12 @@ mod tests {
         );
     }
 
+    #[test]
+    fn host_name_strips_port_and_brackets() {
+        assert_eq!(host_name("example.com:4422"), "example.com");
+        assert_eq!(host_name("[2001:db8::1]:4422"), "2001:db8::1");
+    }
+
     #[test]
     fn known_host_label_adds_default_websocket_port() {
         assert_eq!(
@@ -1120,8 +1906,7 @@ mod tests {
 
     #[tokio::test]
     async fn handle_incoming_routes_session_data_to_virtual_session() {
-        let response_tx = Arc::new(Mutex::new(HashMap::new()));
-        let sessions = Arc::new(Mutex::new(HashMap::new()));
+        let routes = test_routes(None);
         let (control_tx, _control_rx) = mpsc::channel(4);
         let session = Arc::new(WshSession::new_virtual(
             21,
@@ -1129,7 +1914,7 @@ mod tests {
             control_tx,
             vec!["resize".into()],
         ));
-        sessions.lock().await.insert(21, session.clone());
+        routes.sessions.lock().await.insert(21, session.clone());
         let (outgoing_tx, _outgoing_rx) = mpsc::channel(4);
 
         WshClient::handle_incoming(
@@ -1138,13 +1923,11 @@ mod tests {
                 payload: Payload::SessionData(SessionDataPayload {
                     channel_id: 21,
                     data: b"pwd\n".to_vec(),
+                    compression: None,
                 }),
             },
-            &response_tx,
-            &sessions,
+            &routes,
             &outgoing_tx,
-            &None,
-            &None,
         )
         .await;
 
@@ -1157,8 +1940,7 @@ mod tests {
 
     #[tokio::test]
     async fn handle_incoming_removes_closed_session_from_tracking() {
-        let response_tx = Arc::new(Mutex::new(HashMap::new()));
-        let sessions = Arc::new(Mutex::new(HashMap::new()));
+        let routes = test_routes(None);
         let (control_tx, _control_rx) = mpsc::channel(4);
         let session = Arc::new(WshSession::new_virtual(
             22,
@@ -1166,7 +1948,7 @@ mod tests {
             control_tx,
             vec![],
         ));
-        sessions.lock().await.insert(22, session.clone());
+        routes.sessions.lock().await.insert(22, session.clone());
         let (outgoing_tx, _outgoing_rx) = mpsc::channel(4);
 
         WshClient::handle_incoming(
@@ -1174,24 +1956,20 @@ mod tests {
                 msg_type: MsgType::Close,
                 payload: Payload::Close(ClosePayload { channel_id: 22 }),
             },
-            &response_tx,
-            &sessions,
+            &routes,
             &outgoing_tx,
-            &None,
-            &None,
         )
         .await;
 
         assert_eq!(session.state().await, crate::session::SessionState::Closed);
-        assert!(!sessions.lock().await.contains_key(&22));
+        assert!(!routes.sessions.lock().await.contains_key(&22));
     }
 
     #[tokio::test]
     async fn handle_incoming_routes_unknown_session_messages_to_relay_channel() {
-        let response_tx = Arc::new(Mutex::new(HashMap::new()));
-        let sessions = Arc::new(Mutex::new(HashMap::new()));
         let (outgoing_tx, _outgoing_rx) = mpsc::channel(4);
         let (relay_tx, mut relay_rx) = mpsc::channel(4);
+        let routes = test_routes(Some(relay_tx));
 
         WshClient::handle_incoming(
             Envelope {
@@ -1199,13 +1977,11 @@ mod tests {
                 payload: Payload::SessionData(SessionDataPayload {
                     channel_id: 99,
                     data: b"whoami\n".to_vec(),
+                    compression: None,
                 }),
             },
-            &response_tx,
-            &sessions,
+            &routes,
             &outgoing_tx,
-            &None,
-            &Some(relay_tx),
         )
         .await;
 
@@ -1219,6 +1995,30 @@ mod tests {
         }
     }
 
+    #[test]
+    fn decompress_envelope_restores_compressed_session_data() {
+        let data = b"drwxr-xr-x  2 user user 4096 Oct 17 12:00 src\n".repeat(20);
+        let stats = CompressionStats::default();
+        let (wire, compression) = stats.encode(Some(Codec::Deflate), data.clone());
+        assert_eq!(compression.as_deref(), Some("deflate"));
+        let envelope = Envelope {
+            msg_type: MsgType::SessionData,
+            payload: Payload::SessionData(SessionDataPayload {
+                channel_id: 1,
+                data: wire,
+                compression,
+            }),
+        };
+
+        let envelope = decompress_envelope(envelope, &stats).unwrap();
+        let Payload::SessionData(payload) = envelope.payload else {
+            panic!("expected SessionData");
+        };
+        assert_eq!(payload.data, data);
+        assert!(payload.compression.is_none());
+        assert!(stats.ratio() > 1.0);
+    }
+
     #[tokio::test]
     async fn open_session_uses_virtual_data_mode_when_server_returns_virtual_open_ok() {
         let response_tx = Arc::new(Mutex::new(HashMap::new()));
@@ -1241,6 +2041,12 @@ mod tests {
             connected: Arc::new(Mutex::new(true)),
             reverse_connect_rx: Arc::new(Mutex::new(None)),
             relay_message_rx: Arc::new(Mutex::new(None)),
+            transfers: Arc::new(Mutex::new(HashMap::new())),
+            peer_event_rx: Arc::new(Mutex::new(None)),
+            next_transfer_id: Arc::new(AtomicU32::new(1)),
+            server_features: Vec::new(),
+            compression: None,
+            compression_stats: Arc::new(CompressionStats::default()),
         };
 
         let response_task = tokio::spawn(async move {
@@ -1257,6 +2063,8 @@ mod tests {
                     stream_ids: vec![],
                     data_mode: SessionDataMode::Virtual,
                     capabilities: vec!["resize".into(), "signal".into()],
+                    session_id: None,
+                    resume_token: None,
                 }),
             })
             .unwrap();
@@ -1271,4 +2079,36 @@ mod tests {
         );
         response_task.await.unwrap();
     }
+
+    #[test]
+    fn rotation_proof_must_link_pinned_and_presented_keys() {
+        let old = SigningKey::from_bytes(&[1; 32]);
+        let new = SigningKey::from_bytes(&[2; 32]);
+        let new_key = new.verifying_key().to_bytes().to_vec();
+        let old_key = old.verifying_key().to_bytes().to_vec();
+        let proof = HostKeyRotation {
+            new_signature: new
+                .sign(&wsh_core::keys::host_signature_data("sess", b"nonce"))
+                .to_bytes()
+                .to_vec(),
+            old_signature: old
+                .sign(&wsh_core::keys::rotation_signature_data(
+                    &new_key, "sess", b"nonce",
+                ))
+                .to_bytes()
+                .to_vec(),
+            old_key: old_key.clone(),
+            new_key: new_key.clone(),
+        };
+        let old_fp = wsh_core::fingerprint(&old_key);
+        let new_fp = wsh_core::fingerprint(&new_key);
+
+        assert!(verify_rotation(&proof, &old_fp, &new_fp, "sess", b"nonce").is_ok());
+        // Replayed into another handshake.
+        assert!(verify_rotation(&proof, &old_fp, &new_fp, "other", b"nonce").is_err());
+        // Not vouched for by the pinned key.
+        assert!(verify_rotation(&proof, &new_fp, &new_fp, "sess", b"nonce").is_err());
+        // Presented key differs from the one vouched for.
+        assert!(verify_rotation(&proof, &old_fp, &old_fp, "sess", b"nonce").is_err());
+    }
 }
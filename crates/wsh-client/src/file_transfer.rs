@@ -1,10 +1,27 @@
-//! Dedicated-stream file transfer for wsh.
+//! File transfer for wsh.
 //!
-//! Uploads and downloads files over a dedicated data stream,
-//! using 64KB chunks with progress reporting.
+//! Two flavours are provided:
+//!
+//! - [`upload`] / [`download`] move an in-memory buffer over a dedicated
+//!   file stream in 64KB chunks.
+//! - [`upload_file`] / [`download_file`] use the resumable chunked protocol
+//!   (`FILE_TRANSFER_START` / `FILE_CHUNK` / `FILE_RESULT`) over the control
+//!   channel. Every chunk carries a BLAKE3 hash, the receiver keeps only
+//!   verified chunks in a `.wshpart` file, an interrupted transfer resumes
+//!   from the last verified chunk, and the whole-file hash is checked at the
+//!   end. These transfers are flow controlled with `WINDOW_UPDATE` credit
+//!   and can be capped to a byte rate.
+
+use std::path::Path;
+use std::time::Duration;
 
+use tokio::fs::{File, OpenOptions};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio::sync::mpsc;
 use wsh_core::error::{WshError, WshResult};
-use wsh_core::messages::ChannelKind;
+use wsh_core::flow::{window_update, RateLimiter, ReceiveWindow, SendWindow, DEFAULT_WINDOW};
+use wsh_core::messages::*;
+use wsh_core::transfer::{chunk_hash, hash_file, partial_path, resume_point, DEFAULT_CHUNK_SIZE};
 
 use crate::client::WshClient;
 use crate::session::SessionOpts;
@@ -12,6 +29,9 @@ use crate::session::SessionOpts;
 /// Default chunk size for file transfers: 64 KB.
 const CHUNK_SIZE: usize = 64 * 1024;
 
+/// How long to wait for the next transfer message before giving up.
+const TRANSFER_TIMEOUT: Duration = Duration::from_secs(30);
+
 /// Upload file data to a remote path via a dedicated file stream.
 ///
 /// Opens a file channel, sends a header with the remote path, then streams
@@ -128,6 +148,370 @@ pub async fn download(client: &WshClient, remote_path: &str) -> WshResult<Vec<u8
     Ok(data)
 }
 
+/// Upload a local file with the resumable chunked protocol.
+///
+/// If an earlier attempt left verified chunks on the server, only the
+/// remainder is sent. `limit_rate` caps throughput in bytes per second.
+/// Calls `on_progress` with bytes confirmed so far (including resumed bytes)
+/// and the total size.
+///
+/// Returns the total file size.
+pub async fn upload_file<F>(
+    client: &WshClient,
+    local_path: &Path,
+    remote_path: &str,
+    limit_rate: Option<u64>,
+    mut on_progress: F,
+) -> WshResult<u64>
+where
+    F: FnMut(u64, u64),
+{
+    let total_size = tokio::fs::metadata(local_path).await?.len();
+    let file_hash = hash_local(local_path, None).await?;
+
+    let (transfer_id, mut rx) = client.open_transfer().await;
+    let result = async {
+        client
+            .send_fire_and_forget(Envelope {
+                msg_type: MsgType::FileTransferStart,
+                payload: Payload::FileTransferStart(FileTransferStartPayload {
+                    transfer_id,
+                    direction: "upload".into(),
+                    path: remote_path.to_string(),
+                    total_size,
+                    chunk_size: DEFAULT_CHUNK_SIZE,
+                    file_hash: Some(file_hash.clone()),
+                    resume_offset: 0,
+                    prefix_hash: None,
+                    window: None,
+                }),
+            })
+            .await?;
+        let ready = recv_ready(&mut rx).await?;
+
+        // Resume only if the server's partial prefix matches our file.
+        let mut offset = ready.resume_offset.min(total_size);
+        if offset > 0 {
+            let ours = hash_local(local_path, Some(offset)).await?;
+            if ready.prefix_hash.as_deref() != Some(ours.as_str()) {
+                tracing::info!("remote partial file does not match, restarting upload");
+                offset = 0;
+            } else {
+                tracing::info!(offset, "resuming upload");
+            }
+        }
+        on_progress(offset, total_size);
+
+        let mut window = ready.window.map(SendWindow::new);
+        let mut limiter = limit_rate.map(RateLimiter::new);
+        let mut file = File::open(local_path).await?;
+        file.seek(std::io::SeekFrom::Start(offset)).await?;
+        let mut buf = vec![0_u8; ready.chunk_size as usize];
+        loop {
+            let want = (total_size - offset).min(u64::from(ready.chunk_size)) as usize;
+            file.read_exact(&mut buf[..want]).await?;
+            let is_final = offset + want as u64 >= total_size;
+            if let Some(window) = window.as_mut() {
+                while !window.can_send(want) {
+                    on_upload_message(recv_next(&mut rx).await?, Some(window))?;
+                }
+                window.consume(want);
+            }
+            if let Some(limiter) = limiter.as_mut() {
+                tokio::time::sleep(limiter.delay(want)).await;
+            }
+            let hash = chunk_hash(&buf[..want]);
+            let (data, compression) = client.compress_chunk(buf[..want].to_vec());
+            client
+                .send_fire_and_forget(Envelope {
+                    msg_type: MsgType::FileChunk,
+                    payload: Payload::FileChunk(FileChunkPayload {
+                        channel_id: transfer_id,
+                        offset,
+                        data,
+                        is_final,
+                        hash: Some(hash),
+                        compression,
+                    }),
+                })
+                .await?;
+            offset += want as u64;
+            on_progress(offset, total_size);
+
+            // A failure reply can arrive mid-stream (e.g. a corrupted chunk).
+            while let Ok(envelope) = rx.try_recv() {
+                on_upload_message(envelope, window.as_mut())?;
+            }
+            if is_final {
+                break;
+            }
+        }
+
+        loop {
+            let envelope = recv_next(&mut rx).await?;
+            if !matches!(envelope.payload, Payload::WindowUpdate(_)) {
+                check_result(envelope)?;
+                break;
+            }
+        }
+        Ok(total_size)
+    }
+    .await;
+    client.close_transfer(transfer_id).await;
+
+    if result.is_ok() {
+        tracing::info!("uploaded {} bytes to '{}'", total_size, remote_path);
+    }
+    result
+}
+
+/// Download a remote file to `local_path` with the resumable chunked protocol.
+///
+/// Verified chunks are written to `<local_path>.wshpart`, which is renamed
+/// into place once the whole-file hash matches. `limit_rate` caps throughput
+/// in bytes per second. Calls `on_progress` with bytes received so far and
+/// the total size.
+///
+/// Returns the total file size.
+pub async fn download_file<F>(
+    client: &WshClient,
+    remote_path: &str,
+    local_path: &Path,
+    limit_rate: Option<u64>,
+    mut on_progress: F,
+) -> WshResult<u64>
+where
+    F: FnMut(u64, u64),
+{
+    let partial = partial_path(local_path);
+    let existing = tokio::fs::metadata(&partial)
+        .await
+        .map(|m| m.len())
+        .unwrap_or(0);
+    let resume_offset = resume_point(existing, None, DEFAULT_CHUNK_SIZE);
+    let prefix_hash = if resume_offset > 0 {
+        Some(hash_local(&partial, Some(resume_offset)).await?)
+    } else {
+        None
+    };
+
+    let mut window = ReceiveWindow::new(DEFAULT_WINDOW);
+    let mut limiter = limit_rate.map(RateLimiter::new);
+    let (transfer_id, mut rx) = client.open_transfer().await;
+    let result = async {
+        client
+            .send_fire_and_forget(Envelope {
+                msg_type: MsgType::FileTransferStart,
+                payload: Payload::FileTransferStart(FileTransferStartPayload {
+                    transfer_id,
+                    direction: "download".into(),
+                    path: remote_path.to_string(),
+                    total_size: 0,
+                    chunk_size: DEFAULT_CHUNK_SIZE,
+                    file_hash: None,
+                    resume_offset,
+                    prefix_hash,
+                    window: Some(window.size()),
+                }),
+            })
+            .await?;
+        let ready = recv_ready(&mut rx).await?;
+        let file_hash = ready
+            .file_hash
+            .clone()
+            .ok_or_else(|| WshError::InvalidMessage("download ready without file hash".into()))?;
+        let total_size = ready.total_size;
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&partial)
+            .await?;
+        let mut written = ready.resume_offset;
+        file.set_len(written).await?;
+        file.seek(std::io::SeekFrom::Start(written)).await?;
+        if written > 0 {
+            tracing::info!(offset = written, "resuming download");
+        }
+        on_progress(written, total_size);
+
+        loop {
+            let envelope = recv_next(&mut rx).await?;
+            let chunk = match envelope.payload {
+                Payload::FileChunk(chunk) => chunk,
+                _ => {
+                    check_result(envelope)?;
+                    continue;
+                }
+            };
+            if chunk.offset != written {
+                return Err(WshError::Transport(format!(
+                    "out-of-order chunk at offset {} (expected {written})",
+                    chunk.offset
+                )));
+            }
+            if let Some(expected) = &chunk.hash {
+                if chunk_hash(&chunk.data) != *expected {
+                    return Err(WshError::Transport(format!(
+                        "chunk hash mismatch at offset {}",
+                        chunk.offset
+                    )));
+                }
+            }
+            file.write_all(&chunk.data).await?;
+            file.flush().await?;
+            written += chunk.data.len() as u64;
+            on_progress(written, total_size);
+            if chunk.is_final {
+                break;
+            }
+            // Holding back credit is what slows the server down.
+            if let Some(limiter) = limiter.as_mut() {
+                tokio::time::sleep(limiter.delay(chunk.data.len())).await;
+            }
+            if let Some(increment) = window.consumed(chunk.data.len()) {
+                client
+                    .send_fire_and_forget(window_update(transfer_id, increment))
+                    .await?;
+            }
+        }
+        file.sync_all().await?;
+        drop(file);
+
+        if written != total_size || hash_local(&partial, None).await? != file_hash {
+            let _ = tokio::fs::remove_file(&partial).await;
+            return Err(WshError::Transport(
+                "downloaded file failed integrity check".into(),
+            ));
+        }
+        tokio::fs::rename(&partial, local_path).await?;
+        Ok(total_size)
+    }
+    .await;
+    client.close_transfer(transfer_id).await;
+
+    if let Ok(total) = &result {
+        tracing::info!("downloaded {} bytes from '{}'", total, remote_path);
+    }
+    result
+}
+
+/// Fetch the sync manifest (relative path, size, BLAKE3 hash) of a remote
+/// directory. A missing directory yields an empty manifest.
+pub async fn remote_manifest(client: &WshClient, remote_path: &str) -> WshResult<Vec<SyncEntry>> {
+    let (transfer_id, mut rx) = client.open_transfer().await;
+    let result = async {
+        client
+            .send_fire_and_forget(Envelope {
+                msg_type: MsgType::SyncManifestRequest,
+                payload: Payload::SyncManifestRequest(SyncManifestRequestPayload {
+                    transfer_id,
+                    path: remote_path.to_string(),
+                }),
+            })
+            .await?;
+        let envelope = recv_next(&mut rx).await?;
+        match envelope.payload {
+            Payload::SyncManifest(manifest) => Ok(manifest.entries),
+            _ => {
+                check_result(envelope)?;
+                Err(WshError::InvalidMessage("expected SYNC_MANIFEST".into()))
+            }
+        }
+    }
+    .await;
+    client.close_transfer(transfer_id).await;
+    result
+}
+
+/// Delete files (relative to `remote_path`) on the remote side.
+pub async fn remote_delete(
+    client: &WshClient,
+    remote_path: &str,
+    paths: Vec<String>,
+) -> WshResult<()> {
+    let (transfer_id, mut rx) = client.open_transfer().await;
+    let result = async {
+        client
+            .send_fire_and_forget(Envelope {
+                msg_type: MsgType::SyncDelete,
+                payload: Payload::SyncDelete(SyncDeletePayload {
+                    transfer_id,
+                    path: remote_path.to_string(),
+                    paths,
+                }),
+            })
+            .await?;
+        check_result(recv_next(&mut rx).await?)
+    }
+    .await;
+    client.close_transfer(transfer_id).await;
+    result
+}
+
+/// Wait for the next message routed to a transfer.
+async fn recv_next(rx: &mut mpsc::Receiver<Envelope>) -> WshResult<Envelope> {
+    match tokio::time::timeout(TRANSFER_TIMEOUT, rx.recv()).await {
+        Ok(Some(envelope)) => Ok(envelope),
+        Ok(None) => Err(WshError::Transport("transfer channel closed".into())),
+        Err(_) => Err(WshError::Timeout),
+    }
+}
+
+/// Wait for `FILE_TRANSFER_READY`, surfacing a `FILE_RESULT` failure as an error.
+async fn recv_ready(rx: &mut mpsc::Receiver<Envelope>) -> WshResult<FileTransferReadyPayload> {
+    let envelope = recv_next(rx).await?;
+    match envelope.payload {
+        Payload::FileTransferReady(ready) => Ok(ready),
+        _ => {
+            check_result(envelope)?;
+            Err(WshError::InvalidMessage(
+                "expected FILE_TRANSFER_READY".into(),
+            ))
+        }
+    }
+}
+
+/// Apply a message that arrives while uploading: window credit, or a
+/// `FILE_RESULT` reporting failure.
+fn on_upload_message(envelope: Envelope, window: Option<&mut SendWindow>) -> WshResult<()> {
+    match (envelope.payload, window) {
+        (Payload::WindowUpdate(update), Some(window)) => {
+            window.grant(update.increment);
+            Ok(())
+        }
+        (Payload::WindowUpdate(_), None) => Ok(()),
+        (payload, _) => check_result(Envelope {
+            msg_type: envelope.msg_type,
+            payload,
+        }),
+    }
+}
+
+/// Turn a `FILE_RESULT` into `Ok(())` or an error.
+fn check_result(envelope: Envelope) -> WshResult<()> {
+    match envelope.payload {
+        Payload::FileResult(result) if result.success => Ok(()),
+        Payload::FileResult(result) => Err(WshError::Channel(
+            result
+                .error_message
+                .unwrap_or_else(|| "file transfer failed".into()),
+        )),
+        other => Err(WshError::InvalidMessage(format!(
+            "unexpected transfer message: {other:?}"
+        ))),
+    }
+}
+
+/// BLAKE3 of a local file (or its prefix), computed off the async runtime.
+async fn hash_local(path: &Path, limit: Option<u64>) -> WshResult<String> {
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || hash_file(&path, limit))
+        .await
+        .map_err(|e| WshError::Other(format!("hash task failed: {e}")))?
+}
+
 // ── Header builders ──────────────────────────────────────────────────
 
 /// Build the upload header: `[4-byte path_len][path_bytes][8-byte total_size]`
@@ -180,4 +564,30 @@ mod tests {
     fn chunk_size_is_64kb() {
         assert_eq!(CHUNK_SIZE, 65536);
     }
+
+    #[test]
+    fn check_result_surfaces_server_error() {
+        let ok = Envelope {
+            msg_type: MsgType::FileResult,
+            payload: Payload::FileResult(FileResultPayload {
+                channel_id: 1,
+                success: true,
+                metadata: serde_json::Value::Null,
+                error_message: None,
+            }),
+        };
+        assert!(check_result(ok).is_ok());
+
+        let failed = Envelope {
+            msg_type: MsgType::FileResult,
+            payload: Payload::FileResult(FileResultPayload {
+                channel_id: 1,
+                success: false,
+                metadata: serde_json::Value::Null,
+                error_message: Some("chunk hash mismatch".into()),
+            }),
+        };
+        let err = check_result(failed).unwrap_err();
+        assert!(err.to_string().contains("chunk hash mismatch"));
+    }
 }
@@ -0,0 +1,165 @@
+//! Tunnels through jump hosts (`wsh -J`).
+//!
+//! A jump host is an ordinary wsh connection whose server has the TCP
+//! gateway enabled. [`open_tunnel`] asks it to open a TCP connection to the
+//! next hop and returns a local [`TcpStream`] whose bytes are relayed over
+//! that gateway channel as `GATEWAY_DATA`. Handing the stream to
+//! [`WshClient::connect_over`] runs the full handshake end to end — TLS,
+//! host key verification against the next hop's own known_hosts entry, and
+//! authentication — so the jump host only ever relays ciphertext.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time;
+use tracing::debug;
+
+use wsh_core::error::{WshError, WshResult};
+use wsh_core::messages::{
+    Envelope, GatewayClosePayload, GatewayDataPayload, MsgType, OpenTcpPayload, Payload,
+};
+
+use crate::client::WshClient;
+
+/// Gateway channel used for the tunnel; each jump connection carries one.
+const TUNNEL_GATEWAY_ID: u32 = 1;
+
+/// How long the jump host may take to reach the next hop.
+const OPEN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Open a TCP tunnel to `host:port` through an authenticated jump connection.
+///
+/// The jump connection is kept alive by the tunnel and closed with it.
+pub async fn open_tunnel(jump: Arc<WshClient>, host: &str, port: u16) -> WshResult<TcpStream> {
+    let mut relay_rx = jump.take_relay_message_rx().await.ok_or_else(|| {
+        WshError::Other("jump connection is already relaying another tunnel".into())
+    })?;
+
+    jump.send_fire_and_forget(Envelope {
+        msg_type: MsgType::OpenTcp,
+        payload: Payload::OpenTcp(OpenTcpPayload {
+            gateway_id: TUNNEL_GATEWAY_ID,
+            host: host.to_string(),
+            port,
+        }),
+    })
+    .await?;
+
+    let opened = time::timeout(OPEN_TIMEOUT, async {
+        while let Some(envelope) = relay_rx.recv().await {
+            match envelope.payload {
+                Payload::GatewayOk(ok) if ok.gateway_id == TUNNEL_GATEWAY_ID => return Ok(()),
+                Payload::GatewayFail(fail) if fail.gateway_id == TUNNEL_GATEWAY_ID => {
+                    return Err(WshError::Other(format!(
+                        "jump host could not reach {host}:{port}: {}",
+                        fail.message
+                    )));
+                }
+                other => debug!("ignoring message while opening tunnel: {other:?}"),
+            }
+        }
+        Err(WshError::Transport(
+            "jump connection closed while opening tunnel".into(),
+        ))
+    })
+    .await;
+    match opened {
+        Ok(result) => result?,
+        Err(_) => return Err(WshError::Timeout),
+    }
+
+    let (outer, inner) = loopback_pair().await?;
+    tokio::spawn(async move {
+        let (mut reader, mut writer) = inner.into_split();
+        let mut buf = vec![0_u8; 16 * 1024];
+        let mut remote_closed = false;
+        loop {
+            tokio::select! {
+                read = reader.read(&mut buf) => {
+                    let n = match read {
+                        Ok(0) | Err(_) => break,
+                        Ok(n) => n,
+                    };
+                    let sent = jump
+                        .send_fire_and_forget(Envelope {
+                            msg_type: MsgType::GatewayData,
+                            payload: Payload::GatewayData(GatewayDataPayload {
+                                gateway_id: TUNNEL_GATEWAY_ID,
+                                data: buf[..n].to_vec(),
+                            }),
+                        })
+                        .await;
+                    if sent.is_err() {
+                        break;
+                    }
+                }
+                envelope = relay_rx.recv() => match envelope.map(|e| e.payload) {
+                    Some(Payload::GatewayData(data)) if data.gateway_id == TUNNEL_GATEWAY_ID => {
+                        if writer.write_all(&data.data).await.is_err() {
+                            break;
+                        }
+                    }
+                    Some(Payload::GatewayClose(close)) if close.gateway_id == TUNNEL_GATEWAY_ID => {
+                        debug!("jump host closed tunnel: {:?}", close.reason);
+                        remote_closed = true;
+                        break;
+                    }
+                    Some(_) => {}
+                    None => {
+                        remote_closed = true;
+                        break;
+                    }
+                }
+            }
+        }
+
+        let _ = writer.shutdown().await;
+        if !remote_closed {
+            let _ = jump
+                .send_fire_and_forget(Envelope {
+                    msg_type: MsgType::GatewayClose,
+                    payload: Payload::GatewayClose(GatewayClosePayload {
+                        gateway_id: TUNNEL_GATEWAY_ID,
+                        reason: Some("tunnel closed".to_string()),
+                    }),
+                })
+                .await;
+        }
+        let _ = jump.disconnect().await;
+    });
+
+    Ok(outer)
+}
+
+/// A connected pair of loopback TCP streams.
+async fn loopback_pair() -> WshResult<(TcpStream, TcpStream)> {
+    let listener = TcpListener::bind(("127.0.0.1", 0)).await?;
+    let addr = listener.local_addr()?;
+    let connect = TcpStream::connect(addr);
+    let (outer, accepted) = tokio::join!(connect, listener.accept());
+    let outer = outer?;
+    let (inner, peer) = accepted?;
+    // Only our own socket may take the other end.
+    if peer != outer.local_addr()? {
+        return Err(WshError::Transport(format!(
+            "unexpected connection from {peer} on tunnel socket"
+        )));
+    }
+    Ok((outer, inner))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn loopback_pair_is_connected() {
+        let (mut outer, mut inner) = loopback_pair().await.unwrap();
+        outer.write_all(b"ping").await.unwrap();
+        let mut buf = [0u8; 4];
+        inner.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"ping");
+    }
+}
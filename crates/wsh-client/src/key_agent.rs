@@ -0,0 +1,382 @@
+//! Key agent: holds Ed25519 keys in memory and signs on request.
+//!
+//! `wsh key-agent` serves this protocol on a unix socket whose path is
+//! exported as `WSH_AUTH_SOCK`. The client signs auth challenges through the
+//! agent when the requested key is not in the local keystore. With
+//! `wsh connect -A` the server exposes a socket on the remote host whose
+//! requests are relayed back over the connection (`AGENT_FORWARD_REQUEST` /
+//! `AGENT_FORWARD_RESPONSE`), so a `wsh` run there can authenticate onward
+//! while the secret key never leaves this machine.
+//!
+//! Wire format: the same length-prefixed CBOR frames as the control stream,
+//! one [`AgentRequest`] answered by one [`AgentResponse`].
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use ed25519_dalek::{Signer, SigningKey};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{UnixListener, UnixStream};
+use wsh_core::{cbor_decode, fingerprint, frame_encode, WshError, WshResult};
+
+use crate::auth;
+
+/// Environment variable holding the agent socket path.
+pub const AUTH_SOCK_ENV: &str = "WSH_AUTH_SOCK";
+
+/// Largest request or response frame accepted on an agent socket.
+pub const MAX_AGENT_FRAME: usize = 64 * 1024;
+
+/// A request to the agent.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum AgentRequest {
+    /// List the public keys the agent holds.
+    List,
+    /// Sign `data` with the key whose public key is `public_key`.
+    Sign { public_key: Vec<u8>, data: Vec<u8> },
+    /// Load a key into the agent.
+    Add { name: String, secret_key: Vec<u8> },
+    /// Drop every key from the agent.
+    RemoveAll,
+}
+
+/// The agent's answer to an [`AgentRequest`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum AgentResponse {
+    Identities { keys: Vec<AgentIdentity> },
+    Signature { signature: Vec<u8> },
+    Success,
+    Failure { reason: String },
+}
+
+/// A key held by the agent.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AgentIdentity {
+    /// Keystore name the key was added under.
+    pub name: String,
+    /// Raw 32-byte Ed25519 public key.
+    pub public_key: Vec<u8>,
+}
+
+impl AgentIdentity {
+    /// SHA-256 fingerprint of the public key.
+    pub fn fingerprint(&self) -> String {
+        fingerprint(&self.public_key)
+    }
+}
+
+/// In-memory key store behind an agent socket.
+#[derive(Default)]
+pub struct KeyAgent {
+    keys: Mutex<Vec<(String, SigningKey)>>,
+}
+
+impl KeyAgent {
+    /// Create an agent with no keys loaded.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load a key, replacing any earlier copy of the same key.
+    pub fn add(&self, name: &str, key: SigningKey) {
+        let mut keys = self.keys.lock().unwrap_or_else(|e| e.into_inner());
+        keys.retain(|(_, k)| k.verifying_key() != key.verifying_key());
+        keys.push((name.to_string(), key));
+    }
+
+    /// Answer a single request.
+    pub fn handle(&self, request: AgentRequest) -> AgentResponse {
+        let mut keys = self.keys.lock().unwrap_or_else(|e| e.into_inner());
+        match request {
+            AgentRequest::List => AgentResponse::Identities {
+                keys: keys
+                    .iter()
+                    .map(|(name, key)| AgentIdentity {
+                        name: name.clone(),
+                        public_key: auth::public_key_bytes(&key.verifying_key()),
+                    })
+                    .collect(),
+            },
+            AgentRequest::Sign { public_key, data } => {
+                match keys
+                    .iter()
+                    .find(|(_, k)| k.verifying_key().as_bytes()[..] == public_key[..])
+                {
+                    Some((_, key)) => AgentResponse::Signature {
+                        signature: key.sign(&data).to_bytes().to_vec(),
+                    },
+                    None => AgentResponse::Failure {
+                        reason: "key not held by agent".into(),
+                    },
+                }
+            }
+            AgentRequest::Add { name, secret_key } => {
+                match auth::signing_key_from_bytes(&secret_key) {
+                    Ok(key) => {
+                        drop(keys);
+                        self.add(&name, key);
+                        AgentResponse::Success
+                    }
+                    Err(e) => AgentResponse::Failure {
+                        reason: e.to_string(),
+                    },
+                }
+            }
+            AgentRequest::RemoveAll => {
+                keys.clear();
+                AgentResponse::Success
+            }
+        }
+    }
+}
+
+/// Accept agent connections on `listener` until the listener fails.
+pub async fn serve(listener: UnixListener, agent: Arc<KeyAgent>) -> WshResult<()> {
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let agent = agent.clone();
+        tokio::spawn(async move {
+            if let Err(e) = serve_connection(stream, &agent).await {
+                tracing::debug!("agent connection ended: {e}");
+            }
+        });
+    }
+}
+
+async fn serve_connection(mut stream: UnixStream, agent: &KeyAgent) -> WshResult<()> {
+    while let Some(payload) = read_frame(&mut stream).await? {
+        let response = match cbor_decode::<AgentRequest>(&payload) {
+            Ok(request) => agent.handle(request),
+            Err(e) => AgentResponse::Failure {
+                reason: format!("malformed request: {e}"),
+            },
+        };
+        stream.write_all(&frame_encode(&response)?).await?;
+    }
+    Ok(())
+}
+
+/// Read one length-prefixed frame, returning `None` on a clean EOF.
+pub async fn read_frame<R: AsyncRead + Unpin>(reader: &mut R) -> WshResult<Option<Vec<u8>>> {
+    let mut len = [0u8; 4];
+    match reader.read_exact(&mut len).await {
+        Ok(_) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_AGENT_FRAME {
+        return Err(WshError::InvalidMessage(format!(
+            "agent frame too large ({len} bytes)"
+        )));
+    }
+    let mut payload = vec![0u8; len];
+    reader.read_exact(&mut payload).await?;
+    Ok(Some(payload))
+}
+
+/// Write a CBOR payload as one length-prefixed frame.
+pub async fn write_frame<W: AsyncWrite + Unpin>(writer: &mut W, payload: &[u8]) -> WshResult<()> {
+    writer
+        .write_all(&(payload.len() as u32).to_be_bytes())
+        .await?;
+    writer.write_all(payload).await?;
+    Ok(())
+}
+
+/// The agent socket named by `WSH_AUTH_SOCK`, if set.
+pub fn socket_from_env() -> Option<PathBuf> {
+    std::env::var_os(AUTH_SOCK_ENV)
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+}
+
+/// A connection to a running agent.
+pub struct AgentClient {
+    stream: UnixStream,
+}
+
+impl AgentClient {
+    /// Connect to the agent listening on `path`.
+    pub async fn connect(path: &Path) -> WshResult<Self> {
+        let stream = UnixStream::connect(path).await.map_err(|e| {
+            WshError::Transport(format!("cannot reach key agent at {}: {e}", path.display()))
+        })?;
+        Ok(Self { stream })
+    }
+
+    /// Send a request and wait for the response.
+    pub async fn request(&mut self, request: &AgentRequest) -> WshResult<AgentResponse> {
+        let reply = self.request_raw(&encode(request)?).await?;
+        cbor_decode(&reply)
+    }
+
+    /// Send an already-encoded request and return the encoded response.
+    pub async fn request_raw(&mut self, payload: &[u8]) -> WshResult<Vec<u8>> {
+        write_frame(&mut self.stream, payload).await?;
+        read_frame(&mut self.stream)
+            .await?
+            .ok_or_else(|| WshError::Transport("key agent closed the connection".into()))
+    }
+
+    /// List the keys the agent holds.
+    pub async fn identities(&mut self) -> WshResult<Vec<AgentIdentity>> {
+        match self.request(&AgentRequest::List).await? {
+            AgentResponse::Identities { keys } => Ok(keys),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// Ask the agent to sign `data` with the given public key.
+    pub async fn sign(&mut self, public_key: &[u8], data: &[u8]) -> WshResult<Vec<u8>> {
+        let request = AgentRequest::Sign {
+            public_key: public_key.to_vec(),
+            data: data.to_vec(),
+        };
+        match self.request(&request).await? {
+            AgentResponse::Signature { signature } => Ok(signature),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// Load a key into the agent.
+    pub async fn add(&mut self, name: &str, key: &SigningKey) -> WshResult<()> {
+        let request = AgentRequest::Add {
+            name: name.to_string(),
+            secret_key: key.to_bytes().to_vec(),
+        };
+        match self.request(&request).await? {
+            AgentResponse::Success => Ok(()),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// Drop every key from the agent.
+    pub async fn remove_all(&mut self) -> WshResult<()> {
+        match self.request(&AgentRequest::RemoveAll).await? {
+            AgentResponse::Success => Ok(()),
+            other => Err(unexpected(other)),
+        }
+    }
+}
+
+/// Sign an auth challenge through the agent at `socket`.
+///
+/// Uses the agent key added as `key_name` if there is one, otherwise the
+/// first key the agent holds. Returns `(signature, public_key)`.
+pub async fn sign_challenge(
+    socket: &Path,
+    key_name: &str,
+    session_id: &str,
+    nonce: &[u8],
+) -> WshResult<(Vec<u8>, Vec<u8>)> {
+    let mut agent = AgentClient::connect(socket).await?;
+    let keys = agent.identities().await?;
+    let identity = keys
+        .iter()
+        .find(|k| k.name == key_name)
+        .or_else(|| keys.first())
+        .ok_or_else(|| WshError::AuthFailed("key agent holds no keys".into()))?;
+    let transcript = auth::build_transcript(session_id, nonce);
+    let signature = agent.sign(&identity.public_key, &transcript).await?;
+    Ok((signature, identity.public_key.clone()))
+}
+
+/// Answer a request forwarded from a remote host by relaying it to the
+/// local agent at `socket`.
+///
+/// Only listing and signing are forwarded; a remote host can never load or
+/// remove keys. Failures are returned as an encoded
+/// [`AgentResponse::Failure`] so the remote caller is never left waiting.
+pub async fn relay_forwarded(socket: Option<&Path>, payload: &[u8]) -> Vec<u8> {
+    let result = async {
+        let socket = socket
+            .ok_or_else(|| WshError::PermissionDenied("agent forwarding is not enabled".into()))?;
+        match cbor_decode::<AgentRequest>(payload)? {
+            AgentRequest::List | AgentRequest::Sign { .. } => {}
+            _ => {
+                return Err(WshError::PermissionDenied(
+                    "only list and sign are allowed over a forwarded agent".into(),
+                ))
+            }
+        }
+        AgentClient::connect(socket)
+            .await?
+            .request_raw(payload)
+            .await
+    }
+    .await;
+
+    result.unwrap_or_else(|e| {
+        encode(&AgentResponse::Failure {
+            reason: e.to_string(),
+        })
+        .unwrap_or_default()
+    })
+}
+
+/// CBOR-encode a value without the length prefix.
+fn encode<T: Serialize>(value: &T) -> WshResult<Vec<u8>> {
+    Ok(frame_encode(value)?.split_off(4))
+}
+
+fn unexpected(response: AgentResponse) -> WshError {
+    match response {
+        AgentResponse::Failure { reason } => WshError::AuthFailed(format!("key agent: {reason}")),
+        other => WshError::InvalidMessage(format!("unexpected key agent response: {other:?}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn agent_signs_over_socket() {
+        let dir = std::env::temp_dir().join(format!("wsh-agent-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let socket = dir.join("agent.sock");
+        let listener = UnixListener::bind(&socket).unwrap();
+        let agent = Arc::new(KeyAgent::new());
+        tokio::spawn(serve(listener, agent));
+
+        let (signing_key, verifying_key) = auth::generate_keypair();
+        AgentClient::connect(&socket)
+            .await
+            .unwrap()
+            .add("work", &signing_key)
+            .await
+            .unwrap();
+
+        let (signature, public_key) = sign_challenge(&socket, "work", "sess-1", b"nonce")
+            .await
+            .unwrap();
+        assert_eq!(public_key, auth::public_key_bytes(&verifying_key));
+        assert!(auth::verify_challenge(
+            &verifying_key,
+            &signature,
+            "sess-1",
+            b"nonce"
+        ));
+
+        // A forwarded request may sign but not load keys.
+        let add = encode(&AgentRequest::Add {
+            name: "evil".into(),
+            secret_key: vec![0; 32],
+        })
+        .unwrap();
+        let reply: AgentResponse =
+            cbor_decode(&relay_forwarded(Some(&socket), &add).await).unwrap();
+        assert!(matches!(reply, AgentResponse::Failure { .. }));
+        let list = encode(&AgentRequest::List).unwrap();
+        let reply: AgentResponse =
+            cbor_decode(&relay_forwarded(Some(&socket), &list).await).unwrap();
+        assert!(matches!(reply, AgentResponse::Identities { keys } if keys.len() == 1));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,68 @@
+//! OS keychain storage for key passphrases.
+//!
+//! With the `keychain` feature, passphrases of encrypted keystore keys can be
+//! saved in the platform credential store (macOS Keychain, Windows Credential
+//! Manager, Linux kernel keyring) under the service `wsh`, so
+//! [`KeyStore::unlock`](crate::KeyStore::unlock) does not need to prompt.
+//! Without the feature every lookup misses and saving fails.
+
+use wsh_core::{WshError, WshResult};
+
+/// Service name the passphrases are stored under.
+#[cfg_attr(not(feature = "keychain"), allow(dead_code))]
+const SERVICE: &str = "wsh";
+
+/// Whether this build can use the OS keychain.
+pub fn is_available() -> bool {
+    cfg!(feature = "keychain")
+}
+
+/// The saved passphrase for a key, if any.
+#[cfg(feature = "keychain")]
+pub fn get_passphrase(key_name: &str) -> Option<String> {
+    let entry = keyring::Entry::new(SERVICE, key_name).ok()?;
+    match entry.get_password() {
+        Ok(passphrase) => Some(passphrase),
+        Err(keyring::Error::NoEntry) => None,
+        Err(e) => {
+            tracing::debug!("keychain lookup for '{}' failed: {}", key_name, e);
+            None
+        }
+    }
+}
+
+/// Save the passphrase for a key, replacing any earlier one.
+#[cfg(feature = "keychain")]
+pub fn store_passphrase(key_name: &str, passphrase: &str) -> WshResult<()> {
+    keyring::Entry::new(SERVICE, key_name)
+        .and_then(|entry| entry.set_password(passphrase))
+        .map_err(|e| WshError::Other(format!("cannot save passphrase to keychain: {e}")))
+}
+
+/// Remove the saved passphrase for a key. Missing entries are not an error.
+#[cfg(feature = "keychain")]
+pub fn delete_passphrase(key_name: &str) -> WshResult<()> {
+    match keyring::Entry::new(SERVICE, key_name).and_then(|entry| entry.delete_credential()) {
+        Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(e) => Err(WshError::Other(format!(
+            "cannot remove passphrase from keychain: {e}"
+        ))),
+    }
+}
+
+#[cfg(not(feature = "keychain"))]
+pub fn get_passphrase(_key_name: &str) -> Option<String> {
+    None
+}
+
+#[cfg(not(feature = "keychain"))]
+pub fn store_passphrase(_key_name: &str, _passphrase: &str) -> WshResult<()> {
+    Err(WshError::Other(
+        "this build has no OS keychain support (enable the `keychain` feature)".into(),
+    ))
+}
+
+#[cfg(not(feature = "keychain"))]
+pub fn delete_passphrase(_key_name: &str) -> WshResult<()> {
+    Ok(())
+}
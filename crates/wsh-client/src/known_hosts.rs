@@ -2,9 +2,19 @@
 //!
 //! Stores known host fingerprints at `~/.wsh/known_hosts`.
 //! Format: one `host fingerprint` pair per line.
+//!
+//! A line of the form `@cert-authority <patterns> ssh-ed25519 <base64>`
+//! trusts that CA to sign host certificates for hosts matching the
+//! comma-separated `*`/`?` patterns; such hosts skip trust-on-first-use.
+//!
+//! A line of the form `@revoked <fingerprint> [comment]` revokes a host key
+//! (or host CA key): connections presenting it fail regardless of the
+//! [`HostKeyChecking`] mode.
 
+use std::fmt;
 use std::fs;
 use std::path::PathBuf;
+use std::str::FromStr;
 use wsh_core::{WshError, WshResult};
 
 /// Result of verifying a host's fingerprint.
@@ -19,6 +29,55 @@ pub enum HostStatus {
         /// The previously stored fingerprint.
         expected: String,
     },
+    /// The host key is listed as `@revoked`.
+    Revoked,
+}
+
+/// How unknown and changed host keys are handled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HostKeyChecking {
+    /// Only connect to hosts already in known_hosts.
+    Strict,
+    /// Ask before trusting a new host (accepts it when no prompt is set).
+    #[default]
+    Ask,
+    /// Trust and record new hosts without asking; still reject changed keys.
+    AcceptNew,
+    /// Skip known_hosts entirely. Revoked keys are still rejected.
+    Off,
+}
+
+impl HostKeyChecking {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            HostKeyChecking::Strict => "strict",
+            HostKeyChecking::Ask => "ask",
+            HostKeyChecking::AcceptNew => "accept-new",
+            HostKeyChecking::Off => "off",
+        }
+    }
+}
+
+impl fmt::Display for HostKeyChecking {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for HostKeyChecking {
+    type Err = WshError;
+
+    fn from_str(s: &str) -> WshResult<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "strict" | "yes" => Ok(HostKeyChecking::Strict),
+            "ask" => Ok(HostKeyChecking::Ask),
+            "accept-new" => Ok(HostKeyChecking::AcceptNew),
+            "off" | "no" => Ok(HostKeyChecking::Off),
+            other => Err(WshError::Other(format!(
+                "invalid host key checking mode '{other}' (expected strict, ask, accept-new or off)"
+            ))),
+        }
+    }
 }
 
 /// Known hosts file manager.
@@ -42,6 +101,9 @@ impl KnownHosts {
     /// Verify a host's fingerprint against stored records.
     pub fn verify_host(&self, host: &str, fingerprint: &str) -> WshResult<HostStatus> {
         let entries = self.load_entries()?;
+        if revoked_in(&entries, fingerprint) {
+            return Ok(HostStatus::Revoked);
+        }
 
         for (stored_host, stored_fp) in &entries {
             if stored_host == host {
@@ -83,6 +145,44 @@ impl KnownHosts {
         Ok(removed)
     }
 
+    /// Whether a host (or host CA) key fingerprint is listed as `@revoked`.
+    pub fn is_revoked(&self, fingerprint: &str) -> WshResult<bool> {
+        Ok(revoked_in(&self.load_entries()?, fingerprint))
+    }
+
+    /// Revoke a host key fingerprint, dropping any host entries that use it.
+    pub fn revoke(&self, fingerprint: &str) -> WshResult<()> {
+        let mut entries = self.load_entries()?;
+        if revoked_in(&entries, fingerprint) {
+            return Ok(());
+        }
+        entries.retain(|(host, fp)| host.starts_with('@') || fp != fingerprint);
+        entries.push(("@revoked".to_string(), fingerprint.to_string()));
+        self.save_entries(&entries)
+    }
+
+    /// Raw CA keys trusted to sign host certificates for `host` (no port).
+    pub fn cert_authorities_for(&self, host: &str) -> WshResult<Vec<Vec<u8>>> {
+        let authorities = self
+            .load_entries()?
+            .into_iter()
+            .filter(|(marker, _)| marker == "@cert-authority")
+            .filter_map(|(_, rest)| {
+                let (patterns, key) = rest.split_once(' ')?;
+                let host_matches = patterns
+                    .split(',')
+                    .any(|p| glob_match(p.as_bytes(), host.as_bytes()));
+                if !host_matches {
+                    return None;
+                }
+                wsh_core::keys::parse_authorized_keys(key.trim())
+                    .first()
+                    .and_then(|k| k.public_key())
+            })
+            .collect();
+        Ok(authorities)
+    }
+
     /// List all known hosts and their fingerprints.
     pub fn list(&self) -> WshResult<Vec<(String, String)>> {
         self.load_entries()
@@ -149,6 +249,73 @@ impl KnownHosts {
     }
 }
 
+fn revoked_in(entries: &[(String, String)], fingerprint: &str) -> bool {
+    entries.iter().any(|(marker, rest)| {
+        marker == "@revoked"
+            && rest
+                .split_whitespace()
+                .next()
+                .is_some_and(|fp| fp.eq_ignore_ascii_case(fingerprint))
+    })
+}
+
+/// Render a fingerprint as OpenSSH-style "randomart" (the drunken bishop
+/// walk), so a host key can be recognised at a glance.
+///
+/// `fingerprint` is the hex SHA-256 digest used throughout wsh.
+pub fn randomart(fingerprint: &str) -> String {
+    const WIDTH: usize = 17;
+    const HEIGHT: usize = 9;
+    const SYMBOLS: &[u8] = b" .o+=*BOX@%&#/^SE";
+
+    let digest = hex::decode(fingerprint).unwrap_or_else(|_| fingerprint.as_bytes().to_vec());
+    let mut field = [[0u8; WIDTH]; HEIGHT];
+    let (start_x, start_y) = (WIDTH / 2, HEIGHT / 2);
+    let (mut x, mut y) = (start_x, start_y);
+    for byte in digest {
+        // Each byte is four moves, least significant bit pair first.
+        for step in 0..4 {
+            let bits = (byte >> (step * 2)) & 0b11;
+            x = if bits & 0b01 != 0 {
+                (x + 1).min(WIDTH - 1)
+            } else {
+                x.saturating_sub(1)
+            };
+            y = if bits & 0b10 != 0 {
+                (y + 1).min(HEIGHT - 1)
+            } else {
+                y.saturating_sub(1)
+            };
+            let cell = &mut field[y][x];
+            *cell = (*cell + 1).min(SYMBOLS.len() as u8 - 3);
+        }
+    }
+    field[start_y][start_x] = SYMBOLS.len() as u8 - 2;
+    field[y][x] = SYMBOLS.len() as u8 - 1;
+
+    let mut art = String::with_capacity((WIDTH + 3) * (HEIGHT + 2));
+    art.push_str("+---[ED25519 256]-+\n");
+    for row in field {
+        art.push('|');
+        art.extend(row.iter().map(|&n| SYMBOLS[n as usize] as char));
+        art.push_str("|\n");
+    }
+    art.push_str("+----[SHA256]-----+");
+    art
+}
+
+/// Case-insensitive glob match supporting `*` and `?`.
+fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some((b'*', rest)) => (0..=text.len()).any(|skip| glob_match(rest, &text[skip..])),
+        Some((b'?', rest)) => !text.is_empty() && glob_match(rest, &text[1..]),
+        Some((c, rest)) => text
+            .split_first()
+            .is_some_and(|(t, tail)| c.eq_ignore_ascii_case(t) && glob_match(rest, tail)),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -231,4 +398,87 @@ mod tests {
         let list = kh.list().unwrap();
         assert_eq!(list.len(), 2);
     }
+
+    #[test]
+    fn revoked_keys_are_reported() {
+        let kh = temp_known_hosts("revoked");
+        kh.add_host("example.com:4422", "abc123").unwrap();
+        kh.add_host("other:4422", "def456").unwrap();
+        kh.revoke("abc123").unwrap();
+
+        assert!(kh.is_revoked("ABC123").unwrap());
+        assert_eq!(
+            kh.verify_host("example.com:4422", "abc123").unwrap(),
+            HostStatus::Revoked
+        );
+        // Revocation applies to every host, even ones never seen with the key.
+        assert_eq!(
+            kh.verify_host("new-host:4422", "abc123").unwrap(),
+            HostStatus::Revoked
+        );
+        assert_eq!(
+            kh.verify_host("other:4422", "def456").unwrap(),
+            HostStatus::Known
+        );
+        assert!(!kh
+            .list()
+            .unwrap()
+            .iter()
+            .any(|(h, _)| h == "example.com:4422"));
+    }
+
+    #[test]
+    fn host_key_checking_modes_parse() {
+        for mode in [
+            HostKeyChecking::Strict,
+            HostKeyChecking::Ask,
+            HostKeyChecking::AcceptNew,
+            HostKeyChecking::Off,
+        ] {
+            assert_eq!(mode.as_str().parse::<HostKeyChecking>().unwrap(), mode);
+        }
+        assert_eq!(
+            "yes".parse::<HostKeyChecking>().unwrap(),
+            HostKeyChecking::Strict
+        );
+        assert!("maybe".parse::<HostKeyChecking>().is_err());
+    }
+
+    #[test]
+    fn randomart_is_framed_and_deterministic() {
+        let fp = wsh_core::fingerprint(b"host key");
+        let art = randomart(&fp);
+        let lines: Vec<&str> = art.lines().collect();
+        assert_eq!(lines.len(), 11);
+        assert!(lines.iter().all(|line| line.chars().count() == 19));
+        // The walk always ends on exactly one `E` cell.
+        let field: String = lines[1..10].concat();
+        assert_eq!(field.matches('E').count(), 1);
+        assert_eq!(art, randomart(&fp));
+        assert_ne!(art, randomart(&wsh_core::fingerprint(b"other key")));
+    }
+
+    #[test]
+    fn cert_authority_lines_match_host_patterns() {
+        let kh = temp_known_hosts("ca");
+        kh.add_host("example.com:4422", "abc123").unwrap();
+        let mut content = fs::read_to_string(&kh.path).unwrap();
+        content.push_str(
+            "@cert-authority *.example.com,db? ssh-ed25519 \
+             AAAAC3NzaC1lZDI1NTE5AAAAIAABAgMEBQYHCAkKCwwNDg8QERITFBUWFxgZGhscHR4f ca\n",
+        );
+        fs::write(&kh.path, content).unwrap();
+
+        let expected: Vec<u8> = (0..32).collect();
+        assert_eq!(
+            kh.cert_authorities_for("web.example.com").unwrap(),
+            vec![expected.clone()]
+        );
+        assert_eq!(kh.cert_authorities_for("db1").unwrap(), vec![expected]);
+        assert!(kh.cert_authorities_for("example.com").unwrap().is_empty());
+
+        // Rewriting the file keeps the CA line.
+        kh.add_host("other:4422", "def456").unwrap();
+        assert_eq!(kh.cert_authorities_for("db1").unwrap().len(), 1);
+    }
 }
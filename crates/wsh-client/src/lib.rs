@@ -31,19 +31,31 @@
 pub mod auth;
 pub mod client;
 pub mod file_transfer;
+pub mod jump;
+#[cfg(unix)]
+pub mod key_agent;
+pub mod keychain;
 pub mod keystore;
 pub mod known_hosts;
 pub mod mcp;
+#[cfg(unix)]
+pub mod mux;
 pub mod session;
+pub mod socks;
 pub mod transport;
 pub mod virtual_session;
 
 // Re-export primary public types.
-pub use client::{ConnectConfig, RemoteSessionInfo, WshClient};
+pub use client::{
+    ConnectConfig, HostKeyChangePrompt, HostKeyPrompt, PassphrasePrompt, RemoteSessionInfo,
+    TotpPrompt, WshClient,
+};
 pub use keystore::{KeyInfo, KeyStore};
-pub use known_hosts::{HostStatus, KnownHosts};
-pub use session::{SessionInfo, SessionOpts, SessionState, WshSession};
-pub use transport::{AnyTransport, TransportKind, WebSocketSession, WebTransportSession};
+pub use known_hosts::{HostKeyChecking, HostStatus, KnownHosts};
+pub use session::{ResumeHandle, SessionInfo, SessionOpts, SessionState, WshSession};
+pub use transport::{
+    AnyTransport, TransportKind, TransportProfiles, WebSocketSession, WebTransportSession,
+};
 pub use virtual_session::VirtualSessionBackend;
 
 // Re-export wsh-core error types for convenience.
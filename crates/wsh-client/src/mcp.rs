@@ -1,7 +1,8 @@
 //! MCP (Model Context Protocol) tool discovery and invocation over wsh.
 //!
 //! Uses the wsh control channel to discover available tools on the remote
-//! server and invoke them, returning structured JSON results.
+//! server and invoke them, returning structured JSON results. Servers that
+//! advertise `mcp-progress` report on long calls while they run.
 
 use wsh_core::error::{WshError, WshResult};
 use wsh_core::messages::*;
@@ -46,6 +47,7 @@ pub async fn call_tool(
         payload: Payload::McpCall(McpCallPayload {
             tool: name.to_string(),
             arguments: args,
+            progress: None,
         }),
     };
 
@@ -67,3 +69,46 @@ pub async fn call_tool(
         )),
     }
 }
+
+/// Call an MCP tool, passing each progress report to `on_progress`.
+///
+/// Against servers without `mcp-progress` this is [`call_tool`], with its
+/// fixed response timeout and no reports.
+pub async fn call_tool_with_progress(
+    client: &WshClient,
+    name: &str,
+    args: serde_json::Value,
+    mut on_progress: impl FnMut(&McpProgressPayload),
+) -> WshResult<serde_json::Value> {
+    if !client.server_features().iter().any(|f| f == "mcp-progress") {
+        return call_tool(client, name, args).await;
+    }
+    let envelope = Envelope {
+        msg_type: MsgType::McpCall,
+        payload: Payload::McpCall(McpCallPayload {
+            tool: name.to_string(),
+            arguments: args,
+            progress: Some(true),
+        }),
+    };
+
+    let response = client
+        .send_and_wait_with_progress(
+            envelope,
+            MsgType::McpResult,
+            MsgType::McpProgress,
+            |progress| {
+                if let Payload::McpProgress(p) = &progress.payload {
+                    on_progress(p);
+                }
+            },
+        )
+        .await?;
+
+    match response.payload {
+        Payload::McpResult(result) => Ok(result.result),
+        _ => Err(WshError::InvalidMessage(
+            "expected McpResult response".into(),
+        )),
+    }
+}
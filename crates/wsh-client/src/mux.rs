@@ -0,0 +1,395 @@
+//! Connection sharing ("control master").
+//!
+//! `wsh mux start [user@]host` connects and authenticates once, then serves
+//! this protocol on a unix socket at `~/.wsh/mux/<user>@<host>-<port>.sock`.
+//! Later `wsh [user@]host command` and `wsh scp` invocations for the same
+//! target find the socket and run over the shared connection instead of
+//! opening their own, skipping the transport and auth handshakes.
+//!
+//! Wire format: the same length-prefixed CBOR frames as the key agent. Each
+//! socket connection carries one [`MuxRequest`], answered by a stream of
+//! [`MuxResponse`] frames ending in a terminal one (`exit`, `done`, `ok` or
+//! `failure`). File transfers name local paths, which the master reads or
+//! writes itself — it runs as the same user on the same machine.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::{mpsc, watch};
+use wsh_core::messages::ChannelKind;
+use wsh_core::{cbor_decode, frame_encode, WshError, WshResult};
+
+use crate::client::WshClient;
+use crate::file_transfer;
+use crate::key_agent::{read_frame, write_frame};
+use crate::session::SessionOpts;
+
+/// Largest chunk of command output sent in one [`MuxResponse::Output`].
+const OUTPUT_CHUNK: usize = 16 * 1024;
+
+/// A request to the mux master.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum MuxRequest {
+    /// Check that the master is alive and still connected.
+    Check,
+    /// Run a command in a new exec channel, streaming its output.
+    Exec { command: String },
+    /// Upload a local file.
+    Upload {
+        local_path: PathBuf,
+        remote_path: String,
+        limit_rate: Option<u64>,
+    },
+    /// Download a remote file to a local path.
+    Download {
+        remote_path: String,
+        local_path: PathBuf,
+        limit_rate: Option<u64>,
+    },
+    /// Disconnect and stop the master.
+    Stop,
+}
+
+/// A frame sent back by the mux master.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum MuxResponse {
+    /// Command output.
+    Output { data: Vec<u8> },
+    /// Transfer progress in bytes.
+    Progress { done: u64, total: u64 },
+    /// The command exited (terminal).
+    Exit { code: i32 },
+    /// A transfer finished (terminal).
+    Done { size: u64 },
+    /// A check or stop request succeeded (terminal).
+    Ok,
+    /// The request failed (terminal).
+    Failure { reason: String },
+}
+
+/// Socket of the mux master for `user@host:port`.
+pub fn socket_path(user: &str, host: &str, port: u16) -> WshResult<PathBuf> {
+    let home = dirs::home_dir()
+        .ok_or_else(|| WshError::Other("cannot determine home directory".into()))?;
+    Ok(home
+        .join(".wsh")
+        .join("mux")
+        .join(format!("{user}@{host}-{port}.sock")))
+}
+
+/// Serve mux requests over `client` until a `stop` request arrives or the
+/// listener fails.
+pub async fn serve(listener: UnixListener, client: Arc<WshClient>) -> WshResult<()> {
+    let (stop_tx, mut stop_rx) = watch::channel(false);
+    loop {
+        let (stream, _) = tokio::select! {
+            accepted = listener.accept() => accepted?,
+            _ = stop_rx.changed() => return Ok(()),
+        };
+        let client = client.clone();
+        let stop_tx = stop_tx.clone();
+        tokio::spawn(async move {
+            if let Err(e) = serve_connection(stream, &client, &stop_tx).await {
+                tracing::debug!("mux connection ended: {e}");
+            }
+        });
+    }
+}
+
+async fn serve_connection(
+    mut stream: UnixStream,
+    client: &WshClient,
+    stop: &watch::Sender<bool>,
+) -> WshResult<()> {
+    let Some(payload) = read_frame(&mut stream).await? else {
+        return Ok(());
+    };
+    let request = match cbor_decode::<MuxRequest>(&payload) {
+        Ok(request) => request,
+        Err(e) => {
+            let reason = format!("malformed request: {e}");
+            return send(&mut stream, &MuxResponse::Failure { reason }).await;
+        }
+    };
+
+    // Stream intermediate frames through a channel so transfer progress
+    // callbacks (which are synchronous) can report without blocking.
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let work = handle(request, client, stop, tx);
+    tokio::pin!(work);
+    let terminal = loop {
+        tokio::select! {
+            result = &mut work => break result,
+            Some(frame) = rx.recv() => send(&mut stream, &frame).await?,
+        }
+    };
+    while let Ok(frame) = rx.try_recv() {
+        send(&mut stream, &frame).await?;
+    }
+    let terminal = terminal.unwrap_or_else(|e| MuxResponse::Failure {
+        reason: e.to_string(),
+    });
+    send(&mut stream, &terminal).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+async fn handle(
+    request: MuxRequest,
+    client: &WshClient,
+    stop: &watch::Sender<bool>,
+    tx: mpsc::UnboundedSender<MuxResponse>,
+) -> WshResult<MuxResponse> {
+    if !client.is_connected().await {
+        return Err(WshError::Transport("mux master is disconnected".into()));
+    }
+    match request {
+        MuxRequest::Check => Ok(MuxResponse::Ok),
+        MuxRequest::Stop => {
+            let _ = client.disconnect().await;
+            let _ = stop.send(true);
+            Ok(MuxResponse::Ok)
+        }
+        MuxRequest::Exec { command } => {
+            let session = client
+                .open_session(SessionOpts {
+                    kind: ChannelKind::Exec,
+                    command: Some(command),
+                    cols: None,
+                    rows: None,
+                    env: None,
+                })
+                .await?;
+            let mut buf = vec![0u8; OUTPUT_CHUNK];
+            loop {
+                let n = session.read(&mut buf).await?;
+                if n == 0 {
+                    break;
+                }
+                let _ = tx.send(MuxResponse::Output {
+                    data: buf[..n].to_vec(),
+                });
+            }
+            let code = session.exit_code().await.unwrap_or(0);
+            let _ = session.close().await;
+            Ok(MuxResponse::Exit { code })
+        }
+        MuxRequest::Upload {
+            local_path,
+            remote_path,
+            limit_rate,
+        } => {
+            let size = file_transfer::upload_file(
+                client,
+                &local_path,
+                &remote_path,
+                limit_rate,
+                |done, total| {
+                    let _ = tx.send(MuxResponse::Progress { done, total });
+                },
+            )
+            .await?;
+            Ok(MuxResponse::Done { size })
+        }
+        MuxRequest::Download {
+            remote_path,
+            local_path,
+            limit_rate,
+        } => {
+            let size = file_transfer::download_file(
+                client,
+                &remote_path,
+                &local_path,
+                limit_rate,
+                |done, total| {
+                    let _ = tx.send(MuxResponse::Progress { done, total });
+                },
+            )
+            .await?;
+            Ok(MuxResponse::Done { size })
+        }
+    }
+}
+
+async fn send(stream: &mut UnixStream, response: &MuxResponse) -> WshResult<()> {
+    stream.write_all(&frame_encode(response)?).await?;
+    Ok(())
+}
+
+/// A request in flight to a mux master.
+pub struct MuxClient {
+    stream: UnixStream,
+}
+
+impl MuxClient {
+    /// Connect to the master listening on `path`.
+    pub async fn connect(path: &Path) -> WshResult<Self> {
+        let stream = UnixStream::connect(path).await.map_err(|e| {
+            WshError::Transport(format!(
+                "cannot reach mux master at {}: {e}",
+                path.display()
+            ))
+        })?;
+        Ok(Self { stream })
+    }
+
+    /// Connect to the master for `user@host:port` if one is running and
+    /// still connected.
+    pub async fn find(user: &str, host: &str, port: u16) -> Option<Self> {
+        let path = socket_path(user, host, port).ok()?;
+        if !path.exists() {
+            return None;
+        }
+        let mut probe = Self::connect(&path).await.ok()?;
+        match probe.call(&MuxRequest::Check, |_| {}).await {
+            Ok(MuxResponse::Ok) => Self::connect(&path).await.ok(),
+            _ => None,
+        }
+    }
+
+    /// Send `request`, passing intermediate frames to `on_frame` and
+    /// returning the terminal one.
+    pub async fn call<F>(&mut self, request: &MuxRequest, mut on_frame: F) -> WshResult<MuxResponse>
+    where
+        F: FnMut(MuxResponse),
+    {
+        write_frame(&mut self.stream, &frame_encode(request)?[4..]).await?;
+        loop {
+            let frame = read_frame(&mut self.stream)
+                .await?
+                .ok_or_else(|| WshError::Transport("mux master closed the connection".into()))?;
+            match cbor_decode::<MuxResponse>(&frame)? {
+                frame @ (MuxResponse::Output { .. } | MuxResponse::Progress { .. }) => {
+                    on_frame(frame)
+                }
+                MuxResponse::Failure { reason } => {
+                    return Err(WshError::Other(format!("mux master: {reason}")))
+                }
+                terminal => return Ok(terminal),
+            }
+        }
+    }
+
+    /// Run `command`, passing output to `on_output`; returns the exit code.
+    pub async fn exec<F>(mut self, command: &str, mut on_output: F) -> WshResult<i32>
+    where
+        F: FnMut(&[u8]),
+    {
+        let request = MuxRequest::Exec {
+            command: command.to_string(),
+        };
+        let terminal = self
+            .call(&request, |frame| {
+                if let MuxResponse::Output { data } = frame {
+                    on_output(&data);
+                }
+            })
+            .await?;
+        match terminal {
+            MuxResponse::Exit { code } => Ok(code),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// Run an upload or download request, reporting progress; returns the
+    /// number of bytes transferred.
+    pub async fn transfer<F>(mut self, request: &MuxRequest, mut on_progress: F) -> WshResult<u64>
+    where
+        F: FnMut(u64, u64),
+    {
+        let terminal = self
+            .call(request, |frame| {
+                if let MuxResponse::Progress { done, total } = frame {
+                    on_progress(done, total);
+                }
+            })
+            .await?;
+        match terminal {
+            MuxResponse::Done { size } => Ok(size),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// Ask the master to disconnect and exit.
+    pub async fn stop(mut self) -> WshResult<()> {
+        match self.call(&MuxRequest::Stop, |_| {}).await? {
+            MuxResponse::Ok => Ok(()),
+            other => Err(unexpected(other)),
+        }
+    }
+}
+
+fn unexpected(response: MuxResponse) -> WshError {
+    WshError::InvalidMessage(format!("unexpected mux response: {response:?}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requests_round_trip_through_cbor() {
+        let request = MuxRequest::Upload {
+            local_path: PathBuf::from("/tmp/a.txt"),
+            remote_path: "b.txt".into(),
+            limit_rate: Some(1024),
+        };
+        let encoded = frame_encode(&request).unwrap();
+        match cbor_decode::<MuxRequest>(&encoded[4..]).unwrap() {
+            MuxRequest::Upload {
+                local_path,
+                remote_path,
+                limit_rate,
+            } => {
+                assert_eq!(local_path, PathBuf::from("/tmp/a.txt"));
+                assert_eq!(remote_path, "b.txt");
+                assert_eq!(limit_rate, Some(1024));
+            }
+            other => panic!("unexpected request: {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn client_streams_frames_until_terminal() {
+        let dir = std::env::temp_dir().join(format!("wsh-mux-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("mux.sock");
+        let listener = UnixListener::bind(&path).unwrap();
+
+        // A stand-in master that answers any request with fixed frames.
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let payload = read_frame(&mut stream).await.unwrap().unwrap();
+            assert!(matches!(
+                cbor_decode::<MuxRequest>(&payload).unwrap(),
+                MuxRequest::Exec { ref command } if command == "uptime"
+            ));
+            for data in [b"up ".to_vec(), b"3 days\n".to_vec()] {
+                send(&mut stream, &MuxResponse::Output { data })
+                    .await
+                    .unwrap();
+            }
+            send(&mut stream, &MuxResponse::Exit { code: 3 })
+                .await
+                .unwrap();
+        });
+
+        let mut output = Vec::new();
+        let code = MuxClient::connect(&path)
+            .await
+            .unwrap()
+            .exec("uptime", |data| output.extend_from_slice(data))
+            .await
+            .unwrap();
+        assert_eq!(code, 3);
+        assert_eq!(output, b"up 3 days\n");
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -4,6 +4,7 @@
 //! message queue and provides read/write/resize/signal/close operations on a
 //! single channel.
 
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 
 use tokio::sync::Mutex;
@@ -91,6 +92,21 @@ pub struct WshSession {
     /// Sender for control messages (resize, signal, close) — sent to the client's
     /// control dispatch loop.
     control_tx: tokio::sync::mpsc::Sender<ControlAction>,
+    /// Server session ID and token for resuming after a dropped connection.
+    resume: Option<(String, Vec<u8>)>,
+    /// Output bytes received so far, counted from the start of the session.
+    output_offset: AtomicU64,
+}
+
+/// What a client needs to pick a session back up on a new connection.
+#[derive(Debug, Clone)]
+pub struct ResumeHandle {
+    /// Server-side session ID.
+    pub session_id: String,
+    /// Token the server issued for this session.
+    pub token: Vec<u8>,
+    /// Output bytes already received.
+    pub offset: u64,
 }
 
 enum SessionBackend {
@@ -137,6 +153,8 @@ impl WshSession {
             exit_code: Arc::new(Mutex::new(None)),
             backend: SessionBackend::Stream(Arc::new(Mutex::new(stream))),
             control_tx,
+            resume: None,
+            output_offset: AtomicU64::new(0),
         }
     }
 
@@ -156,9 +174,27 @@ impl WshSession {
             exit_code: Arc::new(Mutex::new(None)),
             backend: SessionBackend::Virtual(Arc::new(VirtualSessionBackend::new())),
             control_tx,
+            resume: None,
+            output_offset: AtomicU64::new(0),
         }
     }
 
+    /// Make the session resumable, continuing from output offset `offset`.
+    pub(crate) fn with_resume(mut self, session_id: String, token: Vec<u8>, offset: u64) -> Self {
+        self.resume = Some((session_id, token));
+        self.output_offset = AtomicU64::new(offset);
+        self
+    }
+
+    /// Where to resume this session from, if the server made it resumable.
+    pub fn resume_handle(&self) -> Option<ResumeHandle> {
+        self.resume.clone().map(|(session_id, token)| ResumeHandle {
+            session_id,
+            token,
+            offset: self.output_offset.load(Ordering::Relaxed),
+        })
+    }
+
     /// The channel ID assigned by the server.
     pub fn channel_id(&self) -> u32 {
         self.channel_id
@@ -348,7 +384,11 @@ impl WshSession {
     pub(crate) async fn handle_control(&self, envelope: &Envelope) -> WshResult<()> {
         match &envelope.payload {
             Payload::SessionData(data) => match &self.backend {
-                SessionBackend::Virtual(backend) => backend.push_data(data.data.clone()).await,
+                SessionBackend::Virtual(backend) => {
+                    self.output_offset
+                        .fetch_add(data.data.len() as u64, Ordering::Relaxed);
+                    backend.push_data(data.data.clone()).await
+                }
                 SessionBackend::Stream(_) => Ok(()),
             },
             Payload::Close(_) => {
@@ -424,6 +464,7 @@ mod tests {
             payload: Payload::SessionData(wsh_core::messages::SessionDataPayload {
                 channel_id: 8,
                 data: b"ls\n".to_vec(),
+                compression: None,
             }),
         };
 
@@ -435,6 +476,35 @@ mod tests {
         assert_eq!(&buf[..n], b"ls\n");
     }
 
+    #[tokio::test]
+    async fn resume_handle_tracks_received_output() {
+        let (control_tx, _control_rx) = mpsc::channel(4);
+        let session = WshSession::new_virtual(7, ChannelKind::Pty, control_tx.clone(), vec![]);
+        assert!(session.resume_handle().is_none());
+
+        let session = WshSession::new_virtual(7, ChannelKind::Pty, control_tx, vec![]).with_resume(
+            "s1".into(),
+            b"token".to_vec(),
+            10,
+        );
+        session
+            .handle_control(&Envelope {
+                msg_type: MsgType::SessionData,
+                payload: Payload::SessionData(wsh_core::messages::SessionDataPayload {
+                    channel_id: 7,
+                    data: b"hello".to_vec(),
+                    compression: None,
+                }),
+            })
+            .await
+            .unwrap();
+
+        let handle = session.resume_handle().unwrap();
+        assert_eq!(handle.session_id, "s1");
+        assert_eq!(handle.token, b"token");
+        assert_eq!(handle.offset, 15);
+    }
+
     #[tokio::test]
     async fn close_payload_marks_virtual_session_closed() {
         let (control_tx, _control_rx) = mpsc::channel(4);
@@ -0,0 +1,184 @@
+//! Minimal SOCKS5 server-side handshake for dynamic forwarding.
+//!
+//! Only the `CONNECT` command with no authentication is supported, which is
+//! what browsers and `curl --socks5-hostname` use against a local proxy. The
+//! destination is returned to the caller, which opens it through the wsh
+//! gateway and then reports the outcome with [`send_reply`].
+
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use wsh_core::{WshError, WshResult};
+
+const SOCKS_VERSION: u8 = 0x05;
+const AUTH_NONE: u8 = 0x00;
+const AUTH_NO_ACCEPTABLE: u8 = 0xff;
+const CMD_CONNECT: u8 = 0x01;
+const ATYP_IPV4: u8 = 0x01;
+const ATYP_DOMAIN: u8 = 0x03;
+const ATYP_IPV6: u8 = 0x04;
+
+/// SOCKS5 reply codes sent back to the local application.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum SocksReply {
+    Succeeded = 0x00,
+    GeneralFailure = 0x01,
+    NotAllowed = 0x02,
+    HostUnreachable = 0x04,
+    ConnectionRefused = 0x05,
+    CommandNotSupported = 0x07,
+    AddressTypeNotSupported = 0x08,
+}
+
+impl SocksReply {
+    /// Map a gateway failure code (`GATEWAY_FAIL.code`) to a SOCKS reply.
+    pub fn from_gateway_code(code: u32) -> Self {
+        match code {
+            1 => Self::ConnectionRefused,
+            3 => Self::HostUnreachable,
+            4 | 5 => Self::NotAllowed,
+            _ => Self::GeneralFailure,
+        }
+    }
+}
+
+/// Destination requested by a SOCKS5 client.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SocksTarget {
+    pub host: String,
+    pub port: u16,
+}
+
+/// Run the greeting and request phases, returning the requested destination.
+///
+/// On protocol errors the appropriate failure reply is written before the
+/// error is returned, so the caller only needs to drop the stream.
+pub async fn accept<S>(stream: &mut S) -> WshResult<SocksTarget>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    // Greeting: VER NMETHODS METHODS...
+    let mut header = [0_u8; 2];
+    stream.read_exact(&mut header).await?;
+    if header[0] != SOCKS_VERSION {
+        return Err(WshError::InvalidMessage(format!(
+            "unsupported SOCKS version {}",
+            header[0]
+        )));
+    }
+    let mut methods = vec![0_u8; header[1] as usize];
+    stream.read_exact(&mut methods).await?;
+    if !methods.contains(&AUTH_NONE) {
+        stream
+            .write_all(&[SOCKS_VERSION, AUTH_NO_ACCEPTABLE])
+            .await?;
+        return Err(WshError::InvalidMessage(
+            "SOCKS client offered no supported auth method".into(),
+        ));
+    }
+    stream.write_all(&[SOCKS_VERSION, AUTH_NONE]).await?;
+
+    // Request: VER CMD RSV ATYP DST.ADDR DST.PORT
+    let mut request = [0_u8; 4];
+    stream.read_exact(&mut request).await?;
+    if request[1] != CMD_CONNECT {
+        send_reply(stream, SocksReply::CommandNotSupported).await?;
+        return Err(WshError::InvalidMessage(format!(
+            "unsupported SOCKS command {}",
+            request[1]
+        )));
+    }
+
+    let host = match request[3] {
+        ATYP_IPV4 => {
+            let mut addr = [0_u8; 4];
+            stream.read_exact(&mut addr).await?;
+            Ipv4Addr::from(addr).to_string()
+        }
+        ATYP_IPV6 => {
+            let mut addr = [0_u8; 16];
+            stream.read_exact(&mut addr).await?;
+            Ipv6Addr::from(addr).to_string()
+        }
+        ATYP_DOMAIN => {
+            let len = stream.read_u8().await?;
+            let mut name = vec![0_u8; len as usize];
+            stream.read_exact(&mut name).await?;
+            String::from_utf8(name)
+                .map_err(|_| WshError::InvalidMessage("SOCKS domain is not UTF-8".into()))?
+        }
+        other => {
+            send_reply(stream, SocksReply::AddressTypeNotSupported).await?;
+            return Err(WshError::InvalidMessage(format!(
+                "unsupported SOCKS address type {other}"
+            )));
+        }
+    };
+    let port = stream.read_u16().await?;
+
+    Ok(SocksTarget { host, port })
+}
+
+/// Send the final reply for a `CONNECT` request.
+///
+/// The bound address is always reported as `0.0.0.0:0`; the real socket lives
+/// on the remote server and clients do not rely on it.
+pub async fn send_reply<S>(stream: &mut S, reply: SocksReply) -> WshResult<()>
+where
+    S: AsyncWrite + Unpin,
+{
+    let mut frame = [0_u8; 10];
+    frame[..4].copy_from_slice(&[SOCKS_VERSION, reply as u8, 0x00, ATYP_IPV4]);
+    stream.write_all(&frame).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn accepts_domain_connect_request() {
+        let (mut client, mut server) = tokio::io::duplex(256);
+        let handle = tokio::spawn(async move { accept(&mut server).await });
+
+        client.write_all(&[0x05, 0x01, 0x00]).await.unwrap();
+        let mut choice = [0_u8; 2];
+        client.read_exact(&mut choice).await.unwrap();
+        assert_eq!(choice, [0x05, 0x00]);
+
+        let mut request = vec![0x05, 0x01, 0x00, 0x03, 11];
+        request.extend_from_slice(b"example.com");
+        request.extend_from_slice(&443_u16.to_be_bytes());
+        client.write_all(&request).await.unwrap();
+
+        let target = handle.await.unwrap().unwrap();
+        assert_eq!(
+            target,
+            SocksTarget {
+                host: "example.com".into(),
+                port: 443,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_bind_command() {
+        let (mut client, mut server) = tokio::io::duplex(256);
+        let handle = tokio::spawn(async move { accept(&mut server).await });
+
+        client.write_all(&[0x05, 0x01, 0x00]).await.unwrap();
+        let mut choice = [0_u8; 2];
+        client.read_exact(&mut choice).await.unwrap();
+        client
+            .write_all(&[0x05, 0x02, 0x00, 0x01, 127, 0, 0, 1, 0, 80])
+            .await
+            .unwrap();
+
+        assert!(handle.await.unwrap().is_err());
+        let mut reply = [0_u8; 10];
+        client.read_exact(&mut reply).await.unwrap();
+        assert_eq!(reply[1], SocksReply::CommandNotSupported as u8);
+    }
+}
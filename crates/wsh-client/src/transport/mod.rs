@@ -3,10 +3,15 @@
 //! Selects WebTransport or WebSocket based on the URL scheme:
 //! - `wss://` or `ws://` → WebSocket
 //! - `https://` or `wt://` → WebTransport
+//!
+//! [`profiles`] remembers which transport last reached each host, for
+//! callers that pick the scheme themselves.
 
+pub mod profiles;
 pub mod websocket;
 pub mod webtransport;
 
+pub use profiles::TransportProfiles;
 pub use websocket::WebSocketSession;
 pub use webtransport::WebTransportSession;
 
@@ -20,6 +25,33 @@ pub enum TransportKind {
     WebTransport,
 }
 
+impl TransportKind {
+    /// Short name used in config files: `"ws"` or `"wt"`.
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::WebSocket => "ws",
+            Self::WebTransport => "wt",
+        }
+    }
+
+    /// Parse a short name as returned by [`Self::label`].
+    pub fn from_label(label: &str) -> Option<Self> {
+        match label {
+            "ws" => Some(Self::WebSocket),
+            "wt" => Some(Self::WebTransport),
+            _ => None,
+        }
+    }
+
+    /// Connection URL for this transport to `host:port`.
+    pub fn url(self, host: &str, port: u16) -> String {
+        match self {
+            Self::WebSocket => format!("wss://{host}:{port}"),
+            Self::WebTransport => format!("https://{host}:{port}"),
+        }
+    }
+}
+
 /// Enum-dispatched transport session.
 ///
 /// Wraps both WebSocket and WebTransport sessions so we can use them
@@ -0,0 +1,175 @@
+//! Remembered transport per host.
+//!
+//! Connecting without an explicit transport tries WebTransport first and
+//! falls back to WebSocket. Behind middleboxes that drop UDP the
+//! WebTransport attempt only fails once it times out, so the transport that
+//! worked is recorded at `~/.wsh/transports.json` and tried first on later
+//! connects. Entries expire after a TTL so a host is re-probed once the
+//! network may have changed.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use wsh_core::{WshError, WshResult};
+
+use super::TransportKind;
+
+/// How long a remembered transport is trusted by default.
+pub const DEFAULT_PROFILE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// The order transports are tried in when nothing is remembered.
+pub const DEFAULT_ORDER: [TransportKind; 2] =
+    [TransportKind::WebTransport, TransportKind::WebSocket];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ProfileEntry {
+    /// `"wt"` or `"ws"`.
+    transport: String,
+    /// Unix time the transport last connected.
+    connected_at: u64,
+}
+
+/// Per-host transport memory backed by a JSON file.
+pub struct TransportProfiles {
+    path: PathBuf,
+}
+
+impl TransportProfiles {
+    /// Create a profile store for the given file path.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Create a profile store at the default location (`~/.wsh/transports.json`).
+    pub fn default_location() -> WshResult<Self> {
+        let home = dirs::home_dir()
+            .ok_or_else(|| WshError::Other("cannot determine home directory".into()))?;
+        Ok(Self::new(home.join(".wsh").join("transports.json")))
+    }
+
+    /// The transport that last connected to `host:port`, if recorded less
+    /// than `ttl` ago.
+    pub fn remembered(&self, host: &str, port: u16, ttl: Duration) -> Option<TransportKind> {
+        let entry = self.load().remove(&profile_key(host, port))?;
+        let age = now_secs().saturating_sub(entry.connected_at);
+        if age >= ttl.as_secs() {
+            return None;
+        }
+        TransportKind::from_label(&entry.transport)
+    }
+
+    /// Transports to try for `host:port`: the remembered one first, then
+    /// the rest of [`DEFAULT_ORDER`].
+    pub fn order(&self, host: &str, port: u16, ttl: Duration) -> Vec<TransportKind> {
+        let mut order = DEFAULT_ORDER.to_vec();
+        if let Some(kind) = self.remembered(host, port, ttl) {
+            order.retain(|k| *k != kind);
+            order.insert(0, kind);
+        }
+        order
+    }
+
+    /// Record that `kind` connected to `host:port`.
+    pub fn remember(&self, host: &str, port: u16, kind: TransportKind) -> WshResult<()> {
+        let mut entries = self.load();
+        entries.insert(
+            profile_key(host, port),
+            ProfileEntry {
+                transport: kind.label().to_string(),
+                connected_at: now_secs(),
+            },
+        );
+        self.save(&entries)
+    }
+
+    /// Drop the remembered transport for `host:port`.
+    pub fn forget(&self, host: &str, port: u16) -> WshResult<()> {
+        let mut entries = self.load();
+        if entries.remove(&profile_key(host, port)).is_some() {
+            self.save(&entries)?;
+        }
+        Ok(())
+    }
+
+    /// Read the store; a missing or unreadable file is treated as empty,
+    /// since the worst outcome is probing in the default order.
+    fn load(&self) -> HashMap<String, ProfileEntry> {
+        fs::read_to_string(&self.path)
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self, entries: &HashMap<String, ProfileEntry>) -> WshResult<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let json = serde_json::to_string_pretty(entries)
+            .map_err(|e| WshError::Other(format!("failed to encode transport profiles: {e}")))?;
+        let tmp = self.path.with_extension("json.tmp");
+        fs::write(&tmp, json)?;
+        fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+fn profile_key(host: &str, port: u16) -> String {
+    format!("{}:{port}", host.to_ascii_lowercase())
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_profiles(name: &str) -> TransportProfiles {
+        let path =
+            std::env::temp_dir().join(format!("wsh-transports-{name}-{}.json", std::process::id()));
+        let _ = fs::remove_file(&path);
+        TransportProfiles::new(path)
+    }
+
+    #[test]
+    fn remembered_transport_is_tried_first_until_it_expires() {
+        let profiles = temp_profiles("order");
+        let ttl = Duration::from_secs(60);
+        assert_eq!(profiles.order("example.com", 4422, ttl), DEFAULT_ORDER);
+
+        profiles
+            .remember("Example.com", 4422, TransportKind::WebSocket)
+            .unwrap();
+        assert_eq!(
+            profiles.order("example.com", 4422, ttl),
+            [TransportKind::WebSocket, TransportKind::WebTransport]
+        );
+        assert_eq!(profiles.remembered("example.com", 2222, ttl), None);
+        assert_eq!(
+            profiles.remembered("example.com", 4422, Duration::ZERO),
+            None
+        );
+
+        profiles.forget("example.com", 4422).unwrap();
+        assert_eq!(profiles.remembered("example.com", 4422, ttl), None);
+        let _ = fs::remove_file(&profiles.path);
+    }
+
+    #[test]
+    fn unreadable_store_falls_back_to_default_order() {
+        let profiles = temp_profiles("corrupt");
+        fs::write(&profiles.path, "not json").unwrap();
+        assert_eq!(
+            profiles.order("example.com", 4422, DEFAULT_PROFILE_TTL),
+            DEFAULT_ORDER
+        );
+        let _ = fs::remove_file(&profiles.path);
+    }
+}
@@ -20,7 +20,7 @@ use futures_util::{SinkExt, StreamExt};
 use tokio::net::TcpStream;
 use tokio::sync::{mpsc, Mutex};
 use tokio_tungstenite::tungstenite::Message;
-use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+use tokio_tungstenite::{client_async_tls, connect_async, MaybeTlsStream, WebSocketStream};
 
 use wsh_core::error::{WshError, WshResult};
 use wsh_core::transport::{ByteStream, IdentifiedStream, TransportSession};
@@ -160,7 +160,21 @@ impl WebSocketSession {
             .map_err(|e| WshError::Transport(format!("WebSocket connect error: {e}")))?;
 
         tracing::info!("WebSocket connected to {}", url);
+        Ok(Self::from_stream(ws_stream))
+    }
+
+    /// Run the WebSocket handshake for `url` over an already-connected TCP
+    /// stream (e.g. one tunneled through a jump host).
+    pub async fn connect_over(url: &str, tcp: TcpStream) -> WshResult<Self> {
+        let (ws_stream, _response) = client_async_tls(url, tcp)
+            .await
+            .map_err(|e| WshError::Transport(format!("WebSocket connect error: {e}")))?;
 
+        tracing::info!("WebSocket connected to {} over tunnel", url);
+        Ok(Self::from_stream(ws_stream))
+    }
+
+    fn from_stream(ws_stream: WebSocketStream<MaybeTlsStream<TcpStream>>) -> Self {
         let (ws_sink, ws_stream_read) = ws_stream.split();
         let ws_sink = Arc::new(Mutex::new(ws_sink));
 
@@ -191,7 +205,7 @@ impl WebSocketSession {
             })
         };
 
-        Ok(Self {
+        Self {
             ws_sink,
             control_rx,
             incoming_streams_rx,
@@ -199,7 +213,7 @@ impl WebSocketSession {
             next_stream_id: Arc::new(Mutex::new(1)), // Client uses odd IDs
             dispatch_handle,
             connected,
-        })
+        }
     }
 
     /// Internal dispatch loop that routes incoming WebSocket frames.
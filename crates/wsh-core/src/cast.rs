@@ -0,0 +1,246 @@
+//! Terminal session recordings in asciicast v2 format.
+//!
+//! A `.cast` file is newline-delimited JSON, compatible with asciinema:
+//!
+//! ```text
+//! {"version": 2, "width": 80, "height": 24, "timestamp": 1700000000, "command": "bash"}
+//! [0.248848, "o", "$ "]
+//! [1.001376, "o", "ls\r\n"]
+//! [2.5, "r", "120x40"]
+//! ```
+//!
+//! The first line is a [`CastHeader`]. Every following line is an event
+//! `[seconds_since_start, code, data]` where `code` is `"o"` (output written
+//! to the terminal), `"i"` (input typed by the user) or `"r"` (resize, data
+//! is `"COLSxROWS"`). Output is stored as UTF-8 text; multi-byte characters
+//! split across reads are held back until complete.
+
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{WshError, WshResult};
+
+/// The only asciicast version this module reads and writes.
+pub const CAST_VERSION: u8 = 2;
+
+/// First line of a `.cast` file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CastHeader {
+    pub version: u8,
+    pub width: u16,
+    pub height: u16,
+    /// Unix time the recording started.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+}
+
+impl CastHeader {
+    /// Header for a recording starting now at the given terminal size.
+    pub fn new(width: u16, height: u16) -> Self {
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .ok()
+            .map(|d| d.as_secs());
+        Self {
+            version: CAST_VERSION,
+            width,
+            height,
+            timestamp,
+            command: None,
+            title: None,
+        }
+    }
+}
+
+/// A single timed event: `[time, code, data]`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CastEvent(pub f64, pub String, pub String);
+
+impl CastEvent {
+    /// Seconds since the start of the recording.
+    pub fn time(&self) -> f64 {
+        self.0
+    }
+
+    /// Event code (`"o"`, `"i"`, `"r"`, ...).
+    pub fn code(&self) -> &str {
+        &self.1
+    }
+
+    /// Event data.
+    pub fn data(&self) -> &str {
+        &self.2
+    }
+
+    /// Parse the `"COLSxROWS"` data of a resize event.
+    pub fn resize(&self) -> Option<(u16, u16)> {
+        if self.code() != "r" {
+            return None;
+        }
+        let (cols, rows) = self.data().split_once('x')?;
+        Some((cols.parse().ok()?, rows.parse().ok()?))
+    }
+}
+
+/// Streams events to an asciicast v2 file.
+pub struct CastWriter<W: Write> {
+    inner: W,
+    start: Instant,
+    /// Trailing bytes of an incomplete UTF-8 sequence from the last output.
+    pending: Vec<u8>,
+}
+
+impl<W: Write> CastWriter<W> {
+    /// Write `header` and start the recording clock.
+    pub fn new(mut inner: W, header: &CastHeader) -> WshResult<Self> {
+        let line = serde_json::to_string(header)
+            .map_err(|e| WshError::Codec(format!("cast header: {e}")))?;
+        writeln!(inner, "{line}")?;
+        Ok(Self {
+            inner,
+            start: Instant::now(),
+            pending: Vec::new(),
+        })
+    }
+
+    /// Record terminal output.
+    pub fn output(&mut self, data: &[u8]) -> WshResult<()> {
+        self.output_at(self.start.elapsed(), data)
+    }
+
+    /// Record terminal output at an explicit offset from the start, for
+    /// converting existing recordings.
+    pub fn output_at(&mut self, at: Duration, data: &[u8]) -> WshResult<()> {
+        self.pending.extend_from_slice(data);
+        let text = take_utf8(&mut self.pending);
+        if text.is_empty() {
+            return Ok(());
+        }
+        self.event(at, "o", text)
+    }
+
+    /// Record user input.
+    pub fn input(&mut self, data: &[u8]) -> WshResult<()> {
+        let text = String::from_utf8_lossy(data).into_owned();
+        self.event(self.start.elapsed(), "i", text)
+    }
+
+    /// Record a terminal resize.
+    pub fn resize(&mut self, cols: u16, rows: u16) -> WshResult<()> {
+        self.resize_at(self.start.elapsed(), cols, rows)
+    }
+
+    /// Record a terminal resize at an explicit offset from the start.
+    pub fn resize_at(&mut self, at: Duration, cols: u16, rows: u16) -> WshResult<()> {
+        self.event(at, "r", format!("{cols}x{rows}"))
+    }
+
+    fn event(&mut self, at: Duration, code: &str, data: String) -> WshResult<()> {
+        let event = CastEvent(at.as_secs_f64(), code.to_string(), data);
+        let line = serde_json::to_string(&event)
+            .map_err(|e| WshError::Codec(format!("cast event: {e}")))?;
+        writeln!(self.inner, "{line}")?;
+        self.inner.flush()?;
+        Ok(())
+    }
+}
+
+/// Decode as much of `buf` as forms complete UTF-8, leaving an incomplete
+/// trailing sequence in place. Invalid bytes become U+FFFD.
+fn take_utf8(buf: &mut Vec<u8>) -> String {
+    let mut out = String::new();
+    loop {
+        match std::str::from_utf8(buf) {
+            Ok(s) => {
+                out.push_str(s);
+                buf.clear();
+                return out;
+            }
+            Err(e) => {
+                let valid = e.valid_up_to();
+                out.push_str(std::str::from_utf8(&buf[..valid]).unwrap_or_default());
+                match e.error_len() {
+                    // Incomplete sequence at the end: wait for more bytes.
+                    None => {
+                        buf.drain(..valid);
+                        return out;
+                    }
+                    Some(len) => {
+                        out.push(char::REPLACEMENT_CHARACTER);
+                        buf.drain(..valid + len);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Parse a complete `.cast` file. Blank lines are ignored.
+pub fn parse(content: &str) -> WshResult<(CastHeader, Vec<CastEvent>)> {
+    let mut lines = content.lines().filter(|l| !l.trim().is_empty());
+    let header_line = lines
+        .next()
+        .ok_or_else(|| WshError::InvalidMessage("empty cast file".into()))?;
+    let header: CastHeader = serde_json::from_str(header_line)
+        .map_err(|e| WshError::InvalidMessage(format!("invalid cast header: {e}")))?;
+    if header.version != CAST_VERSION {
+        return Err(WshError::InvalidMessage(format!(
+            "unsupported asciicast version {}",
+            header.version
+        )));
+    }
+    let events = lines
+        .enumerate()
+        .map(|(i, line)| {
+            serde_json::from_str::<CastEvent>(line).map_err(|e| {
+                WshError::InvalidMessage(format!("invalid cast event on line {}: {e}", i + 2))
+            })
+        })
+        .collect::<WshResult<Vec<_>>>()?;
+    Ok((header, events))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writer_output_round_trips() {
+        let mut buf = Vec::new();
+        {
+            let mut writer = CastWriter::new(&mut buf, &CastHeader::new(80, 24)).unwrap();
+            writer.output(b"hello\r\n").unwrap();
+            writer.resize(120, 40).unwrap();
+        }
+        let (header, events) = parse(std::str::from_utf8(&buf).unwrap()).unwrap();
+        assert_eq!((header.width, header.height), (80, 24));
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].code(), "o");
+        assert_eq!(events[0].data(), "hello\r\n");
+        assert_eq!(events[1].resize(), Some((120, 40)));
+    }
+
+    #[test]
+    fn split_utf8_is_held_until_complete() {
+        let snowman = "☃".as_bytes();
+        let mut pending = snowman[..1].to_vec();
+        assert_eq!(take_utf8(&mut pending), "");
+        pending.extend_from_slice(&snowman[1..]);
+        assert_eq!(take_utf8(&mut pending), "☃");
+        assert!(pending.is_empty());
+
+        let mut invalid = vec![b'a', 0xff, b'b'];
+        assert_eq!(take_utf8(&mut invalid), "a\u{fffd}b");
+    }
+
+    #[test]
+    fn parse_rejects_other_versions() {
+        assert!(parse("{\"version\": 1, \"width\": 80, \"height\": 24}\n").is_err());
+    }
+}
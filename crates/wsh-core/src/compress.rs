@@ -0,0 +1,283 @@
+//! Optional payload compression for session output and file chunks.
+//!
+//! Compression is negotiated in the handshake: a client that wants it adds
+//! `compress:<algorithm>` to `HELLO.features`, and the server echoes the
+//! feature back in `SERVER_HELLO` when it agrees. After that either side may
+//! compress the `data` of `SESSION_DATA` and `FILE_CHUNK` frames, marking
+//! each compressed frame with its `compression` field. Frames are
+//! compressed independently, and only when that makes them smaller, so a
+//! receiver can always handle a mix of compressed and plain frames.
+//!
+//! Two algorithms are supported: zstd (preferred) and raw DEFLATE
+//! (RFC 1951), backed by the `zstd` and `flate2` crates. Decompression of
+//! a single frame is capped at [`MAX_DECOMPRESSED_LEN`] bytes.
+
+use std::io::{Read, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+
+use crate::error::{WshError, WshResult};
+
+/// `HELLO`/`SERVER_HELLO` feature prefix for compression algorithms.
+pub const FEATURE_PREFIX: &str = "compress:";
+
+/// Frames shorter than this are sent as-is.
+pub const MIN_COMPRESS_LEN: usize = 64;
+
+/// Largest payload a single compressed frame may expand to.
+pub const MAX_DECOMPRESSED_LEN: usize = 4 * 1024 * 1024;
+
+/// zstd level used for frames; low levels keep interactive output fast.
+const ZSTD_LEVEL: i32 = 3;
+
+/// A negotiated compression algorithm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Codec {
+    /// Zstandard (RFC 8878) frames.
+    Zstd,
+    /// Raw DEFLATE (RFC 1951).
+    Deflate,
+}
+
+impl Codec {
+    /// Algorithms this build supports, in order of preference.
+    pub const ALL: [Codec; 2] = [Codec::Zstd, Codec::Deflate];
+
+    /// Wire name used in features and the `compression` field.
+    pub fn name(self) -> &'static str {
+        match self {
+            Codec::Zstd => "zstd",
+            Codec::Deflate => "deflate",
+        }
+    }
+
+    /// Parse a wire name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|codec| codec.name() == name)
+    }
+
+    /// The `HELLO` feature advertising this algorithm.
+    pub fn feature(self) -> String {
+        format!("{FEATURE_PREFIX}{}", self.name())
+    }
+
+    /// The first supported algorithm offered in a feature list.
+    pub fn negotiate(features: &[String]) -> Option<Self> {
+        features
+            .iter()
+            .filter_map(|f| f.strip_prefix(FEATURE_PREFIX))
+            .find_map(Self::from_name)
+    }
+
+    /// Compress `data`, or `None` when it is too short or would not shrink.
+    pub fn compress(self, data: &[u8]) -> Option<Vec<u8>> {
+        if data.len() < MIN_COMPRESS_LEN {
+            return None;
+        }
+        let out = match self {
+            Codec::Zstd => zstd::bulk::compress(data, ZSTD_LEVEL).ok()?,
+            Codec::Deflate => {
+                let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(data).ok()?;
+                encoder.finish().ok()?
+            }
+        };
+        (out.len() < data.len()).then_some(out)
+    }
+
+    /// Decompress a frame produced by [`Self::compress`], refusing to
+    /// produce more than [`MAX_DECOMPRESSED_LEN`] bytes.
+    pub fn decompress(self, data: &[u8]) -> WshResult<Vec<u8>> {
+        self.decompress_capped(data, MAX_DECOMPRESSED_LEN)
+    }
+
+    fn decompress_capped(self, data: &[u8], max_len: usize) -> WshResult<Vec<u8>> {
+        let decoder: Box<dyn Read + '_> = match self {
+            Codec::Zstd => Box::new(
+                zstd::stream::read::Decoder::new(data)
+                    .map_err(|e| WshError::Codec(format!("zstd: {e}")))?,
+            ),
+            Codec::Deflate => Box::new(DeflateDecoder::new(data)),
+        };
+        let mut out = Vec::new();
+        decoder
+            .take(max_len as u64 + 1)
+            .read_to_end(&mut out)
+            .map_err(|e| WshError::Codec(format!("{}: {e}", self.name())))?;
+        if out.len() > max_len {
+            return Err(WshError::Codec(format!(
+                "decompressed frame exceeds {max_len} bytes"
+            )));
+        }
+        Ok(out)
+    }
+}
+
+/// Decode a frame's `compression` field and data.
+///
+/// `None` passes the data through; unknown algorithms are an error.
+pub fn decode_frame(compression: Option<&str>, data: Vec<u8>) -> WshResult<Vec<u8>> {
+    match compression {
+        None => Ok(data),
+        Some(name) => Codec::from_name(name)
+            .ok_or_else(|| WshError::Codec(format!("unsupported compression: {name}")))?
+            .decompress(&data),
+    }
+}
+
+/// Running totals for one connection's compression work.
+#[derive(Debug, Default)]
+pub struct CompressionStats {
+    raw_bytes: AtomicU64,
+    wire_bytes: AtomicU64,
+    frames: AtomicU64,
+    cpu_micros: AtomicU64,
+}
+
+impl CompressionStats {
+    /// Compress `data` with `codec` (if any), recording the outcome.
+    ///
+    /// Returns the bytes to send and the `compression` field for the frame.
+    pub fn encode(&self, codec: Option<Codec>, data: Vec<u8>) -> (Vec<u8>, Option<String>) {
+        let Some(codec) = codec else {
+            return (data, None);
+        };
+        let started = Instant::now();
+        let compressed = codec.compress(&data);
+        self.record(
+            started,
+            data.len(),
+            compressed.as_ref().map_or(data.len(), Vec::len),
+        );
+        match compressed {
+            Some(out) => (out, Some(codec.name().to_string())),
+            None => (data, None),
+        }
+    }
+
+    /// Decode a received frame, recording the outcome when it was compressed.
+    pub fn decode(&self, compression: Option<&str>, data: Vec<u8>) -> WshResult<Vec<u8>> {
+        if compression.is_none() {
+            return Ok(data);
+        }
+        let started = Instant::now();
+        let wire = data.len();
+        let out = decode_frame(compression, data)?;
+        self.record(started, out.len(), wire);
+        Ok(out)
+    }
+
+    fn record(&self, started: Instant, raw: usize, wire: usize) {
+        self.raw_bytes.fetch_add(raw as u64, Ordering::Relaxed);
+        self.wire_bytes.fetch_add(wire as u64, Ordering::Relaxed);
+        self.frames.fetch_add(1, Ordering::Relaxed);
+        self.cpu_micros
+            .fetch_add(started.elapsed().as_micros() as u64, Ordering::Relaxed);
+    }
+
+    /// Uncompressed bytes seen.
+    pub fn raw_bytes(&self) -> u64 {
+        self.raw_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Bytes actually carried on the wire for those frames.
+    pub fn wire_bytes(&self) -> u64 {
+        self.wire_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Frames that went through the codec.
+    pub fn frames(&self) -> u64 {
+        self.frames.load(Ordering::Relaxed)
+    }
+
+    /// Time spent compressing and decompressing, in microseconds.
+    pub fn cpu_micros(&self) -> u64 {
+        self.cpu_micros.load(Ordering::Relaxed)
+    }
+
+    /// Raw size divided by wire size (`1.0` when nothing was compressed).
+    pub fn ratio(&self) -> f64 {
+        match self.wire_bytes() {
+            0 => 1.0,
+            wire => self.raw_bytes() as f64 / wire as f64,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn terminal_output() -> Vec<u8> {
+        let mut data = Vec::new();
+        for i in 0..500 {
+            data.extend_from_slice(
+                format!(
+                    "\x1b[32mline {i}\x1b[0m: drwxr-xr-x  2 user staff  64 Jan  1 00:00 dir{}\r\n",
+                    i % 7
+                )
+                .as_bytes(),
+            );
+        }
+        data
+    }
+
+    #[test]
+    fn round_trips_terminal_output() {
+        let data = terminal_output();
+        for codec in Codec::ALL {
+            let compressed = codec.compress(&data).unwrap();
+            assert!(
+                compressed.len() * 4 < data.len(),
+                "{}: {} vs {}",
+                codec.name(),
+                compressed.len(),
+                data.len()
+            );
+            assert_eq!(codec.decompress(&compressed).unwrap(), data);
+            assert!(codec.compress(b"short").is_none());
+        }
+    }
+
+    #[test]
+    fn refuses_oversized_or_corrupt_frames() {
+        for codec in Codec::ALL {
+            let bomb = codec.compress(&[b'a'; 1000]).unwrap();
+            assert!(codec.decompress_capped(&bomb, 100).is_err());
+            assert_eq!(codec.decompress_capped(&bomb, 1000).unwrap().len(), 1000);
+            assert!(codec.decompress(&[0xff; 32]).is_err());
+        }
+
+        // "hello" as a stored DEFLATE block from another implementation.
+        let stored = [0x01, 0x05, 0x00, 0xfa, 0xff, b'h', b'e', b'l', b'l', b'o'];
+        assert_eq!(Codec::Deflate.decompress(&stored).unwrap(), b"hello");
+    }
+
+    #[test]
+    fn negotiates_from_features() {
+        let features = vec![
+            "mcp".to_string(),
+            "compress:lz4".to_string(),
+            Codec::Deflate.feature(),
+            Codec::Zstd.feature(),
+        ];
+        assert_eq!(Codec::negotiate(&features), Some(Codec::Deflate));
+        assert_eq!(
+            Codec::negotiate(&Codec::ALL.map(Codec::feature)),
+            Some(Codec::Zstd)
+        );
+        assert_eq!(Codec::negotiate(&["mcp".to_string()]), None);
+
+        let stats = CompressionStats::default();
+        let data = vec![b'x'; 4096];
+        let (wire, compression) = stats.encode(Some(Codec::Zstd), data.clone());
+        assert_eq!(compression.as_deref(), Some("zstd"));
+        assert_eq!(stats.decode(compression.as_deref(), wire).unwrap(), data);
+        assert!(stats.ratio() > 10.0);
+        assert!(decode_frame(Some("lz4"), vec![1, 2, 3]).is_err());
+    }
+}
@@ -0,0 +1,211 @@
+//! Per-channel flow control and rate limiting.
+//!
+//! Bulk channels (chunked file transfers) use credit-based flow control: the
+//! receiver advertises a window of bytes the sender may have in flight and
+//! sends `WINDOW_UPDATE` as it consumes data. A sender that runs out of
+//! credit waits, so one large transfer cannot fill the connection's queues
+//! ahead of interactive traffic sharing the same transport.
+//!
+//! [`RateLimiter`] is a token bucket for capping a channel's throughput
+//! (`--limit-rate`). On the receiving side it works by consuming data more
+//! slowly, which in turn delays window updates and slows the sender.
+
+use std::time::{Duration, Instant};
+
+use crate::error::{WshError, WshResult};
+use crate::messages::{Envelope, MsgType, Payload, WindowUpdatePayload};
+
+/// Default receive window: four default-sized transfer chunks.
+pub const DEFAULT_WINDOW: u32 = 1024 * 1024;
+
+/// Bytes the peer currently allows us to send on a channel.
+#[derive(Debug, Clone)]
+pub struct SendWindow {
+    available: u64,
+}
+
+impl SendWindow {
+    /// Start with the window the peer advertised.
+    pub fn new(initial: u32) -> Self {
+        Self {
+            available: u64::from(initial),
+        }
+    }
+
+    /// Remaining credit in bytes.
+    pub fn available(&self) -> u64 {
+        self.available
+    }
+
+    /// Whether `len` bytes can be sent now.
+    pub fn can_send(&self, len: usize) -> bool {
+        self.available >= len as u64
+    }
+
+    /// Account for `len` bytes sent.
+    pub fn consume(&mut self, len: usize) {
+        self.available = self.available.saturating_sub(len as u64);
+    }
+
+    /// Add credit from a `WINDOW_UPDATE`.
+    pub fn grant(&mut self, increment: u32) {
+        self.available = self.available.saturating_add(u64::from(increment));
+    }
+}
+
+/// Receiver-side bookkeeping: decides when to hand credit back.
+#[derive(Debug, Clone)]
+pub struct ReceiveWindow {
+    size: u32,
+    unacked: u32,
+}
+
+impl ReceiveWindow {
+    /// A window of `size` bytes, as advertised to the sender.
+    pub fn new(size: u32) -> Self {
+        Self { size, unacked: 0 }
+    }
+
+    /// The advertised window size.
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// Whether the sender still had credit for `len` more bytes, i.e. did
+    /// not send past the window.
+    pub fn admits(&self, len: usize) -> bool {
+        u64::from(self.unacked) + len as u64 <= u64::from(self.size)
+    }
+
+    /// Record `len` bytes consumed. Returns the increment to send once at
+    /// least half the window has been consumed since the last update.
+    pub fn consumed(&mut self, len: usize) -> Option<u32> {
+        self.unacked = self
+            .unacked
+            .saturating_add(u32::try_from(len).unwrap_or(u32::MAX));
+        if self.unacked < self.size / 2 {
+            return None;
+        }
+        Some(std::mem::take(&mut self.unacked))
+    }
+}
+
+/// Build a `WINDOW_UPDATE` for `channel_id`.
+pub fn window_update(channel_id: u32, increment: u32) -> Envelope {
+    Envelope {
+        msg_type: MsgType::WindowUpdate,
+        payload: Payload::WindowUpdate(WindowUpdatePayload {
+            channel_id,
+            increment,
+        }),
+    }
+}
+
+/// Token bucket limiting throughput to a fixed number of bytes per second.
+///
+/// Up to one second's worth of bytes may be sent in a burst.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    bytes_per_sec: u64,
+    tokens: f64,
+    last: Instant,
+}
+
+impl RateLimiter {
+    /// Limit to `bytes_per_sec`, starting with a full bucket.
+    pub fn new(bytes_per_sec: u64) -> Self {
+        Self::new_at(bytes_per_sec, Instant::now())
+    }
+
+    fn new_at(bytes_per_sec: u64, now: Instant) -> Self {
+        let bytes_per_sec = bytes_per_sec.max(1);
+        Self {
+            bytes_per_sec,
+            tokens: bytes_per_sec as f64,
+            last: now,
+        }
+    }
+
+    /// Take `len` bytes from the bucket and return how long to wait before
+    /// they may go out.
+    pub fn delay(&mut self, len: usize) -> Duration {
+        self.delay_at(len, Instant::now())
+    }
+
+    fn delay_at(&mut self, len: usize, now: Instant) -> Duration {
+        let rate = self.bytes_per_sec as f64;
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.last = now;
+        self.tokens = (self.tokens + elapsed * rate).min(rate) - len as f64;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / rate)
+        }
+    }
+}
+
+/// Parse a rate such as `500K`, `2M` or `1048576` into bytes per second.
+///
+/// Suffixes `K`, `M` and `G` (case-insensitive, optional trailing `B`) are
+/// powers of 1024, as in curl's `--limit-rate`.
+pub fn parse_rate(input: &str) -> WshResult<u64> {
+    let trimmed = input.trim();
+    let upper = trimmed.to_ascii_uppercase();
+    let digits = upper.strip_suffix('B').unwrap_or(&upper);
+    let (number, multiplier) = match digits.chars().last() {
+        Some('K') => (&digits[..digits.len() - 1], 1024),
+        Some('M') => (&digits[..digits.len() - 1], 1024 * 1024),
+        Some('G') => (&digits[..digits.len() - 1], 1024 * 1024 * 1024),
+        _ => (digits, 1),
+    };
+    number
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(multiplier))
+        .filter(|&rate| rate > 0)
+        .ok_or_else(|| WshError::Other(format!("invalid rate: {trimmed}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn windows_hand_back_credit() {
+        let mut send = SendWindow::new(1000);
+        let mut recv = ReceiveWindow::new(1000);
+        assert!(send.can_send(600));
+        send.consume(600);
+        assert!(!send.can_send(600));
+
+        assert_eq!(recv.consumed(400), None);
+        assert!(!recv.admits(601));
+        let increment = recv.consumed(200).unwrap();
+        assert!(recv.admits(1000));
+        assert_eq!(increment, 600);
+        send.grant(increment);
+        assert_eq!(send.available(), 1000);
+    }
+
+    #[test]
+    fn rate_limiter_delays_past_burst() {
+        let start = Instant::now();
+        let mut limiter = RateLimiter::new_at(1000, start);
+        assert_eq!(limiter.delay_at(1000, start), Duration::ZERO);
+        assert_eq!(limiter.delay_at(500, start), Duration::from_millis(500));
+        // Half a second later the debt is paid off.
+        let later = start + Duration::from_millis(500);
+        assert_eq!(limiter.delay_at(0, later), Duration::ZERO);
+    }
+
+    #[test]
+    fn parses_rates() {
+        assert_eq!(parse_rate("1048576").unwrap(), 1024 * 1024);
+        assert_eq!(parse_rate("500k").unwrap(), 500 * 1024);
+        assert_eq!(parse_rate("2M").unwrap(), 2 * 1024 * 1024);
+        assert_eq!(parse_rate("1GB").unwrap(), 1024 * 1024 * 1024);
+        assert!(parse_rate("0").is_err());
+        assert!(parse_rate("fast").is_err());
+    }
+}
@@ -1,11 +1,25 @@
-//! Parse `authorized_keys` files (SSH format).
+//! Parse `authorized_keys` files (SSH format) and verify wsh certificates.
 //!
 //! Supports reading Ed25519 public keys from both `~/.wsh/authorized_keys`
-//! and `~/.ssh/authorized_keys`, with wsh taking priority.
+//! and `~/.ssh/authorized_keys`, with wsh taking priority. Keys are added to
+//! and removed from `~/.wsh/authorized_keys` only; the ssh file is never
+//! rewritten.
+//!
+//! A [`Certificate`] binds an Ed25519 key to a set of principals (user names
+//! for user certificates, host names for host certificates) for a validity
+//! window, signed by a certificate authority key. An authorized_keys line
+//! carrying the `cert-authority` option trusts that CA for user
+//! certificates; clients trust host CAs through `@cert-authority` lines in
+//! their known_hosts. Certificates are stored one per line as
+//! `wsh-cert-v1 <base64 CBOR> [comment]`.
 
-use crate::error::WshResult;
+use crate::error::{WshError, WshResult};
 use crate::identity;
-use std::path::Path;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Key type tag for wsh certificate lines.
+pub const CERT_TYPE: &str = "wsh-cert-v1";
 
 /// A parsed authorized key entry.
 #[derive(Debug, Clone)]
@@ -24,6 +38,122 @@ pub struct AuthorizedKey {
     pub options: Option<String>,
 }
 
+impl AuthorizedKey {
+    /// The raw 32-byte Ed25519 public key.
+    pub fn public_key(&self) -> Option<Vec<u8>> {
+        extract_raw_ed25519(&self.raw)
+    }
+
+    /// Whether this entry is a CA trusted to sign user certificates.
+    pub fn is_cert_authority(&self) -> bool {
+        self.option_list().iter().any(|o| o == "cert-authority")
+    }
+
+    /// The individual options on this line, with quoted values left intact.
+    pub fn option_list(&self) -> Vec<String> {
+        self.options
+            .as_deref()
+            .map(split_key_options)
+            .unwrap_or_default()
+    }
+
+    /// Unix time after which the key stops being accepted
+    /// (`expiry-time="YYYYMMDD[HHMM[SS]]"`, UTC).
+    ///
+    /// A malformed value expires the key immediately.
+    pub fn expiry_time(&self) -> Option<u64> {
+        self.option_list().iter().find_map(|o| {
+            let value = o.strip_prefix("expiry-time=")?;
+            Some(parse_expiry_time(value.trim_matches('"')).unwrap_or(0))
+        })
+    }
+
+    /// Whether the key's `expiry-time` has passed at unix time `now`.
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.expiry_time().is_some_and(|expiry| now >= expiry)
+    }
+
+    /// Format this entry as an authorized_keys line.
+    pub fn to_line(&self) -> String {
+        let mut line = String::new();
+        if let Some(options) = &self.options {
+            line.push_str(options);
+            line.push(' ');
+        }
+        line.push_str(&self.key_type);
+        line.push(' ');
+        line.push_str(&self.key_data);
+        if !self.comment.is_empty() {
+            line.push(' ');
+            line.push_str(&self.comment);
+        }
+        line
+    }
+}
+
+/// Split an authorized_keys options field on commas outside quotes.
+pub fn split_key_options(options: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut quote_char = '"';
+
+    for ch in options.chars() {
+        if in_quotes {
+            current.push(ch);
+            if ch == quote_char {
+                in_quotes = false;
+            }
+        } else if ch == '"' || ch == '\'' {
+            in_quotes = true;
+            quote_char = ch;
+            current.push(ch);
+        } else if ch == ',' {
+            parts.push(std::mem::take(&mut current).trim().to_string());
+        } else {
+            current.push(ch);
+        }
+    }
+    if !current.trim().is_empty() {
+        parts.push(current.trim().to_string());
+    }
+    parts
+}
+
+/// Parse an `expiry-time` value (`YYYYMMDD[HHMM[SS]]`, optional trailing
+/// `Z`) into unix seconds. Times are always UTC.
+fn parse_expiry_time(value: &str) -> Option<u64> {
+    let digits = value.strip_suffix(['Z', 'z']).unwrap_or(value);
+    if !matches!(digits.len(), 8 | 12 | 14) || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let field =
+        |range: std::ops::Range<usize>| digits.get(range).map_or(Some(0), |v| v.parse().ok());
+    let (year, month, day) = (field(0..4)?, field(4..6)?, field(6..8)?);
+    let (hour, minute, second) = (field(8..10)?, field(10..12)?, field(12..14)?);
+    if !(1..=12).contains(&month)
+        || !(1..=31).contains(&day)
+        || hour > 23
+        || minute > 59
+        || second > 59
+    {
+        return None;
+    }
+    let days = days_from_civil(year as i64, month, day);
+    u64::try_from(days * 86_400 + (hour * 3600 + minute * 60 + second) as i64).ok()
+}
+
+/// Days since 1970-01-01 for a proleptic Gregorian date.
+fn days_from_civil(year: i64, month: u64, day: u64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_index = (month + 9) % 12;
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year as i64;
+    era * 146_097 + day_of_era - 719_468
+}
+
 /// Parse an authorized_keys file, returning all valid Ed25519 entries.
 pub fn parse_authorized_keys(content: &str) -> Vec<AuthorizedKey> {
     content
@@ -32,6 +162,38 @@ pub fn parse_authorized_keys(content: &str) -> Vec<AuthorizedKey> {
         .collect()
 }
 
+/// Parse a public key line, prefixing it with `options` when given.
+///
+/// Fails unless the result is exactly one Ed25519 entry carrying those
+/// options, so option strings cannot smuggle in extra lines or keys.
+pub fn parse_public_key(public_key: &str, options: Option<&str>) -> WshResult<AuthorizedKey> {
+    let public_key = public_key.trim();
+    let options = options.map(str::trim).filter(|o| !o.is_empty());
+    let line = match options {
+        Some(options) => format!("{options} {public_key}"),
+        None => public_key.to_string(),
+    };
+    if line.contains(['\n', '\r']) {
+        return Err(WshError::InvalidMessage(
+            "public key must be a single line".into(),
+        ));
+    }
+    let key = parse_authorized_key_line(&line)
+        .ok_or_else(|| WshError::InvalidMessage("not an ssh-ed25519 public key".into()))?;
+    if key.options.as_deref() != options {
+        return Err(WshError::InvalidMessage(format!(
+            "invalid key options: {}",
+            options.unwrap_or_default()
+        )));
+    }
+    if key.expiry_time() == Some(0) {
+        return Err(WshError::InvalidMessage(
+            "expiry-time must be YYYYMMDD[HHMM[SS]]".into(),
+        ));
+    }
+    Ok(key)
+}
+
 /// Parse a single authorized_keys line.
 fn parse_authorized_key_line(line: &str) -> Option<AuthorizedKey> {
     if line.is_empty() || line.starts_with('#') {
@@ -51,20 +213,20 @@ fn parse_authorized_key_line(line: &str) -> Option<AuthorizedKey> {
             parts[1].to_string(),
             parts.get(2).unwrap_or(&"").to_string(),
         )
-    } else if parts.len() >= 3 {
-        // First field is options, re-parse from after options
-        let after_opts: Vec<&str> = line[parts[0].len()..].trim().splitn(3, ' ').collect();
+    } else {
+        // First field is options, which may contain quoted spaces
+        // (`command="ls -la"`); re-parse from after them.
+        let options_len = options_field_len(line);
+        let after_opts: Vec<&str> = line[options_len..].trim().splitn(3, ' ').collect();
         if after_opts.len() < 2 {
             return None;
         }
         (
-            Some(parts[0].to_string()),
+            Some(line[..options_len].to_string()),
             after_opts[0].to_string(),
             after_opts[1].to_string(),
             after_opts.get(2).unwrap_or(&"").to_string(),
         )
-    } else {
-        return None;
     };
 
     // Only support ed25519
@@ -92,6 +254,20 @@ fn parse_authorized_key_line(line: &str) -> Option<AuthorizedKey> {
     })
 }
 
+/// Byte length of the leading options field: up to the first whitespace
+/// outside double quotes.
+fn options_field_len(line: &str) -> usize {
+    let mut in_quotes = false;
+    for (i, ch) in line.char_indices() {
+        match ch {
+            '"' => in_quotes = !in_quotes,
+            c if c.is_whitespace() && !in_quotes => return i,
+            _ => {}
+        }
+    }
+    line.len()
+}
+
 /// Extract the raw 32-byte Ed25519 public key from SSH wire format.
 ///
 /// SSH wire format: `[4-byte len]["ssh-ed25519"][4-byte len][32-byte key]`
@@ -117,6 +293,83 @@ fn extract_raw_ed25519(wire: &[u8]) -> Option<Vec<u8>> {
     Some(wire[data_offset..data_offset + key_len].to_vec())
 }
 
+/// The authorized_keys file wsh manages under `home`.
+pub fn wsh_authorized_keys_path(home: &Path) -> PathBuf {
+    home.join(".wsh").join("authorized_keys")
+}
+
+/// Append `key` to `~/.wsh/authorized_keys` unless a key with the same
+/// fingerprint is already authorized (in either file).
+///
+/// Returns whether the file changed.
+pub fn add_authorized_key(home: &Path, key: &AuthorizedKey) -> WshResult<bool> {
+    if load_authorized_keys(home)?
+        .iter()
+        .any(|k| k.fingerprint == key.fingerprint)
+    {
+        return Ok(false);
+    }
+    let path = wsh_authorized_keys_path(home);
+    let mut content = match std::fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e.into()),
+    };
+    if !content.is_empty() && !content.ends_with('\n') {
+        content.push('\n');
+    }
+    content.push_str(&key.to_line());
+    content.push('\n');
+    write_authorized_keys(&path, &content)?;
+    Ok(true)
+}
+
+/// Remove the entries for `fingerprint` from `~/.wsh/authorized_keys`,
+/// leaving every other line (comments included) untouched.
+///
+/// Returns how many lines were removed.
+pub fn remove_authorized_key(home: &Path, fingerprint: &str) -> WshResult<usize> {
+    let path = wsh_authorized_keys_path(home);
+    let content = match std::fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e.into()),
+    };
+    let mut removed = 0;
+    let mut kept = String::with_capacity(content.len());
+    for line in content.lines() {
+        if parse_authorized_key_line(line.trim()).is_some_and(|k| k.fingerprint == fingerprint) {
+            removed += 1;
+        } else {
+            kept.push_str(line);
+            kept.push('\n');
+        }
+    }
+    if removed > 0 {
+        write_authorized_keys(&path, &kept)?;
+    }
+    Ok(removed)
+}
+
+/// Replace an authorized_keys file atomically, creating `~/.wsh` (0700)
+/// and the file (0600) as needed.
+fn write_authorized_keys(path: &Path, content: &str) -> WshResult<()> {
+    let dir = path
+        .parent()
+        .ok_or_else(|| WshError::Other("authorized_keys path has no parent".into()))?;
+    std::fs::create_dir_all(dir)?;
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, content)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o700))?;
+        std::fs::set_permissions(&tmp, std::fs::Permissions::from_mode(0o600))?;
+    }
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
 /// Load authorized keys from wsh and ssh directories, with wsh taking priority.
 pub fn load_authorized_keys(home: &Path) -> WshResult<Vec<AuthorizedKey>> {
     let mut keys = Vec::new();
@@ -148,14 +401,209 @@ pub fn load_authorized_keys(home: &Path) -> WshResult<Vec<AuthorizedKey>> {
 }
 
 /// Check if a raw public key is authorized.
+///
+/// `cert-authority` entries only vouch for certificates, not for their own key.
 pub fn is_key_authorized(public_key_raw: &[u8], authorized: &[AuthorizedKey]) -> bool {
+    find_authorized_key(public_key_raw, authorized).is_some()
+}
+
+/// Find the authorized_keys entry for a raw public key, ignoring
+/// `cert-authority` entries.
+pub fn find_authorized_key<'a>(
+    public_key_raw: &[u8],
+    authorized: &'a [AuthorizedKey],
+) -> Option<&'a AuthorizedKey> {
     let fp = identity::fingerprint(public_key_raw);
-    authorized.iter().any(|k| k.fingerprint == fp)
+    authorized
+        .iter()
+        .find(|k| k.fingerprint == fp && !k.is_cert_authority())
+}
+
+/// Find the `cert-authority` entry that signed a user certificate.
+pub fn find_cert_authority<'a>(
+    cert: &Certificate,
+    authorized: &'a [AuthorizedKey],
+) -> Option<&'a AuthorizedKey> {
+    authorized
+        .iter()
+        .filter(|k| k.is_cert_authority())
+        .find(|k| k.public_key().as_deref() == Some(cert.ca_key.as_slice()))
+}
+
+// ── Certificates ──────────────────────────────────────────────────────
+
+/// What a certificate vouches for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CertKind {
+    /// A user key; principals are user names.
+    User,
+    /// A server host key; principals are host names.
+    Host,
+}
+
+/// A CA-signed Ed25519 certificate.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Certificate {
+    pub kind: CertKind,
+    /// Raw 32-byte Ed25519 key being certified.
+    pub public_key: Vec<u8>,
+    /// Free-form identifier recorded in logs (e.g. "alice@laptop").
+    pub key_id: String,
+    /// CA-chosen serial number.
+    pub serial: u64,
+    /// User or host names the certificate is valid for.
+    pub principals: Vec<String>,
+    /// Unix time the certificate becomes valid.
+    pub valid_after: u64,
+    /// Unix time the certificate expires.
+    pub valid_before: u64,
+    /// Raw 32-byte Ed25519 key of the signing CA.
+    pub ca_key: Vec<u8>,
+    /// CA signature over [`Certificate::signed_data`].
+    pub signature: Vec<u8>,
+}
+
+impl Certificate {
+    /// The bytes the CA signs: every field except the signature, CBOR-encoded.
+    pub fn signed_data(&self) -> WshResult<Vec<u8>> {
+        let fields = (
+            CERT_TYPE,
+            self.kind,
+            &self.public_key,
+            &self.key_id,
+            self.serial,
+            &self.principals,
+            self.valid_after,
+            self.valid_before,
+            &self.ca_key,
+        );
+        let mut data = Vec::new();
+        ciborium::into_writer(&fields, &mut data)?;
+        Ok(data)
+    }
+
+    /// Check the CA signature, kind, principal, and validity window at `now`.
+    ///
+    /// Whether `ca_key` is trusted is up to the caller.
+    pub fn verify(&self, kind: CertKind, principal: &str, now: u64) -> WshResult<()> {
+        if self.kind != kind {
+            return Err(WshError::AuthFailed(format!(
+                "expected a {kind:?} certificate, got {:?}",
+                self.kind
+            )));
+        }
+        if now < self.valid_after {
+            return Err(WshError::AuthFailed("certificate is not yet valid".into()));
+        }
+        if now >= self.valid_before {
+            return Err(WshError::AuthFailed("certificate has expired".into()));
+        }
+        if !self.principals.iter().any(|p| p == principal) {
+            return Err(WshError::AuthFailed(format!(
+                "certificate is not valid for '{principal}'"
+            )));
+        }
+        if !verify_ed25519(&self.ca_key, &self.signed_data()?, &self.signature) {
+            return Err(WshError::AuthFailed("bad certificate signature".into()));
+        }
+        Ok(())
+    }
+
+    /// CBOR encoding, as carried in protocol messages.
+    pub fn to_bytes(&self) -> WshResult<Vec<u8>> {
+        let mut data = Vec::new();
+        ciborium::into_writer(self, &mut data)?;
+        Ok(data)
+    }
+
+    /// Decode the CBOR encoding produced by [`Certificate::to_bytes`].
+    pub fn from_bytes(data: &[u8]) -> WshResult<Self> {
+        crate::codec::cbor_decode(data)
+    }
+
+    /// Format as a `wsh-cert-v1 <base64> comment` line.
+    pub fn to_line(&self, comment: &str) -> WshResult<String> {
+        let line = format!("{CERT_TYPE} {}", base64_encode(&self.to_bytes()?));
+        Ok(if comment.is_empty() {
+            line
+        } else {
+            format!("{line} {comment}")
+        })
+    }
+
+    /// Parse a `wsh-cert-v1 <base64> [comment]` line.
+    pub fn parse_line(line: &str) -> WshResult<Self> {
+        let mut parts = line.split_whitespace();
+        if parts.next() != Some(CERT_TYPE) {
+            return Err(WshError::Codec(format!("not a {CERT_TYPE} certificate")));
+        }
+        let data = parts
+            .next()
+            .and_then(base64_decode)
+            .ok_or_else(|| WshError::Codec("invalid certificate encoding".into()))?;
+        Self::from_bytes(&data)
+    }
+}
+
+/// The data a server signs with its host key to prove it holds the key
+/// named in its host certificate: `SHA-256("wsh-host-v1\0" || session_id || nonce)`.
+pub fn host_signature_data(session_id: &str, nonce: &[u8]) -> Vec<u8> {
+    use sha2::{Digest, Sha256};
+    let mut hasher = Sha256::new();
+    hasher.update(b"wsh-host-v1\0");
+    hasher.update(session_id.as_bytes());
+    hasher.update(nonce);
+    hasher.finalize().to_vec()
+}
+
+/// The data a server's previous host key signs to vouch for its new one:
+/// `SHA-256("wsh-host-rotate-v1\0" || new_key || session_id || nonce)`.
+///
+/// Binding the handshake means the old key must still be held live, so a
+/// leaked rotation message cannot be replayed by another server.
+pub fn rotation_signature_data(new_key: &[u8], session_id: &str, nonce: &[u8]) -> Vec<u8> {
+    use sha2::{Digest, Sha256};
+    let mut hasher = Sha256::new();
+    hasher.update(b"wsh-host-rotate-v1\0");
+    hasher.update(new_key);
+    hasher.update(session_id.as_bytes());
+    hasher.update(nonce);
+    hasher.finalize().to_vec()
+}
+
+/// Verify an Ed25519 signature made with a raw 32-byte public key.
+pub fn verify_ed25519(public_key: &[u8], message: &[u8], signature: &[u8]) -> bool {
+    ring::signature::UnparsedPublicKey::new(&ring::signature::ED25519, public_key)
+        .verify(message, signature)
+        .is_ok()
 }
 
 // ── Base64 helpers ────────────────────────────────────────────────────
 
-fn base64_decode(input: &str) -> Option<Vec<u8>> {
+/// Encode as padded standard base64.
+pub fn base64_encode(data: &[u8]) -> String {
+    const TABLE: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut output = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                output.push(TABLE[((n >> (18 - 6 * i)) & 0x3f) as usize] as char);
+            } else {
+                output.push('=');
+            }
+        }
+    }
+    output
+}
+
+/// Decode standard (padded or unpadded) base64.
+pub fn base64_decode(input: &str) -> Option<Vec<u8>> {
     // Standard base64 alphabet
     const TABLE: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
 
@@ -236,4 +684,114 @@ mod tests {
         let keys = parse_authorized_keys(content);
         assert_eq!(keys.len(), 0);
     }
+
+    fn signed_cert(ca: &ring::signature::Ed25519KeyPair) -> Certificate {
+        use ring::signature::KeyPair;
+        let mut cert = Certificate {
+            kind: CertKind::User,
+            public_key: vec![7; 32],
+            key_id: "alice@laptop".into(),
+            serial: 1,
+            principals: vec!["alice".into()],
+            valid_after: 1_000,
+            valid_before: 2_000,
+            ca_key: ca.public_key().as_ref().to_vec(),
+            signature: Vec::new(),
+        };
+        cert.signature = ca.sign(&cert.signed_data().unwrap()).as_ref().to_vec();
+        cert
+    }
+
+    #[test]
+    fn certificate_verifies_and_round_trips() {
+        let ca = ring::signature::Ed25519KeyPair::from_seed_unchecked(&[3; 32]).unwrap();
+        let cert = signed_cert(&ca);
+
+        let line = cert.to_line("alice").unwrap();
+        assert!(line.starts_with("wsh-cert-v1 "));
+        let parsed = Certificate::parse_line(&line).unwrap();
+        assert_eq!(parsed, cert);
+
+        assert!(parsed.verify(CertKind::User, "alice", 1_500).is_ok());
+        assert!(parsed.verify(CertKind::Host, "alice", 1_500).is_err());
+        assert!(parsed.verify(CertKind::User, "bob", 1_500).is_err());
+        assert!(parsed.verify(CertKind::User, "alice", 2_000).is_err());
+
+        let mut tampered = parsed;
+        tampered.principals.push("root".into());
+        assert!(tampered.verify(CertKind::User, "root", 1_500).is_err());
+    }
+
+    #[test]
+    fn cert_authority_entries_match_signing_key() {
+        let ca = ring::signature::Ed25519KeyPair::from_seed_unchecked(&[3; 32]).unwrap();
+        let cert = signed_cert(&ca);
+        let mut wire = Vec::new();
+        wire.extend_from_slice(&11u32.to_be_bytes());
+        wire.extend_from_slice(b"ssh-ed25519");
+        wire.extend_from_slice(&32u32.to_be_bytes());
+        wire.extend_from_slice(&cert.ca_key);
+        let line = format!("ssh-ed25519 {}", base64_encode(&wire));
+
+        let plain = parse_authorized_keys(&line);
+        assert!(find_cert_authority(&cert, &plain).is_none());
+        let trusted = parse_authorized_keys(&format!("cert-authority,no-pty {line} ca"));
+        assert!(find_cert_authority(&cert, &trusted).is_some());
+    }
+
+    #[test]
+    fn options_with_quoted_spaces_and_expiry() {
+        let line = format!(
+            "command=\"tar -czf - /srv\",no-pty,expiry-time=\"20300101\" ssh-ed25519 {} backup",
+            TEST_KEY_B64
+        );
+        let keys = parse_authorized_keys(&line);
+        assert_eq!(keys.len(), 1);
+        assert_eq!(keys[0].comment, "backup");
+        assert_eq!(
+            keys[0].option_list(),
+            [
+                "command=\"tar -czf - /srv\"",
+                "no-pty",
+                "expiry-time=\"20300101\""
+            ]
+        );
+        assert_eq!(keys[0].expiry_time(), Some(1_893_456_000));
+        assert!(!keys[0].is_expired(1_893_455_999));
+        assert!(keys[0].is_expired(1_893_456_000));
+
+        assert_eq!(parse_expiry_time("20240229123045Z"), Some(1_709_209_845));
+        assert_eq!(parse_expiry_time("2024-02-29"), None);
+        let malformed = format!("expiry-time=\"soon\" ssh-ed25519 {} x", TEST_KEY_B64);
+        assert!(parse_authorized_keys(&malformed)[0].is_expired(1));
+    }
+
+    #[test]
+    fn add_and_remove_keep_other_lines() {
+        let home = std::env::temp_dir().join(format!("wsh-keys-admin-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&home);
+        let path = wsh_authorized_keys_path(&home);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, "# team keys\n").unwrap();
+
+        let public_key = format!("ssh-ed25519 {TEST_KEY_B64} laptop");
+        let key = parse_public_key(&public_key, Some("expiry-time=\"20300101\",no-pty")).unwrap();
+        assert!(add_authorized_key(&home, &key).unwrap());
+        assert!(!add_authorized_key(&home, &parse_public_key(&public_key, None).unwrap()).unwrap());
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            content,
+            format!("# team keys\nexpiry-time=\"20300101\",no-pty {public_key}\n")
+        );
+
+        assert_eq!(remove_authorized_key(&home, &key.fingerprint).unwrap(), 1);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "# team keys\n");
+        assert_eq!(remove_authorized_key(&home, &key.fingerprint).unwrap(), 0);
+
+        assert!(parse_public_key(&public_key, Some("no-pty ssh-ed25519 x\n")).is_err());
+        assert!(parse_public_key(&public_key, Some("no-pty extra")).is_err());
+        assert!(parse_public_key("ssh-rsa AAAA x", None).is_err());
+        assert!(parse_public_key(&public_key, Some("expiry-time=\"next week\"")).is_err());
+        let _ = std::fs::remove_dir_all(&home);
+    }
 }
@@ -1,15 +1,20 @@
 //! wsh-core: Shared protocol library for the Web Shell.
 //!
 //! Provides CBOR message types, codec, identity/fingerprint management,
-//! authorized_keys parsing, HMAC session tokens, and abstract transport traits.
+//! authorized_keys parsing, HMAC session tokens, flow control, payload
+//! compression, and abstract transport traits.
 
+pub mod cast;
 pub mod codec;
+pub mod compress;
 pub mod error;
+pub mod flow;
 pub mod identity;
 pub mod keys;
 pub mod messages;
 pub mod remote_runtime;
 pub mod token;
+pub mod transfer;
 pub mod transport;
 
 // Re-export commonly used items at crate root.
@@ -18,7 +23,7 @@ pub use error::{WshError, WshResult};
 pub use identity::{fingerprint, short_fingerprint, FingerprintIndex};
 pub use messages::{AuthMethod, ChannelKind, MsgType, PROTOCOL_VERSION};
 pub use remote_runtime::{
-    PeerType, ReachabilityDescriptor, RemoteIdentity, RemotePeerDescriptor, SessionIntent,
-    SessionTarget, ShellBackend,
+    valid_peer_name, PeerType, ReachabilityDescriptor, RemoteIdentity, RemotePeerDescriptor,
+    SessionIntent, SessionTarget, ShellBackend,
 };
 pub use token::{create_token, generate_secret, verify_token};
@@ -0,0 +1,191 @@
+// wsh protocol types written by hand, not generated from wsh-v1.yaml.
+//
+// The enum variants and struct fields that refer to them in messages.gen.rs
+// must be added to the spec before it is next regenerated.
+
+/// Proof that the server's previous host key vouches for its current one.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HostKeyRotation {
+    /// Current host public key.
+    #[serde(with = "serde_bytes")]
+    pub new_key: Vec<u8>,
+    /// Current key's signature over `host_signature_data`.
+    #[serde(with = "serde_bytes")]
+    pub new_signature: Vec<u8>,
+    /// Previous host public key.
+    #[serde(with = "serde_bytes")]
+    pub old_key: Vec<u8>,
+    /// Previous key's signature over `rotation_signature_data`.
+    #[serde(with = "serde_bytes")]
+    pub old_signature: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct McpProgressPayload {
+    pub tool: String,
+    /// Time since the call started.
+    pub elapsed_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FileTransferStartPayload {
+    pub transfer_id: u32,
+    pub direction: String,
+    pub path: String,
+    #[serde(default)]
+    pub total_size: u64,
+    #[serde(default = "default_file_transfer_start_chunk_size")]
+    pub chunk_size: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_hash: Option<String>,
+    #[serde(default)]
+    pub resume_offset: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prefix_hash: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub window: Option<u32>,
+}
+
+fn default_file_transfer_start_chunk_size() -> u32 {
+    262144
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FileTransferReadyPayload {
+    pub transfer_id: u32,
+    pub total_size: u64,
+    pub chunk_size: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_hash: Option<String>,
+    #[serde(default)]
+    pub resume_offset: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prefix_hash: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub window: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SyncManifestRequestPayload {
+    pub transfer_id: u32,
+    pub path: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SyncManifestPayload {
+    pub transfer_id: u32,
+    #[serde(default)]
+    pub entries: Vec<SyncEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SyncDeletePayload {
+    pub transfer_id: u32,
+    pub path: String,
+    #[serde(default)]
+    pub paths: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AgentForwardPayload {
+    pub request_id: u32,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WindowUpdatePayload {
+    pub channel_id: u32,
+    pub increment: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OpenUnixPayload {
+    pub gateway_id: u32,
+    pub path: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ListenUnixPayload {
+    pub listener_id: u32,
+    pub path: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ReverseSubscribePayload {}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ReversePeerEventPayload {
+    /// `"join"` or `"leave"`.
+    pub event: String,
+    pub peer: PeerInfo,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DrainPayload {
+    pub reason: String,
+    /// Seconds until the server closes remaining connections.
+    pub deadline_secs: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AuthorizedKeysListPayload {}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AuthorizedKeysPayload {
+    pub keys: Vec<AuthorizedKeyInfo>,
+    /// Whether the request that produced this reply changed the file
+    /// (`false` for a list, or an add of a key already present).
+    #[serde(default)]
+    pub changed: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AuthorizedKeyAddPayload {
+    /// Public key line (`ssh-ed25519 <base64> [comment]`).
+    pub public_key: String,
+    /// authorized_keys options to prefix the line with.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub options: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AuthorizedKeyRemovePayload {
+    /// Fingerprint, or a unique prefix of one.
+    pub fingerprint: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthorizedKeyInfo {
+    pub fingerprint: String,
+    pub key_type: String,
+    pub comment: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub options: Option<String>,
+    /// `"wsh"` for `~/.wsh/authorized_keys`, `"ssh"` for `~/.ssh/authorized_keys`.
+    pub source: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncEntry {
+    pub path: String,
+    pub size: u64,
+    pub hash: String,
+}
@@ -124,6 +124,32 @@ pub enum MsgType {
     PolicyUpdate = 0x9d,
 
     TerminalConfig = 0x9e,
+
+    FileTransferStart = 0x9f,
+    FileTransferReady = 0xa0,
+    SyncManifestRequest = 0xa1,
+    SyncManifest = 0xa2,
+    SyncDelete = 0xa3,
+
+    AgentForwardRequest = 0xa4,
+    AgentForwardResponse = 0xa5,
+
+    WindowUpdate = 0xa6,
+
+    OpenUnix = 0xa7,
+    ListenUnix = 0xa8,
+
+    ReverseSubscribe = 0xa9,
+    ReversePeerEvent = 0xaa,
+
+    Drain = 0xab,
+
+    AuthorizedKeysList = 0xac,
+    AuthorizedKeys = 0xad,
+    AuthorizedKeyAdd = 0xae,
+    AuthorizedKeyRemove = 0xaf,
+
+    McpProgress = 0xb0,
 }
 
 impl From<MsgType> for u8 {
@@ -230,6 +256,24 @@ impl TryFrom<u8> for MsgType {
             0x9c => Ok(Self::PolicyResult),
             0x9d => Ok(Self::PolicyUpdate),
             0x9e => Ok(Self::TerminalConfig),
+            0x9f => Ok(Self::FileTransferStart),
+            0xa0 => Ok(Self::FileTransferReady),
+            0xa1 => Ok(Self::SyncManifestRequest),
+            0xa2 => Ok(Self::SyncManifest),
+            0xa3 => Ok(Self::SyncDelete),
+            0xa4 => Ok(Self::AgentForwardRequest),
+            0xa5 => Ok(Self::AgentForwardResponse),
+            0xa6 => Ok(Self::WindowUpdate),
+            0xa7 => Ok(Self::OpenUnix),
+            0xa8 => Ok(Self::ListenUnix),
+            0xa9 => Ok(Self::ReverseSubscribe),
+            0xaa => Ok(Self::ReversePeerEvent),
+            0xab => Ok(Self::Drain),
+            0xac => Ok(Self::AuthorizedKeysList),
+            0xad => Ok(Self::AuthorizedKeys),
+            0xae => Ok(Self::AuthorizedKeyAdd),
+            0xaf => Ok(Self::AuthorizedKeyRemove),
+            0xb0 => Ok(Self::McpProgress),
             _ => Err(format!("unknown message type: 0x{v:02x}")),
         }
     }
@@ -247,6 +291,7 @@ pub enum ChannelKind {
     Tcp,
     Udp,
     Job,
+    TcpForward,
 }
 
 /// AuthMethod enum.
@@ -256,6 +301,7 @@ pub enum AuthMethod {
     #[default]
     Pubkey,
     Password,
+    Totp,
 }
 
 /// SessionDataMode enum.
@@ -379,6 +425,23 @@ pub enum Payload {
     PolicyResult(PolicyResultPayload),
     PolicyUpdate(PolicyUpdatePayload),
     TerminalConfig(TerminalConfigPayload),
+    FileTransferStart(FileTransferStartPayload),
+    FileTransferReady(FileTransferReadyPayload),
+    SyncManifestRequest(SyncManifestRequestPayload),
+    SyncManifest(SyncManifestPayload),
+    SyncDelete(SyncDeletePayload),
+    AgentForward(AgentForwardPayload),
+    WindowUpdate(WindowUpdatePayload),
+    OpenUnix(OpenUnixPayload),
+    ListenUnix(ListenUnixPayload),
+    ReverseSubscribe(ReverseSubscribePayload),
+    ReversePeerEvent(ReversePeerEventPayload),
+    Drain(DrainPayload),
+    AuthorizedKeysList(AuthorizedKeysListPayload),
+    AuthorizedKeys(AuthorizedKeysPayload),
+    AuthorizedKeyAdd(AuthorizedKeyAddPayload),
+    AuthorizedKeyRemove(AuthorizedKeyRemovePayload),
+    McpProgress(McpProgressPayload),
     Empty(EmptyPayload),
 }
 
@@ -482,6 +545,23 @@ impl Payload {
             MsgType::PolicyResult => Ok(Self::PolicyResult(ciborium::from_reader(cursor)?)),
             MsgType::PolicyUpdate => Ok(Self::PolicyUpdate(ciborium::from_reader(cursor)?)),
             MsgType::TerminalConfig => Ok(Self::TerminalConfig(ciborium::from_reader(cursor)?)),
+            MsgType::FileTransferStart => Ok(Self::FileTransferStart(ciborium::from_reader(cursor)?)),
+            MsgType::FileTransferReady => Ok(Self::FileTransferReady(ciborium::from_reader(cursor)?)),
+            MsgType::SyncManifestRequest => Ok(Self::SyncManifestRequest(ciborium::from_reader(cursor)?)),
+            MsgType::SyncManifest => Ok(Self::SyncManifest(ciborium::from_reader(cursor)?)),
+            MsgType::SyncDelete => Ok(Self::SyncDelete(ciborium::from_reader(cursor)?)),
+            MsgType::AgentForwardRequest | MsgType::AgentForwardResponse => Ok(Self::AgentForward(ciborium::from_reader(cursor)?)),
+            MsgType::WindowUpdate => Ok(Self::WindowUpdate(ciborium::from_reader(cursor)?)),
+            MsgType::OpenUnix => Ok(Self::OpenUnix(ciborium::from_reader(cursor)?)),
+            MsgType::ListenUnix => Ok(Self::ListenUnix(ciborium::from_reader(cursor)?)),
+            MsgType::ReverseSubscribe => Ok(Self::ReverseSubscribe(ciborium::from_reader(cursor)?)),
+            MsgType::ReversePeerEvent => Ok(Self::ReversePeerEvent(ciborium::from_reader(cursor)?)),
+            MsgType::Drain => Ok(Self::Drain(ciborium::from_reader(cursor)?)),
+            MsgType::AuthorizedKeysList => Ok(Self::AuthorizedKeysList(ciborium::from_reader(cursor)?)),
+            MsgType::AuthorizedKeys => Ok(Self::AuthorizedKeys(ciborium::from_reader(cursor)?)),
+            MsgType::AuthorizedKeyAdd => Ok(Self::AuthorizedKeyAdd(ciborium::from_reader(cursor)?)),
+            MsgType::AuthorizedKeyRemove => Ok(Self::AuthorizedKeyRemove(ciborium::from_reader(cursor)?)),
+            MsgType::McpProgress => Ok(Self::McpProgress(ciborium::from_reader(cursor)?)),
         }
     }
 }
@@ -524,6 +604,12 @@ pub struct ServerHelloPayload {
 pub struct ChallengePayload {
     #[serde(with = "serde_bytes")]
     pub nonce: Vec<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "option_bytes")]
+    pub host_certificate: Option<Vec<u8>>,
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "option_bytes")]
+    pub host_signature: Option<Vec<u8>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host_key_rotation: Option<HostKeyRotation>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -542,6 +628,10 @@ pub struct AuthPayload {
     pub public_key: Option<Vec<u8>>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub password: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "option_bytes")]
+    pub certificate: Option<Vec<u8>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -583,6 +673,10 @@ pub struct OpenOkPayload {
     pub data_mode: SessionDataMode,
     #[serde(default)]
     pub capabilities: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "option_bytes")]
+    pub resume_token: Option<Vec<u8>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -625,6 +719,9 @@ pub struct SessionDataPayload {
     pub channel_id: u32,
     #[serde(with = "serde_bytes")]
     pub data: Vec<u8>,
+    /// Algorithm `data` is compressed with (see `wsh_core::compress`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compression: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -787,6 +884,10 @@ pub struct McpToolsPayload {
 pub struct McpCallPayload {
     pub tool: String,
     pub arguments: serde_json::Value,
+    /// Ask for `McpProgress` while the call runs (servers advertising
+    /// `mcp-progress` only).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub progress: Option<bool>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -799,6 +900,9 @@ pub struct McpResultPayload {
 #[serde(deny_unknown_fields)]
 pub struct ReverseRegisterPayload {
     pub username: String,
+    /// Human-readable peer name, unique on the relay.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
     #[serde(default)]
     pub capabilities: Vec<String>,
     #[serde(default = "default_reverse_register_peer_type")]
@@ -1293,6 +1397,11 @@ pub struct FileChunkPayload {
     #[serde(with = "serde_bytes")]
     pub data: Vec<u8>,
     pub is_final: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+    /// Algorithm `data` is compressed with (see `wsh_core::compress`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compression: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -1344,6 +1453,8 @@ pub struct PeerInfo {
     pub fingerprint: String,
     pub fingerprint_short: String,
     pub username: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
     #[serde(default)]
     pub capabilities: Vec<String>,
     #[serde(default)]
@@ -1382,6 +1493,22 @@ pub struct SessionSummary {
     pub created_at_secs: u64,
     pub idle_secs: u64,
     pub attached_count: u32,
+    /// `"attached"`, `"detached"` or `"dormant"` (saved, awaiting reattach).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state: Option<String>,
+    /// Unix time of the last input or attach.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_activity_secs: Option<u64>,
+    /// Command the PTY was started with (`None` = default shell).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cols: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rows: Option<u16>,
+    /// Full fingerprint of the owner's key.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fingerprint: Option<String>,
 }
 
 // ── Helper for optional bytes serde ──────────────────────────────────
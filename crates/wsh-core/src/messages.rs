@@ -1,3 +1,5 @@
 // Auto-generated messages — DO NOT EDIT messages.gen.rs directly
 // Edit web/packages/wsh/spec/wsh-v1.yaml and run: node web/packages/wsh/spec/codegen.mjs
 include!("messages.gen.rs");
+// Hand-written additions that are not in the spec yet.
+include!("messages.ext.rs");
@@ -140,11 +140,13 @@ impl RemotePeerDescriptor {
     /// Convert a relay `PeerInfo` payload into the canonical descriptor shape.
     #[must_use]
     pub fn from_wsh_peer_info(peer: &PeerInfo, relay_host: &str, relay_port: u16) -> Self {
+        let mut aliases = vec![peer.fingerprint_short.clone()];
+        aliases.extend(peer.name.iter().map(|name| format!("@{name}")));
         let identity = RemoteIdentity {
             canonical_id: peer.fingerprint.clone(),
             fingerprint: Some(peer.fingerprint.clone()),
             pod_id: None,
-            aliases: vec![peer.fingerprint_short.clone()],
+            aliases,
         };
         let reachability = ReachabilityDescriptor {
             kind: "reverse-relay".to_string(),
@@ -173,6 +175,20 @@ impl RemotePeerDescriptor {
     }
 }
 
+/// Longest accepted relay peer name, in bytes.
+pub const MAX_PEER_NAME_LEN: usize = 64;
+
+/// Whether `name` can be registered as a relay peer name: ASCII letters,
+/// digits, `-`, `_` and `.`, at most [`MAX_PEER_NAME_LEN`] bytes.
+#[must_use]
+pub fn valid_peer_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_PEER_NAME_LEN
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.'))
+}
+
 /// A resolved target request for opening a remote session.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct SessionTarget {
@@ -187,8 +203,8 @@ pub struct SessionTarget {
 #[cfg(test)]
 mod tests {
     use super::{
-        PeerType, RemoteIdentity, RemotePeerDescriptor, SessionIntent, SessionTarget,
-        ShellBackend,
+        valid_peer_name, PeerType, RemoteIdentity, RemotePeerDescriptor, SessionIntent,
+        SessionTarget, ShellBackend,
     };
     use crate::messages::PeerInfo;
 
@@ -215,6 +231,7 @@ mod tests {
             fingerprint: "abc123".into(),
             fingerprint_short: "abc123".into(),
             username: "browser".into(),
+            name: Some("laptop".into()),
             capabilities: vec!["shell".into(), "fs".into()],
             peer_type: "browser-shell".into(),
             shell_backend: "virtual-shell".into(),
@@ -228,12 +245,22 @@ mod tests {
 
         let descriptor = RemotePeerDescriptor::from_wsh_peer_info(&peer, "relay.example", 4422);
         assert_eq!(descriptor.identity.canonical_id, "abc123");
+        assert_eq!(descriptor.identity.aliases, vec!["abc123", "@laptop"]);
         assert_eq!(descriptor.peer_type, PeerType::BrowserShell);
         assert_eq!(descriptor.shell_backend, ShellBackend::VirtualShell);
         assert_eq!(descriptor.reachability[0].relay_host.as_deref(), Some("relay.example"));
         assert!(descriptor.supports_attach);
     }
 
+    #[test]
+    fn validates_peer_names() {
+        assert!(valid_peer_name("build-box_2.lan"));
+        assert!(!valid_peer_name(""));
+        assert!(!valid_peer_name("has space"));
+        assert!(!valid_peer_name("@alice"));
+        assert!(!valid_peer_name(&"x".repeat(65)));
+    }
+
     #[test]
     fn session_target_serializes_intent() {
         let target = SessionTarget {
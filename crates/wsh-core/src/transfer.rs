@@ -0,0 +1,194 @@
+//! Shared helpers for resumable, chunked file transfers.
+//!
+//! Both ends of a `FILE_TRANSFER_START` exchange use the same rules: data is
+//! sent in fixed-size chunks, every chunk carries a BLAKE3 hash, and the
+//! receiver writes only verified chunks to a `<path>.wshpart` file. An
+//! interrupted transfer therefore resumes from the last whole chunk in the
+//! partial file, after the sender confirms the partial prefix still matches
+//! its own data.
+//!
+//! Directory sync builds on the same hashes: each side lists its tree as a
+//! manifest of `(relative path, size, hash)` entries and only files whose
+//! entries differ are transferred.
+
+use crate::error::{WshError, WshResult};
+use crate::messages::SyncEntry;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+
+/// Default chunk size for chunked transfers: 256 KB.
+pub const DEFAULT_CHUNK_SIZE: u32 = 256 * 1024;
+
+/// Suffix appended to the destination path while a transfer is in progress.
+pub const PARTIAL_SUFFIX: &str = ".wshpart";
+
+/// Path of the in-progress partial file for `path`.
+pub fn partial_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(PARTIAL_SUFFIX);
+    PathBuf::from(name)
+}
+
+/// Hex-encoded BLAKE3 hash of a chunk.
+pub fn chunk_hash(data: &[u8]) -> String {
+    blake3::hash(data).to_hex().to_string()
+}
+
+/// Hex-encoded BLAKE3 hash of the first `limit` bytes of a reader
+/// (or all of it when `limit` is `None`).
+pub fn hash_reader<R: Read>(reader: R, limit: Option<u64>) -> WshResult<String> {
+    let mut hasher = blake3::Hasher::new();
+    let mut reader: Box<dyn Read> = match limit {
+        Some(limit) => Box::new(reader.take(limit)),
+        None => Box::new(reader),
+    };
+    let mut buf = vec![0_u8; 64 * 1024];
+    loop {
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+/// Hex-encoded BLAKE3 hash of a file, or of its first `limit` bytes.
+pub fn hash_file(path: &Path, limit: Option<u64>) -> WshResult<String> {
+    hash_reader(std::fs::File::open(path)?, limit)
+}
+
+/// Offset a transfer can resume from given `partial_len` bytes on disk.
+///
+/// Rounds down to a whole chunk so a torn final write is re-sent, and never
+/// exceeds `total_size` when the expected size is known.
+pub fn resume_point(partial_len: u64, total_size: Option<u64>, chunk_size: u32) -> u64 {
+    let chunk_size = u64::from(chunk_size.max(1));
+    let len = match total_size {
+        Some(total) => partial_len.min(total),
+        None => partial_len,
+    };
+    if total_size == Some(len) {
+        return len;
+    }
+    len - len % chunk_size
+}
+
+/// List every regular file under `root` as a sync manifest, sorted by path.
+///
+/// Paths are relative to `root` and always use `/` separators. Symlinks and
+/// in-progress `.wshpart` files are skipped. A missing `root` yields an empty
+/// manifest so the first sync into a new directory works.
+pub fn build_manifest(root: &Path) -> WshResult<Vec<SyncEntry>> {
+    let mut entries = Vec::new();
+    if root.is_file() {
+        return Err(WshError::Other(format!(
+            "{} is a file, not a directory",
+            root.display()
+        )));
+    }
+    if root.is_dir() {
+        walk(root, "", &mut entries)?;
+    }
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(entries)
+}
+
+fn walk(dir: &Path, prefix: &str, entries: &mut Vec<SyncEntry>) -> WshResult<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let rel = if prefix.is_empty() {
+            name.clone()
+        } else {
+            format!("{prefix}/{name}")
+        };
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            walk(&entry.path(), &rel, entries)?;
+        } else if file_type.is_file() && !name.ends_with(PARTIAL_SUFFIX) {
+            entries.push(SyncEntry {
+                size: entry.metadata()?.len(),
+                hash: hash_file(&entry.path(), None)?,
+                path: rel,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Join a manifest-relative path onto `root`, rejecting absolute paths and
+/// `..` components so a peer cannot reach outside the synced tree.
+pub fn join_relative(root: &Path, rel: &str) -> WshResult<PathBuf> {
+    let rel_path = Path::new(rel);
+    let safe = !rel.is_empty()
+        && rel_path
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)));
+    if !safe {
+        return Err(WshError::InvalidMessage(format!("unsafe sync path: {rel}")));
+    }
+    Ok(root.join(rel_path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resume_point_rounds_down_to_chunk() {
+        assert_eq!(resume_point(0, Some(1000), 256), 0);
+        assert_eq!(resume_point(600, Some(1000), 256), 512);
+        assert_eq!(resume_point(1000, Some(1000), 256), 1000);
+        assert_eq!(resume_point(1500, Some(1000), 256), 1000);
+        assert_eq!(resume_point(700, None, 256), 512);
+    }
+
+    #[test]
+    fn prefix_hash_matches_slice_hash() {
+        let data = b"the quick brown fox jumps over the lazy dog";
+        let prefix = hash_reader(&data[..], Some(9)).unwrap();
+        assert_eq!(prefix, chunk_hash(&data[..9]));
+        let full = hash_reader(&data[..], None).unwrap();
+        assert_eq!(full, chunk_hash(data));
+    }
+
+    #[test]
+    fn partial_path_appends_suffix() {
+        assert_eq!(
+            partial_path(Path::new("/tmp/file.tar")),
+            PathBuf::from("/tmp/file.tar.wshpart")
+        );
+    }
+
+    #[test]
+    fn manifest_lists_nested_files() {
+        let root = std::env::temp_dir().join(format!("wsh-manifest-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("sub")).unwrap();
+        std::fs::write(root.join("a.txt"), b"alpha").unwrap();
+        std::fs::write(root.join("sub/b.txt"), b"beta").unwrap();
+        std::fs::write(root.join("sub/c.txt.wshpart"), b"partial").unwrap();
+
+        let manifest = build_manifest(&root).unwrap();
+        let paths: Vec<_> = manifest.iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(paths, ["a.txt", "sub/b.txt"]);
+        assert_eq!(manifest[1].size, 4);
+        assert_eq!(manifest[1].hash, chunk_hash(b"beta"));
+
+        assert!(build_manifest(&root.join("missing")).unwrap().is_empty());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn join_relative_rejects_escapes() {
+        let root = Path::new("/srv/data");
+        assert_eq!(
+            join_relative(root, "a/b.txt").unwrap(),
+            PathBuf::from("/srv/data/a/b.txt")
+        );
+        assert!(join_relative(root, "../etc/passwd").is_err());
+        assert!(join_relative(root, "/etc/passwd").is_err());
+        assert!(join_relative(root, "").is_err());
+    }
+}
@@ -0,0 +1,240 @@
+//! Server side of key agent forwarding (`wsh connect -A`).
+//!
+//! A client that sends `agent-forward` in its HELLO features, authenticated
+//! with a key that has the `AgentForward` scope, gets a unix socket in a
+//! private directory when it opens its first PTY or exec channel. With
+//! `[isolation]` enabled the directory is inside the connection's workspace
+//! and owned by its account, so other users' shells cannot reach the
+//! socket; otherwise it is in the temp directory. The socket path is
+//! exported to the shell as `WSH_AUTH_SOCK`. Each agent request written to
+//! the socket is pushed to the client as `AGENT_FORWARD_REQUEST`; the client
+//! answers from its local agent with `AGENT_FORWARD_RESPONSE`, which is
+//! written back to the socket. The server only relays opaque frames and
+//! never sees key material.
+//!
+//! The socket and its directory are removed when the connection closes.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::{mpsc, oneshot, Mutex};
+use tracing::debug;
+use wsh_core::messages::{AgentForwardPayload, Envelope, MsgType, Payload};
+use wsh_core::WshResult;
+
+use crate::isolation::Workspace;
+
+/// Environment variable the agent socket is exported as.
+pub const AUTH_SOCK_ENV: &str = "WSH_AUTH_SOCK";
+
+/// How long to wait for the client to answer one agent request.
+const AGENT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Largest agent frame relayed in either direction.
+const MAX_AGENT_FRAME: usize = 64 * 1024;
+
+/// Outstanding requests awaiting a client response, keyed by request ID.
+type PendingRequests = Arc<Mutex<HashMap<u32, oneshot::Sender<Vec<u8>>>>>;
+
+/// A forwarded agent socket for one connection.
+pub struct AgentForwarder {
+    dir: PathBuf,
+    socket: PathBuf,
+    pending: PendingRequests,
+    accept_task: tokio::task::JoinHandle<()>,
+}
+
+impl AgentForwarder {
+    /// Create the socket for a connection in `workspace` and start relaying
+    /// requests to `peer_tx`.
+    #[cfg(unix)]
+    pub fn start(workspace: &Workspace, peer_tx: mpsc::Sender<Envelope>) -> WshResult<Self> {
+        use std::os::unix::fs::DirBuilderExt;
+
+        let parent = if workspace.is_confined() {
+            workspace.home().to_path_buf()
+        } else {
+            std::env::temp_dir()
+        };
+        let dir = parent.join(format!(
+            ".wsh-agent-{}",
+            hex::encode(rand::random::<[u8; 8]>())
+        ));
+        std::fs::DirBuilder::new().mode(0o700).create(&dir)?;
+        let socket = dir.join("agent.sock");
+        let listener = tokio::net::UnixListener::bind(&socket)?;
+        if let Err(e) = workspace
+            .hand_over(&dir)
+            .and_then(|()| workspace.hand_over(&socket))
+        {
+            let _ = std::fs::remove_dir_all(&dir);
+            return Err(e);
+        }
+
+        let pending: PendingRequests = Arc::new(Mutex::new(HashMap::new()));
+        let next_id = Arc::new(AtomicU32::new(1));
+        let accept_task = {
+            let pending = pending.clone();
+            tokio::spawn(async move {
+                // Connection tasks live in the set so aborting this task
+                // also stops them (and drops their `peer_tx` clones).
+                let mut connections = tokio::task::JoinSet::new();
+                while let Ok((stream, _)) = listener.accept().await {
+                    connections.spawn(relay_connection(
+                        stream,
+                        peer_tx.clone(),
+                        pending.clone(),
+                        next_id.clone(),
+                    ));
+                }
+            })
+        };
+
+        debug!(socket = %socket.display(), "agent forwarding socket created");
+        Ok(Self {
+            dir,
+            socket,
+            pending,
+            accept_task,
+        })
+    }
+
+    /// Agent forwarding needs unix domain sockets.
+    #[cfg(not(unix))]
+    pub fn start(_workspace: &Workspace, _peer_tx: mpsc::Sender<Envelope>) -> WshResult<Self> {
+        Err(wsh_core::WshError::Other(
+            "agent forwarding is only supported on unix".into(),
+        ))
+    }
+
+    /// Path of the forwarded socket on this host.
+    pub fn socket_path(&self) -> &Path {
+        &self.socket
+    }
+
+    /// Deliver a client's response to the request waiting for it.
+    pub async fn complete(&self, response: &AgentForwardPayload) {
+        match self.pending.lock().await.remove(&response.request_id) {
+            Some(tx) => {
+                let _ = tx.send(response.data.clone());
+            }
+            None => debug!(
+                request_id = response.request_id,
+                "agent response for unknown request"
+            ),
+        }
+    }
+}
+
+impl Drop for AgentForwarder {
+    fn drop(&mut self) {
+        self.accept_task.abort();
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Relay requests from one local agent client until it disconnects.
+#[cfg(unix)]
+async fn relay_connection(
+    mut stream: tokio::net::UnixStream,
+    peer_tx: mpsc::Sender<Envelope>,
+    pending: PendingRequests,
+    next_id: Arc<AtomicU32>,
+) {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    loop {
+        let mut len = [0u8; 4];
+        if stream.read_exact(&mut len).await.is_err() {
+            return;
+        }
+        let len = u32::from_be_bytes(len) as usize;
+        if len > MAX_AGENT_FRAME {
+            debug!(len, "agent request too large");
+            return;
+        }
+        let mut data = vec![0u8; len];
+        if stream.read_exact(&mut data).await.is_err() {
+            return;
+        }
+
+        let request_id = next_id.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = oneshot::channel();
+        pending.lock().await.insert(request_id, tx);
+        let request = Envelope {
+            msg_type: MsgType::AgentForwardRequest,
+            payload: Payload::AgentForward(AgentForwardPayload { request_id, data }),
+        };
+        if peer_tx.send(request).await.is_err() {
+            return;
+        }
+
+        let reply = match tokio::time::timeout(AGENT_REQUEST_TIMEOUT, rx).await {
+            Ok(Ok(reply)) if reply.len() <= MAX_AGENT_FRAME => reply,
+            _ => {
+                pending.lock().await.remove(&request_id);
+                debug!(request_id, "no usable agent response from client");
+                return;
+            }
+        };
+        let mut frame = Vec::with_capacity(4 + reply.len());
+        frame.extend_from_slice(&(reply.len() as u32).to_be_bytes());
+        frame.extend_from_slice(&reply);
+        if stream.write_all(&frame).await.is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn relays_request_and_response() {
+        let (peer_tx, mut peer_rx) = mpsc::channel(4);
+        let forwarder = Arc::new(AgentForwarder::start(&Workspace::unconfined(), peer_tx).unwrap());
+        let dir = forwarder.dir.clone();
+
+        // Play the client: answer the request by echoing it reversed.
+        let responder = forwarder.clone();
+        let client = tokio::spawn(async move {
+            let envelope = peer_rx.recv().await.unwrap();
+            assert_eq!(envelope.msg_type, MsgType::AgentForwardRequest);
+            if let Payload::AgentForward(mut p) = envelope.payload {
+                p.data.reverse();
+                responder.complete(&p).await;
+            }
+        });
+
+        let mut stream = tokio::net::UnixStream::connect(forwarder.socket_path())
+            .await
+            .unwrap();
+        stream.write_all(&[0, 0, 0, 3, 1, 2, 3]).await.unwrap();
+        let mut reply = [0u8; 7];
+        stream.read_exact(&mut reply).await.unwrap();
+        assert_eq!(reply, [0, 0, 0, 3, 3, 2, 1]);
+
+        client.await.unwrap();
+        drop(forwarder);
+        assert!(!dir.exists());
+    }
+
+    #[tokio::test]
+    async fn isolated_socket_lives_in_the_workspace() {
+        let root = std::env::temp_dir().join(format!("wsh-agent-ws-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let isolation = crate::isolation::Isolation::new(root.clone(), &HashMap::new()).unwrap();
+        let workspace = isolation.workspace("alice", "").unwrap();
+
+        let (peer_tx, _peer_rx) = mpsc::channel(4);
+        let forwarder = AgentForwarder::start(&workspace, peer_tx).unwrap();
+        assert!(forwarder.socket_path().starts_with(workspace.home()));
+        drop(forwarder);
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,273 @@
+//! Structured audit log.
+//!
+//! Records security-relevant events — authentication attempts, session
+//! open/close (including the command line of exec channels), MCP tool calls
+//! and file transfers — as newline-delimited JSON, one [`AuditEvent`] per
+//! line with a Unix timestamp in milliseconds:
+//!
+//! ```text
+//! {"timestamp":1760668406123,"event":"auth","transport":"websocket","remote":"10.0.0.5:51234","username":"alice","method":"pubkey","fingerprint":"SHA256:…","success":true}
+//! {"timestamp":1760668406190,"event":"session_open","session_id":"…","username":"alice","fingerprint":"SHA256:…","kind":"exec","command":"uptime"}
+//! ```
+//!
+//! When the file grows past the configured size it is rotated to
+//! `<path>.1`, `<path>.2`, … keeping at most `max_files` old files.
+
+use serde::Serialize;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+use tracing::error;
+use wsh_core::messages::{AuthMethod, ChannelKind};
+
+use crate::metrics::Transport;
+
+/// A single audited event.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum AuditEvent {
+    /// An authentication attempt, successful or not.
+    Auth {
+        transport: &'static str,
+        remote: String,
+        username: String,
+        method: AuthMethod,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        fingerprint: Option<String>,
+        success: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        reason: Option<String>,
+    },
+    /// A PTY or exec channel was opened.
+    SessionOpen {
+        session_id: String,
+        username: String,
+        fingerprint: String,
+        kind: ChannelKind,
+        /// Command line (exec channels, or a PTY started with a command).
+        #[serde(skip_serializing_if = "Option::is_none")]
+        command: Option<String>,
+    },
+    /// A session's process exited.
+    SessionClose {
+        session_id: String,
+        username: String,
+        fingerprint: String,
+        exit_code: i32,
+    },
+    /// An MCP tool was called.
+    McpCall {
+        username: String,
+        fingerprint: String,
+        tool: String,
+        /// Command line, for the built-in `exec` tool.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        command: Option<String>,
+        success: bool,
+    },
+    /// A file transfer was requested.
+    FileTransfer {
+        username: String,
+        fingerprint: String,
+        direction: String,
+        path: String,
+        size: u64,
+        success: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        reason: Option<String>,
+    },
+}
+
+impl AuditEvent {
+    /// An authentication attempt. `outcome` is the key fingerprint on
+    /// success or the rejection reason on failure.
+    pub fn auth(
+        transport: Transport,
+        remote: SocketAddr,
+        username: &str,
+        method: &AuthMethod,
+        outcome: Result<&str, &str>,
+    ) -> Self {
+        AuditEvent::Auth {
+            transport: transport.label(),
+            remote: remote.to_string(),
+            username: username.to_string(),
+            method: method.clone(),
+            fingerprint: outcome.ok().map(str::to_string),
+            success: outcome.is_ok(),
+            reason: outcome.err().map(str::to_string),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct AuditEntry<'a> {
+    timestamp: u64,
+    #[serde(flatten)]
+    event: &'a AuditEvent,
+}
+
+#[derive(Debug)]
+struct AuditFile {
+    path: PathBuf,
+    max_size: u64,
+    max_files: usize,
+}
+
+/// Append-only JSONL audit log with size-based rotation.
+///
+/// A disabled log accepts events and discards them, so call sites do not
+/// need to check whether auditing is configured.
+#[derive(Debug)]
+pub struct AuditLog {
+    file: Option<Mutex<AuditFile>>,
+}
+
+impl AuditLog {
+    /// A log that discards every event.
+    pub fn disabled() -> Self {
+        Self { file: None }
+    }
+
+    /// Log to `path`, rotating once it exceeds `max_size` bytes (`0` never
+    /// rotates) and keeping `max_files` rotated files.
+    pub fn new(path: PathBuf, max_size: u64, max_files: usize) -> Self {
+        Self {
+            file: Some(Mutex::new(AuditFile {
+                path,
+                max_size,
+                max_files,
+            })),
+        }
+    }
+
+    /// Record an event. Errors are logged but do not propagate — a full
+    /// disk must not lock users out.
+    pub async fn record(&self, event: AuditEvent) {
+        let Some(file) = &self.file else {
+            return;
+        };
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        let mut line = match serde_json::to_string(&AuditEntry {
+            timestamp,
+            event: &event,
+        }) {
+            Ok(line) => line,
+            Err(e) => {
+                error!(error = %e, "failed to serialize audit event");
+                return;
+            }
+        };
+        line.push('\n');
+
+        // Held across rotate + append so concurrent writers never interleave.
+        let file = file.lock().await;
+        if let Err(e) = file.append(&line).await {
+            error!(path = %file.path.display(), error = %e, "failed to write audit log");
+        }
+    }
+}
+
+impl AuditFile {
+    async fn append(&self, line: &str) -> std::io::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        if self.max_size > 0 {
+            let len = match tokio::fs::metadata(&self.path).await {
+                Ok(meta) => meta.len(),
+                Err(_) => 0,
+            };
+            if len > 0 && len + line.len() as u64 > self.max_size {
+                self.rotate().await?;
+            }
+        }
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await?;
+        file.write_all(line.as_bytes()).await?;
+        file.flush().await
+    }
+
+    /// Shift `path.N-1` → `path.N`, …, `path` → `path.1`, dropping the oldest.
+    async fn rotate(&self) -> std::io::Result<()> {
+        if self.max_files == 0 {
+            return tokio::fs::remove_file(&self.path).await;
+        }
+        let _ = tokio::fs::remove_file(rotated(&self.path, self.max_files)).await;
+        for n in (1..self.max_files).rev() {
+            let from = rotated(&self.path, n);
+            if tokio::fs::try_exists(&from).await.unwrap_or(false) {
+                tokio::fs::rename(&from, rotated(&self.path, n + 1)).await?;
+            }
+        }
+        tokio::fs::rename(&self.path, rotated(&self.path, 1)).await
+    }
+}
+
+fn rotated(path: &Path, n: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{n}"));
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session_close(exit_code: i32) -> AuditEvent {
+        AuditEvent::SessionClose {
+            session_id: "s1".into(),
+            username: "alice".into(),
+            fingerprint: "SHA256:abc".into(),
+            exit_code,
+        }
+    }
+
+    #[tokio::test]
+    async fn writes_jsonl_and_rotates() {
+        let dir = std::env::temp_dir().join(format!("wsh-audit-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("audit.jsonl");
+        let log = AuditLog::new(path.clone(), 200, 1);
+
+        log.record(AuditEvent::auth(
+            Transport::WebSocket,
+            "127.0.0.1:4000".parse().unwrap(),
+            "alice",
+            &AuthMethod::Password,
+            Err("invalid password"),
+        ))
+        .await;
+        let first = std::fs::read_to_string(&path).unwrap();
+        let entry: serde_json::Value = serde_json::from_str(first.trim()).unwrap();
+        assert_eq!(entry["event"], "auth");
+        assert_eq!(entry["transport"], "websocket");
+        assert_eq!(entry["method"], "password");
+        assert_eq!(entry["success"], false);
+        assert_eq!(entry["reason"], "invalid password");
+        assert!(entry.get("fingerprint").is_none());
+        assert!(entry["timestamp"].as_u64().unwrap() > 0);
+
+        // Each line is ~120 bytes, so the second write rotates the first out
+        // and the third drops it entirely (max_files = 1).
+        log.record(session_close(0)).await;
+        assert_eq!(std::fs::read_to_string(rotated(&path, 1)).unwrap(), first);
+        log.record(session_close(1)).await;
+        let previous = std::fs::read_to_string(rotated(&path, 1)).unwrap();
+        assert!(previous.contains("\"exit_code\":0"));
+        assert!(std::fs::read_to_string(&path)
+            .unwrap()
+            .contains("\"exit_code\":1"));
+        assert!(!rotated(&path, 2).exists());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
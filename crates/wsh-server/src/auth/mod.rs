@@ -1,7 +1,9 @@
 //! Authentication and authorization modules.
 
+pub mod host_cert;
 pub mod permissions;
 pub mod rate_limit;
+pub mod totp;
 
 pub use permissions::{KeyPermissions, SessionScope};
 pub use rate_limit::ServerRateLimits;
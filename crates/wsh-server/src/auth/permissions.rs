@@ -1,7 +1,9 @@
-//! Per-key permission scopes (v1.5 stub).
+//! Per-key permission scopes.
 //!
-//! When fully implemented, authorized_keys options like `command="...",no-pty`
-//! will be parsed into structured permissions attached to each key.
+//! authorized_keys options like `command="...",no-pty` are split by
+//! [`wsh_core::keys::split_key_options`] and turned into structured
+//! permissions, which session setup checks before opening a channel.
+//! `expiry-time` is enforced earlier, when the key authenticates.
 
 use serde::{Deserialize, Serialize};
 
@@ -20,6 +22,10 @@ pub enum SessionScope {
     FileTransfer,
     /// Allowed to act as a relay peer.
     Relay,
+    /// Allowed to open local (`-L`) and remote (`-R`) TCP forwards.
+    PortForward,
+    /// Allowed to forward the client's key agent (`wsh connect -A`).
+    AgentForward,
 }
 
 /// Permissions associated with an authorized key.
@@ -35,6 +41,14 @@ pub struct KeyPermissions {
     pub forced_command: Option<String>,
     /// Maximum number of concurrent sessions for this key.
     pub max_sessions: Option<usize>,
+    /// Destinations allowed for local forwards (`permitopen="host:port"`).
+    /// Empty means any destination the gateway policy allows.
+    #[serde(default)]
+    pub permit_open: Vec<String>,
+    /// Ports allowed for remote forwards (`permitlisten="port"`).
+    /// Empty means any port the gateway policy allows.
+    #[serde(default)]
+    pub permit_listen: Vec<u16>,
 }
 
 impl KeyPermissions {
@@ -48,10 +62,14 @@ impl KeyPermissions {
                 SessionScope::Mcp,
                 SessionScope::FileTransfer,
                 SessionScope::Relay,
+                SessionScope::PortForward,
+                SessionScope::AgentForward,
             ],
             allow_pty: true,
             forced_command: None,
             max_sessions: None,
+            permit_open: Vec::new(),
+            permit_listen: Vec::new(),
         }
     }
 
@@ -60,6 +78,48 @@ impl KeyPermissions {
         self.scopes.contains(scope)
     }
 
+    /// Check whether a local forward to `host:port` is permitted.
+    ///
+    /// Entries in `permit_open` may use `*` for either the host or the port.
+    pub fn permits_open(&self, host: &str, port: u16) -> bool {
+        if !self.has_scope(&SessionScope::PortForward) {
+            return false;
+        }
+        if self.permit_open.is_empty() {
+            return true;
+        }
+        self.permit_open.iter().any(|entry| {
+            let Some((allowed_host, allowed_port)) = entry.rsplit_once(':') else {
+                return false;
+            };
+            let allowed_host = allowed_host.trim_start_matches('[').trim_end_matches(']');
+            let host_ok = allowed_host == "*" || allowed_host.eq_ignore_ascii_case(host);
+            let port_ok = allowed_port == "*" || allowed_port.parse::<u16>() == Ok(port);
+            host_ok && port_ok
+        })
+    }
+
+    /// Check whether a remote forward listening on `port` is permitted.
+    pub fn permits_listen(&self, port: u16) -> bool {
+        if !self.has_scope(&SessionScope::PortForward) {
+            return false;
+        }
+        self.permit_listen.is_empty() || self.permit_listen.contains(&port)
+    }
+
+    /// Check whether connecting to a unix-domain socket is permitted.
+    /// `permitopen` only names TCP destinations, so keys limited by it
+    /// cannot forward sockets.
+    pub fn permits_open_unix(&self) -> bool {
+        self.has_scope(&SessionScope::PortForward) && self.permit_open.is_empty()
+    }
+
+    /// Check whether listening on a unix-domain socket is permitted. Keys
+    /// limited by `permitlisten` cannot listen on sockets.
+    pub fn permits_listen_unix(&self) -> bool {
+        self.has_scope(&SessionScope::PortForward) && self.permit_listen.is_empty()
+    }
+
     /// Parse permissions from an authorized_keys options string.
     ///
     /// Supports SSH-style key options:
@@ -67,7 +127,15 @@ impl KeyPermissions {
     /// - `no-pty` → disallow PTY allocation
     /// - `restrict` → deny all, must combine with `permit-*`
     /// - `restrict,permit-pty` → only PTY allowed
-    /// - `no-agent-forwarding`, `no-port-forwarding` → ignored (no-op)
+    /// - `no-port-forwarding` → deny `-L`/`-R` forwards
+    /// - `permit-port-forwarding` → re-allow forwards under `restrict`
+    /// - `permitopen="host:port"` → limit local forward destinations (repeatable)
+    /// - `permitlisten="port"` → limit remote forward ports (repeatable)
+    /// - `no-agent-forwarding` → deny key agent forwarding
+    /// - `permit-agent-forwarding` → re-allow agent forwarding under `restrict`
+    ///
+    /// `expiry-time="YYYYMMDD[HHMM[SS]]"` is checked during authentication
+    /// (see [`wsh_core::keys::AuthorizedKey::is_expired`]), not here.
     pub fn from_options(fingerprint: String, options: Option<&str>) -> Self {
         let options_str = match options {
             Some(s) if !s.is_empty() => s,
@@ -83,9 +151,15 @@ impl KeyPermissions {
         let mut permit_mcp = false;
         let mut permit_file = false;
         let mut permit_relay = false;
+        let mut permit_port_forwarding = false;
+        let mut no_port_forwarding = false;
+        let mut permit_agent_forwarding = false;
+        let mut no_agent_forwarding = false;
+        let mut permit_open = Vec::new();
+        let mut permit_listen = Vec::new();
 
         // Parse comma-separated options, handling quoted values
-        for opt in split_options(options_str) {
+        for opt in wsh_core::keys::split_key_options(options_str) {
             let opt = opt.trim();
             if opt.is_empty() {
                 continue;
@@ -110,10 +184,24 @@ impl KeyPermissions {
                 permit_file = true;
             } else if opt == "permit-relay" {
                 permit_relay = true;
+            } else if opt == "permit-port-forwarding" {
+                permit_port_forwarding = true;
+            } else if opt == "no-port-forwarding" {
+                no_port_forwarding = true;
+            } else if opt == "permit-agent-forwarding" {
+                permit_agent_forwarding = true;
+            } else if opt == "no-agent-forwarding" {
+                no_agent_forwarding = true;
+            } else if let Some(raw) = opt.strip_prefix("permitopen=") {
+                permit_open.push(raw.trim_matches('"').trim_matches('\'').to_string());
+            } else if let Some(raw) = opt.strip_prefix("permitlisten=") {
+                if let Ok(port) = raw.trim_matches('"').trim_matches('\'').parse::<u16>() {
+                    permit_listen.push(port);
+                }
             } else if let Some(raw) = opt.strip_prefix("max-sessions=") {
                 max_sessions = raw.parse::<usize>().ok().filter(|v| *v > 0);
             }
-            // Ignore unknown options (no-agent-forwarding, etc.)
+            // Ignore unknown options (no-X11-forwarding, etc.)
         }
 
         if restricted {
@@ -134,6 +222,12 @@ impl KeyPermissions {
             if permit_relay {
                 scopes.push(SessionScope::Relay);
             }
+            if permit_port_forwarding && !no_port_forwarding {
+                scopes.push(SessionScope::PortForward);
+            }
+            if permit_agent_forwarding && !no_agent_forwarding {
+                scopes.push(SessionScope::AgentForward);
+            }
             scopes.dedup();
 
             Self {
@@ -142,6 +236,8 @@ impl KeyPermissions {
                 allow_pty,
                 forced_command,
                 max_sessions,
+                permit_open,
+                permit_listen,
             }
         } else {
             // Non-restricted: start with full access, remove denied scopes
@@ -149,38 +245,17 @@ impl KeyPermissions {
             perms.allow_pty = allow_pty;
             perms.forced_command = forced_command;
             perms.max_sessions = max_sessions;
-            perms
-        }
-    }
-}
-
-/// Split options string by commas, respecting quoted values.
-fn split_options(s: &str) -> Vec<String> {
-    let mut parts = Vec::new();
-    let mut current = String::new();
-    let mut in_quotes = false;
-    let mut quote_char = '"';
-
-    for ch in s.chars() {
-        if in_quotes {
-            current.push(ch);
-            if ch == quote_char {
-                in_quotes = false;
+            perms.permit_open = permit_open;
+            perms.permit_listen = permit_listen;
+            if no_port_forwarding {
+                perms.scopes.retain(|s| *s != SessionScope::PortForward);
             }
-        } else if ch == '"' || ch == '\'' {
-            in_quotes = true;
-            quote_char = ch;
-            current.push(ch);
-        } else if ch == ',' {
-            parts.push(std::mem::take(&mut current));
-        } else {
-            current.push(ch);
+            if no_agent_forwarding {
+                perms.scopes.retain(|s| *s != SessionScope::AgentForward);
+            }
+            perms
         }
     }
-    if !current.is_empty() {
-        parts.push(current);
-    }
-    parts
 }
 
 #[cfg(test)]
@@ -204,9 +279,61 @@ mod tests {
         assert!(p.has_scope(&SessionScope::Exec));
     }
 
+    #[test]
+    fn forced_command_keeps_quoted_commas_and_spaces() {
+        let p = KeyPermissions::from_options(
+            "fp".to_string(),
+            Some("command=\"rsync --server -e.LsfxC, /srv\",no-pty,no-port-forwarding"),
+        );
+        assert_eq!(
+            p.forced_command.as_deref(),
+            Some("rsync --server -e.LsfxC, /srv")
+        );
+        assert!(!p.allow_pty);
+        assert!(!p.permits_open("localhost", 22));
+    }
+
     #[test]
     fn max_sessions_parsed_when_positive() {
         let p = KeyPermissions::from_options("fp".to_string(), Some("max-sessions=3"));
         assert_eq!(p.max_sessions, Some(3));
     }
+
+    #[test]
+    fn no_port_forwarding_denies_forwards() {
+        let p = KeyPermissions::from_options("fp".to_string(), Some("no-port-forwarding"));
+        assert!(p.has_scope(&SessionScope::Shell));
+        assert!(!p.permits_open("localhost", 5432));
+        assert!(!p.permits_listen(8080));
+        assert!(!p.permits_open_unix());
+        assert!(!p.permits_listen_unix());
+    }
+
+    #[test]
+    fn agent_forwarding_follows_key_options() {
+        let p = KeyPermissions::from_options("fp".to_string(), Some("no-agent-forwarding"));
+        assert!(!p.has_scope(&SessionScope::AgentForward));
+        let p = KeyPermissions::from_options("fp".to_string(), Some("restrict,permit-pty"));
+        assert!(!p.has_scope(&SessionScope::AgentForward));
+        let p = KeyPermissions::from_options(
+            "fp".to_string(),
+            Some("restrict,permit-pty,permit-agent-forwarding"),
+        );
+        assert!(p.has_scope(&SessionScope::AgentForward));
+    }
+
+    #[test]
+    fn permitopen_and_permitlisten_limit_forwards() {
+        let p = KeyPermissions::from_options(
+            "fp".to_string(),
+            Some("restrict,permit-port-forwarding,permitopen=\"db.internal:5432\",permitopen=\"*:80\",permitlisten=\"8080\""),
+        );
+        assert!(p.permits_open("db.internal", 5432));
+        assert!(p.permits_open("example.com", 80));
+        assert!(!p.permits_open("db.internal", 22));
+        assert!(p.permits_listen(8080));
+        assert!(!p.permits_listen(9090));
+        assert!(!p.permits_open_unix());
+        assert!(!p.permits_listen_unix());
+    }
 }
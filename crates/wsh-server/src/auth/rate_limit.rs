@@ -80,25 +80,56 @@ impl RateLimiter {
 /// Pre-configured rate limiters for the wsh server.
 #[derive(Debug)]
 pub struct ServerRateLimits {
-    /// Auth attempts: max 5 per minute per IP address.
+    /// Failed auth attempts per minute per IP address. See `[limits]`.
     pub auth: RateLimiter,
     /// Attach attempts: max 10 per minute per principal (fingerprint).
     pub attach: RateLimiter,
+    /// How long an IP that exceeds the auth limit is refused outright.
+    /// Zero disables bans.
+    ban_duration: Duration,
+    /// Banned IPs → when the ban lifts.
+    bans: HashMap<IpAddr, Instant>,
 }
 
-impl Default for ServerRateLimits {
-    fn default() -> Self {
+impl ServerRateLimits {
+    /// Create limiters allowing `auth_per_minute` failed auth attempts per
+    /// IP, banning offenders for `ban_secs` (0 = no bans).
+    pub fn new(auth_per_minute: u32, ban_secs: u64) -> Self {
         Self {
-            auth: RateLimiter::new(5, 60),
+            auth: RateLimiter::new(auth_per_minute, 60),
             attach: RateLimiter::new(10, 60),
+            ban_duration: Duration::from_secs(ban_secs),
+            bans: HashMap::new(),
         }
     }
-}
 
-impl ServerRateLimits {
     /// Check if an auth attempt from the given IP is allowed.
-    pub fn check_auth(&mut self, ip: &IpAddr) -> bool {
-        self.auth.check_and_record(&ip.to_string())
+    ///
+    /// Returns the reason to report to the client when it is not. Only
+    /// failures count toward the limit; see [`Self::record_auth_failure`].
+    pub fn check_auth(&mut self, ip: &IpAddr) -> Result<(), String> {
+        let now = Instant::now();
+        if let Some(until) = self.bans.get(ip) {
+            if *until > now {
+                let secs = (*until - now).as_secs().max(1);
+                return Err(format!("temporarily banned: retry in {secs}s"));
+            }
+            self.bans.remove(ip);
+        }
+        if self.auth.check(&ip.to_string()) {
+            return Ok(());
+        }
+        Err("rate limited: too many failed auth attempts".to_string())
+    }
+
+    /// Record a failed auth attempt from the given IP. Reaching the limit
+    /// bans the IP for the configured duration.
+    pub fn record_auth_failure(&mut self, ip: &IpAddr) {
+        let key = ip.to_string();
+        self.auth.check_and_record(&key);
+        if !self.auth.check(&key) && !self.ban_duration.is_zero() {
+            self.bans.insert(*ip, Instant::now() + self.ban_duration);
+        }
     }
 
     /// Check if an attach attempt from the given principal is allowed.
@@ -110,5 +141,44 @@ impl ServerRateLimits {
     pub fn gc(&mut self) {
         self.auth.gc();
         self.attach.gc();
+        let now = Instant::now();
+        self.bans.retain(|_, until| *until > now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bans_ip_after_auth_failure_limit() {
+        let mut limits = ServerRateLimits::new(2, 300);
+        let ip: IpAddr = "192.0.2.7".parse().unwrap();
+        let other: IpAddr = "192.0.2.8".parse().unwrap();
+        assert!(limits.check_auth(&ip).is_ok());
+        limits.record_auth_failure(&ip);
+        assert!(limits.check_auth(&ip).is_ok());
+        limits.record_auth_failure(&ip);
+        assert!(limits
+            .check_auth(&ip)
+            .unwrap_err()
+            .starts_with("temporarily banned"));
+        assert!(limits.check_auth(&other).is_ok());
+
+        let mut unbanned = ServerRateLimits::new(1, 0);
+        unbanned.record_auth_failure(&ip);
+        assert_eq!(
+            unbanned.check_auth(&ip).unwrap_err(),
+            "rate limited: too many failed auth attempts"
+        );
+    }
+
+    #[test]
+    fn successful_auth_does_not_count() {
+        let mut limits = ServerRateLimits::new(2, 300);
+        let ip: IpAddr = "192.0.2.7".parse().unwrap();
+        for _ in 0..10 {
+            assert!(limits.check_auth(&ip).is_ok());
+        }
     }
 }
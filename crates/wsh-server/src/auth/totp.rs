@@ -0,0 +1,166 @@
+//! TOTP second factor (RFC 6238).
+//!
+//! Users listed in `[auth.totp_secrets]` must follow their pubkey or
+//! password login with a one-time code: the server answers AUTH with
+//! AUTH_METHODS `["totp"]` and only sends AUTH_OK once a second AUTH carries
+//! a valid code. Codes are the usual authenticator-app kind — HMAC-SHA1,
+//! six digits, 30-second steps — and one step of clock drift either way is
+//! tolerated. Each step is accepted at most once per user.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+use wsh_core::{WshError, WshResult};
+
+/// Seconds per time step.
+const STEP_SECS: u64 = 30;
+
+/// Digits in a code.
+const DIGITS: u32 = 6;
+
+/// Steps of clock drift accepted on either side of the current one.
+const DRIFT_STEPS: u64 = 1;
+
+/// Per-user TOTP secrets and replay state.
+pub struct TotpVerifier {
+    secrets: HashMap<String, Vec<u8>>,
+    /// Last accepted step per user, so a code cannot be replayed.
+    last_step: Mutex<HashMap<String, u64>>,
+}
+
+impl TotpVerifier {
+    /// Decode the base32 secrets from the config.
+    pub fn new(secrets: &HashMap<String, String>) -> WshResult<Self> {
+        let secrets = secrets
+            .iter()
+            .map(|(user, encoded)| {
+                base32_decode(encoded)
+                    .filter(|secret| !secret.is_empty())
+                    .map(|secret| (user.clone(), secret))
+                    .ok_or_else(|| {
+                        WshError::Other(format!("[auth.totp_secrets] {user}: not a base32 secret"))
+                    })
+            })
+            .collect::<WshResult<_>>()?;
+        Ok(Self {
+            secrets,
+            last_step: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Whether `username` must present a TOTP code.
+    pub fn is_required(&self, username: &str) -> bool {
+        self.secrets.contains_key(username)
+    }
+
+    /// Check `code` for `username` at unix time `now`.
+    pub fn verify(&self, username: &str, code: &str, now: u64) -> WshResult<()> {
+        let secret = self
+            .secrets
+            .get(username)
+            .ok_or_else(|| WshError::AuthFailed("TOTP is not set up for this user".into()))?;
+        let code: u32 = code
+            .trim()
+            .parse()
+            .ok()
+            .filter(|_| code.trim().len() == DIGITS as usize)
+            .ok_or_else(|| WshError::AuthFailed("invalid TOTP code".into()))?;
+
+        let current = now / STEP_SECS;
+        let step = (current.saturating_sub(DRIFT_STEPS)..=current + DRIFT_STEPS)
+            .find(|&step| code_at(secret, step) == code)
+            .ok_or_else(|| WshError::AuthFailed("invalid TOTP code".into()))?;
+
+        let mut last_step = self.last_step.lock().unwrap_or_else(|e| e.into_inner());
+        if last_step.get(username).is_some_and(|&last| step <= last) {
+            return Err(WshError::AuthFailed("TOTP code already used".into()));
+        }
+        last_step.insert(username.to_string(), step);
+        Ok(())
+    }
+}
+
+/// The code for time step `step` (RFC 4226 HOTP with dynamic truncation).
+fn code_at(secret: &[u8], step: u64) -> u32 {
+    let mut mac = Hmac::<Sha1>::new_from_slice(secret).expect("HMAC accepts any key length");
+    mac.update(&step.to_be_bytes());
+    let digest = mac.finalize().into_bytes();
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        digest[offset] & 0x7f,
+        digest[offset + 1],
+        digest[offset + 2],
+        digest[offset + 3],
+    ]);
+    binary % 10_u32.pow(DIGITS)
+}
+
+/// Decode RFC 4648 base32, ignoring case, spaces and padding.
+fn base32_decode(encoded: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(encoded.len() * 5 / 8);
+    let mut buffer = 0_u32;
+    let mut bits = 0;
+    for c in encoded.chars().filter(|c| !c.is_whitespace() && *c != '=') {
+        let value = match c.to_ascii_uppercase() {
+            c @ 'A'..='Z' => c as u32 - 'A' as u32,
+            c @ '2'..='7' => c as u32 - '2' as u32 + 26,
+            _ => return None,
+        };
+        buffer = (buffer << 5) | value;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// RFC 6238 appendix B secret ("12345678901234567890") in base32.
+    const RFC_SECRET: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+
+    fn verifier() -> TotpVerifier {
+        TotpVerifier::new(&HashMap::from([(
+            "alice".to_string(),
+            RFC_SECRET.to_string(),
+        )]))
+        .unwrap()
+    }
+
+    #[test]
+    fn matches_rfc_6238_vectors() {
+        let secret = base32_decode(RFC_SECRET).unwrap();
+        assert_eq!(secret, b"12345678901234567890");
+        // Last six digits of the SHA-1 test vectors.
+        assert_eq!(code_at(&secret, 59 / STEP_SECS), 287082);
+        assert_eq!(code_at(&secret, 1111111109 / STEP_SECS), 81804);
+        assert_eq!(code_at(&secret, 2000000000 / STEP_SECS), 279037);
+    }
+
+    #[test]
+    fn accepts_drift_and_rejects_replay() {
+        let totp = verifier();
+        assert!(totp.is_required("alice"));
+        assert!(!totp.is_required("bob"));
+
+        // Code from the previous step, still within the drift window.
+        assert!(totp.verify("alice", "081804", 1111111109 + 30).is_ok());
+        assert!(totp.verify("alice", "081804", 1111111109 + 30).is_err());
+        assert!(totp.verify("alice", "000000", 1111111109).is_err());
+        assert!(totp.verify("alice", "81804", 1111111109).is_err());
+        assert!(totp.verify("bob", "081804", 1111111109).is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_secret() {
+        let secrets = HashMap::from([("alice".to_string(), "not base32!".to_string())]);
+        assert!(TotpVerifier::new(&secrets).is_err());
+    }
+}
@@ -1,6 +1,7 @@
 //! Server configuration: TOML file + CLI overrides.
 
 use serde::Deserialize;
+use std::net::SocketAddr;
 use std::path::{Path, PathBuf};
 use tracing::info;
 use wsh_core::WshResult;
@@ -14,6 +15,18 @@ pub struct ConfigFile {
     pub auth: AuthSection,
     #[serde(default)]
     pub gateway: GatewaySection,
+    #[serde(default)]
+    pub recording: RecordingSection,
+    #[serde(default)]
+    pub persistence: PersistenceSection,
+    #[serde(default)]
+    pub metrics: MetricsSection,
+    #[serde(default)]
+    pub audit: AuditSection,
+    #[serde(default)]
+    pub isolation: IsolationSection,
+    #[serde(default)]
+    pub limits: LimitsSection,
 }
 
 /// `[server]` section of the config TOML.
@@ -31,6 +44,18 @@ pub struct ServerSection {
     pub session_ttl: u64,
     #[serde(default = "default_idle_timeout")]
     pub idle_timeout: u64,
+    /// Per-session scrollback kept for replay on reattach, in bytes.
+    #[serde(default = "default_scrollback_size")]
+    pub scrollback_size: usize,
+    /// Seconds to keep serving connected clients after a shutdown signal
+    /// before closing them. New connections are refused meanwhile; `0`
+    /// exits immediately.
+    #[serde(default = "default_drain_timeout")]
+    pub drain_timeout: u64,
+    /// Compress PTY output and file transfer chunks for clients that offer
+    /// a codec (`compress:<name>`) in HELLO.
+    #[serde(default = "default_true")]
+    pub compression: bool,
 }
 
 impl Default for ServerSection {
@@ -42,6 +67,9 @@ impl Default for ServerSection {
             max_sessions: default_max_sessions(),
             session_ttl: default_session_ttl(),
             idle_timeout: default_idle_timeout(),
+            scrollback_size: default_scrollback_size(),
+            drain_timeout: default_drain_timeout(),
+            compression: true,
         }
     }
 }
@@ -61,6 +89,16 @@ impl Default for ServerSection {
 /// alice = "sha256:e3b0c44298fc..."  # SHA-256 hex digest of password
 /// bob = "sha256:5e884898da28..."
 /// ```
+///
+/// # Two-Factor Authentication
+///
+/// Users listed in `totp_secrets` must also enter a TOTP code from an
+/// authenticator app after their key or password is accepted:
+///
+/// ```toml
+/// [auth.totp_secrets]
+/// alice = "JBSWY3DPEHPK3PXP"  # base32 secret shared with alice's app
+/// ```
 #[derive(Debug, Clone, Deserialize)]
 pub struct AuthSection {
     #[serde(default = "default_true")]
@@ -70,6 +108,28 @@ pub struct AuthSection {
     /// Username → "sha256:<hex>" password hash pairs.
     #[serde(default)]
     pub password_hashes: std::collections::HashMap<String, String>,
+    /// Username → base32 TOTP secret for users who need a second factor.
+    #[serde(default)]
+    pub totp_secrets: std::collections::HashMap<String, String>,
+    /// Whether clients may forward their key agent (`wsh connect -A`).
+    /// Individual keys can still opt out with `no-agent-forwarding`.
+    #[serde(default = "default_true")]
+    pub allow_agent_forwarding: bool,
+    /// Host private key (`WSH PRIVATE KEY` PEM, as written by `wsh keygen`)
+    /// advertised to clients for known_hosts and used to prove ownership of
+    /// `host_certificate`.
+    #[serde(default)]
+    pub host_key: Option<String>,
+    /// Host key being replaced by `host_key`. While set, the old key signs
+    /// the new one in every handshake so clients that pinned it update
+    /// known_hosts automatically; remove it once clients have reconnected.
+    #[serde(default)]
+    pub previous_host_key: Option<String>,
+    /// CA-signed host certificate (`wsh keygen --sign --host`) presented to
+    /// clients, letting them trust this server through an `@cert-authority`
+    /// known_hosts line instead of trust-on-first-use.
+    #[serde(default)]
+    pub host_certificate: Option<String>,
 }
 
 impl Default for AuthSection {
@@ -78,6 +138,11 @@ impl Default for AuthSection {
             allow_pubkey: true,
             allow_password: true,
             password_hashes: std::collections::HashMap::new(),
+            totp_secrets: std::collections::HashMap::new(),
+            allow_agent_forwarding: true,
+            host_key: None,
+            previous_host_key: None,
+            host_certificate: None,
         }
     }
 }
@@ -95,6 +160,8 @@ impl Default for AuthSection {
 /// allowed_destinations = ["example.com", "10.0.0.0/8:443", "*"]
 /// max_connections = 100
 /// enable_reverse_tunnels = true
+/// allow_unix_sockets = true
+/// allowed_unix_sockets = ["/var/run/docker.sock", "/run/user/1000/*"]
 /// ```
 #[derive(Debug, Clone, Deserialize)]
 pub struct GatewaySection {
@@ -127,6 +194,23 @@ pub struct GatewaySection {
     /// Default: `true`.
     #[serde(default = "default_true")]
     pub enable_reverse_tunnels: bool,
+    /// Whether clients may forward unix-domain sockets on the server
+    /// (`OpenUnix`, and `ListenUnix` when reverse tunnels are enabled),
+    /// e.g. a remote docker socket. Only paths in
+    /// [`allowed_unix_sockets`](Self::allowed_unix_sockets) are reachable.
+    ///
+    /// Default: `false`.
+    #[serde(default)]
+    pub allow_unix_sockets: bool,
+    /// Socket paths that may be opened or listened on when
+    /// `allow_unix_sockets` is set. Entries are absolute paths, or a
+    /// directory followed by `/*` for any socket directly inside it.
+    /// An empty list allows no sockets. With `[isolation]` enabled, client
+    /// paths are re-rooted into the workspace before this check.
+    ///
+    /// Default: `[]`.
+    #[serde(default)]
+    pub allowed_unix_sockets: Vec<String>,
 }
 
 impl Default for GatewaySection {
@@ -136,10 +220,337 @@ impl Default for GatewaySection {
             allowed_destinations: default_gateway_destinations(),
             max_connections: default_gateway_max_connections(),
             enable_reverse_tunnels: true,
+            allow_unix_sockets: false,
+            allowed_unix_sockets: Vec::new(),
+        }
+    }
+}
+
+/// `[recording]` section of the config TOML.
+///
+/// Server-side session recording is off by default. When enabled, PTY
+/// output, resizes and exit codes are written to `<dir>/<session_id>.jsonl`
+/// and can be exported (as JSONL or asciicast v2) with `RECORDING_EXPORT`.
+///
+/// # TOML Example
+///
+/// ```toml
+/// [recording]
+/// enabled = true
+/// dir = "~/.wsh/recordings"
+/// retention_days = 30
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+pub struct RecordingSection {
+    /// Whether PTY sessions are recorded.
+    ///
+    /// Default: `false`.
+    #[serde(default)]
+    pub enabled: bool,
+    /// Directory recordings are written to.
+    ///
+    /// Default: `"~/.wsh/recordings"`.
+    #[serde(default = "default_recording_dir")]
+    pub dir: String,
+    /// Delete recordings older than this many days. `0` keeps them forever.
+    ///
+    /// Default: `0`.
+    #[serde(default)]
+    pub retention_days: u64,
+}
+
+impl Default for RecordingSection {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dir: default_recording_dir(),
+            retention_days: 0,
+        }
+    }
+}
+
+/// `[persistence]` section of the config TOML.
+///
+/// Keeps session metadata (and optionally scrollback) on disk so
+/// `wsh attach` works after a server restart. The shell process itself
+/// does not survive; reattaching respawns it with the original command and
+/// terminal size and replays the saved scrollback.
+///
+/// # TOML Example
+///
+/// ```toml
+/// [persistence]
+/// enabled = true
+/// dir = "~/.wsh/sessions"
+/// scrollback = true
+/// ttl = 86400
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+pub struct PersistenceSection {
+    /// Whether session state is written to disk.
+    ///
+    /// Default: `false`.
+    #[serde(default)]
+    pub enabled: bool,
+    /// Directory session state is written to.
+    ///
+    /// Default: `"~/.wsh/sessions"`.
+    #[serde(default = "default_persistence_dir")]
+    pub dir: String,
+    /// Whether scrollback is saved along with session metadata.
+    ///
+    /// Default: `true`.
+    #[serde(default = "default_true")]
+    pub scrollback: bool,
+    /// How long (seconds) a saved session waits for reattach before it is
+    /// discarded.
+    ///
+    /// Default: `86400` (one day).
+    #[serde(default = "default_session_ttl")]
+    pub ttl: u64,
+}
+
+impl Default for PersistenceSection {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dir: default_persistence_dir(),
+            scrollback: true,
+            ttl: default_session_ttl(),
+        }
+    }
+}
+
+/// `[metrics]` section of the config TOML.
+///
+/// Serves Prometheus metrics (active sessions, auth failures, bytes per
+/// transport, relay peers, handshake latency) over plain HTTP at
+/// `http://<bind>/metrics`. Off by default; keep `bind` on loopback or a
+/// private interface, since the endpoint is unauthenticated.
+///
+/// # TOML Example
+///
+/// ```toml
+/// [metrics]
+/// enabled = true
+/// bind = "127.0.0.1:9422"
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+pub struct MetricsSection {
+    /// Whether the metrics endpoint is served.
+    ///
+    /// Default: `false`.
+    #[serde(default)]
+    pub enabled: bool,
+    /// Address the metrics endpoint listens on.
+    ///
+    /// Default: `"127.0.0.1:9422"`.
+    #[serde(default = "default_metrics_bind")]
+    pub bind: String,
+}
+
+impl Default for MetricsSection {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bind: default_metrics_bind(),
+        }
+    }
+}
+
+/// `[audit]` section of the config TOML.
+///
+/// Writes an audit trail of authentication attempts, session open/close
+/// (with exec command lines) and file transfers as JSONL, including the key
+/// fingerprint behind each action. Off by default.
+///
+/// # TOML Example
+///
+/// ```toml
+/// [audit]
+/// enabled = true
+/// path = "/var/log/wsh/audit.jsonl"
+/// max_size = 10485760
+/// max_files = 5
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+pub struct AuditSection {
+    /// Whether the audit log is written.
+    ///
+    /// Default: `false`.
+    #[serde(default)]
+    pub enabled: bool,
+    /// File the audit log is appended to.
+    ///
+    /// Default: `"~/.wsh/audit.jsonl"`.
+    #[serde(default = "default_audit_path")]
+    pub path: String,
+    /// Rotate once the file exceeds this many bytes. `0` never rotates.
+    ///
+    /// Default: `10485760` (10 MiB).
+    #[serde(default = "default_audit_max_size")]
+    pub max_size: u64,
+    /// Number of rotated files (`audit.jsonl.1`, `.2`, …) to keep.
+    ///
+    /// Default: `5`.
+    #[serde(default = "default_audit_max_files")]
+    pub max_files: usize,
+}
+
+impl Default for AuditSection {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            path: default_audit_path(),
+            max_size: default_audit_max_size(),
+            max_files: default_audit_max_files(),
         }
     }
 }
 
+fn default_audit_path() -> String {
+    "~/.wsh/audit.jsonl".to_string()
+}
+fn default_audit_max_size() -> u64 {
+    10 * 1024 * 1024
+}
+fn default_audit_max_files() -> usize {
+    5
+}
+
+/// `[isolation]` section of the config TOML.
+///
+/// Gives every authenticated user a workspace directory under `root` and
+/// confines file transfers, MCP host tools, forwarded unix socket paths and
+/// the shell's starting directory to it. Key fingerprints are mapped to
+/// system users in `users` (full fingerprint or a prefix of at least 16
+/// hex digits; no key may be a prefix of another). Unmapped keys get `key-<fingerprint prefix>`; password
+/// logins use their username. Off by default.
+///
+/// Shells, exec channels and MCP `exec` run as the user's system account.
+/// A server running as root switches to it; otherwise only users that are
+/// the server's own account may start processes. Users without a usable
+/// account still get their workspace for files.
+///
+/// # TOML Example
+///
+/// ```toml
+/// [isolation]
+/// enabled = true
+/// root = "/srv/wsh/home"
+///
+/// [isolation.users]
+/// "3f2a9c01d4e5b6a7" = "alice"
+/// "b71e0c92aa4f3d58" = "ci"
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+pub struct IsolationSection {
+    /// Whether per-user workspaces are enforced.
+    ///
+    /// Default: `false`.
+    #[serde(default)]
+    pub enabled: bool,
+    /// Directory holding one workspace per user.
+    ///
+    /// Default: `"~/.wsh/home"`.
+    #[serde(default = "default_isolation_root")]
+    pub root: String,
+    /// Key fingerprint (or prefix) → system user, which also names the
+    /// workspace directory under `root`.
+    ///
+    /// Default: empty.
+    #[serde(default)]
+    pub users: std::collections::HashMap<String, String>,
+}
+
+impl Default for IsolationSection {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            root: default_isolation_root(),
+            users: std::collections::HashMap::new(),
+        }
+    }
+}
+
+fn default_isolation_root() -> String {
+    "~/.wsh/home".to_string()
+}
+
+/// `[limits]` section of the config TOML.
+///
+/// Caps how much a single key, IP or connection can use. A key's own
+/// `max-sessions=` option in `authorized_keys` takes precedence over
+/// `max_sessions_per_key`.
+///
+/// # TOML Example
+///
+/// ```toml
+/// [limits]
+/// max_sessions_per_key = 10
+/// auth_attempts_per_minute = 5
+/// auth_ban_secs = 600
+/// max_channels_per_connection = 32
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+pub struct LimitsSection {
+    /// Concurrent sessions one key fingerprint may own. `0` = unlimited.
+    ///
+    /// Default: `0`.
+    #[serde(default)]
+    pub max_sessions_per_key: usize,
+    /// Failed auth attempts allowed per IP address per minute; successful
+    /// logins do not count.
+    ///
+    /// Default: `5`.
+    #[serde(default = "default_auth_attempts_per_minute")]
+    pub auth_attempts_per_minute: u32,
+    /// Seconds an IP that reaches `auth_attempts_per_minute` failures is
+    /// refused.
+    /// `0` only rate-limits, without a ban.
+    ///
+    /// Default: `300`.
+    #[serde(default = "default_auth_ban_secs")]
+    pub auth_ban_secs: u64,
+    /// Channels one client connection may hold open: PTY/exec sessions
+    /// (opened, resumed or attached), gateway connections, reverse tunnel
+    /// listeners, file transfers and running MCP tool calls. `0` = unlimited.
+    ///
+    /// Default: `0`.
+    #[serde(default)]
+    pub max_channels_per_connection: usize,
+}
+
+impl Default for LimitsSection {
+    fn default() -> Self {
+        Self {
+            max_sessions_per_key: 0,
+            auth_attempts_per_minute: default_auth_attempts_per_minute(),
+            auth_ban_secs: default_auth_ban_secs(),
+            max_channels_per_connection: 0,
+        }
+    }
+}
+
+fn default_auth_attempts_per_minute() -> u32 {
+    5
+}
+fn default_auth_ban_secs() -> u64 {
+    300
+}
+
+fn default_metrics_bind() -> String {
+    "127.0.0.1:9422".to_string()
+}
+
+fn default_persistence_dir() -> String {
+    "~/.wsh/sessions".to_string()
+}
+
+fn default_recording_dir() -> String {
+    "~/.wsh/recordings".to_string()
+}
+
 fn default_gateway_destinations() -> Vec<String> {
     vec!["*".to_string()]
 }
@@ -165,6 +576,12 @@ fn default_session_ttl() -> u64 {
 fn default_idle_timeout() -> u64 {
     3600
 }
+fn default_scrollback_size() -> usize {
+    256 * 1024
+}
+fn default_drain_timeout() -> u64 {
+    30
+}
 fn default_true() -> bool {
     true
 }
@@ -187,12 +604,26 @@ pub struct ServerConfig {
     pub session_ttl: u64,
     /// Idle timeout in seconds before a session is reaped.
     pub idle_timeout: u64,
+    /// Per-session scrollback size in bytes.
+    pub scrollback_size: usize,
+    /// Drain period after a shutdown signal, in seconds.
+    /// See [`ServerSection::drain_timeout`].
+    pub drain_timeout: u64,
+    /// Whether payload compression is negotiated. See [`ServerSection::compression`].
+    pub compression: bool,
     /// Whether the relay (peer-to-peer forwarding) subsystem is enabled.
     pub enable_relay: bool,
     /// Whether public-key authentication is accepted.
     pub allow_pubkey: bool,
     /// Whether password authentication is accepted.
     pub allow_password: bool,
+    /// Whether key agent forwarding is offered. See [`AuthSection::allow_agent_forwarding`].
+    pub allow_agent_forwarding: bool,
+    /// Host key and certificate paths. See [`AuthSection::host_certificate`].
+    pub host_key_path: Option<PathBuf>,
+    pub host_certificate_path: Option<PathBuf>,
+    /// Host key being rotated out. See [`AuthSection::previous_host_key`].
+    pub previous_host_key_path: Option<PathBuf>,
     /// Whether the gateway subsystem (TCP/UDP/DNS/listeners) is enabled.
     /// Corresponds to `[gateway] enabled` in the TOML config.
     pub gateway_enabled: bool,
@@ -202,8 +633,53 @@ pub struct ServerConfig {
     pub gateway_max_connections: usize,
     /// Whether reverse tunnel listeners are allowed. See [`GatewaySection::enable_reverse_tunnels`].
     pub gateway_enable_reverse_tunnels: bool,
+    /// Whether unix sockets may be forwarded. See [`GatewaySection::allow_unix_sockets`].
+    pub gateway_allow_unix_sockets: bool,
+    /// Socket path allowlist. See [`GatewaySection::allowed_unix_sockets`].
+    pub gateway_allowed_unix_sockets: Vec<String>,
     /// Username → "sha256:<hex>" password hash pairs for password auth.
     pub password_hashes: std::collections::HashMap<String, String>,
+    /// Username → base32 TOTP secret. See [`AuthSection::totp_secrets`].
+    pub totp_secrets: std::collections::HashMap<String, String>,
+    /// Whether server-side session recording is enabled. See [`RecordingSection::enabled`].
+    pub recording_enabled: bool,
+    /// Directory for session recordings (tilde-expanded).
+    pub recording_dir: PathBuf,
+    /// Recording retention in days (`0` = forever). See [`RecordingSection::retention_days`].
+    pub recording_retention_days: u64,
+    /// Whether session state persists across restarts. See [`PersistenceSection::enabled`].
+    pub persist_sessions: bool,
+    /// Directory for persisted session state (tilde-expanded).
+    pub persist_dir: PathBuf,
+    /// Whether scrollback is persisted. See [`PersistenceSection::scrollback`].
+    pub persist_scrollback: bool,
+    /// Seconds a persisted session waits for reattach. See [`PersistenceSection::ttl`].
+    pub persist_ttl: u64,
+    /// Address of the Prometheus endpoint, or `None` when disabled.
+    /// See [`MetricsSection`].
+    pub metrics_bind: Option<SocketAddr>,
+    /// Audit log file (tilde-expanded), or `None` when disabled.
+    /// See [`AuditSection`].
+    pub audit_path: Option<PathBuf>,
+    /// Audit log rotation size in bytes. See [`AuditSection::max_size`].
+    pub audit_max_size: u64,
+    /// Rotated audit logs to keep. See [`AuditSection::max_files`].
+    pub audit_max_files: usize,
+    /// Root of the per-user workspaces (tilde-expanded), or `None` when
+    /// isolation is disabled. See [`IsolationSection`].
+    pub isolation_root: Option<PathBuf>,
+    /// Key fingerprint (or prefix) → workspace name. See [`IsolationSection::users`].
+    pub isolation_users: std::collections::HashMap<String, String>,
+    /// Sessions per key fingerprint (`0` = unlimited).
+    /// See [`LimitsSection::max_sessions_per_key`].
+    pub max_sessions_per_key: usize,
+    /// Failed auth attempts per IP per minute. See [`LimitsSection::auth_attempts_per_minute`].
+    pub auth_attempts_per_minute: u32,
+    /// Ban length for IPs over the auth limit. See [`LimitsSection::auth_ban_secs`].
+    pub auth_ban_secs: u64,
+    /// Channels per connection (`0` = unlimited).
+    /// See [`LimitsSection::max_channels_per_connection`].
+    pub max_channels_per_connection: usize,
 }
 
 impl ServerConfig {
@@ -252,6 +728,12 @@ impl ServerConfig {
                     server: ServerSection::default(),
                     auth: AuthSection::default(),
                     gateway: GatewaySection::default(),
+                    recording: RecordingSection::default(),
+                    persistence: PersistenceSection::default(),
+                    metrics: MetricsSection::default(),
+                    audit: AuditSection::default(),
+                    isolation: IsolationSection::default(),
+                    limits: LimitsSection::default(),
                 }
             }
         } else {
@@ -259,6 +741,12 @@ impl ServerConfig {
                 server: ServerSection::default(),
                 auth: AuthSection::default(),
                 gateway: GatewaySection::default(),
+                recording: RecordingSection::default(),
+                persistence: PersistenceSection::default(),
+                metrics: MetricsSection::default(),
+                audit: AuditSection::default(),
+                isolation: IsolationSection::default(),
+                limits: LimitsSection::default(),
             }
         };
 
@@ -273,6 +761,16 @@ impl ServerConfig {
         let max_sessions = cli_max_sessions.unwrap_or(file_config.server.max_sessions);
         let session_ttl = cli_session_ttl.unwrap_or(file_config.server.session_ttl);
         let idle_timeout = cli_idle_timeout.unwrap_or(file_config.server.idle_timeout);
+        let metrics_bind = if file_config.metrics.enabled {
+            Some(file_config.metrics.bind.parse().map_err(|e| {
+                wsh_core::WshError::Other(format!(
+                    "invalid [metrics] bind {:?}: {e}",
+                    file_config.metrics.bind
+                ))
+            })?)
+        } else {
+            None
+        };
 
         Ok(Self {
             port,
@@ -281,14 +779,55 @@ impl ServerConfig {
             max_sessions,
             session_ttl,
             idle_timeout,
+            scrollback_size: file_config.server.scrollback_size,
+            drain_timeout: file_config.server.drain_timeout,
+            compression: file_config.server.compression,
             enable_relay: cli_enable_relay,
             allow_pubkey: file_config.auth.allow_pubkey,
             allow_password: file_config.auth.allow_password,
+            allow_agent_forwarding: file_config.auth.allow_agent_forwarding,
+            host_key_path: file_config.auth.host_key.as_deref().map(expand_tilde_str),
+            previous_host_key_path: file_config
+                .auth
+                .previous_host_key
+                .as_deref()
+                .map(expand_tilde_str),
+            host_certificate_path: file_config
+                .auth
+                .host_certificate
+                .as_deref()
+                .map(expand_tilde_str),
             gateway_enabled: file_config.gateway.enabled,
             gateway_allowed_destinations: file_config.gateway.allowed_destinations,
             gateway_max_connections: file_config.gateway.max_connections,
             gateway_enable_reverse_tunnels: file_config.gateway.enable_reverse_tunnels,
+            gateway_allow_unix_sockets: file_config.gateway.allow_unix_sockets,
+            gateway_allowed_unix_sockets: file_config.gateway.allowed_unix_sockets,
             password_hashes: file_config.auth.password_hashes,
+            totp_secrets: file_config.auth.totp_secrets,
+            recording_enabled: file_config.recording.enabled,
+            recording_dir: expand_tilde_str(&file_config.recording.dir),
+            recording_retention_days: file_config.recording.retention_days,
+            persist_sessions: file_config.persistence.enabled,
+            persist_dir: expand_tilde_str(&file_config.persistence.dir),
+            persist_scrollback: file_config.persistence.scrollback,
+            persist_ttl: file_config.persistence.ttl,
+            metrics_bind,
+            audit_path: file_config
+                .audit
+                .enabled
+                .then(|| expand_tilde_str(&file_config.audit.path)),
+            audit_max_size: file_config.audit.max_size,
+            audit_max_files: file_config.audit.max_files,
+            isolation_root: file_config
+                .isolation
+                .enabled
+                .then(|| expand_tilde_str(&file_config.isolation.root)),
+            isolation_users: file_config.isolation.users,
+            max_sessions_per_key: file_config.limits.max_sessions_per_key,
+            auth_attempts_per_minute: file_config.limits.auth_attempts_per_minute,
+            auth_ban_secs: file_config.limits.auth_ban_secs,
+            max_channels_per_connection: file_config.limits.max_channels_per_connection,
         })
     }
 }
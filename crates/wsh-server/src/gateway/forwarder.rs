@@ -1,14 +1,17 @@
-//! TCP/UDP forwarding — handles `OpenTcp` / `OpenUdp` / `ResolveDns` by
-//! connecting to remote hosts, relaying data bidirectionally, and resolving
-//! hostnames.
+//! TCP/UDP forwarding — handles `OpenTcp` / `OpenUdp` / `OpenUnix` /
+//! `ResolveDns` by connecting to remote hosts or local unix-domain sockets,
+//! relaying data bidirectionally, and resolving hostnames.
 //!
 //! Each outbound connection is tracked by `gateway_id` and can be cancelled
 //! via the [`GatewayForwarder::close`] method, which sends a signal through
-//! an `mpsc` channel to the spawned relay task.
+//! an `mpsc` channel to the spawned relay task. `TcpForward` channels (used
+//! by `wsh -L` / `-R`) are relayed the same way but tracked by their
+//! server-assigned `channel_id`, and closed via
+//! [`GatewayForwarder::close_channel`].
 
 use super::policy::GatewayPolicyEnforcer;
 use super::resolver::DnsResolver;
-use super::GatewayEvent;
+use super::{GatewayEvent, RelayStream, RelayTarget};
 use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
@@ -29,12 +32,16 @@ const UDP_IDLE_TIMEOUT: Duration = Duration::from_secs(60);
 pub struct GatewayForwarder {
     /// Shared policy enforcer for destination checks and connection counting.
     policy: Arc<GatewayPolicyEnforcer>,
-    /// Active TCP connections: `gateway_id` to cancel-signal sender.
+    /// Active TCP and unix socket connections: `gateway_id` to cancel-signal sender.
     tcp_connections: Mutex<HashMap<u32, mpsc::Sender<()>>>,
     /// Active UDP sockets: `gateway_id` to cancel-signal sender.
     udp_connections: Mutex<HashMap<u32, mpsc::Sender<()>>>,
     /// Write channels for client→TCP data: `gateway_id` to data sender.
     write_channels: Mutex<HashMap<u32, mpsc::Sender<Vec<u8>>>>,
+    /// Active `TcpForward` channels: `channel_id` to cancel-signal sender.
+    channel_connections: Mutex<HashMap<u32, mpsc::Sender<()>>>,
+    /// Write channels for `TcpForward` channels: `channel_id` to data sender.
+    channel_writes: Mutex<HashMap<u32, mpsc::Sender<Vec<u8>>>>,
 }
 
 impl GatewayForwarder {
@@ -50,6 +57,8 @@ impl GatewayForwarder {
             tcp_connections: Mutex::new(HashMap::new()),
             udp_connections: Mutex::new(HashMap::new()),
             write_channels: Mutex::new(HashMap::new()),
+            channel_connections: Mutex::new(HashMap::new()),
+            channel_writes: Mutex::new(HashMap::new()),
         }
     }
 
@@ -85,39 +94,207 @@ impl GatewayForwarder {
                     .map(|a| a.ip().to_string())
                     .unwrap_or_default();
 
-                let guard = self.policy.acquire();
+                info!(gateway_id, addr = %addr, "TCP connection established");
+                self.spawn_relay(RelayTarget::Gateway(gateway_id), stream, data_tx, None)
+                    .await;
 
-                // Create cancel channel
-                let (cancel_tx, cancel_rx) = mpsc::channel::<()>(1);
+                build_gateway_ok(gateway_id, Some(&resolved_addr))
+            }
+            Err(e) => {
+                warn!(gateway_id, addr = %addr, error = %e, "TCP connect failed");
+                build_gateway_fail(gateway_id, 1, &e.to_string()) // CONNECTION_REFUSED
+            }
+        }
+    }
+
+    /// Handle an `OpenUnix` message: policy check, connect to the unix-domain
+    /// socket at `path`, spawn relay, respond.
+    ///
+    /// Returns [`MsgType::GatewayOk`] or [`MsgType::GatewayFail`]; data then
+    /// flows through `GatewayData` exactly as for a TCP connection.
+    ///
+    /// # Arguments
+    ///
+    /// * `gateway_id` - Client-assigned identifier for this gateway connection.
+    /// * `path` - Absolute path of the socket on this host.
+    /// * `data_tx` - Channel for sending socket→client data events back to the session loop.
+    #[cfg(unix)]
+    pub async fn handle_open_unix(
+        &self,
+        gateway_id: u32,
+        path: &str,
+        data_tx: mpsc::Sender<GatewayEvent>,
+    ) -> Envelope {
+        if let Err(reason) = self.policy.check_unix(path) {
+            return build_gateway_fail(gateway_id, 4, &reason); // POLICY_DENIED
+        }
+
+        match tokio::net::UnixStream::connect(path).await {
+            Ok(stream) => {
+                info!(gateway_id, path, "unix socket connection established");
+                self.spawn_relay(RelayTarget::Gateway(gateway_id), stream, data_tx, None)
+                    .await;
+                build_gateway_ok(gateway_id, None)
+            }
+            Err(e) => {
+                warn!(gateway_id, path, error = %e, "unix socket connect failed");
+                build_gateway_fail(gateway_id, 1, &e.to_string()) // CONNECTION_REFUSED
+            }
+        }
+    }
+
+    /// Unix socket forwarding needs unix domain sockets.
+    #[cfg(not(unix))]
+    pub async fn handle_open_unix(
+        &self,
+        gateway_id: u32,
+        _path: &str,
+        _data_tx: mpsc::Sender<GatewayEvent>,
+    ) -> Envelope {
+        build_gateway_fail(gateway_id, 4, "unix sockets are not supported on this host")
+    }
+
+    /// Handle an `Open` of kind `TcpForward`: in a spawned task, policy
+    /// check, connect to `host:port`, and relay the connection as channel
+    /// `channel_id`.
+    ///
+    /// The outcome is reported through `data_tx` as
+    /// [`GatewayEvent::ChannelOpened`] or [`GatewayEvent::ChannelOpenFailed`].
+    ///
+    /// # Arguments
+    ///
+    /// * `channel_id` - Channel ID allocated by the server for this forward.
+    /// * `host` - Target hostname or IP address.
+    /// * `port` - Target TCP port.
+    /// * `data_tx` - Channel for sending TCP→client data events back to the session loop.
+    pub fn handle_open_forward(
+        self: &Arc<Self>,
+        channel_id: u32,
+        host: String,
+        port: u16,
+        data_tx: mpsc::Sender<GatewayEvent>,
+    ) {
+        let forwarder = self.clone();
+        tokio::spawn(async move {
+            match forwarder.connect_forward(channel_id, &host, port).await {
+                Ok(stream) => {
+                    forwarder
+                        .spawn_relay(
+                            RelayTarget::Channel(channel_id),
+                            stream,
+                            data_tx,
+                            Some(GatewayEvent::ChannelOpened { channel_id }),
+                        )
+                        .await;
+                }
+                Err(reason) => {
+                    let _ = data_tx
+                        .send(GatewayEvent::ChannelOpenFailed { channel_id, reason })
+                        .await;
+                }
+            }
+        });
+    }
+
+    /// Policy check and connect for a `TcpForward` channel.
+    async fn connect_forward(
+        &self,
+        channel_id: u32,
+        host: &str,
+        port: u16,
+    ) -> Result<TcpStream, String> {
+        self.policy.check_connect(host, port)?;
+
+        let addr = format!("{}:{}", host, port);
+        match TcpStream::connect(&addr).await {
+            Ok(stream) => {
+                info!(channel_id, addr = %addr, "TCP forward established");
+                Ok(stream)
+            }
+            Err(e) => {
+                warn!(channel_id, addr = %addr, error = %e, "TCP forward connect failed");
+                Err(e.to_string())
+            }
+        }
+    }
+
+    /// Relay an already-connected stream as `TcpForward` channel
+    /// `channel_id` (used by the listener for accepted inbound connections).
+    pub async fn relay_channel<S>(
+        &self,
+        channel_id: u32,
+        stream: S,
+        data_tx: mpsc::Sender<GatewayEvent>,
+    ) where
+        S: RelayStream + 'static,
+    {
+        self.spawn_relay(RelayTarget::Channel(channel_id), stream, data_tx, None)
+            .await;
+    }
+
+    /// Track a connected stream under `target` and relay it in a spawned
+    /// task until either side closes it or [`close`](Self::close) /
+    /// [`close_channel`](Self::close_channel) is called.
+    ///
+    /// `opened`, if given, is queued on `data_tx` once the stream is tracked
+    /// and before the relay can report any data; if the session loop is
+    /// gone by then, the stream is dropped instead.
+    async fn spawn_relay<S>(
+        &self,
+        target: RelayTarget,
+        stream: S,
+        data_tx: mpsc::Sender<GatewayEvent>,
+        opened: Option<GatewayEvent>,
+    ) where
+        S: RelayStream + 'static,
+    {
+        let guard = self.policy.acquire();
+
+        // Create cancel and write (client→stream) channels
+        let (cancel_tx, cancel_rx) = mpsc::channel::<()>(1);
+        let (write_tx, write_rx) = mpsc::channel::<Vec<u8>>(64);
+        match target {
+            RelayTarget::Gateway(gateway_id) => {
                 self.tcp_connections
                     .lock()
                     .await
                     .insert(gateway_id, cancel_tx);
-
-                // Create write channel (client→TCP)
-                let (write_tx, write_rx) = mpsc::channel::<Vec<u8>>(64);
                 self.write_channels
                     .lock()
                     .await
                     .insert(gateway_id, write_tx);
-
-                info!(gateway_id, addr = %addr, "TCP connection established");
-
-                // Spawn bidirectional relay task — guard lives until relay ends
-                let gateway_id_copy = gateway_id;
-                tokio::spawn(async move {
-                    let _guard = guard; // keep alive for connection counting
-                    Self::tcp_relay(stream, cancel_rx, write_rx, data_tx, gateway_id_copy).await;
-                    debug!(gateway_id = gateway_id_copy, "TCP relay ended");
-                });
-
-                build_gateway_ok(gateway_id, Some(&resolved_addr))
             }
-            Err(e) => {
-                warn!(gateway_id, addr = %addr, error = %e, "TCP connect failed");
-                build_gateway_fail(gateway_id, 1, &e.to_string()) // CONNECTION_REFUSED
+            RelayTarget::Channel(channel_id) => {
+                self.channel_connections
+                    .lock()
+                    .await
+                    .insert(channel_id, cancel_tx);
+                self.channel_writes
+                    .lock()
+                    .await
+                    .insert(channel_id, write_tx);
+            }
+        }
+
+        if let Some(event) = opened {
+            if data_tx.send(event).await.is_err() {
+                debug!(?target, "session loop gone, dropping stream");
+                match target {
+                    RelayTarget::Gateway(gateway_id) => self.close(gateway_id).await,
+                    RelayTarget::Channel(channel_id) => {
+                        self.close_channel(channel_id).await;
+                    }
+                }
+                return;
             }
         }
+
+        // Spawn bidirectional relay task — guard lives until relay ends
+        tokio::spawn(async move {
+            let _guard = guard; // keep alive for connection counting
+            Self::stream_relay(stream, cancel_rx, write_rx, data_tx, target).await;
+            debug!(?target, "stream relay ended");
+        });
     }
 
     /// Handle an `OpenUdp` message: policy check, bind local socket, connect to remote,
@@ -251,55 +428,88 @@ impl GatewayForwarder {
         self.write_channels.lock().await.insert(gateway_id, tx);
     }
 
-    /// Bidirectional TCP relay (runs in a spawned task).
+    /// Forward `SessionData` from the client to a `TcpForward` channel.
+    ///
+    /// # Arguments
+    ///
+    /// * `channel_id` - The identifier of the forward channel.
+    /// * `data` - The payload bytes to forward to the remote TCP peer.
+    pub async fn handle_channel_data(&self, channel_id: u32, data: Vec<u8>) {
+        let channels = self.channel_writes.lock().await;
+        if let Some(tx) = channels.get(&channel_id) {
+            if tx.send(data).await.is_err() {
+                debug!(channel_id, "write channel closed, relay ended");
+            }
+        } else {
+            debug!(channel_id, "no write channel for forward channel");
+        }
+    }
+
+    /// Close a `TcpForward` channel by `channel_id`.
+    ///
+    /// Returns whether `channel_id` was a forward channel.
+    pub async fn close_channel(&self, channel_id: u32) -> bool {
+        let cancel = self.channel_connections.lock().await.remove(&channel_id);
+        self.channel_writes.lock().await.remove(&channel_id);
+        match cancel {
+            Some(tx) => {
+                let _ = tx.send(()).await;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Bidirectional stream relay for TCP and unix socket connections (runs
+    /// in a spawned task).
     ///
     /// Three concurrent branches:
     /// - **Cancel**: Shuts down the relay when the gateway connection is closed.
-    /// - **TCP→Client**: Reads from the remote TCP peer and sends `GatewayEvent::Data`
-    ///   through `data_tx` for forwarding to the wsh client.
-    /// - **Client→TCP**: Reads from `write_rx` (data sent by the client via `GatewayData`)
-    ///   and writes it to the remote TCP peer.
-    pub(super) async fn tcp_relay(
-        stream: TcpStream,
+    /// - **Stream→Client**: Reads from the peer and sends a data event for
+    ///   `target` through `data_tx` for forwarding to the wsh client.
+    /// - **Client→Stream**: Reads from `write_rx` (data sent by the client via
+    ///   `GatewayData`, or `SessionData` for a channel) and writes it to the peer.
+    pub(super) async fn stream_relay<S: RelayStream>(
+        stream: S,
         mut cancel_rx: mpsc::Receiver<()>,
         mut write_rx: mpsc::Receiver<Vec<u8>>,
         data_tx: mpsc::Sender<GatewayEvent>,
-        gateway_id: u32,
+        target: RelayTarget,
     ) {
-        let (mut read_half, mut write_half) = stream.into_split();
+        let (mut read_half, mut write_half) = tokio::io::split(stream);
         let mut buf = vec![0u8; 8192];
 
         loop {
             tokio::select! {
                 _ = cancel_rx.recv() => {
-                    debug!(gateway_id, "TCP relay cancelled");
+                    debug!(?target, "stream relay cancelled");
                     break;
                 }
                 result = read_half.read(&mut buf) => {
                     match result {
                         Ok(0) => {
-                            debug!(gateway_id, "TCP peer closed connection");
-                            let _ = data_tx.send(GatewayEvent::Closed { gateway_id }).await;
+                            debug!(?target, "peer closed connection");
+                            let _ = data_tx.send(target.closed()).await;
                             break;
                         }
                         Ok(n) => {
                             let chunk = buf[..n].to_vec();
-                            if data_tx.send(GatewayEvent::Data { gateway_id, data: chunk }).await.is_err() {
-                                debug!(gateway_id, "data_tx closed, ending relay");
+                            if data_tx.send(target.data(chunk)).await.is_err() {
+                                debug!(?target, "data_tx closed, ending relay");
                                 break;
                             }
                         }
                         Err(e) => {
-                            warn!(gateway_id, error = %e, "TCP read error");
-                            let _ = data_tx.send(GatewayEvent::Closed { gateway_id }).await;
+                            warn!(?target, error = %e, "stream read error");
+                            let _ = data_tx.send(target.closed()).await;
                             break;
                         }
                     }
                 }
                 Some(data) = write_rx.recv() => {
                     if let Err(e) = write_half.write_all(&data).await {
-                        warn!(gateway_id, error = %e, "TCP write error");
-                        let _ = data_tx.send(GatewayEvent::Closed { gateway_id }).await;
+                        warn!(?target, error = %e, "stream write error");
+                        let _ = data_tx.send(target.closed()).await;
                         break;
                     }
                 }
@@ -419,3 +629,47 @@ fn build_dns_result(gateway_id: u32, addresses: Vec<String>, ttl: Option<u32>) -
         }),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gateway::GatewayPolicy;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn relays_forward_channel_as_channel_events() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 5];
+            stream.read_exact(&mut buf).await.unwrap();
+            stream.write_all(&buf).await.unwrap();
+        });
+
+        let forwarder = Arc::new(GatewayForwarder::new(Arc::new(GatewayPolicyEnforcer::new(
+            GatewayPolicy::default(),
+        ))));
+        let (data_tx, mut data_rx) = mpsc::channel(8);
+        forwarder.handle_open_forward(7, "127.0.0.1".into(), port, data_tx);
+        assert!(matches!(
+            data_rx.recv().await.unwrap(),
+            GatewayEvent::ChannelOpened { channel_id: 7 }
+        ));
+        forwarder.handle_channel_data(7, b"hello".to_vec()).await;
+
+        match data_rx.recv().await.unwrap() {
+            GatewayEvent::ChannelData { channel_id, data } => {
+                assert_eq!(channel_id, 7);
+                assert_eq!(data, b"hello");
+            }
+            other => panic!("unexpected event: {other:?}"),
+        }
+        assert!(matches!(
+            data_rx.recv().await.unwrap(),
+            GatewayEvent::ChannelClosed { channel_id: 7 }
+        ));
+        assert!(forwarder.close_channel(7).await);
+        assert!(!forwarder.close_channel(7).await);
+    }
+}
@@ -1,24 +1,25 @@
 //! Reverse tunnel listener management.
 //!
-//! On `ListenRequest`: bind a TCP listener on the server, accept incoming
-//! connections, and notify the client via `InboundOpen` messages sent through
-//! an `mpsc` channel.
+//! On `ListenRequest` (or `ListenUnix`): bind a TCP (or unix-domain socket)
+//! listener on the server, accept incoming connections, and notify the client
+//! via `InboundOpen` messages sent through an `mpsc` channel.
 //!
 //! Each listener runs in its own spawned accept-loop task that can be
 //! cancelled via [`ReverseListenerManager::close_listener`].
 
 use super::forwarder::GatewayForwarder;
 use super::policy::GatewayPolicyEnforcer;
-use super::GatewayEvent;
+use super::{GatewayEvent, RelayStream, RelayTarget};
 use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::Arc;
-use tokio::net::{TcpListener, TcpStream};
+use tokio::net::TcpListener;
 use tokio::sync::{mpsc, Mutex};
 use tracing::{debug, info, warn};
 use wsh_core::messages::*;
 
-/// Manages reverse tunnel TCP listeners.
+/// Manages reverse tunnel TCP and unix socket listeners.
 ///
 /// Holds a shared [`GatewayPolicyEnforcer`] for listen-permission checks and
 /// connection counting, and a map of active listeners keyed by `listener_id`.
@@ -27,10 +28,11 @@ pub struct ReverseListenerManager {
     policy: Arc<GatewayPolicyEnforcer>,
     /// Active listeners: `listener_id` to [`ListenerEntry`].
     listeners: Mutex<HashMap<u32, ListenerEntry>>,
-    /// Pending inbound TCP connections awaiting `InboundAccept`: `channel_id` to `TcpStream`.
-    pending_connections: Arc<Mutex<HashMap<u32, TcpStream>>>,
-    /// Global channel_id counter shared across all listeners to prevent collisions
-    /// in `pending_connections`.
+    /// Pending inbound connections awaiting `InboundAccept`: `channel_id` to stream.
+    pending_connections: Arc<Mutex<HashMap<u32, Box<dyn RelayStream>>>>,
+    /// Global channel_id counter shared across all listeners, and with the
+    /// server's own channels, to prevent collisions in `pending_connections`
+    /// and among `TcpForward` channels.
     next_channel_id: Arc<AtomicU32>,
 }
 
@@ -43,8 +45,36 @@ struct ListenerEntry {
     /// accept loop to shut down.
     cancel_tx: mpsc::Sender<()>,
     /// The port the OS actually bound (may differ from the requested port
-    /// when the request specified port 0).
+    /// when the request specified port 0; `0` for unix sockets).
     actual_port: u16,
+    /// Socket file of a unix listener, removed when the listener closes.
+    socket_path: Option<PathBuf>,
+}
+
+/// A bound reverse tunnel listener.
+enum BoundListener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(tokio::net::UnixListener, String),
+}
+
+impl BoundListener {
+    /// Accept a connection, returning the stream with the peer's address and
+    /// port. Unix socket peers are unnamed, so they report the listening
+    /// path and port `0`.
+    async fn accept(&self) -> std::io::Result<(Box<dyn RelayStream>, String, u16)> {
+        match self {
+            BoundListener::Tcp(listener) => {
+                let (stream, peer) = listener.accept().await?;
+                Ok((Box::new(stream), peer.ip().to_string(), peer.port()))
+            }
+            #[cfg(unix)]
+            BoundListener::Unix(listener, path) => {
+                let (stream, _) = listener.accept().await?;
+                Ok((Box::new(stream), path.clone(), 0))
+            }
+        }
+    }
 }
 
 impl ReverseListenerManager {
@@ -54,12 +84,14 @@ impl ReverseListenerManager {
     ///
     /// * `policy` - Shared [`GatewayPolicyEnforcer`] used for listen-permission
     ///   checks and connection counting.
-    pub fn new(policy: Arc<GatewayPolicyEnforcer>) -> Self {
+    /// * `next_channel_id` - The server's channel_id counter, so an inbound
+    ///   connection accepted as a `TcpForward` channel gets a unique id.
+    pub fn new(policy: Arc<GatewayPolicyEnforcer>, next_channel_id: Arc<AtomicU32>) -> Self {
         Self {
             policy,
             listeners: Mutex::new(HashMap::new()),
             pending_connections: Arc::new(Mutex::new(HashMap::new())),
-            next_channel_id: Arc::new(AtomicU32::new(1)),
+            next_channel_id,
         }
     }
 
@@ -92,41 +124,20 @@ impl ReverseListenerManager {
             Ok(tcp_listener) => {
                 let actual_port = tcp_listener.local_addr().map(|a| a.port()).unwrap_or(port);
 
-                let guard = self.policy.acquire();
-
-                let (cancel_tx, cancel_rx) = mpsc::channel::<()>(1);
-                self.listeners.lock().await.insert(
-                    listener_id,
-                    ListenerEntry {
-                        cancel_tx,
-                        actual_port,
-                    },
-                );
-
                 info!(
                     listener_id,
                     addr = %addr,
                     actual_port,
                     "reverse tunnel listener started"
                 );
-
-                // Spawn accept loop — guard lives until loop ends
-                let listener_id_copy = listener_id;
-                let pending = self.pending_connections.clone();
-                let channel_counter = self.next_channel_id.clone();
-                tokio::spawn(async move {
-                    let _guard = guard; // keep alive for connection counting
-                    Self::accept_loop(
-                        tcp_listener,
-                        cancel_rx,
-                        listener_id_copy,
-                        inbound_tx,
-                        pending,
-                        channel_counter,
-                    )
-                    .await;
-                    debug!(listener_id = listener_id_copy, "accept loop ended");
-                });
+                self.start_listener(
+                    listener_id,
+                    BoundListener::Tcp(tcp_listener),
+                    actual_port,
+                    None,
+                    inbound_tx,
+                )
+                .await;
 
                 build_listen_ok(listener_id, actual_port)
             }
@@ -137,6 +148,105 @@ impl ReverseListenerManager {
         }
     }
 
+    /// Handle a `ListenUnix` message.
+    ///
+    /// Checks the policy, binds a unix-domain socket at `path`, spawns an
+    /// accept loop, and returns [`MsgType::ListenOk`] (with `actual_port`
+    /// `0`) or [`MsgType::ListenFail`]. An existing file at `path` is not
+    /// replaced. The socket file is removed when the listener closes.
+    ///
+    /// # Arguments
+    ///
+    /// * `listener_id` - Client-assigned identifier for this listener.
+    /// * `path` - Absolute path of the socket to create on this host.
+    /// * `inbound_tx` - Channel sender used to notify the session loop when a
+    ///   new inbound connection is accepted.
+    #[cfg(unix)]
+    pub async fn handle_listen_unix(
+        &self,
+        listener_id: u32,
+        path: &str,
+        inbound_tx: mpsc::Sender<InboundEvent>,
+    ) -> Envelope {
+        if let Err(reason) = self
+            .policy
+            .check_listen(0)
+            .and_then(|()| self.policy.check_unix(path))
+        {
+            return build_listen_fail(listener_id, &reason);
+        }
+
+        match tokio::net::UnixListener::bind(path) {
+            Ok(unix_listener) => {
+                info!(listener_id, path, "reverse tunnel unix listener started");
+                self.start_listener(
+                    listener_id,
+                    BoundListener::Unix(unix_listener, path.to_string()),
+                    0,
+                    Some(PathBuf::from(path)),
+                    inbound_tx,
+                )
+                .await;
+
+                build_listen_ok(listener_id, 0)
+            }
+            Err(e) => {
+                warn!(listener_id, path, error = %e, "unix listen bind failed");
+                build_listen_fail(listener_id, &e.to_string())
+            }
+        }
+    }
+
+    /// Unix socket listeners need unix domain sockets.
+    #[cfg(not(unix))]
+    pub async fn handle_listen_unix(
+        &self,
+        listener_id: u32,
+        _path: &str,
+        _inbound_tx: mpsc::Sender<InboundEvent>,
+    ) -> Envelope {
+        build_listen_fail(listener_id, "unix sockets are not supported on this host")
+    }
+
+    /// Register a bound listener under `listener_id` and spawn its accept loop.
+    async fn start_listener(
+        &self,
+        listener_id: u32,
+        listener: BoundListener,
+        actual_port: u16,
+        socket_path: Option<PathBuf>,
+        inbound_tx: mpsc::Sender<InboundEvent>,
+    ) {
+        let guard = self.policy.acquire();
+
+        let (cancel_tx, cancel_rx) = mpsc::channel::<()>(1);
+        self.listeners.lock().await.insert(
+            listener_id,
+            ListenerEntry {
+                cancel_tx,
+                actual_port,
+                socket_path,
+            },
+        );
+
+        // Spawn accept loop — guard lives until loop ends
+        let pending = self.pending_connections.clone();
+        let channel_counter = self.next_channel_id.clone();
+        tokio::spawn(async move {
+            let _guard = guard; // keep alive for connection counting
+            Self::accept_loop(
+                listener,
+                cancel_rx,
+                listener_id,
+                inbound_tx,
+                pending,
+                channel_counter,
+            )
+            .await;
+            debug!(listener_id, "accept loop ended");
+        });
+    }
+
     /// Close a listener by its `listener_id`.
     ///
     /// Sends a cancel signal to the accept loop and removes the entry from
@@ -149,6 +259,9 @@ impl ReverseListenerManager {
     pub async fn close_listener(&self, listener_id: u32) -> Option<Envelope> {
         if let Some(entry) = self.listeners.lock().await.remove(&listener_id) {
             let _ = entry.cancel_tx.send(()).await;
+            if let Some(path) = &entry.socket_path {
+                let _ = std::fs::remove_file(path);
+            }
             info!(listener_id, "reverse tunnel listener closed");
             Some(build_listen_close(listener_id))
         } else {
@@ -157,7 +270,7 @@ impl ReverseListenerManager {
     }
 
     /// Handle an `InboundReject` from the client: remove and drop the pending
-    /// TcpStream so it doesn't leak.
+    /// stream so it doesn't leak.
     ///
     /// # Arguments
     ///
@@ -176,31 +289,45 @@ impl ReverseListenerManager {
         }
     }
 
-    /// Handle an `InboundAccept` from the client: take the pending TcpStream,
+    /// Handle an `InboundAccept` from the client: take the pending stream,
     /// spawn a bidirectional relay, and register the write channel.
     ///
+    /// With a `gateway_id` the connection is relayed as that gateway
+    /// connection (`GatewayData`); without one it is relayed as a
+    /// `TcpForward` channel under `channel_id` (`SessionData`).
+    ///
     /// # Arguments
     ///
     /// * `channel_id` - The channel_id from the original `InboundOpen`.
-    /// * `gateway_id` - Client-assigned gateway ID for data routing.
-    /// * `data_tx` - Channel for sending TCP→client data events.
+    /// * `gateway_id` - Client-assigned gateway ID for data routing, if any.
+    /// * `data_tx` - Channel for sending stream→client data events.
     /// * `forwarder` - The gateway forwarder, used to register write channels.
+    ///
+    /// Returns whether a relay was started.
     pub async fn handle_inbound_accept(
         &self,
         channel_id: u32,
-        gateway_id: u32,
+        gateway_id: Option<u32>,
         data_tx: mpsc::Sender<GatewayEvent>,
         forwarder: &GatewayForwarder,
-    ) {
+    ) -> bool {
         let stream = self.pending_connections.lock().await.remove(&channel_id);
-        match stream {
-            Some(tcp_stream) => {
+        match (stream, gateway_id) {
+            (Some(stream), None) => {
+                info!(
+                    channel_id,
+                    "inbound connection relayed as a forward channel"
+                );
+                forwarder.relay_channel(channel_id, stream, data_tx).await;
+                true
+            }
+            (Some(stream), Some(gateway_id)) => {
                 let guard = self.policy.acquire();
 
                 // Create cancel channel
                 let (cancel_tx, cancel_rx) = mpsc::channel::<()>(1);
 
-                // Create write channel (client→TCP)
+                // Create write channel (client→stream)
                 let (write_tx, write_rx) = mpsc::channel::<Vec<u8>>(64);
                 forwarder.register_write_channel(gateway_id, write_tx).await;
 
@@ -209,26 +336,32 @@ impl ReverseListenerManager {
                 tokio::spawn(async move {
                     let _guard = guard;
                     let _cancel_tx = cancel_tx; // keep alive; drop ends relay
-                    GatewayForwarder::tcp_relay(
-                        tcp_stream, cancel_rx, write_rx, data_tx, gateway_id,
+                    GatewayForwarder::stream_relay(
+                        stream,
+                        cancel_rx,
+                        write_rx,
+                        data_tx,
+                        RelayTarget::Gateway(gateway_id),
                     )
                     .await;
                     debug!(gateway_id, "inbound relay ended");
                 });
+                true
             }
-            None => {
+            (None, _) => {
                 warn!(channel_id, "no pending connection for InboundAccept");
+                false
             }
         }
     }
 
     /// Accept loop for a reverse tunnel listener.
     async fn accept_loop(
-        listener: TcpListener,
+        listener: BoundListener,
         mut cancel_rx: mpsc::Receiver<()>,
         listener_id: u32,
         inbound_tx: mpsc::Sender<InboundEvent>,
-        pending: Arc<Mutex<HashMap<u32, TcpStream>>>,
+        pending: Arc<Mutex<HashMap<u32, Box<dyn RelayStream>>>>,
         channel_counter: Arc<AtomicU32>,
     ) {
         loop {
@@ -239,7 +372,7 @@ impl ReverseListenerManager {
                 }
                 result = listener.accept() => {
                     match result {
-                        Ok((stream, peer_addr)) => {
+                        Ok((stream, peer_addr, peer_port)) => {
                             let channel_id = channel_counter.fetch_add(1, Ordering::Relaxed);
 
                             info!(
@@ -249,14 +382,14 @@ impl ReverseListenerManager {
                                 "inbound connection accepted"
                             );
 
-                            // Store TcpStream pending InboundAccept from client
+                            // Store stream pending InboundAccept from client
                             pending.lock().await.insert(channel_id, stream);
 
                             let event = InboundEvent {
                                 listener_id,
                                 channel_id,
-                                peer_addr: peer_addr.ip().to_string(),
-                                peer_port: peer_addr.port(),
+                                peer_addr,
+                                peer_port,
                             };
 
                             if inbound_tx.send(event).await.is_err() {
@@ -286,9 +419,9 @@ pub struct InboundEvent {
     /// Globally unique channel identifier, assigned from a shared atomic
     /// counter to prevent collisions across listeners.
     pub channel_id: u32,
-    /// IP address of the connecting peer.
+    /// IP address of the connecting peer (the socket path for unix listeners).
     pub peer_addr: String,
-    /// Source port of the connecting peer.
+    /// Source port of the connecting peer (`0` for unix listeners).
     pub peer_port: u16,
 }
 
@@ -1,7 +1,10 @@
-//! Gateway module — TCP/UDP forwarding, DNS resolution, and reverse tunnel listeners.
+//! Gateway module — TCP/UDP and unix socket forwarding, DNS resolution, and
+//! reverse tunnel listeners.
 //!
-//! Handles gateway message types (`0x70`–`0x7d`) by forwarding TCP/UDP
-//! connections through the server to external destinations.
+//! Handles gateway message types (`0x70`–`0x7e`, plus `OpenUnix` /
+//! `ListenUnix`) by forwarding TCP/UDP connections through the server to
+//! external destinations, and unix-domain sockets (e.g. a docker socket) to
+//! and from the client.
 //!
 //! # Submodule Architecture
 //!
@@ -14,7 +17,7 @@
 //!   consult the enforcer before proceeding.
 //!
 //! - **[`forwarder`]** — The [`GatewayForwarder`] handles `OpenTcp`,
-//!   `OpenUdp`, and `ResolveDns` requests. It checks the policy, establishes
+//!   `OpenUdp`, `OpenUnix`, and `ResolveDns` requests. It checks the policy, establishes
 //!   the outbound connection or DNS lookup, spawns relay tasks, and returns
 //!   `GatewayOk` / `GatewayFail` envelopes to the caller.
 //!
@@ -23,20 +26,24 @@
 //!   type (`A` or `AAAA`). Used internally by the forwarder.
 //!
 //! - **[`listener`]** — The [`ReverseListenerManager`] handles `ListenRequest`
-//!   messages by binding TCP listeners on the server, accepting inbound
-//!   connections, and notifying the client via `InboundOpen` messages sent
-//!   through an `mpsc` channel.
+//!   and `ListenUnix` messages by binding TCP or unix socket listeners on the
+//!   server, accepting inbound connections, and notifying the client via
+//!   `InboundOpen` messages sent through an `mpsc` channel.
 //!
 //! # Data Flow
 //!
 //! ```text
 //! Client message
 //!   → dispatch_message (server.rs)
-//!     → GatewayForwarder::handle_open_tcp / handle_open_udp / handle_resolve_dns
+//!     → GatewayForwarder::handle_open_tcp / handle_open_udp / handle_open_unix / handle_resolve_dns
 //!         → GatewayPolicyEnforcer::check_connect
 //!         → DnsResolver::resolve (for DNS requests)
-//!         → spawn relay task (for TCP)
-//!     → ReverseListenerManager::handle_listen_request
+//!         → spawn relay task (for TCP and unix sockets)
+//!     → GatewayForwarder::handle_open_forward (`Open` of kind `TcpForward`)
+//!         → spawned task: GatewayPolicyEnforcer::check_connect, connect
+//!         → `OpenOk` / `OpenFail` through the session loop
+//!         → spawn relay task reporting as a channel (`SessionData` / `Close`)
+//!     → ReverseListenerManager::handle_listen_request / handle_listen_unix
 //!         → GatewayPolicyEnforcer::check_listen
 //!         → spawn accept loop → InboundEvent → client
 //! ```
@@ -51,14 +58,58 @@ pub use listener::ReverseListenerManager;
 pub use policy::GatewayPolicy;
 pub use resolver::DnsResolver;
 
-/// Event sent from a TCP relay task back to the session loop.
+use tokio::io::{AsyncRead, AsyncWrite};
+
+/// A bidirectional byte stream the gateway relays: a TCP connection or a
+/// unix-domain socket connection.
+pub trait RelayStream: AsyncRead + AsyncWrite + Send + Unpin {}
+
+impl<S: AsyncRead + AsyncWrite + Send + Unpin> RelayStream for S {}
+
+/// Event sent from a stream relay task back to the session loop.
 ///
 /// The session loop converts these into outbound control messages
-/// (`GatewayData` or `GatewayClose`) and sends them to the client.
+/// (`GatewayData` or `GatewayClose`, and `OpenOk`, `OpenFail`,
+/// `SessionData` or `Close` for `TcpForward` channels) and sends them to the
+/// client.
 #[derive(Debug)]
 pub enum GatewayEvent {
-    /// Data received from a remote TCP peer, to be forwarded to the client.
+    /// Data received from a remote peer, to be forwarded to the client.
     Data { gateway_id: u32, data: Vec<u8> },
-    /// Remote TCP peer closed the connection.
+    /// Remote peer closed the connection.
     Closed { gateway_id: u32 },
+    /// A `TcpForward` channel connected to its destination.
+    ChannelOpened { channel_id: u32 },
+    /// A `TcpForward` channel could not be opened.
+    ChannelOpenFailed { channel_id: u32, reason: String },
+    /// Data received on a `TcpForward` channel.
+    ChannelData { channel_id: u32, data: Vec<u8> },
+    /// Remote peer closed a `TcpForward` channel.
+    ChannelClosed { channel_id: u32 },
+}
+
+/// What a stream relay reports its traffic under: a client-assigned
+/// gateway connection or a server-assigned `TcpForward` channel.
+#[derive(Debug, Clone, Copy)]
+pub enum RelayTarget {
+    Gateway(u32),
+    Channel(u32),
+}
+
+impl RelayTarget {
+    /// Event carrying `data` read from the peer.
+    fn data(self, data: Vec<u8>) -> GatewayEvent {
+        match self {
+            RelayTarget::Gateway(gateway_id) => GatewayEvent::Data { gateway_id, data },
+            RelayTarget::Channel(channel_id) => GatewayEvent::ChannelData { channel_id, data },
+        }
+    }
+
+    /// Event reporting that the peer closed the connection.
+    fn closed(self) -> GatewayEvent {
+        match self {
+            RelayTarget::Gateway(gateway_id) => GatewayEvent::Closed { gateway_id },
+            RelayTarget::Channel(channel_id) => GatewayEvent::ChannelClosed { channel_id },
+        }
+    }
 }
@@ -9,6 +9,7 @@
 //!   plus an atomic connection counter, performing all access-control checks.
 
 use std::collections::HashSet;
+use std::net::IpAddr;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 
@@ -19,13 +20,20 @@ use std::sync::Arc;
 ///
 /// # Destination Matching
 ///
-/// The `allowed_destinations` list supports three forms:
+/// The `allowed_destinations` list supports these forms:
 ///
-/// | Pattern           | Matches                                   |
-/// |-------------------|-------------------------------------------|
-/// | `"*"`             | Any host and port (wildcard).              |
-/// | `"example.com"`   | The exact hostname on **any** port.        |
-/// | `"example.com:443"`| The exact hostname **and** port pair.     |
+/// | Pattern             | Matches                                       |
+/// |---------------------|-----------------------------------------------|
+/// | `"*"`               | Any host and port (wildcard).                 |
+/// | `"example.com"`     | The exact hostname on **any** port.           |
+/// | `"example.com:443"` | The exact hostname **and** port pair.         |
+/// | `"*.example.com"`   | Any subdomain of `example.com`.               |
+/// | `"10.0.0.0/8:443"`  | Any IP literal inside the CIDR block.         |
+/// | `"[::1]:22"`        | Bracketed IPv6 literal with a port.           |
+///
+/// Every host form may carry a `:port` suffix (`:*` for any port). CIDR
+/// blocks only match destinations given as IP literals; hostnames are not
+/// resolved for the check.
 ///
 /// An empty list means **no** destinations are allowed.
 #[derive(Debug, Clone)]
@@ -39,15 +47,26 @@ pub struct GatewayPolicy {
     pub max_connections: usize,
     /// Whether reverse tunnels (`ListenRequest`) are enabled.
     pub enable_reverse_tunnels: bool,
+    /// Whether unix-domain sockets may be forwarded (`OpenUnix`, and
+    /// `ListenUnix` when reverse tunnels are enabled).
+    pub allow_unix_sockets: bool,
+    /// Socket paths reachable when `allow_unix_sockets` is set: exact
+    /// absolute paths, or `"/dir/*"` for any socket directly in `/dir`.
+    ///
+    /// Empty = allow none.
+    pub allowed_unix_sockets: Vec<String>,
 }
 
-/// Default policy: allow all destinations, 100 max connections, reverse tunnels enabled.
+/// Default policy: allow all destinations, 100 max connections, reverse
+/// tunnels enabled, unix socket forwarding disabled.
 impl Default for GatewayPolicy {
     fn default() -> Self {
         Self {
             allowed_destinations: vec!["*".to_string()],
             max_connections: 100,
             enable_reverse_tunnels: true,
+            allow_unix_sockets: false,
+            allowed_unix_sockets: Vec::new(),
         }
     }
 }
@@ -125,6 +144,15 @@ impl GatewayPolicyEnforcer {
             return Ok(());
         }
 
+        if self
+            .policy
+            .allowed_destinations
+            .iter()
+            .any(|pattern| destination_matches(pattern, host, port))
+        {
+            return Ok(());
+        }
+
         Err(format!("destination not allowed: {}:{}", host, port))
     }
 
@@ -159,6 +187,45 @@ impl GatewayPolicyEnforcer {
         Ok(())
     }
 
+    /// Check if forwarding through the unix-domain socket at `path` is
+    /// allowed. Listening on a socket must also pass
+    /// [`check_listen`](Self::check_listen).
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(String)` if unix socket forwarding is disabled, `path`
+    /// is not absolute or contains `.`/`..` components, `path` is not in
+    /// `allowed_unix_sockets`, or the connection limit has been reached.
+    pub fn check_unix(&self, path: &str) -> Result<(), String> {
+        if !self.policy.allow_unix_sockets {
+            return Err("unix socket forwarding is disabled".to_string());
+        }
+        if !path.starts_with('/') {
+            return Err(format!("socket path must be absolute: {path}"));
+        }
+        if path.split('/').any(|part| part == "." || part == "..") {
+            return Err(format!("socket path must be normalized: {path}"));
+        }
+        if !self
+            .policy
+            .allowed_unix_sockets
+            .iter()
+            .any(|pattern| socket_path_matches(pattern, path))
+        {
+            return Err(format!("socket not allowed: {path}"));
+        }
+
+        let current = self.active_connections.load(Ordering::Relaxed);
+        if current >= self.policy.max_connections {
+            return Err(format!(
+                "connection limit reached ({}/{})",
+                current, self.policy.max_connections
+            ));
+        }
+
+        Ok(())
+    }
+
     /// Increment active connection count. Returns an owned guard that
     /// decrements on drop. The guard is `Send` so it can be moved into
     /// spawned tasks — the connection stays counted until the task ends.
@@ -179,6 +246,84 @@ impl GatewayPolicyEnforcer {
     }
 }
 
+/// Match a single allowlist pattern against a destination.
+///
+/// Handles the wildcard, subdomain, CIDR and `:port` forms described on
+/// [`GatewayPolicy`]; exact matches are covered by the enforcer's fast path.
+fn destination_matches(pattern: &str, host: &str, port: u16) -> bool {
+    let (host_pattern, port_pattern) = split_host_port(pattern);
+    let port_ok = match port_pattern {
+        None | Some("*") => true,
+        Some(p) => p.parse::<u16>() == Ok(port),
+    };
+    if !port_ok {
+        return false;
+    }
+
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    if host_pattern == "*" || host_pattern.eq_ignore_ascii_case(host) {
+        return true;
+    }
+    if let Some(suffix) = host_pattern.strip_prefix("*.") {
+        let host = host.to_ascii_lowercase();
+        let suffix = suffix.to_ascii_lowercase();
+        return host.len() > suffix.len() && host.ends_with(&format!(".{suffix}"));
+    }
+    if let Some((network, prefix_len)) = host_pattern.split_once('/') {
+        let (Ok(network), Ok(prefix_len), Ok(addr)) = (
+            network.parse::<IpAddr>(),
+            prefix_len.parse::<u32>(),
+            host.parse::<IpAddr>(),
+        ) else {
+            return false;
+        };
+        return ip_in_cidr(addr, network, prefix_len);
+    }
+    false
+}
+
+/// Match a socket allowlist entry: an exact path, or `dir/*` for any
+/// entry directly inside `dir`.
+fn socket_path_matches(pattern: &str, path: &str) -> bool {
+    match pattern.strip_suffix("/*") {
+        Some(dir) => path
+            .strip_prefix(dir)
+            .and_then(|rest| rest.strip_prefix('/'))
+            .is_some_and(|name| !name.is_empty() && !name.contains('/')),
+        None => pattern == path,
+    }
+}
+
+/// Split `host[:port]`, keeping bare IPv6 literals and `[v6]:port` intact.
+fn split_host_port(pattern: &str) -> (&str, Option<&str>) {
+    if let Some(rest) = pattern.strip_prefix('[') {
+        return match rest.split_once(']') {
+            Some((host, tail)) => (host, tail.strip_prefix(':')),
+            None => (pattern, None),
+        };
+    }
+    match pattern.rsplit_once(':') {
+        // More than one colon without brackets is an IPv6 literal or CIDR.
+        Some((host, _)) if host.contains(':') => (pattern, None),
+        Some((host, port)) => (host, Some(port)),
+        None => (pattern, None),
+    }
+}
+
+fn ip_in_cidr(addr: IpAddr, network: IpAddr, prefix_len: u32) -> bool {
+    match (addr, network) {
+        (IpAddr::V4(addr), IpAddr::V4(network)) if prefix_len <= 32 => {
+            let mask = u32::MAX.checked_shl(32 - prefix_len).unwrap_or(0);
+            u32::from(addr) & mask == u32::from(network) & mask
+        }
+        (IpAddr::V6(addr), IpAddr::V6(network)) if prefix_len <= 128 => {
+            let mask = u128::MAX.checked_shl(128 - prefix_len).unwrap_or(0);
+            u128::from(addr) & mask == u128::from(network) & mask
+        }
+        _ => false,
+    }
+}
+
 /// RAII guard that decrements the connection count on drop.
 /// Owns an `Arc<AtomicUsize>` so it is `Send` and can be moved into spawned tasks.
 pub struct ConnectionGuard {
@@ -209,18 +354,44 @@ mod tests {
             allowed_destinations: vec!["example.com".to_string()],
             max_connections: 100,
             enable_reverse_tunnels: true,
+            allow_unix_sockets: false,
+            allowed_unix_sockets: Vec::new(),
         };
         let enforcer = GatewayPolicyEnforcer::new(policy);
         assert!(enforcer.check_connect("example.com", 80).is_ok());
         assert!(enforcer.check_connect("evil.com", 80).is_err());
     }
 
+    #[test]
+    fn test_allow_patterns() {
+        let policy = GatewayPolicy {
+            allowed_destinations: vec![
+                "*.internal.example:443".to_string(),
+                "10.0.0.0/8:22".to_string(),
+                "db.example:*".to_string(),
+            ],
+            max_connections: 100,
+            enable_reverse_tunnels: true,
+            allow_unix_sockets: false,
+            allowed_unix_sockets: Vec::new(),
+        };
+        let enforcer = GatewayPolicyEnforcer::new(policy);
+        assert!(enforcer.check_connect("api.internal.example", 443).is_ok());
+        assert!(enforcer.check_connect("internal.example", 443).is_err());
+        assert!(enforcer.check_connect("api.internal.example", 80).is_err());
+        assert!(enforcer.check_connect("10.1.2.3", 22).is_ok());
+        assert!(enforcer.check_connect("11.1.2.3", 22).is_err());
+        assert!(enforcer.check_connect("db.example", 5432).is_ok());
+    }
+
     #[test]
     fn test_connection_limit() {
         let policy = GatewayPolicy {
             allowed_destinations: vec!["*".to_string()],
             max_connections: 2,
             enable_reverse_tunnels: true,
+            allow_unix_sockets: false,
+            allowed_unix_sockets: Vec::new(),
         };
         let enforcer = GatewayPolicyEnforcer::new(policy);
         let _g1 = enforcer.acquire();
@@ -234,6 +405,8 @@ mod tests {
             allowed_destinations: vec!["*".to_string()],
             max_connections: 1,
             enable_reverse_tunnels: true,
+            allow_unix_sockets: false,
+            allowed_unix_sockets: Vec::new(),
         };
         let enforcer = GatewayPolicyEnforcer::new(policy);
         {
@@ -250,8 +423,47 @@ mod tests {
             allowed_destinations: vec!["*".to_string()],
             max_connections: 100,
             enable_reverse_tunnels: false,
+            allow_unix_sockets: false,
+            allowed_unix_sockets: Vec::new(),
         };
         let enforcer = GatewayPolicyEnforcer::new(policy);
         assert!(enforcer.check_listen(8080).is_err());
     }
+
+    #[test]
+    fn test_unix_sockets() {
+        let enforcer = GatewayPolicyEnforcer::new(GatewayPolicy::default());
+        assert!(enforcer.check_unix("/var/run/docker.sock").is_err());
+
+        let policy = GatewayPolicy {
+            allow_unix_sockets: true,
+            allowed_unix_sockets: vec![
+                "/var/run/docker.sock".to_string(),
+                "/run/user/1000/*".to_string(),
+            ],
+            ..GatewayPolicy::default()
+        };
+        let enforcer = GatewayPolicyEnforcer::new(policy);
+        assert!(enforcer.check_unix("/var/run/docker.sock").is_ok());
+        assert!(enforcer.check_unix("/run/user/1000/lsp.sock").is_ok());
+        assert!(enforcer.check_unix("docker.sock").is_err());
+    }
+
+    #[test]
+    fn test_unix_socket_outside_allowlist_refused() {
+        let policy = GatewayPolicy {
+            allow_unix_sockets: true,
+            allowed_unix_sockets: vec!["/run/user/1000/*".to_string()],
+            ..GatewayPolicy::default()
+        };
+        let enforcer = GatewayPolicyEnforcer::new(policy);
+        assert!(enforcer.check_unix("/var/run/docker.sock").is_err());
+        assert!(enforcer
+            .check_unix("/run/user/1000/nested/app.sock")
+            .is_err());
+        assert!(enforcer
+            .check_unix("/run/user/1000/../../../var/run/docker.sock")
+            .is_err());
+        assert!(enforcer.check_unix("/run/user/10000/app.sock").is_err());
+    }
 }
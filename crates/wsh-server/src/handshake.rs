@@ -4,12 +4,14 @@
 //! 1. Client sends HELLO
 //! 2. Server sends CHALLENGE with random nonce
 //! 3. Client sends AUTH (pubkey signature or password)
-//! 4. Server sends AUTH_OK (with session token) or AUTH_FAIL
+//! 4. For users with a TOTP secret, server sends AUTH_METHODS `["totp"]`
+//!    and the client sends a second AUTH carrying the code
+//! 5. Server sends AUTH_OK (with session token) or AUTH_FAIL
 
 use rand::Rng;
 use sha2::{Digest, Sha256};
 use tracing::{debug, info, warn};
-use wsh_core::keys::AuthorizedKey;
+use wsh_core::keys::{AuthorizedKey, CertKind, Certificate};
 use wsh_core::messages::*;
 use wsh_core::{self, WshError, WshResult};
 
@@ -20,6 +22,9 @@ pub struct AuthResult {
     pub username: String,
     /// Fingerprint of the key used (empty string for password auth).
     pub fingerprint: String,
+    /// Fingerprint of the `cert-authority` entry that signed the user's
+    /// certificate, whose authorized_keys options apply to the session.
+    pub ca_fingerprint: Option<String>,
     /// Session token bytes.
     pub token: Vec<u8>,
     /// Session ID.
@@ -40,6 +45,16 @@ pub struct HelloResult {
     pub fingerprints: Vec<String>,
 }
 
+/// The handshake an AUTH message answers.
+pub struct AuthContext<'a> {
+    /// Nonce sent in CHALLENGE.
+    pub nonce: &'a [u8],
+    /// Session ID from SERVER_HELLO; part of the auth transcript.
+    pub session_id: &'a str,
+    /// Username from HELLO; user certificates must name it.
+    pub username: &'a str,
+}
+
 /// Supported protocol versions (in priority order, newest first).
 const SUPPORTED_VERSIONS: &[&str] = &["wsh-v1"];
 
@@ -103,6 +118,9 @@ pub fn handle_hello(
         msg_type: MsgType::Challenge,
         payload: Payload::Challenge(ChallengePayload {
             nonce: nonce.clone(),
+            host_certificate: None,
+            host_signature: None,
+            host_key_rotation: None,
         }),
     };
 
@@ -115,13 +133,28 @@ pub fn handle_hello(
     })
 }
 
-/// Verify an AUTH message against authorized keys or password.
+/// Attach the host certificate (if any), a host key signature and the
+/// rotation proof (if a previous host key is configured) to a CHALLENGE.
 ///
-/// `session_id` is the one from SERVER_HELLO — it's part of the auth transcript.
+/// `session_id` must be the one the client uses in its auth transcript.
+pub fn add_host_proof(
+    challenge: &mut Envelope,
+    host: &crate::auth::host_cert::HostIdentity,
+    session_id: &str,
+) {
+    if let Payload::Challenge(c) = &mut challenge.payload {
+        if let Some(certificate) = host.certificate() {
+            c.host_signature = Some(host.sign(session_id, &c.nonce));
+            c.host_certificate = Some(certificate.to_vec());
+        }
+        c.host_key_rotation = host.rotation_proof(session_id, &c.nonce);
+    }
+}
+
+/// Verify an AUTH message against authorized keys or password.
 pub fn verify_auth(
     auth: &AuthPayload,
-    nonce: &[u8],
-    session_id: &str,
+    context: &AuthContext<'_>,
     authorized_keys: &[AuthorizedKey],
     server_secret: &[u8],
     session_ttl: u64,
@@ -133,21 +166,39 @@ pub fn verify_auth(
             if !allow_pubkey {
                 return Err(WshError::AuthFailed("pubkey auth disabled".into()));
             }
-            verify_pubkey_auth(
-                auth,
-                nonce,
-                session_id,
-                authorized_keys,
-                server_secret,
-                session_ttl,
-            )
+            verify_pubkey_auth(auth, context, authorized_keys, server_secret, session_ttl)
         }
         AuthMethod::Password => {
             if !allow_password {
                 return Err(WshError::AuthFailed("password auth disabled".into()));
             }
-            verify_password_auth(auth, session_id, server_secret, session_ttl)
+            verify_password_auth(auth, context.session_id, server_secret, session_ttl)
         }
+        AuthMethod::Totp => Err(WshError::AuthFailed(
+            "totp is only accepted as a second factor".into(),
+        )),
+    }
+}
+
+/// Build the AUTH_METHODS envelope that asks for a TOTP code once the
+/// first factor has been accepted.
+pub fn build_totp_request() -> Envelope {
+    Envelope {
+        msg_type: MsgType::AuthMethods,
+        payload: Payload::AuthMethods(AuthMethodsPayload {
+            methods: vec![AuthMethod::Totp],
+        }),
+    }
+}
+
+/// Extract the code from the AUTH sent in answer to [`build_totp_request`].
+pub fn totp_code(envelope: &Envelope) -> WshResult<&str> {
+    match &envelope.payload {
+        Payload::Auth(auth) if auth.method == AuthMethod::Totp => auth
+            .code
+            .as_deref()
+            .ok_or_else(|| WshError::AuthFailed("missing code in totp auth".into())),
+        _ => Err(WshError::AuthFailed("expected a TOTP code".into())),
     }
 }
 
@@ -166,12 +217,16 @@ fn build_transcript(session_id: &str, nonce: &[u8]) -> Vec<u8> {
 /// Verify pubkey-based authentication.
 fn verify_pubkey_auth(
     auth: &AuthPayload,
-    nonce: &[u8],
-    session_id: &str,
+    context: &AuthContext<'_>,
     authorized_keys: &[AuthorizedKey],
     server_secret: &[u8],
     session_ttl: u64,
 ) -> WshResult<AuthResult> {
+    let AuthContext {
+        nonce,
+        session_id,
+        username,
+    } = *context;
     let public_key = auth
         .public_key
         .as_ref()
@@ -181,12 +236,28 @@ fn verify_pubkey_auth(
         .as_ref()
         .ok_or_else(|| WshError::AuthFailed("missing signature in pubkey auth".into()))?;
 
-    // Check if the key is authorized
-    if !wsh_core::keys::is_key_authorized(public_key, authorized_keys) {
-        let fp = wsh_core::fingerprint(public_key);
-        warn!(fingerprint = %fp, "unauthorized key");
-        return Err(WshError::AuthFailed("key not authorized".into()));
-    }
+    // Check if the key is authorized, directly or through a certificate
+    let ca_fingerprint = match &auth.certificate {
+        Some(encoded) => Some(verify_user_certificate(
+            encoded,
+            public_key,
+            username,
+            authorized_keys,
+        )?),
+        None => {
+            let Some(entry) = wsh_core::keys::find_authorized_key(public_key, authorized_keys)
+            else {
+                let fp = wsh_core::fingerprint(public_key);
+                warn!(fingerprint = %fp, "unauthorized key");
+                return Err(WshError::AuthFailed("key not authorized".into()));
+            };
+            if entry.is_expired(unix_now()) {
+                warn!(fingerprint = %entry.fingerprint, "expired key");
+                return Err(WshError::AuthFailed("key has expired".into()));
+            }
+            None
+        }
+    };
 
     // Verify the signature over the transcript.
     // Transcript: SHA-256("wsh-v1\0" || session_id || nonce)
@@ -215,11 +286,45 @@ fn verify_pubkey_auth(
     Ok(AuthResult {
         username: String::new(), // Will be filled from HELLO
         fingerprint: fp,
+        ca_fingerprint,
         token,
         session_id: session_id.to_string(),
     })
 }
 
+/// Check a user certificate for `public_key` against the trusted CAs,
+/// returning the fingerprint of the CA entry that signed it.
+fn verify_user_certificate(
+    encoded: &[u8],
+    public_key: &[u8],
+    username: &str,
+    authorized_keys: &[AuthorizedKey],
+) -> WshResult<String> {
+    let cert = Certificate::from_bytes(encoded)
+        .map_err(|e| WshError::AuthFailed(format!("invalid certificate: {e}")))?;
+    if cert.public_key != public_key {
+        return Err(WshError::AuthFailed(
+            "certificate was issued for a different key".into(),
+        ));
+    }
+    let Some(ca) = wsh_core::keys::find_cert_authority(&cert, authorized_keys) else {
+        warn!(key_id = %cert.key_id, "certificate signed by an untrusted CA");
+        return Err(WshError::AuthFailed(
+            "certificate authority not trusted".into(),
+        ));
+    };
+    let now = unix_now();
+    if ca.is_expired(now) {
+        warn!(fingerprint = %ca.fingerprint, "expired certificate authority");
+        return Err(WshError::AuthFailed(
+            "certificate authority has expired".into(),
+        ));
+    }
+    cert.verify(CertKind::User, username, now)?;
+    info!(key_id = %cert.key_id, serial = cert.serial, "user certificate accepted");
+    Ok(ca.fingerprint.clone())
+}
+
 /// Verify password-based authentication against config-defined hashes.
 ///
 /// Password hashes in config are stored as "sha256:<hex>" pairs.
@@ -245,6 +350,7 @@ fn verify_password_auth(
     Ok(AuthResult {
         username: String::new(),    // Filled by caller from HELLO
         fingerprint: String::new(), // No fingerprint for password auth
+        ca_fingerprint: None,
         token,
         session_id: session_id.to_string(),
     })
@@ -286,6 +392,14 @@ pub fn build_auth_fail(reason: &str) -> Envelope {
     }
 }
 
+/// Current unix time in seconds.
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
 /// Generate a random session ID.
 fn generate_session_id() -> String {
     let mut rng = rand::thread_rng();
@@ -0,0 +1,551 @@
+//! Per-user workspace roots.
+//!
+//! With `[isolation]` enabled, every authenticated connection is assigned a
+//! workspace directory under the configured root, named after the user its
+//! key fingerprint maps to. File transfers, sync, MCP host tools, forwarded
+//! unix socket paths and the working directory of spawned shells are all
+//! confined to that directory: absolute client paths are taken relative to
+//! the workspace (like a chroot), and `..` or symlinks cannot climb out of
+//! it.
+//!
+//! The mapped user is also the system account that the connection's shells,
+//! exec channels and MCP `exec` run as. A server running as root switches
+//! to it: the PTY runs this binary in [`RUN_AS_ARG`] mode, which drops to
+//! the account and execs the command. Otherwise only the server's own
+//! account can be used. Users without a usable account (such as unmapped
+//! keys with no `key-<prefix>` account) keep their workspace for files, but
+//! cannot start processes.
+
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::path::{Component, Path, PathBuf};
+
+use tracing::info;
+use wsh_core::{WshError, WshResult};
+
+/// Longest user name accepted as a workspace directory.
+const MAX_USER_LEN: usize = 64;
+
+/// Hex digits of the fingerprint used to name unmapped keys.
+const UNMAPPED_PREFIX_LEN: usize = 16;
+
+/// Fewest hex digits a fingerprint prefix in the mapping may have.
+const MIN_MAPPED_PREFIX_LEN: usize = 16;
+
+/// Hex digits in a full SHA-256 fingerprint.
+const FINGERPRINT_LEN: usize = 64;
+
+/// First argument that makes the server binary switch to a system account
+/// and exec a command: `wsh-server __run-as <account> <program> [args...]`.
+pub const RUN_AS_ARG: &str = "__run-as";
+
+/// Maps authenticated identities to workspace directories.
+#[derive(Debug, Clone)]
+pub struct Isolation {
+    root: PathBuf,
+    /// Fingerprint (or fingerprint prefix), lowercased → user name. No
+    /// entry is a prefix of another.
+    users: Vec<(String, String)>,
+}
+
+impl Isolation {
+    /// Create a policy rooted at `root` with a fingerprint → user mapping.
+    ///
+    /// Mapping keys are full fingerprints or prefixes of at least
+    /// [`MIN_MAPPED_PREFIX_LEN`] hex digits, and no key may be a prefix of
+    /// another, so a fingerprint matches at most one entry. Entries whose
+    /// user name is not a safe directory name are rejected.
+    pub fn new(root: PathBuf, users: &HashMap<String, String>) -> WshResult<Self> {
+        let mut mapped: Vec<(String, String)> = Vec::with_capacity(users.len());
+        for (fingerprint, user) in users {
+            if !valid_user_name(user) {
+                return Err(WshError::Other(format!(
+                    "[isolation] invalid user name {user:?} for {fingerprint}"
+                )));
+            }
+            let prefix = normalize_fingerprint(fingerprint);
+            if !(MIN_MAPPED_PREFIX_LEN..=FINGERPRINT_LEN).contains(&prefix.len())
+                || !prefix.chars().all(|c| c.is_ascii_hexdigit())
+            {
+                return Err(WshError::Other(format!(
+                    "[isolation] {fingerprint:?} is not a fingerprint or a prefix of at least \
+                     {MIN_MAPPED_PREFIX_LEN} hex digits"
+                )));
+            }
+            if let Some((other, _)) = mapped
+                .iter()
+                .find(|(other, _)| other.starts_with(&prefix) || prefix.starts_with(other.as_str()))
+            {
+                return Err(WshError::Other(format!(
+                    "[isolation] fingerprint prefixes {prefix} and {other} overlap"
+                )));
+            }
+            mapped.push((prefix, user.clone()));
+        }
+        Ok(Self {
+            root,
+            users: mapped,
+        })
+    }
+
+    /// The user a connection is isolated as.
+    ///
+    /// Key logins use the fingerprint mapping, falling back to
+    /// `key-<fingerprint prefix>` for unmapped keys; the client-supplied
+    /// username is never trusted for them. Password logins (no fingerprint)
+    /// use the verified username.
+    pub fn user_for(&self, username: &str, fingerprint: &str) -> Result<String, String> {
+        if fingerprint.is_empty() {
+            return if valid_user_name(username) {
+                Ok(username.to_string())
+            } else {
+                Err(format!("username {username:?} cannot name a workspace"))
+            };
+        }
+        let fingerprint = normalize_fingerprint(fingerprint);
+        if let Some((_, user)) = self
+            .users
+            .iter()
+            .find(|(prefix, _)| fingerprint.starts_with(prefix.as_str()))
+        {
+            return Ok(user.clone());
+        }
+        let short: String = fingerprint
+            .chars()
+            .filter(char::is_ascii_alphanumeric)
+            .take(UNMAPPED_PREFIX_LEN)
+            .collect();
+        Ok(format!("key-{short}"))
+    }
+
+    /// Create (if needed) and return the workspace for a connection.
+    pub fn workspace(&self, username: &str, fingerprint: &str) -> WshResult<Workspace> {
+        let user = self
+            .user_for(username, fingerprint)
+            .map_err(WshError::Other)?;
+        let account = account_for(&user);
+        let home = self.root.join(&user);
+        let created = !home.is_dir();
+        if created {
+            std::fs::create_dir_all(&self.root)?;
+            create_private_dir(&home)?;
+        }
+        let workspace = Workspace {
+            home: home.canonicalize()?,
+            confined: true,
+            account,
+        };
+        if created {
+            workspace.hand_over(&workspace.home)?;
+            info!(user = %user, path = %home.display(), "created user workspace");
+        }
+        Ok(workspace)
+    }
+}
+
+/// The system account a workspace's processes run as.
+#[derive(Debug, Clone)]
+enum Account {
+    /// The server's own account.
+    Server,
+    /// Another account, switched to through [`RUN_AS_ARG`].
+    Switch(String),
+    /// None: processes are refused, for this reason.
+    Unavailable(String),
+}
+
+/// The directory a connection's paths resolve against.
+#[derive(Debug, Clone)]
+pub struct Workspace {
+    home: PathBuf,
+    confined: bool,
+    account: Account,
+}
+
+impl Workspace {
+    /// No isolation: paths resolve from the server user's home directory and
+    /// absolute paths are used as given, like scp.
+    pub fn unconfined() -> Self {
+        Self {
+            home: dirs::home_dir().unwrap_or_default(),
+            confined: false,
+            account: Account::Server,
+        }
+    }
+
+    /// Home directory of this workspace (default cwd for shells and exec).
+    pub fn home(&self) -> &Path {
+        &self.home
+    }
+
+    /// Whether paths are confined to [`Self::home`].
+    pub fn is_confined(&self) -> bool {
+        self.confined
+    }
+
+    /// The account to switch to for processes started in this workspace
+    /// (`None`: the server's own), or why none may be started.
+    pub fn run_as(&self) -> Result<Option<&str>, String> {
+        match &self.account {
+            Account::Server => Ok(None),
+            Account::Switch(name) => Ok(Some(name)),
+            Account::Unavailable(reason) => Err(reason.clone()),
+        }
+    }
+
+    /// Give `path` to the account this workspace's processes run as, so
+    /// they can use what the server creates for them. Does nothing unless
+    /// the workspace switches accounts.
+    pub fn hand_over(&self, path: &Path) -> WshResult<()> {
+        #[cfg(unix)]
+        if let Account::Switch(name) = &self.account {
+            if let Some((uid, gid)) = lookup_account(name) {
+                std::os::unix::fs::chown(path, Some(uid), Some(gid))?;
+            }
+        }
+        #[cfg(not(unix))]
+        let _ = path;
+        Ok(())
+    }
+
+    /// Resolve a client-supplied path.
+    ///
+    /// `~/` and relative paths are taken from the workspace home. When
+    /// confined, absolute paths are re-rooted under the home as well, and
+    /// the result is rejected if `..` or a symlink would leave it.
+    pub fn resolve(&self, path: &str) -> Result<PathBuf, String> {
+        let rest = if path == "~" {
+            ""
+        } else {
+            path.strip_prefix("~/").unwrap_or(path)
+        };
+        if !self.confined {
+            let path = Path::new(rest);
+            return Ok(if path.is_absolute() {
+                path.to_path_buf()
+            } else {
+                self.home.join(path)
+            });
+        }
+
+        let mut resolved = self.home.clone();
+        for component in Path::new(rest).components() {
+            match component {
+                Component::Normal(part) => resolved.push(part),
+                Component::ParentDir => {
+                    if resolved == self.home {
+                        return Err(format!("{path}: escapes the workspace"));
+                    }
+                    resolved.pop();
+                }
+                Component::RootDir | Component::CurDir | Component::Prefix(_) => {}
+            }
+        }
+        if !within(&self.home, &resolved) {
+            return Err(format!("{path}: escapes the workspace"));
+        }
+        Ok(resolved)
+    }
+
+    /// Whether `path`, below a directory returned by [`Self::resolve`], is
+    /// still inside the workspace once symlinked parent directories are
+    /// followed. Always true when unconfined.
+    pub fn contains(&self, path: &Path) -> bool {
+        !self.confined
+            || path
+                .parent()
+                .is_some_and(|parent| within(&self.home, parent))
+    }
+
+    /// Resolve the server-side path of a forwarded unix socket.
+    ///
+    /// Confined workspaces re-root it like [`Self::resolve`]; otherwise it
+    /// is passed through unchanged for the gateway policy to check.
+    pub fn resolve_socket(&self, path: &str) -> Result<String, String> {
+        if !self.confined {
+            return Ok(path.to_string());
+        }
+        let resolved = self.resolve(path)?;
+        resolved
+            .to_str()
+            .map(str::to_string)
+            .ok_or_else(|| format!("{path}: socket path is not valid UTF-8"))
+    }
+}
+
+/// Program and arguments that run `program args` as `account` through this
+/// binary's [`RUN_AS_ARG`] mode; unchanged when `account` is `None`.
+pub fn run_as_command(
+    account: Option<&str>,
+    program: String,
+    args: Vec<String>,
+) -> WshResult<(String, Vec<String>)> {
+    let Some(account) = account else {
+        return Ok((program, args));
+    };
+    let exe = std::env::current_exe()?;
+    let exe = exe
+        .to_str()
+        .ok_or_else(|| WshError::Other("server binary path is not valid UTF-8".into()))?;
+    let mut wrapped = vec![RUN_AS_ARG.to_string(), account.to_string(), program];
+    wrapped.extend(args);
+    Ok((exe.to_string(), wrapped))
+}
+
+/// [`RUN_AS_ARG`] mode: switch to the account in `args[0]` and exec
+/// `args[1..]`. Returns the exit status only if that fails.
+#[cfg(unix)]
+pub fn run_as_main(args: &[OsString]) -> i32 {
+    use std::os::unix::process::CommandExt;
+
+    let [account, program, rest @ ..] = args else {
+        eprintln!("usage: wsh-server {RUN_AS_ARG} <account> <program> [args...]");
+        return 2;
+    };
+    let account = account.to_string_lossy();
+    if let Err(e) = switch_account(&account) {
+        eprintln!("wsh-server: cannot switch to account {account}: {e}");
+        return 126;
+    }
+    let e = std::process::Command::new(program)
+        .args(rest)
+        .env("USER", &*account)
+        .env("LOGNAME", &*account)
+        .exec();
+    eprintln!("wsh-server: cannot run {}: {e}", program.to_string_lossy());
+    127
+}
+
+#[cfg(not(unix))]
+pub fn run_as_main(_args: &[OsString]) -> i32 {
+    eprintln!("wsh-server: {RUN_AS_ARG} is not supported on this host");
+    2
+}
+
+/// Take on the groups, gid and uid of `name`, for good.
+#[cfg(unix)]
+fn switch_account(name: &str) -> std::io::Result<()> {
+    let (uid, gid) = lookup_account(name)
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "no such account"))?;
+    let c_name = std::ffi::CString::new(name)?;
+    // SAFETY: `c_name` is a valid C string; groups and gid go before the uid
+    // so the process still has the privilege to change them.
+    unsafe {
+        if libc::initgroups(c_name.as_ptr(), gid as _) != 0
+            || libc::setgid(gid) != 0
+            || libc::setuid(uid) != 0
+        {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// How processes of `user`'s workspace get an account.
+#[cfg(unix)]
+fn account_for(user: &str) -> Account {
+    let Some((uid, _)) = lookup_account(user) else {
+        return Account::Unavailable(format!("no system account {user:?} to run processes as"));
+    };
+    // SAFETY: geteuid has no preconditions and cannot fail.
+    let euid = unsafe { libc::geteuid() };
+    if uid == euid {
+        Account::Server
+    } else if euid == 0 {
+        Account::Switch(user.to_string())
+    } else {
+        Account::Unavailable(format!("server cannot run processes as {user:?}"))
+    }
+}
+
+#[cfg(not(unix))]
+fn account_for(user: &str) -> Account {
+    Account::Unavailable(format!("server cannot run processes as {user:?}"))
+}
+
+/// Uid and primary gid of the system account `name`.
+#[cfg(unix)]
+fn lookup_account(name: &str) -> Option<(u32, u32)> {
+    let c_name = std::ffi::CString::new(name).ok()?;
+    let mut buf = vec![0 as libc::c_char; 16 * 1024];
+    // SAFETY: `passwd` is plain data; getpwnam_r fills it, pointing into `buf`.
+    let mut pwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut found = std::ptr::null_mut();
+    // SAFETY: every pointer is valid for the call and `buf.len()` is its size.
+    let rc = unsafe {
+        libc::getpwnam_r(
+            c_name.as_ptr(),
+            &mut pwd,
+            buf.as_mut_ptr(),
+            buf.len(),
+            &mut found,
+        )
+    };
+    (rc == 0 && !found.is_null()).then_some((pwd.pw_uid, pwd.pw_gid))
+}
+
+/// Whether the deepest existing ancestor of `path` (following symlinks)
+/// still lies inside `home`.
+fn within(home: &Path, path: &Path) -> bool {
+    let mut existing = path;
+    loop {
+        if let Ok(real) = existing.canonicalize() {
+            return real.starts_with(home);
+        }
+        match existing.parent() {
+            Some(parent) => existing = parent,
+            None => return false,
+        }
+    }
+}
+
+/// Whether `name` is usable as a single directory name.
+fn valid_user_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_USER_LEN
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+/// Lowercase a fingerprint and drop an optional `SHA256:` label.
+fn normalize_fingerprint(fingerprint: &str) -> String {
+    let fingerprint = fingerprint.trim();
+    let bare = fingerprint
+        .strip_prefix("SHA256:")
+        .or_else(|| fingerprint.strip_prefix("sha256:"))
+        .unwrap_or(fingerprint);
+    bare.to_ascii_lowercase()
+}
+
+#[cfg(unix)]
+fn create_private_dir(dir: &Path) -> WshResult<()> {
+    use std::os::unix::fs::DirBuilderExt;
+    std::fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(dir)?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn create_private_dir(dir: &Path) -> WshResult<()> {
+    std::fs::create_dir_all(dir)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_root(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("wsh-isolation-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn maps_fingerprints_to_users() {
+        let users = HashMap::from([
+            ("ab00000000000000".to_string(), "team".to_string()),
+            ("SHA256:ABCD000000000000".to_string(), "alice".to_string()),
+        ]);
+        let isolation = Isolation::new(PathBuf::from("/srv/wsh"), &users).unwrap();
+        assert_eq!(
+            isolation.user_for("root", "abcd000000000000ff").unwrap(),
+            "alice"
+        );
+        assert_eq!(
+            isolation.user_for("root", "ab0000000000000099").unwrap(),
+            "team"
+        );
+        assert_eq!(
+            isolation.user_for("root", "0123456789abcdef0123").unwrap(),
+            "key-0123456789abcdef"
+        );
+        assert_eq!(isolation.user_for("bob", "").unwrap(), "bob");
+        assert!(isolation.user_for("../etc", "").is_err());
+
+        let reject = |key: &str, user: &str| {
+            let users = HashMap::from([(key.to_string(), user.to_string())]);
+            Isolation::new(PathBuf::from("/srv/wsh"), &users).is_err()
+        };
+        assert!(reject("ab00000000000000", "../x"));
+        assert!(reject("ab", "team"));
+        assert!(reject("zz00000000000000", "team"));
+        assert!(reject(&"a".repeat(65), "team"));
+
+        let overlapping = HashMap::from([
+            ("ab00000000000000".to_string(), "team".to_string()),
+            ("ab00000000000000ff".to_string(), "alice".to_string()),
+        ]);
+        assert!(Isolation::new(PathBuf::from("/srv/wsh"), &overlapping).is_err());
+    }
+
+    #[test]
+    fn confined_paths_stay_in_workspace() {
+        let root = temp_root("confine");
+        let isolation = Isolation::new(root.clone(), &HashMap::new()).unwrap();
+        let workspace = isolation.workspace("alice", "").unwrap();
+        let home = workspace.home().to_path_buf();
+        assert!(home.ends_with("alice"));
+
+        assert_eq!(
+            workspace.resolve("notes.txt").unwrap(),
+            home.join("notes.txt")
+        );
+        assert_eq!(workspace.resolve("~/a/b").unwrap(), home.join("a/b"));
+        assert_eq!(
+            workspace.resolve("/etc/passwd").unwrap(),
+            home.join("etc/passwd")
+        );
+        assert_eq!(workspace.resolve("a/../b").unwrap(), home.join("b"));
+        assert_eq!(workspace.resolve("~").unwrap(), home);
+        assert!(workspace.resolve("../bob/secret").is_err());
+        assert!(workspace.resolve("/../../etc").is_err());
+
+        let socket = workspace.resolve_socket("/var/run/docker.sock").unwrap();
+        assert_eq!(PathBuf::from(socket), home.join("var/run/docker.sock"));
+        assert!(workspace.resolve_socket("../bob/app.sock").is_err());
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink("/", home.join("escape")).unwrap();
+            assert!(workspace.resolve("escape/etc/passwd").is_err());
+            assert!(workspace.resolve_socket("/escape/run/app.sock").is_err());
+        }
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn processes_need_a_system_account() {
+        let root = temp_root("account");
+        let isolation = Isolation::new(root.clone(), &HashMap::new()).unwrap();
+        let workspace = isolation.workspace("no-such-account-wsh", "").unwrap();
+        assert!(workspace.run_as().is_err());
+        assert_eq!(Workspace::unconfined().run_as(), Ok(None));
+
+        let (program, args) =
+            run_as_command(Some("alice"), "sh".into(), vec!["-c".into(), "id".into()]).unwrap();
+        assert_eq!(PathBuf::from(program), std::env::current_exe().unwrap());
+        assert_eq!(args, [RUN_AS_ARG, "alice", "sh", "-c", "id"]);
+        assert_eq!(
+            run_as_command(None, "sh".into(), Vec::new()).unwrap(),
+            ("sh".to_string(), Vec::new())
+        );
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn unconfined_keeps_absolute_paths() {
+        let workspace = Workspace::unconfined();
+        assert_eq!(
+            workspace.resolve("/etc/hosts").unwrap(),
+            PathBuf::from("/etc/hosts")
+        );
+        assert_eq!(workspace.resolve("x").unwrap(), workspace.home().join("x"));
+        assert_eq!(workspace.resolve_socket("app.sock").unwrap(), "app.sock");
+    }
+}
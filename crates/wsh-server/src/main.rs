@@ -3,14 +3,19 @@
 //! Accepts WebTransport (QUIC) and WebSocket connections, authenticates
 //! clients via public key or password, and provides PTY-backed shell sessions.
 
+mod agent_forward;
+mod audit;
 mod auth;
 mod config;
 mod gateway;
 mod handshake;
+mod isolation;
 mod mcp;
+mod metrics;
 mod relay;
 mod server;
 mod session;
+mod transfer;
 mod transport;
 
 use clap::Parser;
@@ -66,8 +71,17 @@ struct Cli {
     log_level: String,
 }
 
+fn main() {
+    // Processes of isolated workspaces re-run this binary to switch accounts.
+    let args: Vec<_> = std::env::args_os().collect();
+    if args.get(1).is_some_and(|arg| arg == isolation::RUN_AS_ARG) {
+        std::process::exit(isolation::run_as_main(&args[2..]));
+    }
+    serve();
+}
+
 #[tokio::main]
-async fn main() {
+async fn serve() {
     install_rustls_crypto_provider();
 
     let cli = Cli::parse();
@@ -143,21 +157,28 @@ async fn main() {
         }
     };
 
-    // Run until shutdown signal
+    // Run until shutdown signal (the first one drains, a second one forces)
     let tls_arc = Arc::new(tls_config);
-
-    tokio::select! {
-        result = wsh_server.run(tls_arc) => {
-            if let Err(e) = result {
-                error!(error = %e, "server error");
-                std::process::exit(1);
+    let sessions = wsh_server.shared_sessions();
+    let (signal_tx, signal_rx) = tokio::sync::mpsc::channel(2);
+    tokio::spawn(async move {
+        loop {
+            shutdown_signal().await;
+            if signal_tx.send(()).await.is_err() {
+                break;
             }
         }
-        _ = shutdown_signal() => {
-            info!("received shutdown signal");
-        }
+    });
+
+    if let Err(e) = wsh_server.run(tls_arc, signal_rx).await {
+        error!(error = %e, "server error");
+        std::process::exit(1);
     }
 
+    // Save session state so `wsh attach` works after restart (no-op unless
+    // [persistence] is enabled).
+    sessions.persist_all().await;
+
     info!("wsh-server stopped");
 }
 
@@ -0,0 +1,429 @@
+//! Built-in MCP tools for driving this host: command execution, file
+//! read/write and session management.
+//!
+//! Every tool needs the key's `Mcp` scope plus the scope of the operation it
+//! performs (`Exec`/`Shell` for `exec`, `FileTransfer` for file access), so
+//! an agent discovering tools only sees what its key is allowed to do. Tools
+//! run as the server user, like shell sessions, with paths and the default
+//! `cwd` resolved inside the caller's workspace.
+
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio::process::Command;
+use tracing::debug;
+use wsh_core::keys::{base64_decode, base64_encode};
+use wsh_core::messages::{McpCallPayload, McpResultPayload, McpToolSpec};
+
+use crate::auth::permissions::{KeyPermissions, SessionScope};
+use crate::isolation::Workspace;
+use crate::session::SessionManager;
+
+/// Default and maximum `exec` timeout, in seconds.
+const DEFAULT_EXEC_TIMEOUT: u64 = 60;
+const MAX_EXEC_TIMEOUT: u64 = 3600;
+
+/// Largest stdout/stderr (each) returned by `exec`, and largest `read_file`.
+const MAX_OUTPUT: usize = 1024 * 1024;
+
+/// The caller a host tool runs on behalf of.
+pub struct HostContext<'a> {
+    pub username: &'a str,
+    pub permissions: &'a KeyPermissions,
+    pub sessions: &'a SessionManager,
+    pub workspace: &'a Workspace,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum HostTool {
+    Exec,
+    ReadFile,
+    WriteFile,
+    ListSessions,
+    KillSession,
+}
+
+impl HostTool {
+    const ALL: [HostTool; 5] = [
+        HostTool::Exec,
+        HostTool::ReadFile,
+        HostTool::WriteFile,
+        HostTool::ListSessions,
+        HostTool::KillSession,
+    ];
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|tool| tool.name() == name)
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            HostTool::Exec => "exec",
+            HostTool::ReadFile => "read_file",
+            HostTool::WriteFile => "write_file",
+            HostTool::ListSessions => "list_sessions",
+            HostTool::KillSession => "kill_session",
+        }
+    }
+
+    fn permitted(self, permissions: &KeyPermissions) -> bool {
+        if !permissions.has_scope(&SessionScope::Mcp) {
+            return false;
+        }
+        match self {
+            HostTool::Exec => {
+                permissions.forced_command.is_none()
+                    && (permissions.has_scope(&SessionScope::Exec)
+                        || permissions.has_scope(&SessionScope::Shell))
+            }
+            HostTool::ReadFile | HostTool::WriteFile => {
+                permissions.has_scope(&SessionScope::FileTransfer)
+            }
+            HostTool::ListSessions | HostTool::KillSession => true,
+        }
+    }
+
+    fn spec(self) -> McpToolSpec {
+        let (description, properties, required) = match self {
+            HostTool::Exec => (
+                "Run a shell command on the host and return its output.",
+                json!({
+                    "command": {"type": "string", "description": "Command line, run with sh -c"},
+                    "cwd": {"type": "string", "description": "Working directory (default: home)"},
+                    "timeout_secs": {"type": "integer", "description": "Kill the command after this many seconds (default 60)"},
+                }),
+                vec!["command"],
+            ),
+            HostTool::ReadFile => (
+                "Read a file from the host. Binary content is returned base64-encoded.",
+                json!({
+                    "path": {"type": "string", "description": "File path (relative paths are under home)"},
+                    "offset": {"type": "integer", "description": "Byte offset to start reading at"},
+                    "length": {"type": "integer", "description": "Maximum bytes to read (default and cap: 1 MiB)"},
+                }),
+                vec!["path"],
+            ),
+            HostTool::WriteFile => (
+                "Write a file on the host, creating parent directories as needed.",
+                json!({
+                    "path": {"type": "string", "description": "File path (relative paths are under home)"},
+                    "content": {"type": "string", "description": "Data to write"},
+                    "encoding": {"type": "string", "enum": ["utf-8", "base64"], "description": "Encoding of content (default utf-8)"},
+                    "append": {"type": "boolean", "description": "Append instead of replacing the file"},
+                }),
+                vec!["path", "content"],
+            ),
+            HostTool::ListSessions => ("List your shell sessions on the host.", json!({}), vec![]),
+            HostTool::KillSession => (
+                "Terminate one of your shell sessions.",
+                json!({
+                    "session_id": {"type": "string", "description": "Session to terminate"},
+                }),
+                vec!["session_id"],
+            ),
+        };
+        McpToolSpec {
+            name: self.name().to_string(),
+            description: description.to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": properties,
+                "required": required,
+            }),
+        }
+    }
+}
+
+/// Whether `name` is one of the built-in host tools.
+pub fn is_host_tool(name: &str) -> bool {
+    HostTool::from_name(name).is_some()
+}
+
+/// Host tools the given key may use.
+pub fn list_tools(permissions: &KeyPermissions) -> Vec<McpToolSpec> {
+    HostTool::ALL
+        .into_iter()
+        .filter(|tool| tool.permitted(permissions))
+        .map(HostTool::spec)
+        .collect()
+}
+
+/// Call a host tool on behalf of `ctx`.
+pub async fn call(call: &McpCallPayload, ctx: &HostContext<'_>) -> McpResultPayload {
+    let result = match HostTool::from_name(&call.tool) {
+        None => Err(format!("unknown tool: {}", call.tool)),
+        Some(tool) if !tool.permitted(ctx.permissions) => {
+            Err(format!("{} not permitted for this key", call.tool))
+        }
+        Some(tool) => {
+            debug!(tool = %call.tool, username = %ctx.username, "calling MCP host tool");
+            let args = &call.arguments;
+            match tool {
+                HostTool::Exec => exec(args, ctx.workspace).await,
+                HostTool::ReadFile => read_file(args, ctx.workspace).await,
+                HostTool::WriteFile => write_file(args, ctx.workspace).await,
+                HostTool::ListSessions => Ok(list_sessions(ctx).await),
+                HostTool::KillSession => kill_session(args, ctx).await,
+            }
+        }
+    };
+    McpResultPayload {
+        result: result.unwrap_or_else(|e| json!({ "error": e })),
+    }
+}
+
+fn str_arg<'a>(args: &'a Value, name: &str) -> Result<&'a str, String> {
+    args.get(name)
+        .and_then(Value::as_str)
+        .ok_or_else(|| format!("missing string argument: {name}"))
+}
+
+fn path_arg(args: &Value, workspace: &Workspace) -> Result<PathBuf, String> {
+    workspace.resolve(str_arg(args, "path")?)
+}
+
+async fn exec(args: &Value, workspace: &Workspace) -> Result<Value, String> {
+    let command = str_arg(args, "command")?;
+    let cwd = match args.get("cwd").and_then(Value::as_str) {
+        Some(dir) => workspace.resolve(dir)?,
+        None if workspace.home().as_os_str().is_empty() => PathBuf::from("/"),
+        None => workspace.home().to_path_buf(),
+    };
+    let timeout_secs = args
+        .get("timeout_secs")
+        .and_then(Value::as_u64)
+        .unwrap_or(DEFAULT_EXEC_TIMEOUT)
+        .clamp(1, MAX_EXEC_TIMEOUT);
+
+    let (program, argv) = crate::isolation::run_as_command(
+        workspace.run_as()?,
+        "sh".to_string(),
+        vec!["-c".to_string(), command.to_string()],
+    )
+    .map_err(|e| e.to_string())?;
+    let mut cmd = Command::new(program);
+    cmd.args(argv).current_dir(&cwd).kill_on_drop(true);
+    let output = tokio::time::timeout(Duration::from_secs(timeout_secs), cmd.output())
+        .await
+        .map_err(|_| format!("command timed out after {timeout_secs}s"))?
+        .map_err(|e| format!("cannot run command: {e}"))?;
+
+    let truncated = output.stdout.len() > MAX_OUTPUT || output.stderr.len() > MAX_OUTPUT;
+    let text =
+        |bytes: &[u8]| String::from_utf8_lossy(&bytes[..bytes.len().min(MAX_OUTPUT)]).into_owned();
+    Ok(json!({
+        "stdout": text(&output.stdout),
+        "stderr": text(&output.stderr),
+        "exit_code": output.status.code().unwrap_or(-1),
+        "truncated": truncated,
+    }))
+}
+
+async fn read_file(args: &Value, workspace: &Workspace) -> Result<Value, String> {
+    let path = path_arg(args, workspace)?;
+    let offset = args.get("offset").and_then(Value::as_u64).unwrap_or(0);
+    let length = args
+        .get("length")
+        .and_then(Value::as_u64)
+        .map_or(MAX_OUTPUT, |n| (n as usize).min(MAX_OUTPUT));
+
+    let mut file = tokio::fs::File::open(&path)
+        .await
+        .map_err(|e| format!("cannot open {}: {e}", path.display()))?;
+    let size = file
+        .metadata()
+        .await
+        .map_err(|e| format!("cannot stat {}: {e}", path.display()))?
+        .len();
+    file.seek(std::io::SeekFrom::Start(offset))
+        .await
+        .map_err(|e| format!("cannot seek {}: {e}", path.display()))?;
+    let mut data = Vec::new();
+    file.take(length as u64)
+        .read_to_end(&mut data)
+        .await
+        .map_err(|e| format!("cannot read {}: {e}", path.display()))?;
+
+    let eof = offset + data.len() as u64 >= size;
+    let (content, encoding) = match String::from_utf8(data) {
+        Ok(text) => (text, "utf-8"),
+        Err(e) => (base64_encode(e.as_bytes()), "base64"),
+    };
+    Ok(json!({
+        "path": path.display().to_string(),
+        "size": size,
+        "offset": offset,
+        "content": content,
+        "encoding": encoding,
+        "eof": eof,
+    }))
+}
+
+async fn write_file(args: &Value, workspace: &Workspace) -> Result<Value, String> {
+    let path = path_arg(args, workspace)?;
+    let content = str_arg(args, "content")?;
+    let data = match args.get("encoding").and_then(Value::as_str) {
+        None | Some("utf-8") => content.as_bytes().to_vec(),
+        Some("base64") => base64_decode(content).ok_or("content is not valid base64")?,
+        Some(other) => return Err(format!("unsupported encoding: {other}")),
+    };
+    let append = args.get("append").and_then(Value::as_bool).unwrap_or(false);
+
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent)
+            .await
+            .map_err(|e| format!("cannot create {}: {e}", parent.display()))?;
+    }
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(append)
+        .truncate(!append)
+        .open(&path)
+        .await
+        .map_err(|e| format!("cannot open {}: {e}", path.display()))?;
+    file.write_all(&data)
+        .await
+        .map_err(|e| format!("cannot write {}: {e}", path.display()))?;
+    file.flush()
+        .await
+        .map_err(|e| format!("cannot write {}: {e}", path.display()))?;
+
+    Ok(json!({
+        "path": path.display().to_string(),
+        "bytes_written": data.len(),
+    }))
+}
+
+async fn list_sessions(ctx: &HostContext<'_>) -> Value {
+    let sessions: Vec<Value> = ctx
+        .sessions
+        .list()
+        .await
+        .into_iter()
+        .filter(|s| s.username == ctx.username)
+        .map(|s| {
+            json!({
+                "state": s.state(),
+                "session_id": s.id,
+                "name": s.name,
+                "created_at": s.created_at_secs,
+                "idle_secs": s.idle_secs,
+                "attached": s.attached_count,
+                "command": s.command,
+            })
+        })
+        .collect();
+    json!({ "sessions": sessions })
+}
+
+async fn kill_session(args: &Value, ctx: &HostContext<'_>) -> Result<Value, String> {
+    let session_id = str_arg(args, "session_id")?;
+    // Report someone else's session as missing rather than revealing it.
+    let not_found = || format!("no such session: {session_id}");
+    let owned = ctx
+        .sessions
+        .with_session(session_id, |s| Ok(s.username == ctx.username))
+        .await
+        .map_err(|_| not_found())?;
+    if !owned {
+        return Err(not_found());
+    }
+    // The output pump sees EOF and cleans the session up.
+    ctx.sessions
+        .with_session(session_id, |s| s.pty.kill())
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(json!({ "killed": session_id }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call_payload(tool: &str, arguments: Value) -> McpCallPayload {
+        McpCallPayload {
+            tool: tool.to_string(),
+            arguments,
+            progress: None,
+        }
+    }
+
+    #[test]
+    fn tools_follow_key_scopes() {
+        let full = KeyPermissions::full_access("SHA256:test".into());
+        assert_eq!(list_tools(&full).len(), HostTool::ALL.len());
+
+        let files_only = KeyPermissions::from_options(
+            "SHA256:test".into(),
+            Some("restrict,permit-mcp,permit-file-transfer"),
+        );
+        let names: Vec<String> = list_tools(&files_only)
+            .into_iter()
+            .map(|t| t.name)
+            .collect();
+        assert!(names.contains(&"read_file".to_string()));
+        assert!(!names.contains(&"exec".to_string()));
+
+        let no_mcp = KeyPermissions::from_options("SHA256:test".into(), Some("restrict"));
+        assert!(list_tools(&no_mcp).is_empty());
+    }
+
+    #[tokio::test]
+    async fn exec_and_file_round_trip() {
+        let permissions = KeyPermissions::full_access("SHA256:test".into());
+        let sessions = SessionManager::new(4, 3600, 3600);
+        let ctx = HostContext {
+            username: "alice",
+            permissions: &permissions,
+            sessions: &sessions,
+            workspace: &Workspace::unconfined(),
+        };
+
+        let result = call(&call_payload("exec", json!({"command": "echo hi"})), &ctx).await;
+        assert_eq!(result.result["stdout"], "hi\n");
+        assert_eq!(result.result["exit_code"], 0);
+
+        let dir = std::env::temp_dir().join(format!("wsh-mcp-host-{}", std::process::id()));
+        let path = dir.join("note.txt").display().to_string();
+        let result = call(
+            &call_payload("write_file", json!({"path": path, "content": "hello"})),
+            &ctx,
+        )
+        .await;
+        assert_eq!(result.result["bytes_written"], 5);
+        let result = call(
+            &call_payload("read_file", json!({"path": path, "offset": 1})),
+            &ctx,
+        )
+        .await;
+        assert_eq!(result.result["content"], "ello");
+        assert_eq!(result.result["eof"], true);
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let result = call(
+            &call_payload("kill_session", json!({"session_id": "nope"})),
+            &ctx,
+        )
+        .await;
+        assert_eq!(result.result["error"], "no such session: nope");
+    }
+
+    #[tokio::test]
+    async fn denies_tools_outside_scope() {
+        let permissions = KeyPermissions::from_options(
+            "SHA256:test".into(),
+            Some("restrict,permit-mcp,permit-file-transfer"),
+        );
+        let sessions = SessionManager::new(4, 3600, 3600);
+        let ctx = HostContext {
+            username: "alice",
+            permissions: &permissions,
+            sessions: &sessions,
+            workspace: &Workspace::unconfined(),
+        };
+        let result = call(&call_payload("exec", json!({"command": "true"})), &ctx).await;
+        assert_eq!(result.result["error"], "exec not permitted for this key");
+    }
+}
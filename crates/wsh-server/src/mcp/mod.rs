@@ -1,6 +1,8 @@
-//! MCP (Model Context Protocol) tool bridging and proxying.
+//! MCP (Model Context Protocol) server: built-in host tools, CLI tool
+//! bridging and proxying to local MCP servers.
 
 pub mod bridge;
+pub mod host;
 pub mod proxy;
 
 pub use bridge::McpBridge;
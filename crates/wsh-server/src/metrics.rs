@@ -0,0 +1,348 @@
+//! Prometheus metrics: counters collected by the server and a minimal
+//! `GET /metrics` HTTP endpoint serving them in the text exposition format.
+//!
+//! The endpoint is plain HTTP on its own bind address (see
+//! [`crate::config::MetricsSection`]) so it can stay on loopback or a
+//! private interface while the shell listeners face the internet.
+
+use std::fmt::Write as _;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{debug, info, warn};
+use wsh_core::{WshError, WshResult};
+
+/// Time a scrape has to send its request and receive the response.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Pause after a failed `accept` (e.g. out of file descriptors).
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Upper bounds (seconds) of the handshake latency histogram buckets.
+const HANDSHAKE_BUCKETS: [f64; 10] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0];
+
+/// Transport a connection arrived on, used as the `transport` label.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transport {
+    WebTransport,
+    WebSocket,
+}
+
+impl Transport {
+    const ALL: [Transport; 2] = [Transport::WebTransport, Transport::WebSocket];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Transport::WebTransport => "webtransport",
+            Transport::WebSocket => "websocket",
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Per-transport counters.
+#[derive(Debug, Default)]
+struct TransportCounters {
+    auth_failures: AtomicU64,
+    bytes_sent: AtomicU64,
+    bytes_received: AtomicU64,
+}
+
+/// Handshake latency histogram (connection accepted → AUTH_OK sent).
+#[derive(Debug, Default)]
+struct Histogram {
+    buckets: [AtomicU64; HANDSHAKE_BUCKETS.len()],
+    count: AtomicU64,
+    sum_micros: AtomicU64,
+}
+
+impl Histogram {
+    fn observe(&self, elapsed: Duration) {
+        let secs = elapsed.as_secs_f64();
+        for (bound, bucket) in HANDSHAKE_BUCKETS.iter().zip(&self.buckets) {
+            if secs <= *bound {
+                bucket.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_micros
+            .fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+    }
+}
+
+/// Counters updated by the connection handlers.
+///
+/// Gauges that the server already tracks elsewhere (active sessions, relay
+/// peers) are not duplicated here; they are passed to [`ServerMetrics::render`].
+#[derive(Debug, Default)]
+pub struct ServerMetrics {
+    transports: [TransportCounters; 2],
+    handshake: [Histogram; 2],
+}
+
+impl ServerMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count a rejected authentication attempt.
+    pub fn auth_failed(&self, transport: Transport) {
+        self.transports[transport.index()]
+            .auth_failures
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count `len` bytes written to a client.
+    pub fn sent(&self, transport: Transport, len: usize) {
+        self.transports[transport.index()]
+            .bytes_sent
+            .fetch_add(len as u64, Ordering::Relaxed);
+    }
+
+    /// Count `len` bytes read from a client.
+    pub fn received(&self, transport: Transport, len: usize) {
+        self.transports[transport.index()]
+            .bytes_received
+            .fetch_add(len as u64, Ordering::Relaxed);
+    }
+
+    /// Record how long a successful handshake took.
+    pub fn handshake_completed(&self, transport: Transport, elapsed: Duration) {
+        self.handshake[transport.index()].observe(elapsed);
+    }
+
+    /// Render all metrics in the Prometheus text exposition format.
+    pub fn render(&self, active_sessions: usize, relay_peers: usize) -> String {
+        let mut out = String::new();
+
+        gauge(
+            &mut out,
+            "wsh_active_sessions",
+            "Shell sessions currently open.",
+            active_sessions,
+        );
+        gauge(
+            &mut out,
+            "wsh_relay_peers",
+            "Peers registered for reverse connections.",
+            relay_peers,
+        );
+
+        let per_transport =
+            |out: &mut String,
+             name: &str,
+             help: &str,
+             value: fn(&TransportCounters) -> &AtomicU64| {
+                let _ = writeln!(out, "# HELP {name} {help}");
+                let _ = writeln!(out, "# TYPE {name} counter");
+                for transport in Transport::ALL {
+                    let counters = &self.transports[transport.index()];
+                    let _ = writeln!(
+                        out,
+                        "{name}{{transport=\"{}\"}} {}",
+                        transport.label(),
+                        value(counters).load(Ordering::Relaxed)
+                    );
+                }
+            };
+        per_transport(
+            &mut out,
+            "wsh_auth_failures_total",
+            "Rejected authentication attempts.",
+            |c| &c.auth_failures,
+        );
+        per_transport(
+            &mut out,
+            "wsh_bytes_sent_total",
+            "Bytes written to clients.",
+            |c| &c.bytes_sent,
+        );
+        per_transport(
+            &mut out,
+            "wsh_bytes_received_total",
+            "Bytes read from clients.",
+            |c| &c.bytes_received,
+        );
+
+        let name = "wsh_handshake_duration_seconds";
+        let _ = writeln!(
+            out,
+            "# HELP {name} Time from connection to AUTH_OK for successful handshakes."
+        );
+        let _ = writeln!(out, "# TYPE {name} histogram");
+        for transport in Transport::ALL {
+            let histogram = &self.handshake[transport.index()];
+            let label = transport.label();
+            for (bound, bucket) in HANDSHAKE_BUCKETS.iter().zip(&histogram.buckets) {
+                let _ = writeln!(
+                    out,
+                    "{name}_bucket{{transport=\"{label}\",le=\"{bound}\"}} {}",
+                    bucket.load(Ordering::Relaxed)
+                );
+            }
+            let count = histogram.count.load(Ordering::Relaxed);
+            let _ = writeln!(
+                out,
+                "{name}_bucket{{transport=\"{label}\",le=\"+Inf\"}} {count}"
+            );
+            let _ = writeln!(
+                out,
+                "{name}_sum{{transport=\"{label}\"}} {}",
+                histogram.sum_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0
+            );
+            let _ = writeln!(out, "{name}_count{{transport=\"{label}\"}} {count}");
+        }
+
+        out
+    }
+}
+
+fn gauge(out: &mut String, name: &str, help: &str, value: usize) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} gauge");
+    let _ = writeln!(out, "{name} {value}");
+}
+
+/// Bind the metrics endpoint's listener on `addr`.
+pub async fn bind(addr: SocketAddr) -> WshResult<TcpListener> {
+    let listener = TcpListener::bind(addr)
+        .await
+        .map_err(|e| WshError::Transport(format!("metrics bind {addr} failed: {e}")))?;
+    info!(addr = %addr, "metrics endpoint listening");
+    Ok(listener)
+}
+
+/// Serve `GET /metrics` on `listener`, calling `render` for each scrape.
+///
+/// Runs forever; a failed `accept` is logged and retried. Each request is
+/// answered and the connection closed (no keep-alive).
+pub async fn serve<F, Fut>(listener: TcpListener, render: F)
+where
+    F: Fn() -> Fut + Clone + Send + Sync + 'static,
+    Fut: Future<Output = String> + Send,
+{
+    loop {
+        let (stream, remote) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                warn!(error = %e, "metrics accept failed");
+                tokio::time::sleep(ACCEPT_BACKOFF).await;
+                continue;
+            }
+        };
+        let render = render.clone();
+        tokio::spawn(async move {
+            match tokio::time::timeout(REQUEST_TIMEOUT, respond(stream, render)).await {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => debug!(remote = %remote, error = %e, "metrics request failed"),
+                Err(_) => debug!(remote = %remote, "metrics request timed out"),
+            }
+        });
+    }
+}
+
+async fn respond<F, Fut>(mut stream: TcpStream, render: F) -> std::io::Result<()>
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = String>,
+{
+    // Only the request line matters; read until the end of the headers.
+    let mut request = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+        let n = stream.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        request.extend_from_slice(&buf[..n]);
+        if request.len() > 8192 {
+            break;
+        }
+    }
+
+    let request_line = request
+        .split(|&b| b == b'\n')
+        .next()
+        .map(|line| String::from_utf8_lossy(line).trim().to_string())
+        .unwrap_or_default();
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or("");
+    let path = parts.next().unwrap_or("");
+
+    let (status, content_type, body) = match (method, path.split('?').next().unwrap_or("")) {
+        ("GET", "/metrics") => (
+            "200 OK",
+            "text/plain; version=0.0.4; charset=utf-8",
+            render().await,
+        ),
+        ("GET", _) => ("404 Not Found", "text/plain", "not found\n".to_string()),
+        _ => {
+            warn!(method = %method, "unsupported metrics request method");
+            (
+                "405 Method Not Allowed",
+                "text/plain",
+                "method not allowed\n".to_string(),
+            )
+        }
+    };
+
+    let response = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_counters_and_histogram() {
+        let metrics = ServerMetrics::new();
+        metrics.auth_failed(Transport::WebSocket);
+        metrics.sent(Transport::WebTransport, 100);
+        metrics.received(Transport::WebTransport, 40);
+        metrics.handshake_completed(Transport::WebSocket, Duration::from_millis(30));
+
+        let text = metrics.render(3, 1);
+        assert!(text.contains("wsh_active_sessions 3\n"));
+        assert!(text.contains("wsh_relay_peers 1\n"));
+        assert!(text.contains("wsh_auth_failures_total{transport=\"websocket\"} 1\n"));
+        assert!(text.contains("wsh_auth_failures_total{transport=\"webtransport\"} 0\n"));
+        assert!(text.contains("wsh_bytes_sent_total{transport=\"webtransport\"} 100\n"));
+        assert!(text.contains("wsh_bytes_received_total{transport=\"webtransport\"} 40\n"));
+        assert!(text.contains(
+            "wsh_handshake_duration_seconds_bucket{transport=\"websocket\",le=\"0.025\"} 0\n"
+        ));
+        assert!(text.contains(
+            "wsh_handshake_duration_seconds_bucket{transport=\"websocket\",le=\"0.05\"} 1\n"
+        ));
+        assert!(text.contains("wsh_handshake_duration_seconds_count{transport=\"websocket\"} 1\n"));
+    }
+
+    #[tokio::test]
+    async fn serves_metrics_over_http() {
+        let listener = bind("127.0.0.1:0".parse().unwrap()).await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve(listener, || async { "wsh_up 1\n".to_string() }));
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.ends_with("\r\n\r\nwsh_up 1\n"));
+    }
+}
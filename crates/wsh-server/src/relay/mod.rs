@@ -4,4 +4,4 @@ pub mod broker;
 pub mod registry;
 
 pub use broker::RelayBroker;
-pub use registry::{PeerEntry, PeerMetadata, PeerRegistry};
+pub use registry::{PeerEntry, PeerEvent, PeerMetadata, PeerRegistry};
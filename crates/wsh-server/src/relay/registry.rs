@@ -1,18 +1,21 @@
 //! Peer registry for reverse connections.
 //!
 //! Stores connected peers indexed by their public key fingerprint,
-//! allowing other clients to discover and connect to them.
+//! allowing other clients to discover and connect to them. Peers may also
+//! register a human-readable name, and subscribers are told when peers
+//! join or leave.
 
 use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Instant;
-use tokio::sync::RwLock;
-use tracing::{debug, info};
+use tokio::sync::{broadcast, RwLock};
+use tracing::{debug, info, warn};
 use wsh_core::identity::FingerprintIndex;
 
 /// Additional metadata advertised by a reverse peer.
 #[derive(Debug, Clone)]
 pub struct PeerMetadata {
+    pub name: Option<String>,
     pub peer_type: String,
     pub shell_backend: String,
     pub supports_attach: bool,
@@ -24,6 +27,7 @@ pub struct PeerMetadata {
 impl Default for PeerMetadata {
     fn default() -> Self {
         Self {
+            name: None,
             peer_type: "host".to_string(),
             shell_backend: "pty".to_string(),
             supports_attach: false,
@@ -41,6 +45,8 @@ pub struct PeerEntry {
     pub fingerprint: String,
     /// Username of the peer.
     pub username: String,
+    /// Human-readable name, unique among registered peers.
+    pub name: Option<String>,
     /// Capabilities advertised by the peer.
     pub capabilities: Vec<String>,
     /// Canonical peer category.
@@ -63,6 +69,15 @@ pub struct PeerEntry {
     pub connection_id: u64,
 }
 
+/// A change in the set of registered peers.
+#[derive(Debug, Clone)]
+pub enum PeerEvent {
+    /// A peer registered (or re-registered with new metadata).
+    Joined(PeerEntry),
+    /// A peer unregistered or was garbage-collected.
+    Left(PeerEntry),
+}
+
 /// Registry of peers available for reverse connections.
 pub struct PeerRegistry {
     /// Peers indexed by full fingerprint.
@@ -71,38 +86,30 @@ pub struct PeerRegistry {
     index: Arc<RwLock<FingerprintIndex>>,
     /// Monotonic connection ID counter.
     next_conn_id: Arc<tokio::sync::Mutex<u64>>,
+    /// Join/leave notifications for subscribers.
+    events: broadcast::Sender<PeerEvent>,
 }
 
 impl PeerRegistry {
     /// Create a new empty peer registry.
     pub fn new() -> Self {
+        let (events, _) = broadcast::channel(64);
         Self {
             peers: Arc::new(RwLock::new(HashMap::new())),
             index: Arc::new(RwLock::new(FingerprintIndex::new())),
             next_conn_id: Arc::new(tokio::sync::Mutex::new(1)),
+            events,
         }
     }
 
-    /// Register a peer with a specific connection ID (from the server's ID space).
-    /// This ensures the registry's connection_id matches peer_senders keys.
-    /// If `server_conn_id` is `None`, generates an internal one (legacy fallback).
-    pub async fn register(
-        &self,
-        fingerprint: String,
-        username: String,
-        capabilities: Vec<String>,
-    ) -> u64 {
-        self.register_with_conn_id(
-            fingerprint,
-            username,
-            capabilities,
-            PeerMetadata::default(),
-            None,
-        )
-            .await
+    /// Receive a [`PeerEvent`] for every later join and leave.
+    pub fn subscribe(&self) -> broadcast::Receiver<PeerEvent> {
+        self.events.subscribe()
     }
 
     /// Register a peer with an explicit server-assigned connection ID.
+    /// This ensures the registry's connection_id matches peer_senders keys.
+    /// If `server_conn_id` is `None`, generates an internal one (legacy fallback).
     pub async fn register_with_conn_id(
         &self,
         fingerprint: String,
@@ -121,10 +128,22 @@ impl PeerRegistry {
             }
         };
 
+        let mut peers = self.peers.write().await;
+        let name = metadata.name.filter(|name| {
+            let taken = peers
+                .values()
+                .any(|p| p.fingerprint != fingerprint && p.name.as_deref() == Some(name.as_str()));
+            if taken {
+                warn!(name = %name, "peer name already in use, registering without it");
+            }
+            !taken
+        });
+
         let now = Instant::now();
         let entry = PeerEntry {
             fingerprint: fingerprint.clone(),
             username: username.clone(),
+            name,
             capabilities,
             peer_type: metadata.peer_type,
             shell_backend: metadata.shell_backend,
@@ -137,13 +156,14 @@ impl PeerRegistry {
             connection_id: conn_id,
         };
 
-        let mut peers = self.peers.write().await;
-        peers.insert(fingerprint.clone(), entry);
+        peers.insert(fingerprint.clone(), entry.clone());
+        drop(peers);
 
         let mut index = self.index.write().await;
         index.insert(fingerprint.clone(), username.clone());
 
         info!(fingerprint = %&fingerprint[..8.min(fingerprint.len())], username = %username, "peer registered");
+        let _ = self.events.send(PeerEvent::Joined(entry));
 
         conn_id
     }
@@ -151,10 +171,11 @@ impl PeerRegistry {
     /// Unregister a peer by fingerprint.
     pub async fn unregister(&self, fingerprint: &str) {
         let mut peers = self.peers.write().await;
-        if peers.remove(fingerprint).is_some() {
+        if let Some(entry) = peers.remove(fingerprint) {
             let mut index = self.index.write().await;
             index.remove(fingerprint);
             debug!(fingerprint = %&fingerprint[..8.min(fingerprint.len())], "peer unregistered");
+            let _ = self.events.send(PeerEvent::Left(entry));
         }
     }
 
@@ -172,8 +193,15 @@ impl PeerRegistry {
         peers.values().cloned().collect()
     }
 
-    /// Look up a peer by fingerprint prefix.
+    /// Look up a peer by `@name` or fingerprint prefix.
     pub async fn resolve(&self, prefix: &str) -> Option<PeerEntry> {
+        if let Some(name) = prefix.strip_prefix('@') {
+            let peers = self.peers.read().await;
+            return peers
+                .values()
+                .find(|p| p.name.as_deref() == Some(name))
+                .cloned();
+        }
         let index = self.index.read().await;
         match index.resolve(prefix) {
             Ok(Some((fp, _))) => {
@@ -189,9 +217,9 @@ impl PeerRegistry {
         let mut peers = self.peers.write().await;
         let mut removed = Vec::new();
 
-        peers.retain(|fp, entry| {
+        peers.retain(|_, entry| {
             if entry.last_seen.elapsed().as_secs() > max_idle_secs {
-                removed.push(fp.clone());
+                removed.push(entry.clone());
                 false
             } else {
                 true
@@ -200,13 +228,20 @@ impl PeerRegistry {
 
         if !removed.is_empty() {
             let mut index = self.index.write().await;
-            for fp in &removed {
-                index.remove(fp);
+            for entry in &removed {
+                index.remove(&entry.fingerprint);
             }
             debug!(count = removed.len(), "GC removed idle peers");
         }
 
         removed
+            .into_iter()
+            .map(|entry| {
+                let fingerprint = entry.fingerprint.clone();
+                let _ = self.events.send(PeerEvent::Left(entry));
+                fingerprint
+            })
+            .collect()
     }
 
     /// Number of registered peers.
@@ -214,3 +249,64 @@ impl PeerRegistry {
         self.peers.read().await.len()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn named(name: &str) -> PeerMetadata {
+        PeerMetadata {
+            name: Some(name.to_string()),
+            ..PeerMetadata::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn names_are_unique_and_resolvable() {
+        let registry = PeerRegistry::new();
+        registry
+            .register_with_conn_id(
+                "aaaa1111".into(),
+                "alice".into(),
+                vec![],
+                named("laptop"),
+                None,
+            )
+            .await;
+        registry
+            .register_with_conn_id(
+                "bbbb2222".into(),
+                "bob".into(),
+                vec![],
+                named("laptop"),
+                None,
+            )
+            .await;
+
+        let peer = registry.resolve("@laptop").await.unwrap();
+        assert_eq!(peer.fingerprint, "aaaa1111");
+        assert!(registry.resolve("bbbb").await.unwrap().name.is_none());
+        assert!(registry.resolve("@desktop").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn subscribers_see_joins_and_leaves() {
+        let registry = PeerRegistry::new();
+        let mut events = registry.subscribe();
+        registry
+            .register_with_conn_id(
+                "aaaa1111".into(),
+                "alice".into(),
+                vec!["shell".into()],
+                PeerMetadata::default(),
+                None,
+            )
+            .await;
+        registry.unregister("aaaa1111").await;
+
+        assert!(matches!(events.recv().await, Ok(PeerEvent::Joined(e)) if e.username == "alice"));
+        assert!(
+            matches!(events.recv().await, Ok(PeerEvent::Left(e)) if e.fingerprint == "aaaa1111")
+        );
+    }
+}
@@ -3,26 +3,41 @@
 //! Owns the server secret (for token signing), session manager, relay subsystem,
 //! and MCP bridge. Coordinates the lifecycle of all incoming connections.
 
+use crate::audit::{AuditEvent, AuditLog};
 use crate::config::ServerConfig;
 use crate::gateway::forwarder::GatewayForwarder;
 use crate::gateway::listener::ReverseListenerManager;
 use crate::gateway::policy::{GatewayPolicy, GatewayPolicyEnforcer};
 use crate::gateway::GatewayEvent;
 use crate::handshake;
+use crate::isolation::{Isolation, Workspace};
 use crate::mcp::{McpBridge, McpProxy};
-use crate::relay::{PeerMetadata, PeerRegistry, RelayBroker};
-use crate::session::SessionManager;
+use crate::metrics::{ServerMetrics, Transport};
+use crate::relay::{PeerEntry, PeerEvent, PeerMetadata, PeerRegistry, RelayBroker};
+use crate::session::persist::SessionStore;
+use crate::session::pty::SpawnOptions;
+use crate::session::recording::{
+    create_recording_dir, load_recording, prune_recordings, to_asciicast,
+};
+use crate::session::{RecordingEvent, SessionManager};
+use crate::transfer::ChunkSink;
 use crate::transport::{websocket, webtransport};
 use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::path::PathBuf;
-use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::atomic::{AtomicU32, AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
 use tokio::sync::{mpsc, RwLock};
 use tracing::{debug, info, warn};
-use wsh_core::keys::{load_authorized_keys, AuthorizedKey};
+use wsh_core::compress::{Codec, CompressionStats};
+use wsh_core::keys::{
+    add_authorized_key, load_authorized_keys, parse_authorized_keys, parse_public_key,
+    remove_authorized_key, wsh_authorized_keys_path, AuthorizedKey,
+};
 use wsh_core::messages::*;
-use wsh_core::{decode_envelope, fingerprint, frame_encode, verify_token, WshError, WshResult};
+use wsh_core::{
+    decode_envelope, fingerprint, frame_encode, valid_peer_name, verify_token, WshError, WshResult,
+};
 
 /// Per-connection context threaded through the session loop.
 struct ConnectionContext {
@@ -30,6 +45,8 @@ struct ConnectionContext {
     username: String,
     /// Key fingerprint (from auth).
     fingerprint: String,
+    /// Fingerprint of the CA that signed the user's certificate, if any.
+    ca_fingerprint: Option<String>,
     /// Session ID assigned during auth.
     session_id: String,
     /// Session token.
@@ -38,6 +55,33 @@ struct ConnectionContext {
     peer_tx: mpsc::Sender<Envelope>,
     /// Connection ID from peer registry (set when registered as reverse peer).
     conn_id: Option<u64>,
+    /// Whether the client offered its key agent (`agent-forward` in HELLO).
+    agent_forward_offered: bool,
+    /// Forwarded agent socket, created with the first PTY/exec channel.
+    agent_forwarder: Option<crate::agent_forward::AgentForwarder>,
+    /// Directory client paths and new shells resolve against.
+    workspace: Workspace,
+    /// PTY/exec and `TcpForward` channels opened or resumed on this
+    /// connection and not yet closed or exited.
+    channels: std::collections::HashSet<u32>,
+    /// Sessions attached to (`Attach`) on this connection and not detached.
+    attached: std::collections::HashSet<String>,
+    /// Gateway connections (outbound or accepted inbound) opened on this
+    /// connection and not yet closed.
+    gateways: std::collections::HashSet<u32>,
+    /// Reverse tunnel listeners opened on this connection and not yet closed.
+    listeners: std::collections::HashSet<u32>,
+    /// `TcpForward` channel whose connect is still in flight, if any.
+    forward_connecting: Option<u32>,
+    /// `Open` and `Resume` requests held back until `forward_connecting`
+    /// is answered, so replies reach the client in request order.
+    deferred_opens: std::collections::VecDeque<Envelope>,
+    /// Codec negotiated in HELLO for PTY output and file chunks.
+    compression: Option<Codec>,
+    /// Byte and CPU counters for this connection's compressed frames.
+    compression_stats: Arc<CompressionStats>,
+    /// MCP tool calls running for this connection.
+    mcp_calls: Arc<AtomicUsize>,
 }
 
 /// A share link entry for session sharing.
@@ -123,6 +167,163 @@ struct TerminalConfigState {
     options: serde_json::Value,
 }
 
+/// Where a PTY session's output currently goes. Replaced when a client
+/// resumes the session on a new connection.
+#[derive(Clone, Debug)]
+struct PtySink {
+    channel_id: u32,
+    peer_tx: mpsc::Sender<Envelope>,
+    /// Codec negotiated by the connection the output goes to.
+    compression: Option<Codec>,
+    compression_stats: Arc<CompressionStats>,
+}
+
+impl PtySink {
+    /// Sink for output sent to `channel_id` on the connection of `ctx`.
+    fn new(channel_id: u32, ctx: &ConnectionContext) -> Self {
+        Self {
+            channel_id,
+            peer_tx: ctx.peer_tx.clone(),
+            compression: ctx.compression,
+            compression_stats: ctx.compression_stats.clone(),
+        }
+    }
+
+    /// Wrap PTY output for this sink, compressed if negotiated.
+    fn session_data(&self, data: Vec<u8>) -> Envelope {
+        let (data, compression) = self.compression_stats.encode(self.compression, data);
+        Envelope {
+            msg_type: MsgType::SessionData,
+            payload: Payload::SessionData(SessionDataPayload {
+                channel_id: self.channel_id,
+                data,
+                compression,
+            }),
+        }
+    }
+}
+
+/// Interval between `McpProgress` messages for calls that ask for them.
+const MCP_PROGRESS_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// Everything an MCP tool call needs, detached from the connection so calls
+/// can run without holding up its control loop.
+struct McpCaller {
+    username: String,
+    fingerprint: String,
+    permissions: crate::auth::permissions::KeyPermissions,
+    workspace: Workspace,
+    sessions: Arc<SessionManager>,
+    bridge: Arc<RwLock<McpBridge>>,
+    proxy: Arc<RwLock<McpProxy>>,
+    audit: Arc<AuditLog>,
+    /// Keeps the call counted in the connection's `mcp_calls` while alive.
+    _in_flight: McpInFlight,
+}
+
+/// One running MCP call, counted in a connection's `mcp_calls` until dropped.
+struct McpInFlight(Arc<AtomicUsize>);
+
+impl McpInFlight {
+    fn new(calls: &Arc<AtomicUsize>) -> Self {
+        calls.fetch_add(1, Ordering::Relaxed);
+        Self(calls.clone())
+    }
+}
+
+impl Drop for McpInFlight {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl McpCaller {
+    /// Run a call (host tools first, then bridge, then proxy) and audit it.
+    async fn call(&self, p: &McpCallPayload) -> McpResultPayload {
+        let result = if !self
+            .permissions
+            .has_scope(&crate::auth::permissions::SessionScope::Mcp)
+        {
+            McpResultPayload {
+                result: serde_json::json!({
+                    "error": "MCP tools not permitted for this key",
+                }),
+            }
+        } else if crate::mcp::host::is_host_tool(&p.tool) {
+            let host_ctx = crate::mcp::host::HostContext {
+                username: &self.username,
+                permissions: &self.permissions,
+                sessions: &self.sessions,
+                workspace: &self.workspace,
+            };
+            crate::mcp::host::call(p, &host_ctx).await
+        } else {
+            let bridge = self.bridge.read().await;
+            if bridge.has_tool(&p.tool) {
+                bridge.call(p).await
+            } else {
+                drop(bridge);
+                let proxy = self.proxy.read().await;
+                proxy.call(p).await
+            }
+        };
+        self.audit
+            .record(AuditEvent::McpCall {
+                username: self.username.clone(),
+                fingerprint: self.fingerprint.clone(),
+                tool: p.tool.clone(),
+                command: (p.tool == "exec")
+                    .then(|| p.arguments.get("command")?.as_str().map(str::to_string))
+                    .flatten(),
+                success: result.result.get("error").is_none(),
+            })
+            .await;
+        result
+    }
+
+    /// Run a call and send its `McpResult` to `peer_tx`.
+    async fn call_and_reply(self, p: McpCallPayload, peer_tx: mpsc::Sender<Envelope>) {
+        let result = self.call(&p).await;
+        let _ = peer_tx
+            .send(Envelope {
+                msg_type: MsgType::McpResult,
+                payload: Payload::McpResult(result),
+            })
+            .await;
+    }
+
+    /// Run a call, sending `McpProgress` to `peer_tx` every
+    /// [`MCP_PROGRESS_INTERVAL`] until it finishes, then the `McpResult`.
+    async fn call_with_progress(self, p: McpCallPayload, peer_tx: mpsc::Sender<Envelope>) {
+        let started = std::time::Instant::now();
+        let mut ticker = tokio::time::interval(MCP_PROGRESS_INTERVAL);
+        ticker.tick().await;
+        let call = self.call(&p);
+        tokio::pin!(call);
+        let result = loop {
+            tokio::select! {
+                result = &mut call => break result,
+                _ = ticker.tick() => {
+                    let progress = Envelope {
+                        msg_type: MsgType::McpProgress,
+                        payload: Payload::McpProgress(McpProgressPayload {
+                            tool: p.tool.clone(),
+                            elapsed_ms: started.elapsed().as_millis() as u64,
+                        }),
+                    };
+                    let _ = peer_tx.send(progress).await;
+                }
+            }
+        };
+        let _ = peer_tx
+            .send(Envelope {
+                msg_type: MsgType::McpResult,
+                payload: Payload::McpResult(result),
+            })
+            .await;
+    }
+}
+
 /// Per-session echo tracking for predictive local echo.
 #[derive(Clone, Debug)]
 struct EchoTracker {
@@ -138,8 +339,14 @@ pub struct WshServer {
     config: ServerConfig,
     /// HMAC secret for session tokens.
     secret: Vec<u8>,
-    /// Authorized keys loaded from disk.
-    authorized_keys: Vec<AuthorizedKey>,
+    /// Authorized keys loaded from disk, reloaded after remote edits.
+    authorized_keys: std::sync::RwLock<Arc<Vec<AuthorizedKey>>>,
+    /// Home directory whose `.wsh`/`.ssh` authorized_keys are used.
+    keys_home: PathBuf,
+    /// Host key and certificate presented in CHALLENGE, if configured.
+    host_identity: Option<crate::auth::host_cert::HostIdentity>,
+    /// TOTP secrets for users who need a second factor.
+    totp: crate::auth::totp::TotpVerifier,
     /// Session manager.
     sessions: Arc<SessionManager>,
     /// Peer registry for reverse connections.
@@ -158,6 +365,8 @@ pub struct WshServer {
     reverse_listener: Arc<ReverseListenerManager>,
     /// Whether gateway is enabled.
     gateway_enabled: bool,
+    /// Resumable chunked file transfers.
+    transfers: Arc<crate::transfer::TransferManager>,
     /// Per-connection outbound senders, keyed by connection_id.
     /// Used to forward ReverseConnect messages to specific peers.
     peer_senders: Arc<RwLock<HashMap<u64, mpsc::Sender<Envelope>>>>,
@@ -189,6 +398,14 @@ pub struct WshServer {
     /// Channel-to-session mapping: channel_id → session_id.
     /// Used by Close/Resize to operate on the correct session.
     channel_sessions: Arc<RwLock<HashMap<u32, String>>>,
+    /// Output destination of each running PTY pump: session_id → PtySink.
+    pty_sinks: Arc<RwLock<HashMap<String, PtySink>>>,
+    /// Counters exported on the Prometheus endpoint.
+    metrics: Arc<ServerMetrics>,
+    /// Audit trail of auth attempts, sessions and file transfers.
+    audit: Arc<AuditLog>,
+    /// Per-user workspace policy, when `[isolation]` is enabled.
+    isolation: Option<Isolation>,
     /// Relay pairs: maps conn_id → partner conn_id for bidirectional relay.
     /// When a ReverseConnect bridge is established between a CLI client and a
     /// browser peer, both directions are stored here so that forwardable
@@ -224,12 +441,54 @@ impl WshServer {
             info!(count = authorized_keys.len(), "loaded authorized keys");
         }
 
+        // Host key, certificate and the key being rotated out
+        let host_identity = match &config.host_key_path {
+            Some(key) => {
+                let cert = config.host_certificate_path.as_deref();
+                let mut identity = crate::auth::host_cert::HostIdentity::load(key, cert)?;
+                info!(
+                    fingerprint = %identity.fingerprint(),
+                    certificate = cert.is_some(),
+                    "loaded host key"
+                );
+                if let Some(previous) = &config.previous_host_key_path {
+                    identity = identity.with_previous(previous)?;
+                    info!(
+                        previous = %identity.previous_fingerprint().unwrap_or_default(),
+                        "rotating host key; the previous key vouches for the new one"
+                    );
+                }
+                Some(identity)
+            }
+            None => {
+                if config.host_certificate_path.is_some() {
+                    warn!("[auth] host_certificate needs host_key; ignoring it");
+                }
+                if config.previous_host_key_path.is_some() {
+                    warn!("[auth] previous_host_key needs host_key; ignoring it");
+                }
+                None
+            }
+        };
+
+        // Second factor
+        let totp = crate::auth::totp::TotpVerifier::new(&config.totp_secrets)?;
+        if !config.totp_secrets.is_empty() {
+            info!(
+                count = config.totp_secrets.len(),
+                "TOTP required for configured users"
+            );
+        }
+
         // Session manager
-        let sessions = Arc::new(SessionManager::new(
-            config.max_sessions,
-            config.session_ttl,
-            config.idle_timeout,
-        ));
+        let mut session_manager =
+            SessionManager::new(config.max_sessions, config.session_ttl, config.idle_timeout)
+                .with_scrollback_size(config.scrollback_size);
+        if config.persist_sessions {
+            let store = SessionStore::new(config.persist_dir.clone(), config.persist_scrollback)?;
+            session_manager = session_manager.with_store(store, config.persist_ttl);
+        }
+        let sessions = Arc::new(session_manager);
 
         // Relay
         let peer_registry = Arc::new(PeerRegistry::new());
@@ -239,29 +498,64 @@ impl WshServer {
         let mcp_bridge = Arc::new(RwLock::new(McpBridge::new()));
         let mcp_proxy = Arc::new(RwLock::new(McpProxy::new()));
 
-        // Recording directory
-        let recording_dir = dirs::home_dir().map(|h| h.join(".wsh").join("recordings"));
+        // Recording directory (only when server-side recording is enabled)
+        let recording_dir = config
+            .recording_enabled
+            .then(|| config.recording_dir.clone());
         if let Some(ref dir) = recording_dir {
-            if let Err(e) = std::fs::create_dir_all(dir) {
+            if let Err(e) = create_recording_dir(dir) {
                 warn!(path = %dir.display(), error = %e, "could not create recordings dir");
             }
         }
 
+        // Audit log (only when [audit] is enabled)
+        let audit = Arc::new(match &config.audit_path {
+            Some(path) => {
+                info!(path = %path.display(), "audit log enabled");
+                AuditLog::new(path.clone(), config.audit_max_size, config.audit_max_files)
+            }
+            None => AuditLog::disabled(),
+        });
+
+        // Per-user workspaces (only when [isolation] is enabled)
+        let isolation = match &config.isolation_root {
+            Some(root) => {
+                info!(root = %root.display(), mapped = config.isolation_users.len(), "user isolation enabled");
+                Some(Isolation::new(root.clone(), &config.isolation_users)?)
+            }
+            None => None,
+        };
+
         // Gateway
         let gateway_policy = GatewayPolicy {
             allowed_destinations: config.gateway_allowed_destinations.clone(),
             max_connections: config.gateway_max_connections,
             enable_reverse_tunnels: config.gateway_enable_reverse_tunnels,
+            allow_unix_sockets: config.gateway_allow_unix_sockets,
+            allowed_unix_sockets: config.gateway_allowed_unix_sockets.clone(),
         };
         let policy_enforcer = Arc::new(GatewayPolicyEnforcer::new(gateway_policy));
         let gateway_forwarder = Arc::new(GatewayForwarder::new(policy_enforcer.clone()));
-        let reverse_listener = Arc::new(ReverseListenerManager::new(policy_enforcer));
+        let next_channel_id = Arc::new(AtomicU32::new(1));
+        let reverse_listener = Arc::new(ReverseListenerManager::new(
+            policy_enforcer,
+            next_channel_id.clone(),
+        ));
         let gateway_enabled = config.gateway_enabled;
 
+        // Auth rate limits and bans, per [limits]
+        let rate_limits = Arc::new(tokio::sync::Mutex::new(crate::auth::ServerRateLimits::new(
+            config.auth_attempts_per_minute,
+            config.auth_ban_secs,
+        )));
+
         Ok(Self {
             config,
             secret,
-            authorized_keys,
+            authorized_keys: std::sync::RwLock::new(Arc::new(authorized_keys)),
+            keys_home: home,
+            host_identity,
+            totp,
             sessions,
             peer_registry,
             relay_broker,
@@ -271,10 +565,9 @@ impl WshServer {
             gateway_forwarder,
             reverse_listener,
             gateway_enabled,
+            transfers: Arc::new(crate::transfer::TransferManager::new()),
             peer_senders: Arc::new(RwLock::new(HashMap::new())),
-            rate_limits: Arc::new(tokio::sync::Mutex::new(
-                crate::auth::ServerRateLimits::default(),
-            )),
+            rate_limits,
             shutdown_tx: tokio::sync::broadcast::channel(1).0,
             guest_tokens: Arc::new(RwLock::new(HashMap::new())),
             session_acls: Arc::new(RwLock::new(HashMap::new())),
@@ -287,16 +580,34 @@ impl WshServer {
             share_entries: Arc::new(RwLock::new(HashMap::new())),
             conn_session_map: Arc::new(RwLock::new(HashMap::new())),
             channel_sessions: Arc::new(RwLock::new(HashMap::new())),
+            pty_sinks: Arc::new(RwLock::new(HashMap::new())),
+            metrics: Arc::new(ServerMetrics::new()),
+            audit,
+            isolation,
             relay_pairs: Arc::new(RwLock::new(HashMap::new())),
             pending_relay_pairs: Arc::new(RwLock::new(HashMap::new())),
             next_conn_id: Arc::new(AtomicU64::new(1)),
-            next_channel_id: Arc::new(AtomicU32::new(1)),
+            next_channel_id,
         })
     }
 
+    /// Shared handle to the session manager (used to persist state on shutdown).
+    pub fn shared_sessions(&self) -> Arc<SessionManager> {
+        self.sessions.clone()
+    }
+
     /// Start listening on both WebTransport and WebSocket.
-    pub async fn run(self, tls_config: Arc<rustls::ServerConfig>) -> WshResult<()> {
+    ///
+    /// Runs until the listeners close or `signals` fires. On a signal the
+    /// server stops accepting connections and drains (see [`Self::drain`]);
+    /// a second signal cuts the drain short.
+    pub async fn run(
+        self,
+        tls_config: Arc<rustls::ServerConfig>,
+        mut signals: mpsc::Receiver<()>,
+    ) -> WshResult<()> {
         let server = Arc::new(self);
+        server.sessions.restore().await;
 
         let quic_addr: SocketAddr = format!("0.0.0.0:{}", server.config.port)
             .parse()
@@ -333,6 +644,8 @@ impl WshServer {
         let gc_channel_sessions = server.channel_sessions.clone();
         let gc_relay_pairs = server.relay_pairs.clone();
         let gc_pending_relay_pairs = server.pending_relay_pairs.clone();
+        let gc_recording_dir = server.recording_dir.clone();
+        let recording_retention_days = server.config.recording_retention_days;
         let idle_warning_grace: u64 = 300; // Warn 5 minutes before idle timeout
         tokio::spawn(async move {
             let mut interval = tokio::time::interval(std::time::Duration::from_secs(60));
@@ -377,6 +690,7 @@ impl WshServer {
                 }
 
                 gc_sessions.gc().await;
+                gc_sessions.persist_all().await;
                 gc_registry.gc(3600).await;
 
                 // GC expired guest tokens
@@ -397,6 +711,17 @@ impl WshServer {
                     shares.retain(|_, s| s.created.elapsed().as_secs() < s.ttl);
                 }
 
+                // Prune recordings past the retention period
+                if let Some(dir) = gc_recording_dir.as_deref() {
+                    if recording_retention_days > 0 {
+                        let max_age =
+                            std::time::Duration::from_secs(recording_retention_days * 86400);
+                        if let Err(e) = prune_recordings(dir, max_age).await {
+                            warn!(path = %dir.display(), error = %e, "recording retention sweep failed");
+                        }
+                    }
+                }
+
                 // GC rate limiters periodically
                 {
                     let mut limits = gc_rate_limits.lock().await;
@@ -481,6 +806,24 @@ impl WshServer {
             }
         });
 
+        // Prometheus endpoint, when [metrics] is enabled
+        if let Some(addr) = server.config.metrics_bind {
+            let srv = server.clone();
+            tokio::spawn(async move {
+                let render = move || {
+                    let srv = srv.clone();
+                    async move {
+                        srv.metrics
+                            .render(srv.sessions.count().await, srv.peer_registry.count().await)
+                    }
+                };
+                match crate::metrics::bind(addr).await {
+                    Ok(listener) => crate::metrics::serve(listener, render).await,
+                    Err(e) => warn!(error = %e, "metrics endpoint not started"),
+                }
+            });
+        }
+
         info!(
             webtransport_port = server.config.port,
             websocket_port = server.config.port,
@@ -489,12 +832,16 @@ impl WshServer {
         );
 
         // Accept connections from both transports
+        let mut draining = false;
         loop {
             tokio::select! {
                 Some(wt_conn) = wt_rx.recv() => {
                     let srv = server.clone();
                     tokio::spawn(async move {
                         if let Err(e) = srv.handle_webtransport(wt_conn).await {
+                            if matches!(e, WshError::AuthFailed(_)) {
+                                srv.metrics.auth_failed(Transport::WebTransport);
+                            }
                             warn!(error = %e, "WebTransport connection error");
                         }
                     });
@@ -503,10 +850,18 @@ impl WshServer {
                     let srv = server.clone();
                     tokio::spawn(async move {
                         if let Err(e) = srv.handle_websocket(ws_conn).await {
+                            if matches!(e, WshError::AuthFailed(_)) {
+                                srv.metrics.auth_failed(Transport::WebSocket);
+                            }
                             warn!(error = %e, "WebSocket connection error");
                         }
                     });
                 }
+                Some(()) = signals.recv() => {
+                    info!("received shutdown signal");
+                    draining = true;
+                    break;
+                }
                 else => {
                     info!("all listeners closed, shutting down");
                     break;
@@ -514,13 +869,76 @@ impl WshServer {
             }
         }
 
+        if draining {
+            // Refuse new connections but keep serving the existing ones.
+            drop(wt_rx);
+            drop(ws_rx);
+            tokio::select! {
+                _ = server.drain() => {}
+                Some(()) = signals.recv() => {
+                    warn!("received second shutdown signal, skipping drain");
+                }
+            }
+        }
+
         // Broadcast shutdown to all connected clients
         info!("broadcasting shutdown to connected clients");
         let _ = server.shutdown_tx.send(());
+        if draining && !server.peer_senders.read().await.is_empty() {
+            // Give connection tasks a moment to deliver the notice.
+            tokio::time::sleep(std::time::Duration::from_millis(250)).await;
+        }
 
         Ok(())
     }
 
+    /// Let connected clients finish before shutting down.
+    ///
+    /// Sends every connection a `DRAIN` notice, then waits until they have
+    /// all disconnected or `drain_timeout` elapses. Sessions stay alive
+    /// throughout, so detached ones are still around to be persisted.
+    async fn drain(&self) {
+        let timeout = self.config.drain_timeout;
+        let connections: Vec<mpsc::Sender<Envelope>> =
+            self.peer_senders.read().await.values().cloned().collect();
+        if timeout == 0 || connections.is_empty() {
+            return;
+        }
+        info!(
+            connections = connections.len(),
+            timeout_secs = timeout,
+            "draining connections"
+        );
+        let notice = Envelope {
+            msg_type: MsgType::Drain,
+            payload: Payload::Drain(DrainPayload {
+                reason: "server shutting down".into(),
+                deadline_secs: timeout,
+            }),
+        };
+        for tx in connections {
+            let _ = tx.try_send(notice.clone());
+        }
+
+        let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(timeout);
+        let mut tick = tokio::time::interval(std::time::Duration::from_secs(1));
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep_until(deadline) => {
+                    let remaining = self.peer_senders.read().await.len();
+                    info!(remaining, "drain period over, closing connections");
+                    return;
+                }
+                _ = tick.tick() => {
+                    if self.peer_senders.read().await.is_empty() {
+                        info!("all connections drained");
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
     /// Handle a WebTransport connection through the auth handshake.
     async fn handle_webtransport(
         &self,
@@ -528,6 +946,7 @@ impl WshServer {
     ) -> WshResult<()> {
         let remote = conn.remote_addr;
         info!(remote = %remote, "handling WebTransport connection");
+        let started = std::time::Instant::now();
 
         // Accept the first bidirectional stream as the control channel.
         let (mut send, mut recv) = conn
@@ -550,13 +969,16 @@ impl WshServer {
         };
 
         // Send SERVER_HELLO + CHALLENGE
-        let server_fingerprints: Vec<String> = self
-            .authorized_keys
-            .iter()
-            .map(|k| k.fingerprint.clone())
-            .collect();
-        let features = self.build_feature_list();
-        let hello_result = handshake::handle_hello(&hello, &server_fingerprints, Some(&features))?;
+        let server_fingerprints = self.server_fingerprints();
+        let compression = self.negotiate_compression(&hello);
+        let mut features = self.build_feature_list();
+        features.extend(compression.map(Codec::feature));
+        let mut hello_result =
+            handshake::handle_hello(&hello, &server_fingerprints, Some(&features))?;
+        if let Some(host) = &self.host_identity {
+            // "pending": see the transcript note below.
+            handshake::add_host_proof(&mut hello_result.challenge, host, "pending");
+        }
 
         // Deliberately skip SERVER_HELLO and send only CHALLENGE — sending
         // both back-to-back on one QUIC stream (no message-boundary framing
@@ -592,19 +1014,29 @@ impl WshServer {
                 return Err(WshError::InvalidMessage("expected AUTH message".into()));
             }
         };
+        let audit_auth = |outcome: Result<&str, &str>| {
+            AuditEvent::auth(
+                Transport::WebTransport,
+                remote,
+                &hello.username,
+                &auth.method,
+                outcome,
+            )
+        };
 
         // Rate limit check (WebTransport)
         {
             let ip = remote.ip();
             let rate_limited = {
                 let mut limits = self.rate_limits.lock().await;
-                !limits.check_auth(&ip)
+                limits.check_auth(&ip).err()
             };
-            if rate_limited {
-                let fail = handshake::build_auth_fail("rate limited: too many auth attempts");
+            if let Some(reason) = rate_limited {
+                self.audit.record(audit_auth(Err(&reason))).await;
+                let fail = handshake::build_auth_fail(&reason);
                 let fail_frame = frame_encode(&fail)?;
                 let _ = send.write_all(&fail_frame).await;
-                return Err(WshError::AuthFailed("rate limited".into()));
+                return Err(WshError::AuthFailed(reason));
             }
         }
 
@@ -614,12 +1046,16 @@ impl WshServer {
                 Some(ref password) => {
                     if let Some(expected_hash) = self.config.password_hashes.get(&hello.username) {
                         if !handshake::verify_password_hash(password, expected_hash) {
+                            self.auth_failed(remote.ip(), audit_auth(Err("invalid password")))
+                                .await;
                             let fail = handshake::build_auth_fail("invalid password");
                             let fail_frame = frame_encode(&fail)?;
                             let _ = send.write_all(&fail_frame).await;
                             return Err(WshError::AuthFailed("invalid password".into()));
                         }
                     } else {
+                        self.auth_failed(remote.ip(), audit_auth(Err("unknown user")))
+                            .await;
                         let fail = handshake::build_auth_fail("unknown user");
                         let fail_frame = frame_encode(&fail)?;
                         let _ = send.write_all(&fail_frame).await;
@@ -629,6 +1065,8 @@ impl WshServer {
                     }
                 }
                 None => {
+                    self.auth_failed(remote.ip(), audit_auth(Err("password required")))
+                        .await;
                     let fail = handshake::build_auth_fail("password required");
                     let fail_frame = frame_encode(&fail)?;
                     let _ = send.write_all(&fail_frame).await;
@@ -645,11 +1083,15 @@ impl WshServer {
         // AuthResult (and thus AUTH_OK/ctx.session_id below); that's fine,
         // per-connection bookkeeping elsewhere is keyed by the separate
         // integer conn_id, not this string.
+        let context = handshake::AuthContext {
+            nonce: &hello_result.nonce,
+            session_id: "pending",
+            username: &hello.username,
+        };
         match handshake::verify_auth(
             &auth,
-            &hello_result.nonce,
-            "pending",
-            &self.authorized_keys,
+            &context,
+            &self.authorized_keys(),
             &self.secret,
             self.config.session_ttl,
             self.config.allow_pubkey,
@@ -657,6 +1099,24 @@ impl WshServer {
         ) {
             Ok(mut result) => {
                 result.username = hello.username.clone();
+
+                // Second factor
+                if self.totp.is_required(&result.username) {
+                    let request_frame = frame_encode(&handshake::build_totp_request())?;
+                    send.write_all(&request_frame).await.map_err(|e| {
+                        WshError::Transport(format!("WebTransport write failed: {e}"))
+                    })?;
+                    let reply = decode_envelope(&read_webtransport_frame(&mut recv).await?)?;
+                    if let Err(e) = self.check_totp(&result.username, &reply) {
+                        self.auth_failed(remote.ip(), audit_auth(Err(&e.to_string())))
+                            .await;
+                        let fail = handshake::build_auth_fail(&e.to_string());
+                        let fail_frame = frame_encode(&fail)?;
+                        let _ = send.write_all(&fail_frame).await;
+                        return Err(e);
+                    }
+                }
+
                 let ok = handshake::build_auth_ok(
                     &result.session_id,
                     &result.token,
@@ -666,6 +1126,8 @@ impl WshServer {
                 send.write_all(&ok_frame)
                     .await
                     .map_err(|e| WshError::Transport(format!("WebTransport write failed: {e}")))?;
+                self.metrics
+                    .handshake_completed(Transport::WebTransport, started.elapsed());
 
                 info!(
                     remote = %remote,
@@ -673,6 +1135,7 @@ impl WshServer {
                     session_id = %result.session_id,
                     "WebTransport auth OK"
                 );
+                self.audit.record(audit_auth(Ok(&result.fingerprint))).await;
 
                 let (peer_tx, peer_rx) = mpsc::channel::<Envelope>(64);
                 // Assign a unique conn_id and register in peer_senders/conn_session_map
@@ -686,18 +1149,33 @@ impl WshServer {
                     .write()
                     .await
                     .insert(conn_id, result.session_id.clone());
+                let workspace = self.workspace_for(&result.username, &result.fingerprint)?;
                 let mut ctx = ConnectionContext {
                     username: result.username.clone(),
                     fingerprint: result.fingerprint.clone(),
+                    ca_fingerprint: result.ca_fingerprint.clone(),
                     session_id: result.session_id.clone(),
                     token: result.token.clone(),
                     peer_tx,
                     conn_id: Some(conn_id),
+                    agent_forward_offered: hello.features.iter().any(|f| f == "agent-forward"),
+                    agent_forwarder: None,
+                    workspace,
+                    channels: Default::default(),
+                    attached: Default::default(),
+                    gateways: Default::default(),
+                    listeners: Default::default(),
+                    forward_connecting: None,
+                    deferred_opens: Default::default(),
+                    compression,
+                    compression_stats: Default::default(),
+                    mcp_calls: Default::default(),
                 };
 
                 // Session message loop
-                self.session_loop_quic(&mut send, &mut recv, &mut ctx, peer_rx)
-                    .await?;
+                let loop_result = self
+                    .session_loop_quic(&mut send, &mut recv, &mut ctx, peer_rx)
+                    .await;
 
                 // Cleanup: unregister peer if registered
                 if let Some(cid) = ctx.conn_id {
@@ -706,8 +1184,14 @@ impl WshServer {
                     self.clear_relay_links(cid).await;
                 }
                 self.peer_registry.unregister(&ctx.fingerprint).await;
+                self.transfers.release_owner(&ctx.session_id).await;
+                self.release_forwards(&mut ctx).await;
+                log_compression(&ctx);
+                loop_result?;
             }
             Err(e) => {
+                self.auth_failed(remote.ip(), audit_auth(Err(&e.to_string())))
+                    .await;
                 let fail = handshake::build_auth_fail(&e.to_string());
                 let fail_frame = frame_encode(&fail)?;
                 let _ = send.write_all(&fail_frame).await;
@@ -718,6 +1202,26 @@ impl WshServer {
         Ok(())
     }
 
+    /// Audit a failed auth attempt and count it toward the per-IP limit.
+    async fn auth_failed(&self, ip: std::net::IpAddr, event: AuditEvent) {
+        self.rate_limits.lock().await.record_auth_failure(&ip);
+        self.audit.record(event).await;
+    }
+
+    /// Check the TOTP code a client sent in answer to AUTH_METHODS.
+    fn check_totp(&self, username: &str, reply: &Envelope) -> WshResult<()> {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let result =
+            handshake::totp_code(reply).and_then(|code| self.totp.verify(username, code, now));
+        if let Err(e) = &result {
+            warn!(username = %username, error = %e, "TOTP check failed");
+        }
+        result
+    }
+
     /// Spawn a background task that pumps PTY output to the client as
     /// `SessionData` control messages, and sends `Exit` + `Close` once the
     /// child process terminates.
@@ -730,17 +1234,25 @@ impl WshServer {
     /// existing control-channel envelope path via `peer_tx` — the same
     /// sender `session_loop_ws`/`session_loop_quic` already drain for
     /// gateway data and relay-forwarded messages.
-    fn spawn_pty_output_pump(
-        &self,
-        session_id: String,
-        channel_id: u32,
-        peer_tx: mpsc::Sender<Envelope>,
-    ) {
+    ///
+    /// The pump outlives the connection: while no client is connected it
+    /// keeps draining the PTY into the ring buffer, and `Resume` points it at
+    /// the new connection's `PtySink`.
+    fn spawn_pty_output_pump(&self, session_id: String, sink: PtySink) {
+        let channel_id = sink.channel_id;
         let sessions = self.sessions.clone();
+        let pty_sinks = self.pty_sinks.clone();
+        let audit = self.audit.clone();
         tokio::spawn(async move {
-            let (reader, child_handle) = match sessions
+            let (reader, child_handle, recorder, username, fingerprint) = match sessions
                 .with_session(&session_id, |session| {
-                    Ok((session.pty.reader(), session.pty.child_handle()))
+                    Ok((
+                        session.pty.reader(),
+                        session.pty.child_handle(),
+                        session.recorder.clone(),
+                        session.username.clone(),
+                        session.fingerprint.clone(),
+                    ))
                 })
                 .await
             {
@@ -750,7 +1262,14 @@ impl WshServer {
                     return;
                 }
             };
+            // A Resume may already have pointed the session elsewhere.
+            pty_sinks
+                .write()
+                .await
+                .entry(session_id.clone())
+                .or_insert(sink);
 
+            let mut detached = false;
             loop {
                 let reader = reader.clone();
                 let read_result = tokio::task::spawn_blocking(move || {
@@ -776,18 +1295,27 @@ impl WshServer {
                     break;
                 }
 
-                sessions.touch(&session_id).await;
+                // Hold the sink while buffering and sending so a concurrent
+                // Resume sees each chunk either in its replay or live, never both.
+                let sinks = pty_sinks.read().await;
+                sessions.push_output(&session_id, &buf[..n]).await;
+                if let Some(recorder) = &recorder {
+                    recorder
+                        .record(RecordingEvent::Output(buf[..n].to_vec()))
+                        .await;
+                }
 
-                let data_msg = Envelope {
-                    msg_type: MsgType::SessionData,
-                    payload: Payload::SessionData(SessionDataPayload {
-                        channel_id,
-                        data: buf[..n].to_vec(),
-                    }),
+                let Some(sink) = sinks.get(&session_id) else {
+                    continue;
                 };
-                if peer_tx.send(data_msg).await.is_err() {
-                    debug!(session_id = %session_id, "PTY output pump: peer channel closed, stopping");
-                    return;
+                let data_msg = sink.session_data(buf[..n].to_vec());
+                match sink.peer_tx.send(data_msg).await {
+                    Ok(()) => detached = false,
+                    Err(_) if !detached => {
+                        debug!(session_id = %session_id, "PTY output pump: client gone, buffering until resume");
+                        detached = true;
+                    }
+                    Err(_) => {}
                 }
             }
 
@@ -803,19 +1331,34 @@ impl WshServer {
             .map(|status| status.exit_code().try_into().unwrap_or(-1))
             .unwrap_or(-1);
 
+            let sink = pty_sinks.write().await.remove(&session_id);
+            let channel_id = sink.as_ref().map_or(channel_id, |sink| sink.channel_id);
             info!(session_id = %session_id, channel_id, code, "PTY session ended");
+            audit
+                .record(AuditEvent::SessionClose {
+                    session_id: session_id.clone(),
+                    username,
+                    fingerprint,
+                    exit_code: code,
+                })
+                .await;
+            if let Some(recorder) = &recorder {
+                recorder.record(RecordingEvent::Exit { code }).await;
+            }
 
-            let exit_msg = Envelope {
-                msg_type: MsgType::Exit,
-                payload: Payload::Exit(ExitPayload { channel_id, code }),
-            };
-            let _ = peer_tx.send(exit_msg).await;
+            if let Some(sink) = sink {
+                let exit_msg = Envelope {
+                    msg_type: MsgType::Exit,
+                    payload: Payload::Exit(ExitPayload { channel_id, code }),
+                };
+                let _ = sink.peer_tx.send(exit_msg).await;
 
-            let close_msg = Envelope {
-                msg_type: MsgType::Close,
-                payload: Payload::Close(ClosePayload { channel_id }),
-            };
-            let _ = peer_tx.send(close_msg).await;
+                let close_msg = Envelope {
+                    msg_type: MsgType::Close,
+                    payload: Payload::Close(ClosePayload { channel_id }),
+                };
+                let _ = sink.peer_tx.send(close_msg).await;
+            }
 
             if let Err(e) = sessions.remove(&session_id).await {
                 debug!(session_id = %session_id, error = %e, "PTY output pump: session already removed");
@@ -827,6 +1370,7 @@ impl WshServer {
     async fn handle_websocket(&self, mut conn: websocket::WebSocketConnection) -> WshResult<()> {
         let remote = conn.remote_addr;
         info!(remote = %remote, "handling WebSocket connection");
+        let started = std::time::Instant::now();
 
         // Read HELLO
         let hello_bytes = websocket::ws_recv_control(&mut conn.ws_stream)
@@ -844,13 +1388,15 @@ impl WshServer {
         };
 
         // Send SERVER_HELLO + CHALLENGE
-        let server_fingerprints: Vec<String> = self
-            .authorized_keys
-            .iter()
-            .map(|k| k.fingerprint.clone())
-            .collect();
-        let features = self.build_feature_list();
-        let hello_result = handshake::handle_hello(&hello, &server_fingerprints, Some(&features))?;
+        let server_fingerprints = self.server_fingerprints();
+        let compression = self.negotiate_compression(&hello);
+        let mut features = self.build_feature_list();
+        features.extend(compression.map(Codec::feature));
+        let mut hello_result =
+            handshake::handle_hello(&hello, &server_fingerprints, Some(&features))?;
+        if let Some(host) = &self.host_identity {
+            handshake::add_host_proof(&mut hello_result.challenge, host, &hello_result.session_id);
+        }
 
         let sh_frame = frame_encode(&hello_result.server_hello)?;
         websocket::ws_send_control(&mut conn.ws_stream, &sh_frame).await?;
@@ -873,19 +1419,29 @@ impl WshServer {
                 return Err(WshError::InvalidMessage("expected AUTH message".into()));
             }
         };
+        let audit_auth = |outcome: Result<&str, &str>| {
+            AuditEvent::auth(
+                Transport::WebSocket,
+                remote,
+                &hello.username,
+                &auth.method,
+                outcome,
+            )
+        };
 
         // Rate limit check (WebSocket)
         {
             let ip = remote.ip();
             let rate_limited = {
                 let mut limits = self.rate_limits.lock().await;
-                !limits.check_auth(&ip)
+                limits.check_auth(&ip).err()
             };
-            if rate_limited {
-                let fail = handshake::build_auth_fail("rate limited: too many auth attempts");
+            if let Some(reason) = rate_limited {
+                self.audit.record(audit_auth(Err(&reason))).await;
+                let fail = handshake::build_auth_fail(&reason);
                 let fail_frame = frame_encode(&fail)?;
                 let _ = websocket::ws_send_control(&mut conn.ws_stream, &fail_frame).await;
-                return Err(WshError::AuthFailed("rate limited".into()));
+                return Err(WshError::AuthFailed(reason));
             }
         }
 
@@ -895,6 +1451,8 @@ impl WshServer {
                 Some(ref password) => {
                     if let Some(expected_hash) = self.config.password_hashes.get(&hello.username) {
                         if !handshake::verify_password_hash(password, expected_hash) {
+                            self.auth_failed(remote.ip(), audit_auth(Err("invalid password")))
+                                .await;
                             let fail = handshake::build_auth_fail("invalid password");
                             let fail_frame = frame_encode(&fail)?;
                             let _ =
@@ -902,6 +1460,8 @@ impl WshServer {
                             return Err(WshError::AuthFailed("invalid password".into()));
                         }
                     } else {
+                        self.auth_failed(remote.ip(), audit_auth(Err("unknown user")))
+                            .await;
                         let fail = handshake::build_auth_fail("unknown user");
                         let fail_frame = frame_encode(&fail)?;
                         let _ = websocket::ws_send_control(&mut conn.ws_stream, &fail_frame).await;
@@ -911,6 +1471,8 @@ impl WshServer {
                     }
                 }
                 None => {
+                    self.auth_failed(remote.ip(), audit_auth(Err("password required")))
+                        .await;
                     let fail = handshake::build_auth_fail("password required");
                     let fail_frame = frame_encode(&fail)?;
                     let _ = websocket::ws_send_control(&mut conn.ws_stream, &fail_frame).await;
@@ -922,11 +1484,15 @@ impl WshServer {
         }
 
         // Verify
+        let context = handshake::AuthContext {
+            nonce: &hello_result.nonce,
+            session_id: &hello_result.session_id,
+            username: &hello.username,
+        };
         match handshake::verify_auth(
             &auth,
-            &hello_result.nonce,
-            &hello_result.session_id,
-            &self.authorized_keys,
+            &context,
+            &self.authorized_keys(),
             &self.secret,
             self.config.session_ttl,
             self.config.allow_pubkey,
@@ -934,6 +1500,27 @@ impl WshServer {
         ) {
             Ok(mut result) => {
                 result.username = hello.username.clone();
+
+                // Second factor
+                if self.totp.is_required(&result.username) {
+                    let request_frame = frame_encode(&handshake::build_totp_request())?;
+                    websocket::ws_send_control(&mut conn.ws_stream, &request_frame).await?;
+                    let reply_bytes = websocket::ws_recv_control(&mut conn.ws_stream)
+                        .await?
+                        .ok_or_else(|| {
+                            WshError::Transport("connection closed before TOTP code".into())
+                        })?;
+                    let reply = decode_envelope(&reply_bytes)?;
+                    if let Err(e) = self.check_totp(&result.username, &reply) {
+                        self.auth_failed(remote.ip(), audit_auth(Err(&e.to_string())))
+                            .await;
+                        let fail = handshake::build_auth_fail(&e.to_string());
+                        let fail_frame = frame_encode(&fail)?;
+                        let _ = websocket::ws_send_control(&mut conn.ws_stream, &fail_frame).await;
+                        return Err(e);
+                    }
+                }
+
                 let ok = handshake::build_auth_ok(
                     &result.session_id,
                     &result.token,
@@ -941,6 +1528,8 @@ impl WshServer {
                 );
                 let ok_frame = frame_encode(&ok)?;
                 websocket::ws_send_control(&mut conn.ws_stream, &ok_frame).await?;
+                self.metrics
+                    .handshake_completed(Transport::WebSocket, started.elapsed());
 
                 info!(
                     remote = %remote,
@@ -948,6 +1537,7 @@ impl WshServer {
                     session_id = %result.session_id,
                     "WebSocket auth OK"
                 );
+                self.audit.record(audit_auth(Ok(&result.fingerprint))).await;
 
                 let (peer_tx, peer_rx) = mpsc::channel::<Envelope>(64);
                 // Assign a unique conn_id and register in peer_senders/conn_session_map
@@ -961,17 +1551,31 @@ impl WshServer {
                     .write()
                     .await
                     .insert(conn_id, result.session_id.clone());
+                let workspace = self.workspace_for(&result.username, &result.fingerprint)?;
                 let mut ctx = ConnectionContext {
                     username: result.username.clone(),
                     fingerprint: result.fingerprint.clone(),
+                    ca_fingerprint: result.ca_fingerprint.clone(),
                     session_id: result.session_id.clone(),
                     token: result.token.clone(),
                     peer_tx,
                     conn_id: Some(conn_id),
+                    agent_forward_offered: hello.features.iter().any(|f| f == "agent-forward"),
+                    agent_forwarder: None,
+                    workspace,
+                    channels: Default::default(),
+                    attached: Default::default(),
+                    gateways: Default::default(),
+                    listeners: Default::default(),
+                    forward_connecting: None,
+                    deferred_opens: Default::default(),
+                    compression,
+                    compression_stats: Default::default(),
+                    mcp_calls: Default::default(),
                 };
 
                 // Session message loop
-                self.session_loop_ws(&mut conn, &mut ctx, peer_rx).await?;
+                let loop_result = self.session_loop_ws(&mut conn, &mut ctx, peer_rx).await;
 
                 // Cleanup: unregister peer if registered
                 if let Some(cid) = ctx.conn_id {
@@ -980,8 +1584,14 @@ impl WshServer {
                     self.clear_relay_links(cid).await;
                 }
                 self.peer_registry.unregister(&ctx.fingerprint).await;
+                self.transfers.release_owner(&ctx.session_id).await;
+                self.release_forwards(&mut ctx).await;
+                log_compression(&ctx);
+                loop_result?;
             }
             Err(e) => {
+                self.auth_failed(remote.ip(), audit_auth(Err(&e.to_string())))
+                    .await;
                 let fail = handshake::build_auth_fail(&e.to_string());
                 let fail_frame = frame_encode(&fail)?;
                 let _ = websocket::ws_send_control(&mut conn.ws_stream, &fail_frame).await;
@@ -1012,6 +1622,87 @@ impl WshServer {
         &self.relay_broker
     }
 
+    /// Close the gateway connections, `TcpForward` channels and reverse
+    /// listeners a connection left open when it ended.
+    async fn release_forwards(&self, ctx: &mut ConnectionContext) {
+        for gateway_id in ctx.gateways.drain() {
+            self.gateway_forwarder.close(gateway_id).await;
+        }
+        for channel_id in ctx.channels.drain() {
+            self.gateway_forwarder.close_channel(channel_id).await;
+        }
+        for listener_id in ctx.listeners.drain() {
+            self.reverse_listener.close_listener(listener_id).await;
+        }
+    }
+
+    /// Turn a relay event into the messages to send the client.
+    ///
+    /// Answering a `TcpForward` open also dispatches the `Open` / `Resume`
+    /// requests deferred behind it, up to the next forward that has to
+    /// connect; their replies follow the answer.
+    async fn gateway_event_messages(
+        &self,
+        event: GatewayEvent,
+        ctx: &mut ConnectionContext,
+        inbound_tx: mpsc::Sender<crate::gateway::listener::InboundEvent>,
+        data_tx: mpsc::Sender<GatewayEvent>,
+    ) -> WshResult<Vec<Envelope>> {
+        let msg = match event {
+            GatewayEvent::Data { gateway_id, data } => build_gateway_data(gateway_id, data),
+            GatewayEvent::Closed { gateway_id } => {
+                self.gateway_forwarder.close(gateway_id).await;
+                ctx.gateways.remove(&gateway_id);
+                build_gateway_close_msg(gateway_id)
+            }
+            GatewayEvent::ChannelOpened { channel_id } => {
+                info!(channel_id, "tcp forward channel opened");
+                Envelope {
+                    msg_type: MsgType::OpenOk,
+                    payload: Payload::OpenOk(OpenOkPayload {
+                        channel_id,
+                        stream_ids: vec![],
+                        data_mode: SessionDataMode::Virtual,
+                        capabilities: vec![],
+                        session_id: None,
+                        resume_token: None,
+                    }),
+                }
+            }
+            GatewayEvent::ChannelOpenFailed { channel_id, reason } => {
+                ctx.channels.remove(&channel_id);
+                Envelope {
+                    msg_type: MsgType::OpenFail,
+                    payload: Payload::OpenFail(OpenFailPayload { reason }),
+                }
+            }
+            GatewayEvent::ChannelData { channel_id, data } => {
+                let (data, compression) = ctx.compression_stats.encode(ctx.compression, data);
+                build_channel_data(channel_id, data, compression)
+            }
+            GatewayEvent::ChannelClosed { channel_id } => {
+                self.gateway_forwarder.close_channel(channel_id).await;
+                ctx.channels.remove(&channel_id);
+                build_channel_close(channel_id)
+            }
+        };
+        let answered = matches!(msg.msg_type, MsgType::OpenOk | MsgType::OpenFail);
+        let mut msgs = vec![msg];
+        if answered {
+            ctx.forward_connecting = None;
+            while ctx.forward_connecting.is_none() {
+                let Some(envelope) = ctx.deferred_opens.pop_front() else {
+                    break;
+                };
+                let response = self
+                    .dispatch_message(envelope, ctx, inbound_tx.clone(), data_tx.clone())
+                    .await?;
+                msgs.extend(response);
+            }
+        }
+        Ok(msgs)
+    }
+
     // ── Session message loops ──────────────────────────────────────────
 
     /// Post-auth message loop over WebTransport.
@@ -1038,6 +1729,7 @@ impl WshServer {
                         }),
                     };
                     if let Ok(frame) = frame_encode(&shutdown_msg) {
+                        self.metrics.sent(Transport::WebTransport, frame.len());
                         let _ = send.write_all(&frame).await;
                     }
                     break;
@@ -1046,30 +1738,33 @@ impl WshServer {
                 Some(event) = inbound_rx.recv() => {
                     let msg = build_inbound_open(&event);
                     let frame = frame_encode(&msg)?;
+                    self.metrics.sent(Transport::WebTransport, frame.len());
                     send.write_all(&frame)
                         .await
                         .map_err(|e| WshError::Transport(format!("WebTransport write: {e}")))?;
                 }
 
                 Some(event) = data_rx.recv() => {
-                    let msg = match &event {
-                        GatewayEvent::Data { gateway_id, data } => {
-                            build_gateway_data(*gateway_id, data.clone())
-                        }
-                        GatewayEvent::Closed { gateway_id } => {
-                            self.gateway_forwarder.close(*gateway_id).await;
-                            build_gateway_close_msg(*gateway_id)
-                        }
-                    };
-                    let frame = frame_encode(&msg)?;
-                    send.write_all(&frame)
-                        .await
-                        .map_err(|e| WshError::Transport(format!("WebTransport write: {e}")))?;
+                    let msgs = self
+                        .gateway_event_messages(event, ctx, inbound_tx.clone(), data_tx.clone())
+                        .await?;
+                    for msg in msgs {
+                        let frame = frame_encode(&msg)?;
+                        self.metrics.sent(Transport::WebTransport, frame.len());
+                        send.write_all(&frame)
+                            .await
+                            .map_err(|e| WshError::Transport(format!("WebTransport write: {e}")))?;
+                    }
                 }
 
                 // Peer push messages (e.g. forwarded ReverseConnect)
                 Some(envelope) = peer_rx.recv() => {
+                    // A process that exits closes its channel from this side.
+                    if let Payload::Close(p) = &envelope.payload {
+                        ctx.channels.remove(&p.channel_id);
+                    }
                     let frame = frame_encode(&envelope)?;
+                    self.metrics.sent(Transport::WebTransport, frame.len());
                     send.write_all(&frame)
                         .await
                         .map_err(|e| WshError::Transport(format!("WebTransport write: {e}")))?;
@@ -1078,9 +1773,11 @@ impl WshServer {
                 frame_result = read_webtransport_frame(recv) => {
                     match frame_result {
                         Ok(data) => {
+                            self.metrics.received(Transport::WebTransport, data.len());
                             let envelope = decode_envelope(&data)?;
                             if let Some(response) = self.dispatch_message(envelope, ctx, inbound_tx.clone(), data_tx.clone()).await? {
                                 let frame = frame_encode(&response)?;
+                                self.metrics.sent(Transport::WebTransport, frame.len());
                                 send.write_all(&frame)
                                     .await
                                     .map_err(|e| WshError::Transport(format!("WebTransport write: {e}")))?;
@@ -1121,6 +1818,7 @@ impl WshServer {
                         }),
                     };
                     if let Ok(frame) = frame_encode(&shutdown_msg) {
+                        self.metrics.sent(Transport::WebSocket, frame.len());
                         let _ = websocket::ws_send_control(&mut conn.ws_stream, &frame).await;
                     }
                     break;
@@ -1129,35 +1827,40 @@ impl WshServer {
                 Some(event) = inbound_rx.recv() => {
                     let msg = build_inbound_open(&event);
                     let frame = frame_encode(&msg)?;
+                    self.metrics.sent(Transport::WebSocket, frame.len());
                     websocket::ws_send_control(&mut conn.ws_stream, &frame).await?;
                 }
 
                 Some(event) = data_rx.recv() => {
-                    let msg = match &event {
-                        GatewayEvent::Data { gateway_id, data } => {
-                            build_gateway_data(*gateway_id, data.clone())
-                        }
-                        GatewayEvent::Closed { gateway_id } => {
-                            self.gateway_forwarder.close(*gateway_id).await;
-                            build_gateway_close_msg(*gateway_id)
-                        }
-                    };
-                    let frame = frame_encode(&msg)?;
-                    websocket::ws_send_control(&mut conn.ws_stream, &frame).await?;
+                    let msgs = self
+                        .gateway_event_messages(event, ctx, inbound_tx.clone(), data_tx.clone())
+                        .await?;
+                    for msg in msgs {
+                        let frame = frame_encode(&msg)?;
+                        self.metrics.sent(Transport::WebSocket, frame.len());
+                        websocket::ws_send_control(&mut conn.ws_stream, &frame).await?;
+                    }
                 }
 
                 // Peer push messages (e.g. forwarded ReverseConnect)
                 Some(envelope) = peer_rx.recv() => {
+                    // A process that exits closes its channel from this side.
+                    if let Payload::Close(p) = &envelope.payload {
+                        ctx.channels.remove(&p.channel_id);
+                    }
                     let frame = frame_encode(&envelope)?;
+                    self.metrics.sent(Transport::WebSocket, frame.len());
                     websocket::ws_send_control(&mut conn.ws_stream, &frame).await?;
                 }
 
                 ws_result = websocket::ws_recv_control(&mut conn.ws_stream) => {
                     match ws_result {
                         Ok(Some(data)) => {
+                            self.metrics.received(Transport::WebSocket, data.len());
                             let envelope = decode_envelope(&data)?;
                             if let Some(response) = self.dispatch_message(envelope, ctx, inbound_tx.clone(), data_tx.clone()).await? {
                                 let frame = frame_encode(&response)?;
+                                self.metrics.sent(Transport::WebSocket, frame.len());
                                 websocket::ws_send_control(&mut conn.ws_stream, &frame).await?;
                             }
                         }
@@ -1204,7 +1907,12 @@ impl WshServer {
 
     /// Build the list of features this server advertises based on configuration.
     fn build_feature_list(&self) -> Vec<String> {
-        let mut features = vec!["mcp".to_string(), "file-transfer".to_string()];
+        let mut features = vec![
+            "mcp".to_string(),
+            "file-transfer".to_string(),
+            "authorized-keys".to_string(),
+            "mcp-progress".to_string(),
+        ];
         if self.gateway_enabled {
             features.push("gateway".to_string());
         }
@@ -1214,9 +1922,251 @@ impl WshServer {
         if self.recording_dir.is_some() {
             features.push("recording".to_string());
         }
+        if self.config.allow_agent_forwarding {
+            features.push("agent-forward".to_string());
+        }
         features
     }
 
+    /// Pick the codec for a connection from the client's HELLO features,
+    /// unless compression is disabled in `[server]`.
+    fn negotiate_compression(&self, hello: &HelloPayload) -> Option<Codec> {
+        if !self.config.compression {
+            return None;
+        }
+        Codec::negotiate(&hello.features)
+    }
+
+    /// Channel environment with `WSH_AUTH_SOCK` added when the client
+    /// forwards its key agent and this key and server allow it.
+    ///
+    /// The forwarded socket is created on first use and shared by every
+    /// channel on the connection.
+    fn agent_forward_env(
+        &self,
+        ctx: &mut ConnectionContext,
+        permissions: &crate::auth::permissions::KeyPermissions,
+        requested: Option<&HashMap<String, String>>,
+    ) -> Option<HashMap<String, String>> {
+        let allowed = ctx.agent_forward_offered
+            && self.config.allow_agent_forwarding
+            && permissions.has_scope(&crate::auth::permissions::SessionScope::AgentForward);
+        if allowed && ctx.agent_forwarder.is_none() {
+            match crate::agent_forward::AgentForwarder::start(&ctx.workspace, ctx.peer_tx.clone()) {
+                Ok(forwarder) => ctx.agent_forwarder = Some(forwarder),
+                Err(e) => warn!(error = %e, "cannot create agent forwarding socket"),
+            }
+        }
+        let forwarder = ctx.agent_forwarder.as_ref().filter(|_| allowed);
+        match forwarder {
+            Some(forwarder) => {
+                let mut env = requested.cloned().unwrap_or_default();
+                env.insert(
+                    crate::agent_forward::AUTH_SOCK_ENV.to_string(),
+                    forwarder.socket_path().display().to_string(),
+                );
+                Some(env)
+            }
+            None => requested.cloned(),
+        }
+    }
+
+    /// Fingerprints advertised in SERVER_HELLO, host key first.
+    fn server_fingerprints(&self) -> Vec<String> {
+        self.host_identity
+            .iter()
+            .map(|host| host.fingerprint().to_string())
+            .chain(self.authorized_keys().iter().map(|k| k.fingerprint.clone()))
+            .collect()
+    }
+
+    /// Workspace for an authenticated connection: the user's isolated
+    /// directory with `[isolation]` enabled, the server user's home otherwise.
+    fn workspace_for(&self, username: &str, fingerprint: &str) -> WshResult<Workspace> {
+        match &self.isolation {
+            Some(isolation) => isolation.workspace(username, fingerprint),
+            None => Ok(Workspace::unconfined()),
+        }
+    }
+
+    /// Reason to refuse a new channel when `ctx` is at
+    /// `[limits] max_channels_per_connection`. Sessions, attachments,
+    /// gateway connections, listeners, file transfers and running MCP calls
+    /// all count.
+    async fn channel_limit(&self, ctx: &ConnectionContext) -> Option<String> {
+        let max = self.config.max_channels_per_connection;
+        if max == 0 {
+            return None;
+        }
+        let open = ctx.channels.len()
+            + ctx.attached.len()
+            + ctx.gateways.len()
+            + ctx.listeners.len()
+            + ctx.mcp_calls.load(Ordering::Relaxed)
+            + self.transfers.active_for(&ctx.session_id).await;
+        (open >= max).then(|| format!("max channels per connection reached ({max})"))
+    }
+
+    /// An [`McpCaller`] acting for the connection of `ctx`.
+    fn mcp_caller(&self, ctx: &ConnectionContext) -> McpCaller {
+        McpCaller {
+            username: ctx.username.clone(),
+            fingerprint: ctx.fingerprint.clone(),
+            permissions: self.key_permissions(ctx),
+            workspace: ctx.workspace.clone(),
+            sessions: self.sessions.clone(),
+            bridge: self.mcp_bridge.clone(),
+            proxy: self.mcp_proxy.clone(),
+            audit: self.audit.clone(),
+            _in_flight: McpInFlight::new(&ctx.mcp_calls),
+        }
+    }
+
+    /// Resolve the authorized_keys option permissions for an authenticated
+    /// connection. Certificate logins take the options of the signing CA's line.
+    fn key_permissions(&self, ctx: &ConnectionContext) -> crate::auth::permissions::KeyPermissions {
+        let entry_fingerprint = ctx.ca_fingerprint.as_deref().unwrap_or(&ctx.fingerprint);
+        let authorized_keys = self.authorized_keys();
+        let key_options = authorized_keys
+            .iter()
+            .find(|k| k.fingerprint == entry_fingerprint)
+            .and_then(|k| k.options.as_deref());
+        crate::auth::permissions::KeyPermissions::from_options(ctx.fingerprint.clone(), key_options)
+    }
+
+    /// Current authorized keys.
+    fn authorized_keys(&self) -> Arc<Vec<AuthorizedKey>> {
+        self.authorized_keys
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Whether the caller logged in with the administrator key (the first
+    /// authorized key).
+    fn is_admin(&self, ctx: &ConnectionContext) -> bool {
+        self.authorized_keys()
+            .first()
+            .is_some_and(|k| k.fingerprint == ctx.fingerprint)
+    }
+
+    /// Whether the caller may edit authorized_keys over the protocol.
+    ///
+    /// Anyone with an unrestricted shell could edit the file anyway; with
+    /// `[isolation]` on, shells no longer imply that, so only the
+    /// administrator may.
+    fn may_manage_keys(&self, ctx: &ConnectionContext) -> bool {
+        if self.is_admin(ctx) {
+            return true;
+        }
+        let permissions = self.key_permissions(ctx);
+        self.isolation.is_none()
+            && permissions.has_scope(&crate::auth::permissions::SessionScope::Shell)
+            && permissions.forced_command.is_none()
+    }
+
+    /// Re-read authorized_keys after an edit so it applies to new logins.
+    fn reload_authorized_keys(&self) -> WshResult<()> {
+        let keys = load_authorized_keys(&self.keys_home)?;
+        info!(count = keys.len(), "reloaded authorized keys");
+        *self
+            .authorized_keys
+            .write()
+            .unwrap_or_else(|e| e.into_inner()) = Arc::new(keys);
+        Ok(())
+    }
+
+    /// `AUTHORIZED_KEYS` reply listing the current keys and where each lives.
+    fn authorized_keys_reply(&self, changed: bool) -> Envelope {
+        let wsh_keys = std::fs::read_to_string(wsh_authorized_keys_path(&self.keys_home))
+            .map(|content| parse_authorized_keys(&content))
+            .unwrap_or_default();
+        let keys = self
+            .authorized_keys()
+            .iter()
+            .map(|k| AuthorizedKeyInfo {
+                fingerprint: k.fingerprint.clone(),
+                key_type: k.key_type.clone(),
+                comment: k.comment.clone(),
+                options: k.options.clone(),
+                source: if wsh_keys.iter().any(|w| w.fingerprint == k.fingerprint) {
+                    "wsh".into()
+                } else {
+                    "ssh".into()
+                },
+            })
+            .collect();
+        Envelope {
+            msg_type: MsgType::AuthorizedKeys,
+            payload: Payload::AuthorizedKeys(AuthorizedKeysPayload { keys, changed }),
+        }
+    }
+
+    /// Handle `AUTHORIZED_KEY_ADD`/`AUTHORIZED_KEY_REMOVE`, returning the
+    /// updated list or an error message.
+    fn edit_authorized_keys(&self, ctx: &ConnectionContext, payload: &Payload) -> Envelope {
+        let result = match payload {
+            Payload::AuthorizedKeyAdd(p) => parse_public_key(&p.public_key, p.options.as_deref())
+                .and_then(|key| {
+                    let added = add_authorized_key(&self.keys_home, &key)?;
+                    if added {
+                        info!(
+                            by = %ctx.fingerprint,
+                            fingerprint = %key.fingerprint,
+                            "authorized key added"
+                        );
+                    }
+                    Ok(added)
+                })
+                .map_err(|e| e.to_string()),
+            Payload::AuthorizedKeyRemove(p) => self.remove_key(ctx, &p.fingerprint),
+            _ => Err("unexpected authorized keys request".into()),
+        };
+        match result {
+            Ok(changed) => {
+                if changed {
+                    if let Err(e) = self.reload_authorized_keys() {
+                        warn!(error = %e, "failed to reload authorized_keys");
+                    }
+                }
+                self.authorized_keys_reply(changed)
+            }
+            Err(message) => Envelope {
+                msg_type: MsgType::Error,
+                payload: Payload::Error(ErrorPayload { code: 1, message }),
+            },
+        }
+    }
+
+    /// Remove the `~/.wsh/authorized_keys` entry matching a fingerprint or
+    /// unique fingerprint prefix.
+    fn remove_key(&self, ctx: &ConnectionContext, prefix: &str) -> Result<bool, String> {
+        let prefix = prefix.trim().to_ascii_lowercase();
+        if prefix.is_empty() {
+            return Err("no fingerprint given".into());
+        }
+        let keys = self.authorized_keys();
+        let matches: Vec<&AuthorizedKey> = keys
+            .iter()
+            .filter(|k| k.fingerprint.starts_with(&prefix))
+            .collect();
+        let key = match matches.as_slice() {
+            [] => return Err(format!("no authorized key matches {prefix}")),
+            [key] => key,
+            _ => return Err(format!("{prefix} matches {} keys", matches.len())),
+        };
+        let removed =
+            remove_authorized_key(&self.keys_home, &key.fingerprint).map_err(|e| e.to_string())?;
+        if removed == 0 {
+            return Err(format!(
+                "{} is listed in ~/.ssh/authorized_keys, which wsh does not edit",
+                key.fingerprint
+            ));
+        }
+        info!(by = %ctx.fingerprint, fingerprint = %key.fingerprint, "authorized key removed");
+        Ok(true)
+    }
+
     /// Check whether the caller is the **owner** of a session (no ACL fallback).
     /// Use this for privileged operations like Grant, Revoke, GuestInvite, ShareSession
     /// where only the session creator should be able to act.
@@ -1248,6 +2198,22 @@ impl WshServer {
         false
     }
 
+    /// Respawn a session saved by a previous server process, if `username`
+    /// owns one with this ID. Returns whether a session was revived.
+    async fn revive_if_dormant(&self, session_id: &str, username: &str) -> bool {
+        match self
+            .sessions
+            .revive(session_id, username, self.recording_dir.as_deref())
+            .await
+        {
+            Ok(revived) => revived,
+            Err(e) => {
+                warn!(session_id, error = %e, "failed to revive persisted session");
+                false
+            }
+        }
+    }
+
     /// Sanitize a session_id to prevent path traversal attacks.
     /// Returns None if the session_id contains dangerous characters.
     fn sanitize_session_id(session_id: &str) -> Option<&str> {
@@ -1286,6 +2252,7 @@ impl WshServer {
                 | MsgType::McpTools
                 | MsgType::McpCall
                 | MsgType::McpResult
+                | MsgType::McpProgress
                 | MsgType::EchoAck
                 | MsgType::EchoState
                 | MsgType::TermSync
@@ -1375,10 +2342,26 @@ impl WshServer {
             }
         }
 
+        // Replies to OPEN / RESUME are matched to requests by order, so none
+        // may overtake a forward that is still connecting.
+        if ctx.forward_connecting.is_some()
+            && matches!(envelope.msg_type, MsgType::Open | MsgType::Resume)
+        {
+            ctx.deferred_opens.push_back(envelope);
+            return Ok(None);
+        }
+
         match (&envelope.msg_type, &envelope.payload) {
             // ── Reverse peer messages ───────────────────────────────
             (MsgType::ReverseRegister, Payload::ReverseRegister(p)) => {
                 let fp = fingerprint(&p.public_key);
+                let name = match &p.name {
+                    Some(name) if !valid_peer_name(name) => {
+                        warn!(name = %name, "ignoring invalid reverse peer name");
+                        None
+                    }
+                    other => other.clone(),
+                };
                 // Register in the relay peer registry, passing the server-assigned conn_id
                 // so that ReverseConnect lookups match peer_senders keys.
                 let cid = ctx.conn_id.unwrap_or(0);
@@ -1389,6 +2372,7 @@ impl WshServer {
                         p.username.clone(),
                         p.capabilities.clone(),
                         PeerMetadata {
+                            name,
                             peer_type: p.peer_type.clone(),
                             shell_backend: p.shell_backend.clone(),
                             supports_attach: p.supports_attach,
@@ -1409,32 +2393,49 @@ impl WshServer {
             }
             (MsgType::ReverseList, Payload::ReverseList(_)) => {
                 let entries = self.peer_registry.list().await;
-                let peers: Vec<PeerInfo> = entries
-                    .iter()
-                    .map(|e| PeerInfo {
-                        fingerprint: e.fingerprint.clone(),
-                        fingerprint_short: if e.fingerprint.len() >= 8 {
-                            e.fingerprint[..8].to_string()
-                        } else {
-                            e.fingerprint.clone()
-                        },
-                        username: e.username.clone(),
-                        capabilities: e.capabilities.clone(),
-                        peer_type: e.peer_type.clone(),
-                        shell_backend: e.shell_backend.clone(),
-                        source: "wsh-relay".to_string(),
-                        supports_attach: e.supports_attach,
-                        supports_replay: e.supports_replay,
-                        supports_echo: e.supports_echo,
-                        supports_term_sync: e.supports_term_sync,
-                        last_seen: Some(e.last_seen.elapsed().as_secs()),
-                    })
-                    .collect();
+                let peers: Vec<PeerInfo> = entries.iter().map(peer_info).collect();
                 Ok(Some(Envelope {
                     msg_type: MsgType::ReversePeers,
                     payload: Payload::ReversePeers(ReversePeersPayload { peers }),
                 }))
             }
+            (MsgType::ReverseSubscribe, Payload::ReverseSubscribe(_)) => {
+                let Some(cid) = ctx.conn_id else {
+                    return Ok(None);
+                };
+                let Some(tx) = self.peer_senders.read().await.get(&cid).cloned() else {
+                    return Ok(None);
+                };
+                let mut events = self.peer_registry.subscribe();
+                tokio::spawn(async move {
+                    loop {
+                        let event = tokio::select! {
+                            event = events.recv() => event,
+                            _ = tx.closed() => break,
+                        };
+                        let (kind, entry) = match event {
+                            Ok(PeerEvent::Joined(entry)) => ("join", entry),
+                            Ok(PeerEvent::Left(entry)) => ("leave", entry),
+                            Err(tokio::sync::broadcast::error::RecvError::Lagged(missed)) => {
+                                debug!(conn_id = cid, missed, "peer subscriber lagged");
+                                continue;
+                            }
+                            Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                        };
+                        let envelope = Envelope {
+                            msg_type: MsgType::ReversePeerEvent,
+                            payload: Payload::ReversePeerEvent(ReversePeerEventPayload {
+                                event: kind.to_string(),
+                                peer: peer_info(&entry),
+                            }),
+                        };
+                        if tx.send(envelope).await.is_err() {
+                            break;
+                        }
+                    }
+                });
+                Ok(None)
+            }
             (MsgType::ReverseConnect, Payload::ReverseConnect(p)) => {
                 match self
                     .relay_broker
@@ -1505,6 +2506,7 @@ impl WshServer {
 
             // ── Session management ──────────────────────────────────
             (MsgType::Attach, Payload::Attach(p)) => {
+                self.revive_if_dormant(&p.session_id, &ctx.username).await;
                 // Rate limit attach attempts
                 {
                     let mut limits = self.rate_limits.lock().await;
@@ -1541,6 +2543,14 @@ impl WshServer {
                         }),
                     }));
                 }
+                if !ctx.attached.contains(&p.session_id) {
+                    if let Some(message) = self.channel_limit(ctx).await {
+                        return Ok(Some(Envelope {
+                            msg_type: MsgType::Error,
+                            payload: Payload::Error(ErrorPayload { code: 3, message }),
+                        }));
+                    }
+                }
                 if let Err(e) = self.sessions.attach(&p.session_id).await {
                     return Ok(Some(Envelope {
                         msg_type: MsgType::Error,
@@ -1550,6 +2560,7 @@ impl WshServer {
                         }),
                     }));
                 }
+                ctx.attached.insert(p.session_id.clone());
                 // Update conn_session_map so E2E relay is session-scoped
                 if let Some(cid) = ctx.conn_id {
                     self.conn_session_map
@@ -1593,6 +2604,7 @@ impl WshServer {
                 for s in all_sessions {
                     if self.check_session_access(&s.id, &ctx.username).await {
                         visible.push(SessionSummary {
+                            state: Some(s.state().to_string()),
                             session_id: s.id,
                             name: s.name,
                             username: s.username,
@@ -1600,6 +2612,11 @@ impl WshServer {
                             created_at_secs: s.created_at_secs,
                             idle_secs: s.idle_secs,
                             attached_count: s.attached_count,
+                            last_activity_secs: Some(s.last_activity_secs),
+                            command: s.command,
+                            cols: Some(s.size.0),
+                            rows: Some(s.size.1),
+                            fingerprint: Some(s.fingerprint),
                         });
                     }
                 }
@@ -1622,6 +2639,7 @@ impl WshServer {
                 }
                 match self.sessions.detach(&p.session_id).await {
                     Ok(()) => {
+                        ctx.attached.remove(&p.session_id);
                         if let Some(cid) = ctx.conn_id {
                             self.conn_session_map.write().await.remove(&cid);
                         }
@@ -1643,35 +2661,62 @@ impl WshServer {
             (MsgType::Resume, Payload::Resume(p)) => {
                 if let Err(e) = verify_token(&self.secret, &p.session_id, &p.token) {
                     return Ok(Some(Envelope {
-                        msg_type: MsgType::Error,
-                        payload: Payload::Error(ErrorPayload {
-                            code: 2,
-                            message: format!("invalid token: {e}"),
+                        msg_type: MsgType::OpenFail,
+                        payload: Payload::OpenFail(OpenFailPayload {
+                            reason: format!("invalid token: {e}"),
                         }),
                     }));
                 }
+                if let Some(reason) = self.channel_limit(ctx).await {
+                    return Ok(Some(Envelope {
+                        msg_type: MsgType::OpenFail,
+                        payload: Payload::OpenFail(OpenFailPayload { reason }),
+                    }));
+                }
+                let revived = self.revive_if_dormant(&p.session_id, &ctx.username).await;
                 // Verify the caller owns or has been granted access to this session
                 if !self
                     .check_session_access(&p.session_id, &ctx.username)
                     .await
                 {
                     return Ok(Some(Envelope {
-                        msg_type: MsgType::Error,
-                        payload: Payload::Error(ErrorPayload {
-                            code: 2,
-                            message: "not authorized to resume this session".into(),
+                        msg_type: MsgType::OpenFail,
+                        payload: Payload::OpenFail(OpenFailPayload {
+                            reason: "not authorized to resume this session".into(),
                         }),
                     }));
                 }
-                if let Err(e) = self.sessions.attach(&p.session_id).await {
-                    return Ok(Some(Envelope {
-                        msg_type: MsgType::Error,
-                        payload: Payload::Error(ErrorPayload {
-                            code: 3,
-                            message: e.to_string(),
-                        }),
-                    }));
+
+                // Hold the sink table while replaying so the output pump can
+                // neither deliver a chunk twice nor drop one in between.
+                let mut sinks = self.pty_sinks.write().await;
+                // `last_seq` is the number of output bytes the client has seen.
+                let replay_data = match self
+                    .sessions
+                    .with_session(&p.session_id, |s| Ok(s.ring_buffer.read_since(p.last_seq)))
+                    .await
+                {
+                    Ok(data) => data,
+                    Err(e) => {
+                        return Ok(Some(Envelope {
+                            msg_type: MsgType::OpenFail,
+                            payload: Payload::OpenFail(OpenFailPayload {
+                                reason: e.to_string(),
+                            }),
+                        }));
+                    }
+                };
+
+                let channel_id = self.next_channel_id.fetch_add(1, Ordering::Relaxed);
+                {
+                    let mut channels = self.channel_sessions.write().await;
+                    if let Some(old) = sinks.get(&p.session_id) {
+                        channels.remove(&old.channel_id);
+                        ctx.channels.remove(&old.channel_id);
+                    }
+                    channels.insert(channel_id, p.session_id.clone());
                 }
+                ctx.channels.insert(channel_id);
                 // Update conn_session_map so E2E relay is session-scoped
                 if let Some(cid) = ctx.conn_id {
                     self.conn_session_map
@@ -1679,33 +2724,36 @@ impl WshServer {
                         .await
                         .insert(cid, p.session_id.clone());
                 }
-                // For resume, replay from last_seq - ring buffer replays all for now
-                let replay_data = self
-                    .sessions
-                    .with_session(&p.session_id, |s| Ok(s.ring_buffer.read_all()))
-                    .await
-                    .unwrap_or_default();
-                if !replay_data.is_empty() {
-                    let replay_envelope = Envelope {
-                        msg_type: MsgType::GatewayData,
-                        payload: Payload::GatewayData(GatewayDataPayload {
-                            gateway_id: 0,
-                            data: replay_data,
+
+                let _ = ctx
+                    .peer_tx
+                    .send(Envelope {
+                        msg_type: MsgType::OpenOk,
+                        payload: Payload::OpenOk(OpenOkPayload {
+                            channel_id,
+                            stream_ids: vec![],
+                            data_mode: SessionDataMode::Virtual,
+                            capabilities: vec![],
+                            session_id: Some(p.session_id.clone()),
+                            resume_token: Some(p.token.clone()),
                         }),
-                    };
-                    let _ = ctx.peer_tx.try_send(replay_envelope);
+                    })
+                    .await;
+                let sink = PtySink::new(channel_id, ctx);
+                if !replay_data.is_empty() {
+                    let _ = ctx.peer_tx.send(sink.session_data(replay_data)).await;
                 }
-                info!(session_id = %p.session_id, last_seq = p.last_seq, "client resumed");
-                Ok(Some(Envelope {
-                    msg_type: MsgType::Presence,
-                    payload: Payload::Presence(PresencePayload {
-                        attachments: vec![AttachmentInfo {
-                            session_id: p.session_id.clone(),
-                            mode: "control".into(),
-                            username: Some(ctx.username.clone()),
-                        }],
-                    }),
-                }))
+
+                if revived {
+                    // A session revived from disk has no output pump yet.
+                    drop(sinks);
+                    self.spawn_pty_output_pump(p.session_id.clone(), sink);
+                } else {
+                    sinks.insert(p.session_id.clone(), sink);
+                }
+                self.sessions.touch(&p.session_id).await;
+                info!(session_id = %p.session_id, channel_id, last_seq = p.last_seq, "client resumed");
+                Ok(None)
             }
 
             // ── Channel management ──────────────────────────────────
@@ -1713,15 +2761,7 @@ impl WshServer {
                 let cols = p.cols.unwrap_or(80);
                 let rows = p.rows.unwrap_or(24);
                 // Look up key options for permission enforcement
-                let key_options = self
-                    .authorized_keys
-                    .iter()
-                    .find(|k| k.fingerprint == ctx.fingerprint)
-                    .and_then(|k| k.options.as_deref());
-                let permissions = crate::auth::permissions::KeyPermissions::from_options(
-                    ctx.fingerprint.clone(),
-                    key_options,
-                );
+                let permissions = self.key_permissions(ctx);
 
                 // Check PTY permission
                 if p.kind == ChannelKind::Pty && !permissions.allow_pty {
@@ -1774,8 +2814,12 @@ impl WshServer {
                         }),
                     }));
                 }
-                // Enforce key-specific session cap if present.
-                if let Some(max_sessions) = permissions.max_sessions {
+                // Enforce the per-key session cap: the key's own option, else [limits].
+                let max_sessions = permissions.max_sessions.or_else(|| {
+                    let max = self.config.max_sessions_per_key;
+                    (max > 0 && !ctx.fingerprint.is_empty()).then_some(max)
+                });
+                if let Some(max_sessions) = max_sessions {
                     let active_for_key =
                         self.sessions.count_for_fingerprint(&ctx.fingerprint).await;
                     if active_for_key >= max_sessions {
@@ -1789,6 +2833,12 @@ impl WshServer {
                         }));
                     }
                 }
+                if let Some(reason) = self.channel_limit(ctx).await {
+                    return Ok(Some(Envelope {
+                        msg_type: MsgType::OpenFail,
+                        payload: Payload::OpenFail(OpenFailPayload { reason }),
+                    }));
+                }
 
                 match p.kind {
                     ChannelKind::Pty | ChannelKind::Exec => {
@@ -1796,16 +2846,32 @@ impl WshServer {
                             .forced_command
                             .clone()
                             .or_else(|| p.command.clone());
+                        let run_as = match ctx.workspace.run_as() {
+                            Ok(run_as) => run_as.map(str::to_string),
+                            Err(reason) => {
+                                return Ok(Some(Envelope {
+                                    msg_type: MsgType::OpenFail,
+                                    payload: Payload::OpenFail(OpenFailPayload { reason }),
+                                }));
+                            }
+                        };
+                        let env = self.agent_forward_env(ctx, &permissions, p.env.as_ref());
                         match self
                             .sessions
                             .create(
                                 ctx.username.clone(),
                                 ctx.fingerprint.clone(),
                                 permissions,
-                                effective_command_owned.as_deref(),
-                                cols,
-                                rows,
-                                p.env.as_ref(),
+                                SpawnOptions {
+                                    env: env.as_ref(),
+                                    cwd: ctx.workspace.is_confined().then(|| ctx.workspace.home()),
+                                    run_as: run_as.as_deref(),
+                                    ..SpawnOptions::new(
+                                        effective_command_owned.as_deref(),
+                                        cols,
+                                        rows,
+                                    )
+                                },
                                 self.recording_dir.as_deref(),
                             )
                             .await
@@ -1814,6 +2880,7 @@ impl WshServer {
                                 // Atomic monotonic counter — collision-free channel IDs
                                 let channel_id =
                                     self.next_channel_id.fetch_add(1, Ordering::Relaxed);
+                                ctx.channels.insert(channel_id);
                                 // Register channel → session mapping for Close/Resize routing
                                 self.channel_sessions
                                     .write()
@@ -1827,6 +2894,15 @@ impl WshServer {
                                         .insert(cid, session_id.clone());
                                 }
                                 info!(session_id = %session_id, channel_id, kind = ?p.kind, "channel opened");
+                                self.audit
+                                    .record(AuditEvent::SessionOpen {
+                                        session_id: session_id.clone(),
+                                        username: ctx.username.clone(),
+                                        fingerprint: ctx.fingerprint.clone(),
+                                        kind: p.kind.clone(),
+                                        command: effective_command_owned.clone(),
+                                    })
+                                    .await;
 
                                 // Neither the WebSocket nor the WebTransport transport
                                 // layer implements a second multiplexed data stream for
@@ -1837,8 +2913,7 @@ impl WshServer {
                                 // messages ("virtual" mode), not raw stream bytes.
                                 self.spawn_pty_output_pump(
                                     session_id.clone(),
-                                    channel_id,
-                                    ctx.peer_tx.clone(),
+                                    PtySink::new(channel_id, ctx),
                                 );
 
                                 Ok(Some(Envelope {
@@ -1848,6 +2923,12 @@ impl WshServer {
                                         stream_ids: vec![],
                                         data_mode: SessionDataMode::Virtual,
                                         capabilities: vec![],
+                                        resume_token: Some(wsh_core::create_token(
+                                            &self.secret,
+                                            &session_id,
+                                            self.config.session_ttl,
+                                        )),
+                                        session_id: Some(session_id),
                                     }),
                                 }))
                             }
@@ -1859,6 +2940,35 @@ impl WshServer {
                             })),
                         }
                     }
+                    ChannelKind::TcpForward => {
+                        let open_fail = |reason: String| {
+                            Ok(Some(Envelope {
+                                msg_type: MsgType::OpenFail,
+                                payload: Payload::OpenFail(OpenFailPayload { reason }),
+                            }))
+                        };
+                        if !self.gateway_enabled {
+                            return open_fail("gateway disabled".to_string());
+                        }
+                        // The destination travels in `command` as `host:port`.
+                        let Some((host, port)) =
+                            p.command.as_deref().and_then(parse_forward_destination)
+                        else {
+                            return open_fail("tcp forward needs a host:port destination".into());
+                        };
+                        if !permissions.permits_open(&host, port) {
+                            return open_fail("port forwarding not permitted for this key".into());
+                        }
+                        // Connect off the dispatch path; the session loop sends
+                        // OPEN_OK / OPEN_FAIL when the forwarder reports back.
+                        let channel_id = self.next_channel_id.fetch_add(1, Ordering::Relaxed);
+                        ctx.channels.insert(channel_id);
+                        ctx.forward_connecting = Some(channel_id);
+                        debug!(channel_id, %host, port, "tcp forward connecting");
+                        self.gateway_forwarder
+                            .handle_open_forward(channel_id, host, port, data_tx);
+                        Ok(None)
+                    }
                     _ => Ok(Some(Envelope {
                         msg_type: MsgType::OpenFail,
                         payload: Payload::OpenFail(OpenFailPayload {
@@ -1877,6 +2987,18 @@ impl WshServer {
                 debug!(channel_id = p.channel_id, cols = p.cols, rows = p.rows, session_id = %sid, "resize request");
                 // Touch session activity on the correct session
                 self.sessions.touch(sid).await;
+                if let Ok(Some(recorder)) = self
+                    .sessions
+                    .with_session(sid, |s| Ok(s.recorder.clone()))
+                    .await
+                {
+                    recorder
+                        .record(RecordingEvent::Resize {
+                            cols: p.cols,
+                            rows: p.rows,
+                        })
+                        .await;
+                }
                 Ok(None)
             }
             (MsgType::Signal, Payload::Signal(p)) => {
@@ -1892,9 +3014,33 @@ impl WshServer {
                     let ch_map = self.channel_sessions.read().await;
                     ch_map.get(&p.channel_id).cloned()
                 };
-                if let Some(sid) = target_session {
+                if target_session.is_none() && ctx.channels.contains(&p.channel_id) {
+                    // A `TcpForward` channel of this connection.
+                    match ctx
+                        .compression_stats
+                        .decode(p.compression.as_deref(), p.data.clone())
+                    {
+                        Ok(data) => {
+                            self.gateway_forwarder
+                                .handle_channel_data(p.channel_id, data)
+                                .await
+                        }
+                        Err(e) => {
+                            warn!(channel_id = p.channel_id, error = %e, "cannot decode SessionData");
+                        }
+                    }
+                } else if let Some(sid) = target_session {
                     self.sessions.touch(&sid).await;
-                    let data = p.data.clone();
+                    let data = match ctx
+                        .compression_stats
+                        .decode(p.compression.as_deref(), p.data.clone())
+                    {
+                        Ok(data) => data,
+                        Err(e) => {
+                            warn!(channel_id = p.channel_id, error = %e, "cannot decode SessionData");
+                            return Ok(None);
+                        }
+                    };
                     if let Err(e) = self
                         .sessions
                         .with_session(&sid, |session| session.pty.write_blocking(&data))
@@ -1908,6 +3054,13 @@ impl WshServer {
                 Ok(None)
             }
             (MsgType::Close, Payload::Close(p)) => {
+                if ctx.channels.contains(&p.channel_id)
+                    && self.gateway_forwarder.close_channel(p.channel_id).await
+                {
+                    debug!(channel_id = p.channel_id, "tcp forward channel closed");
+                    ctx.channels.remove(&p.channel_id);
+                    return Ok(None);
+                }
                 // Look up the session for this channel_id
                 let target_session = {
                     let ch_map = self.channel_sessions.read().await;
@@ -1920,6 +3073,7 @@ impl WshServer {
                     warn!(channel_id = p.channel_id, error = %e, "detach failed on close");
                 }
                 self.channel_sessions.write().await.remove(&p.channel_id);
+                ctx.channels.remove(&p.channel_id);
                 Ok(None)
             }
 
@@ -1999,33 +3153,36 @@ impl WshServer {
 
             // ── MCP messages ────────────────────────────────────────
             (MsgType::McpDiscover, Payload::McpDiscover(_)) => {
-                let bridge = self.mcp_bridge.read().await;
-                let mut tools = bridge.list_tools();
-                let proxy = self.mcp_proxy.read().await;
-                tools.extend(proxy.list_tools());
+                let permissions = self.key_permissions(ctx);
+                let mut tools = Vec::new();
+                if permissions.has_scope(&crate::auth::permissions::SessionScope::Mcp) {
+                    tools.extend(crate::mcp::host::list_tools(&permissions));
+                    let bridge = self.mcp_bridge.read().await;
+                    tools.extend(bridge.list_tools());
+                    let proxy = self.mcp_proxy.read().await;
+                    tools.extend(proxy.list_tools());
+                }
                 Ok(Some(Envelope {
                     msg_type: MsgType::McpTools,
                     payload: Payload::McpTools(McpToolsPayload { tools }),
                 }))
             }
             (MsgType::McpCall, Payload::McpCall(p)) => {
-                // Try bridge first, then proxy
-                let bridge = self.mcp_bridge.read().await;
-                if bridge.has_tool(&p.tool) {
-                    let result = bridge.call(p).await;
-                    Ok(Some(Envelope {
+                if let Some(reason) = self.channel_limit(ctx).await {
+                    return Ok(Some(Envelope {
                         msg_type: MsgType::McpResult,
-                        payload: Payload::McpResult(result),
-                    }))
+                        payload: Payload::McpResult(McpResultPayload {
+                            result: serde_json::json!({ "error": reason }),
+                        }),
+                    }));
+                }
+                let caller = self.mcp_caller(ctx);
+                if p.progress == Some(true) {
+                    tokio::spawn(caller.call_with_progress(p.clone(), ctx.peer_tx.clone()));
                 } else {
-                    drop(bridge);
-                    let proxy = self.mcp_proxy.read().await;
-                    let result = proxy.call(p).await;
-                    Ok(Some(Envelope {
-                        msg_type: MsgType::McpResult,
-                        payload: Payload::McpResult(result),
-                    }))
+                    tokio::spawn(caller.call_and_reply(p.clone(), ctx.peer_tx.clone()));
                 }
+                Ok(None)
             }
 
             // ── Gateway messages ────────────────────────────────────
@@ -2040,10 +3197,33 @@ impl WshServer {
                         }),
                     }));
                 }
+                if !self.key_permissions(ctx).permits_open(&p.host, p.port) {
+                    return Ok(Some(Envelope {
+                        msg_type: MsgType::GatewayFail,
+                        payload: Payload::GatewayFail(GatewayFailPayload {
+                            gateway_id: p.gateway_id,
+                            code: 4,
+                            message: "port forwarding not permitted for this key".to_string(),
+                        }),
+                    }));
+                }
+                if let Some(reason) = self.channel_limit(ctx).await {
+                    return Ok(Some(Envelope {
+                        msg_type: MsgType::GatewayFail,
+                        payload: Payload::GatewayFail(GatewayFailPayload {
+                            gateway_id: p.gateway_id,
+                            code: 4,
+                            message: reason,
+                        }),
+                    }));
+                }
                 let resp = self
                     .gateway_forwarder
                     .handle_open_tcp(p.gateway_id, &p.host, p.port, data_tx)
                     .await;
+                if resp.msg_type == MsgType::GatewayOk {
+                    ctx.gateways.insert(p.gateway_id);
+                }
                 Ok(Some(resp))
             }
             (MsgType::OpenUdp, Payload::OpenUdp(p)) => {
@@ -2057,10 +3237,86 @@ impl WshServer {
                         }),
                     }));
                 }
+                if !self.key_permissions(ctx).permits_open(&p.host, p.port) {
+                    return Ok(Some(Envelope {
+                        msg_type: MsgType::GatewayFail,
+                        payload: Payload::GatewayFail(GatewayFailPayload {
+                            gateway_id: p.gateway_id,
+                            code: 4,
+                            message: "port forwarding not permitted for this key".to_string(),
+                        }),
+                    }));
+                }
+                if let Some(reason) = self.channel_limit(ctx).await {
+                    return Ok(Some(Envelope {
+                        msg_type: MsgType::GatewayFail,
+                        payload: Payload::GatewayFail(GatewayFailPayload {
+                            gateway_id: p.gateway_id,
+                            code: 4,
+                            message: reason,
+                        }),
+                    }));
+                }
                 let resp = self
                     .gateway_forwarder
                     .handle_open_udp(p.gateway_id, &p.host, p.port, data_tx)
                     .await;
+                if resp.msg_type == MsgType::GatewayOk {
+                    ctx.gateways.insert(p.gateway_id);
+                }
+                Ok(Some(resp))
+            }
+            (MsgType::OpenUnix, Payload::OpenUnix(p)) => {
+                if !self.gateway_enabled {
+                    return Ok(Some(Envelope {
+                        msg_type: MsgType::GatewayFail,
+                        payload: Payload::GatewayFail(GatewayFailPayload {
+                            gateway_id: p.gateway_id,
+                            code: 5,
+                            message: "gateway disabled".to_string(),
+                        }),
+                    }));
+                }
+                if !self.key_permissions(ctx).permits_open_unix() {
+                    return Ok(Some(Envelope {
+                        msg_type: MsgType::GatewayFail,
+                        payload: Payload::GatewayFail(GatewayFailPayload {
+                            gateway_id: p.gateway_id,
+                            code: 4,
+                            message: "socket forwarding not permitted for this key".to_string(),
+                        }),
+                    }));
+                }
+                if let Some(reason) = self.channel_limit(ctx).await {
+                    return Ok(Some(Envelope {
+                        msg_type: MsgType::GatewayFail,
+                        payload: Payload::GatewayFail(GatewayFailPayload {
+                            gateway_id: p.gateway_id,
+                            code: 4,
+                            message: reason,
+                        }),
+                    }));
+                }
+                let path = match ctx.workspace.resolve_socket(&p.path) {
+                    Ok(path) => path,
+                    Err(message) => {
+                        return Ok(Some(Envelope {
+                            msg_type: MsgType::GatewayFail,
+                            payload: Payload::GatewayFail(GatewayFailPayload {
+                                gateway_id: p.gateway_id,
+                                code: 4,
+                                message,
+                            }),
+                        }));
+                    }
+                };
+                let resp = self
+                    .gateway_forwarder
+                    .handle_open_unix(p.gateway_id, &path, data_tx)
+                    .await;
+                if resp.msg_type == MsgType::GatewayOk {
+                    ctx.gateways.insert(p.gateway_id);
+                }
                 Ok(Some(resp))
             }
             (MsgType::ResolveDns, Payload::ResolveDns(p)) => {
@@ -2088,6 +3344,7 @@ impl WshServer {
             }
             (MsgType::GatewayClose, Payload::GatewayClose(p)) => {
                 self.gateway_forwarder.close(p.gateway_id).await;
+                ctx.gateways.remove(&p.gateway_id);
                 Ok(None)
             }
             (MsgType::ListenRequest, Payload::ListenRequest(p)) => {
@@ -2100,31 +3357,114 @@ impl WshServer {
                         }),
                     }));
                 }
+                if !self.key_permissions(ctx).permits_listen(p.port) {
+                    return Ok(Some(Envelope {
+                        msg_type: MsgType::ListenFail,
+                        payload: Payload::ListenFail(ListenFailPayload {
+                            listener_id: p.listener_id,
+                            reason: "remote forwarding not permitted for this key".to_string(),
+                        }),
+                    }));
+                }
+                if let Some(reason) = self.channel_limit(ctx).await {
+                    return Ok(Some(Envelope {
+                        msg_type: MsgType::ListenFail,
+                        payload: Payload::ListenFail(ListenFailPayload {
+                            listener_id: p.listener_id,
+                            reason,
+                        }),
+                    }));
+                }
                 let resp = self
                     .reverse_listener
                     .handle_listen_request(p.listener_id, p.port, &p.bind_addr, inbound_tx)
                     .await;
+                if resp.msg_type == MsgType::ListenOk {
+                    ctx.listeners.insert(p.listener_id);
+                }
+                Ok(Some(resp))
+            }
+            (MsgType::ListenUnix, Payload::ListenUnix(p)) => {
+                if !self.gateway_enabled {
+                    return Ok(Some(Envelope {
+                        msg_type: MsgType::ListenFail,
+                        payload: Payload::ListenFail(ListenFailPayload {
+                            listener_id: p.listener_id,
+                            reason: "gateway disabled".to_string(),
+                        }),
+                    }));
+                }
+                if !self.key_permissions(ctx).permits_listen_unix() {
+                    return Ok(Some(Envelope {
+                        msg_type: MsgType::ListenFail,
+                        payload: Payload::ListenFail(ListenFailPayload {
+                            listener_id: p.listener_id,
+                            reason: "socket forwarding not permitted for this key".to_string(),
+                        }),
+                    }));
+                }
+                if let Some(reason) = self.channel_limit(ctx).await {
+                    return Ok(Some(Envelope {
+                        msg_type: MsgType::ListenFail,
+                        payload: Payload::ListenFail(ListenFailPayload {
+                            listener_id: p.listener_id,
+                            reason,
+                        }),
+                    }));
+                }
+                let path = match ctx.workspace.resolve_socket(&p.path) {
+                    Ok(path) => path,
+                    Err(reason) => {
+                        return Ok(Some(Envelope {
+                            msg_type: MsgType::ListenFail,
+                            payload: Payload::ListenFail(ListenFailPayload {
+                                listener_id: p.listener_id,
+                                reason,
+                            }),
+                        }));
+                    }
+                };
+                let resp = self
+                    .reverse_listener
+                    .handle_listen_unix(p.listener_id, &path, inbound_tx)
+                    .await;
+                if resp.msg_type == MsgType::ListenOk {
+                    ctx.listeners.insert(p.listener_id);
+                }
                 Ok(Some(resp))
             }
             (MsgType::ListenClose, Payload::ListenClose(p)) => {
+                ctx.listeners.remove(&p.listener_id);
                 let resp = self.reverse_listener.close_listener(p.listener_id).await;
                 Ok(resp)
             }
             (MsgType::InboundAccept, Payload::InboundAccept(p)) => {
-                if let Some(gateway_id) = p.gateway_id {
+                // Without a gateway_id the connection becomes a `TcpForward`
+                // channel under the InboundOpen's channel_id.
+                if let Some(reason) = self.channel_limit(ctx).await {
+                    warn!(channel_id = p.channel_id, %reason, "refusing inbound connection");
                     self.reverse_listener
-                        .handle_inbound_accept(
-                            p.channel_id,
-                            gateway_id,
-                            data_tx,
-                            &self.gateway_forwarder,
-                        )
+                        .handle_inbound_reject(p.channel_id)
                         .await;
-                } else {
-                    debug!(
-                        channel_id = p.channel_id,
-                        "InboundAccept without gateway_id, ignoring"
-                    );
+                    return Ok(Some(match p.gateway_id {
+                        Some(gateway_id) => build_gateway_close_msg(gateway_id),
+                        None => build_channel_close(p.channel_id),
+                    }));
+                }
+                if self
+                    .reverse_listener
+                    .handle_inbound_accept(
+                        p.channel_id,
+                        p.gateway_id,
+                        data_tx,
+                        &self.gateway_forwarder,
+                    )
+                    .await
+                {
+                    match p.gateway_id {
+                        Some(gateway_id) => ctx.gateways.insert(gateway_id),
+                        None => ctx.channels.insert(p.channel_id),
+                    };
                 }
                 Ok(None)
             }
@@ -2177,6 +3517,17 @@ impl WshServer {
 
                 match recording_path {
                     Some(path) if path.exists() => match tokio::fs::read_to_string(&path).await {
+                        Ok(_) if p.format == "asciicast" => {
+                            let entries = load_recording(&path).await.unwrap_or_default();
+                            Ok(Some(Envelope {
+                                msg_type: MsgType::RecordingExport,
+                                payload: Payload::RecordingExport(RecordingExportPayload {
+                                    session_id: p.session_id.clone(),
+                                    format: p.format.clone(),
+                                    data: Some(to_asciicast(&entries, (80, 24))),
+                                }),
+                            }))
+                        }
                         Ok(data) => Ok(Some(Envelope {
                             msg_type: MsgType::RecordingExport,
                             payload: Payload::RecordingExport(RecordingExportPayload {
@@ -2328,13 +3679,12 @@ impl WshServer {
                         let _ = session.pty.kill();
                         // Get current size
                         let (cols, rows) = session.pty.size();
-                        // Spawn new PTY with same size
-                        let new_pty = crate::session::pty::PtyHandle::spawn(
-                            p.command.as_deref(),
-                            cols,
-                            rows,
-                            None,
-                        )?;
+                        // Spawn new PTY with same size and workspace
+                        let new_pty = crate::session::pty::PtyHandle::spawn(&SpawnOptions {
+                            cwd: session.cwd.as_deref(),
+                            run_as: session.run_as.as_deref(),
+                            ..SpawnOptions::new(p.command.as_deref(), cols, rows)
+                        })?;
                         session.pty = new_pty;
                         session.last_activity = std::time::Instant::now();
                         Ok(())
@@ -3086,19 +4436,106 @@ impl WshServer {
                 }))
             }
 
-            (MsgType::FileChunk, Payload::FileChunk(p)) => {
-                if !self
-                    .check_session_access(&ctx.session_id, &ctx.username)
-                    .await
+            (MsgType::FileTransferStart, Payload::FileTransferStart(p)) => {
+                let permissions = self.key_permissions(ctx);
+                let resp = if !permissions
+                    .has_scope(&crate::auth::permissions::SessionScope::FileTransfer)
                 {
+                    Envelope {
+                        msg_type: MsgType::FileResult,
+                        payload: Payload::FileResult(FileResultPayload {
+                            channel_id: p.transfer_id,
+                            success: false,
+                            metadata: serde_json::Value::Object(Default::default()),
+                            error_message: Some("file transfer not permitted for this key".into()),
+                        }),
+                    }
+                } else if let Some(reason) = self.channel_limit(ctx).await {
+                    Envelope {
+                        msg_type: MsgType::FileResult,
+                        payload: Payload::FileResult(FileResultPayload {
+                            channel_id: p.transfer_id,
+                            success: false,
+                            metadata: serde_json::Value::Object(Default::default()),
+                            error_message: Some(reason),
+                        }),
+                    }
+                } else {
+                    self.transfers
+                        .start(
+                            &ctx.session_id,
+                            &ctx.workspace,
+                            p.clone(),
+                            ChunkSink {
+                                peer_tx: ctx.peer_tx.clone(),
+                                compression: ctx.compression,
+                                stats: ctx.compression_stats.clone(),
+                            },
+                        )
+                        .await
+                };
+                let (success, size, reason) = match &resp.payload {
+                    Payload::FileTransferReady(ready) => (true, ready.total_size, None),
+                    Payload::FileResult(result) => {
+                        (false, p.total_size, result.error_message.clone())
+                    }
+                    _ => (false, p.total_size, None),
+                };
+                self.audit
+                    .record(AuditEvent::FileTransfer {
+                        username: ctx.username.clone(),
+                        fingerprint: ctx.fingerprint.clone(),
+                        direction: p.direction.clone(),
+                        path: p.path.clone(),
+                        size,
+                        success,
+                        reason,
+                    })
+                    .await;
+                Ok(Some(resp))
+            }
+
+            (MsgType::SyncManifestRequest, Payload::SyncManifestRequest(p)) => {
+                let permissions = self.key_permissions(ctx);
+                if !permissions.has_scope(&crate::auth::permissions::SessionScope::FileTransfer) {
                     return Ok(Some(Envelope {
-                        msg_type: MsgType::Error,
-                        payload: Payload::Error(ErrorPayload {
-                            code: 2,
-                            message: "not authorized".into(),
+                        msg_type: MsgType::FileResult,
+                        payload: Payload::FileResult(FileResultPayload {
+                            channel_id: p.transfer_id,
+                            success: false,
+                            metadata: serde_json::Value::Object(Default::default()),
+                            error_message: Some("file transfer not permitted for this key".into()),
+                        }),
+                    }));
+                }
+                Ok(Some(self.transfers.manifest(&ctx.workspace, p).await))
+            }
+
+            (MsgType::SyncDelete, Payload::SyncDelete(p)) => {
+                let permissions = self.key_permissions(ctx);
+                if !permissions.has_scope(&crate::auth::permissions::SessionScope::FileTransfer) {
+                    return Ok(Some(Envelope {
+                        msg_type: MsgType::FileResult,
+                        payload: Payload::FileResult(FileResultPayload {
+                            channel_id: p.transfer_id,
+                            success: false,
+                            metadata: serde_json::Value::Object(Default::default()),
+                            error_message: Some("file transfer not permitted for this key".into()),
                         }),
                     }));
                 }
+                Ok(Some(self.transfers.delete(&ctx.workspace, p).await))
+            }
+
+            (MsgType::AgentForwardResponse, Payload::AgentForward(p)) => {
+                match &ctx.agent_forwarder {
+                    Some(forwarder) => forwarder.complete(p).await,
+                    None => debug!("agent response without a forwarded agent"),
+                }
+                Ok(None)
+            }
+
+            (MsgType::FileChunk, Payload::FileChunk(p)) => {
                 debug!(
                     channel_id = p.channel_id,
                     offset = p.offset,
@@ -3106,6 +4543,16 @@ impl WshServer {
                     is_final = p.is_final,
                     "file chunk"
                 );
+                // Chunks only apply to uploads this connection started, which
+                // were authorized at FileTransferStart.
+                Ok(self
+                    .transfers
+                    .handle_chunk(&ctx.session_id, p.clone(), &ctx.compression_stats)
+                    .await)
+            }
+
+            (MsgType::WindowUpdate, Payload::WindowUpdate(p)) => {
+                self.transfers.grant(&ctx.session_id, p).await;
                 Ok(None)
             }
 
@@ -3187,12 +4634,7 @@ impl WshServer {
             (MsgType::PolicyUpdate, Payload::PolicyUpdate(p)) => {
                 // Only the server administrator (first authorized key) can update policies.
                 // The first key in authorized_keys is treated as admin.
-                let is_admin = self
-                    .authorized_keys
-                    .first()
-                    .map(|k| k.fingerprint == ctx.fingerprint)
-                    .unwrap_or(false);
-                if !is_admin {
+                if !self.is_admin(ctx) {
                     return Ok(Some(Envelope {
                         msg_type: MsgType::Error,
                         payload: Payload::Error(ErrorPayload {
@@ -3212,6 +4654,28 @@ impl WshServer {
                 Ok(None)
             }
 
+            // ── Remote authorized_keys ──────────────────────────────
+            (
+                MsgType::AuthorizedKeysList
+                | MsgType::AuthorizedKeyAdd
+                | MsgType::AuthorizedKeyRemove,
+                payload,
+            ) => {
+                if !self.may_manage_keys(ctx) {
+                    return Ok(Some(Envelope {
+                        msg_type: MsgType::Error,
+                        payload: Payload::Error(ErrorPayload {
+                            code: 2,
+                            message: "not permitted to manage authorized keys".into(),
+                        }),
+                    }));
+                }
+                if let Payload::AuthorizedKeysList(_) = payload {
+                    return Ok(Some(self.authorized_keys_reply(false)));
+                }
+                Ok(Some(self.edit_authorized_keys(ctx, payload)))
+            }
+
             // ── Terminal frontend config ────────────────────────────
             (MsgType::TerminalConfig, Payload::TerminalConfig(p)) => {
                 // Verify the caller has access to their own session
@@ -3268,6 +4732,40 @@ fn build_gateway_close_msg(gateway_id: u32) -> Envelope {
     }
 }
 
+/// Build a [`MsgType::SessionData`] envelope carrying `TcpForward` channel data.
+fn build_channel_data(channel_id: u32, data: Vec<u8>, compression: Option<String>) -> Envelope {
+    Envelope {
+        msg_type: MsgType::SessionData,
+        payload: Payload::SessionData(SessionDataPayload {
+            channel_id,
+            data,
+            compression,
+        }),
+    }
+}
+
+/// Build a [`MsgType::Close`] envelope to notify the client a channel closed.
+fn build_channel_close(channel_id: u32) -> Envelope {
+    Envelope {
+        msg_type: MsgType::Close,
+        payload: Payload::Close(ClosePayload { channel_id }),
+    }
+}
+
+/// Split a `TcpForward` destination (`host:port`, IPv6 hosts in brackets)
+/// into host and port.
+fn parse_forward_destination(destination: &str) -> Option<(String, u16)> {
+    let (host, port) = destination.rsplit_once(':')?;
+    let host = host
+        .strip_prefix('[')
+        .and_then(|h| h.strip_suffix(']'))
+        .unwrap_or(host);
+    if host.is_empty() {
+        return None;
+    }
+    Some((host.to_string(), port.parse().ok()?))
+}
+
 /// Build an [`MsgType::InboundOpen`] envelope from a reverse tunnel
 /// [`InboundEvent`](crate::gateway::listener::InboundEvent).
 ///
@@ -3285,6 +4783,48 @@ fn build_inbound_open(event: &crate::gateway::listener::InboundEvent) -> Envelop
     }
 }
 
+/// Log how much a connection's negotiated compression saved.
+fn log_compression(ctx: &ConnectionContext) {
+    let (Some(codec), stats) = (ctx.compression, &ctx.compression_stats) else {
+        return;
+    };
+    if stats.frames() == 0 {
+        return;
+    }
+    info!(
+        session_id = %ctx.session_id,
+        codec = codec.name(),
+        raw_bytes = stats.raw_bytes(),
+        wire_bytes = stats.wire_bytes(),
+        ratio = format_args!("{:.2}", stats.ratio()),
+        cpu_ms = stats.cpu_micros() / 1000,
+        "compression stats"
+    );
+}
+
+/// Describe a registered reverse peer for `ReversePeers` and `ReversePeerEvent`.
+fn peer_info(e: &PeerEntry) -> PeerInfo {
+    PeerInfo {
+        fingerprint: e.fingerprint.clone(),
+        fingerprint_short: if e.fingerprint.len() >= 8 {
+            e.fingerprint[..8].to_string()
+        } else {
+            e.fingerprint.clone()
+        },
+        username: e.username.clone(),
+        name: e.name.clone(),
+        capabilities: e.capabilities.clone(),
+        peer_type: e.peer_type.clone(),
+        shell_backend: e.shell_backend.clone(),
+        source: "wsh-relay".to_string(),
+        supports_attach: e.supports_attach,
+        supports_replay: e.supports_replay,
+        supports_echo: e.supports_echo,
+        supports_term_sync: e.supports_term_sync,
+        last_seen: Some(e.last_seen.elapsed().as_secs()),
+    }
+}
+
 /// Read a length-prefixed frame from a WebTransport recv stream.
 async fn read_webtransport_frame(recv: &mut wtransport::RecvStream) -> WshResult<Vec<u8>> {
     let mut len_buf = [0u8; 4];
@@ -1,9 +1,12 @@
 //! Session lifecycle management.
 //!
 //! Tracks all active sessions, handles creation, attachment, detachment,
-//! and garbage collection of expired/idle sessions.
+//! and garbage collection of expired/idle sessions. With a [`SessionStore`]
+//! configured, session state is snapshotted to disk and sessions saved by a
+//! previous server process are kept as "dormant" until someone attaches.
 
-use super::pty::PtyHandle;
+use super::persist::{unix_now, PersistedSession, SessionStore};
+use super::pty::{PtyHandle, SpawnOptions};
 use super::recording::{RecordingEvent, SessionRecorder};
 use super::ring_buffer::RingBuffer;
 use crate::auth::permissions::KeyPermissions;
@@ -29,6 +32,12 @@ pub struct Session {
     pub fingerprint: String,
     /// Permissions granted to this session.
     pub permissions: KeyPermissions,
+    /// Command the PTY was started with (`None` = default shell).
+    pub command: Option<String>,
+    /// Workspace the PTY was started in (`None` = not isolated).
+    pub cwd: Option<std::path::PathBuf>,
+    /// System account the PTY runs as (`None` = the server's own).
+    pub run_as: Option<String>,
     /// The PTY backing this session.
     pub pty: PtyHandle,
     /// Ring buffer for output replay on reattach.
@@ -37,6 +46,8 @@ pub struct Session {
     pub recorder: Option<SessionRecorder>,
     /// When the session was created.
     pub created_at: Instant,
+    /// Unix time the session was created (survives restarts).
+    pub created_unix: u64,
     /// Last activity timestamp (for idle timeout).
     pub last_activity: Instant,
     /// Number of currently attached clients.
@@ -57,6 +68,29 @@ pub struct SessionInfo {
     pub created_at_secs: u64,
     pub idle_secs: u64,
     pub attached_count: u32,
+    /// Full key fingerprint of the owner.
+    pub fingerprint: String,
+    /// Command the PTY was started with (`None` = default shell).
+    pub command: Option<String>,
+    /// Terminal size as `(cols, rows)`.
+    pub size: (u16, u16),
+    /// Unix time of the last activity.
+    pub last_activity_secs: u64,
+    /// Saved to disk and waiting for a reattach after a restart.
+    pub dormant: bool,
+}
+
+impl SessionInfo {
+    /// `"dormant"`, `"attached"` or `"detached"`.
+    pub fn state(&self) -> &'static str {
+        if self.dormant {
+            "dormant"
+        } else if self.attached_count > 0 {
+            "attached"
+        } else {
+            "detached"
+        }
+    }
 }
 
 /// Manages all active sessions.
@@ -65,6 +99,14 @@ pub struct SessionManager {
     max_sessions: usize,
     default_ttl: u64,
     default_idle_timeout: u64,
+    /// Ring buffer (scrollback) size per session, in bytes.
+    scrollback_size: usize,
+    /// On-disk state store, when persistence is enabled.
+    store: Option<SessionStore>,
+    /// How long a saved session may wait for reattach, in seconds.
+    persist_ttl: u64,
+    /// Sessions restored from disk that have no PTY yet.
+    dormant: RwLock<HashMap<String, PersistedSession>>,
 }
 
 impl SessionManager {
@@ -75,21 +117,177 @@ impl SessionManager {
             max_sessions,
             default_ttl,
             default_idle_timeout,
+            scrollback_size: DEFAULT_RING_BUFFER_SIZE,
+            store: None,
+            persist_ttl: 0,
+            dormant: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Set the per-session scrollback size in bytes.
+    pub fn with_scrollback_size(mut self, bytes: usize) -> Self {
+        self.scrollback_size = bytes;
+        self
+    }
+
+    /// Persist session state to `store`, keeping saved sessions for `ttl_secs`.
+    pub fn with_store(mut self, store: SessionStore, ttl_secs: u64) -> Self {
+        self.store = Some(store);
+        self.persist_ttl = ttl_secs;
+        self
+    }
+
+    /// Load sessions saved by a previous server process as dormant sessions.
+    ///
+    /// Returns how many were restored.
+    pub async fn restore(&self) -> usize {
+        let Some(store) = &self.store else {
+            return 0;
+        };
+        let saved = store.load_all(self.persist_ttl).await;
+        let count = saved.len();
+        let mut dormant = self.dormant.write().await;
+        for session in saved {
+            dormant.insert(session.id.clone(), session);
+        }
+        if count > 0 {
+            info!(count, "restored persisted sessions");
+        }
+        count
+    }
+
+    /// Snapshot every live session to the store.
+    pub async fn persist_all(&self) {
+        let Some(store) = &self.store else {
+            return;
+        };
+        let now = unix_now();
+        let snapshots: Vec<(PersistedSession, Vec<u8>)> = {
+            let sessions = self.sessions.read().await;
+            sessions
+                .values()
+                .map(|s| {
+                    let (cols, rows) = s.pty.size();
+                    (
+                        PersistedSession {
+                            id: s.id.clone(),
+                            name: s.name.clone(),
+                            username: s.username.clone(),
+                            fingerprint: s.fingerprint.clone(),
+                            permissions: s.permissions.clone(),
+                            command: s.command.clone(),
+                            cols,
+                            rows,
+                            cwd: s.cwd.clone(),
+                            run_as: s.run_as.clone(),
+                            created_at: s.created_unix,
+                            saved_at: now,
+                        },
+                        s.ring_buffer.read_all(),
+                    )
+                })
+                .collect()
+        };
+        for (session, scrollback) in &snapshots {
+            if let Err(e) = store.save(session, scrollback).await {
+                warn!(session_id = %session.id, error = %e, "failed to persist session");
+            }
+        }
+        debug!(count = snapshots.len(), "persisted sessions");
+    }
+
+    /// Bring a dormant session back to life for `username`.
+    ///
+    /// Respawns the PTY with the saved command and size and preloads the
+    /// saved scrollback. Returns `Ok(false)` if there is no dormant session
+    /// with this ID owned by `username`.
+    pub async fn revive(
+        &self,
+        session_id: &str,
+        username: &str,
+        recording_dir: Option<&std::path::Path>,
+    ) -> WshResult<bool> {
+        let saved = {
+            let mut dormant = self.dormant.write().await;
+            match dormant.get(session_id) {
+                Some(saved) if saved.username == username => dormant.remove(session_id),
+                _ => None,
+            }
+        };
+        let Some(saved) = saved else {
+            return Ok(false);
+        };
+        if self.count().await >= self.max_sessions {
+            self.dormant.write().await.insert(saved.id.clone(), saved);
+            return Err(WshError::Other(format!(
+                "max sessions ({}) reached",
+                self.max_sessions
+            )));
+        }
+
+        let pty = PtyHandle::spawn(&SpawnOptions {
+            cwd: saved.cwd.as_deref(),
+            run_as: saved.run_as.as_deref(),
+            ..SpawnOptions::new(saved.command.as_deref(), saved.cols, saved.rows)
+        })?;
+        let mut ring_buffer = RingBuffer::new(self.scrollback_size);
+        if let Some(store) = &self.store {
+            ring_buffer.write(&store.scrollback(session_id).await);
         }
+        let recorder =
+            recording_dir.map(|dir| SessionRecorder::new(dir.join(format!("{session_id}.jsonl"))));
+        let now = Instant::now();
+        let session = Session {
+            id: saved.id.clone(),
+            name: saved.name,
+            username: saved.username,
+            fingerprint: saved.fingerprint,
+            permissions: saved.permissions,
+            command: saved.command,
+            cwd: saved.cwd,
+            run_as: saved.run_as,
+            pty,
+            ring_buffer,
+            recorder,
+            created_at: now,
+            created_unix: saved.created_at,
+            last_activity: now,
+            attached_count: 0,
+            ttl_secs: self.default_ttl,
+            idle_timeout_secs: self.default_idle_timeout,
+        };
+        self.sessions
+            .write()
+            .await
+            .insert(saved.id.clone(), session);
+        info!(session_id, "revived persisted session");
+        Ok(true)
     }
 
-    /// Create a new session with a PTY.
+    /// Record PTY output: append to the scrollback and touch activity.
+    pub async fn push_output(&self, session_id: &str, data: &[u8]) {
+        let mut sessions = self.sessions.write().await;
+        if let Some(session) = sessions.get_mut(session_id) {
+            session.ring_buffer.write(data);
+            session.last_activity = Instant::now();
+        }
+    }
+
+    /// Create a new session with a PTY started as described by `spawn`.
     pub async fn create(
         &self,
         username: String,
         fingerprint: String,
         permissions: KeyPermissions,
-        command: Option<&str>,
-        cols: u16,
-        rows: u16,
-        env: Option<&std::collections::HashMap<String, String>>,
+        spawn: SpawnOptions<'_>,
         recording_dir: Option<&std::path::Path>,
     ) -> WshResult<String> {
+        let SpawnOptions {
+            command,
+            cwd,
+            run_as,
+            ..
+        } = spawn;
         // Pre-check with read lock (fast rejection for common case)
         {
             let sessions = self.sessions.read().await;
@@ -103,7 +301,7 @@ impl SessionManager {
 
         // Spawn PTY and prepare session (outside lock)
         let session_id = generate_session_id();
-        let pty = PtyHandle::spawn(command, cols, rows, env)?;
+        let pty = PtyHandle::spawn(&spawn)?;
 
         let recorder = if let Some(dir) = recording_dir {
             let path = dir.join(format!("{session_id}.jsonl"));
@@ -125,9 +323,13 @@ impl SessionManager {
             fingerprint,
             permissions,
             pty,
-            ring_buffer: RingBuffer::new(DEFAULT_RING_BUFFER_SIZE),
+            command: command.map(str::to_string),
+            cwd: cwd.map(std::path::Path::to_path_buf),
+            run_as: run_as.map(str::to_string),
+            ring_buffer: RingBuffer::new(self.scrollback_size),
             recorder,
             created_at: now,
+            created_unix: unix_now(),
             last_activity: now,
             attached_count: 1,
             ttl_secs: self.default_ttl,
@@ -149,8 +351,29 @@ impl SessionManager {
         Ok(session_id)
     }
 
-    /// List all active sessions.
+    /// List all active sessions, including dormant ones awaiting reattach.
     pub async fn list(&self) -> Vec<SessionInfo> {
+        let now = unix_now();
+        let dormant: Vec<SessionInfo> = self
+            .dormant
+            .read()
+            .await
+            .values()
+            .map(|s| SessionInfo {
+                id: s.id.clone(),
+                name: s.name.clone(),
+                username: s.username.clone(),
+                fingerprint_short: s.fingerprint.chars().take(8).collect(),
+                created_at_secs: now.saturating_sub(s.created_at),
+                idle_secs: now.saturating_sub(s.saved_at),
+                attached_count: 0,
+                fingerprint: s.fingerprint.clone(),
+                command: s.command.clone(),
+                size: (s.cols, s.rows),
+                last_activity_secs: s.saved_at,
+                dormant: true,
+            })
+            .collect();
         let sessions = self.sessions.read().await;
         sessions
             .values()
@@ -171,8 +394,14 @@ impl SessionManager {
                     created_at_secs: created,
                     idle_secs: idle,
                     attached_count: s.attached_count,
+                    fingerprint: s.fingerprint.clone(),
+                    command: s.command.clone(),
+                    size: s.pty.size(),
+                    last_activity_secs: now.saturating_sub(idle),
+                    dormant: false,
                 }
             })
+            .chain(dormant)
             .collect()
     }
 
@@ -231,6 +460,10 @@ impl SessionManager {
         let mut sessions = self.sessions.write().await;
         if sessions.remove(session_id).is_some() {
             info!(session_id, "session removed");
+            drop(sessions);
+            if let Some(store) = &self.store {
+                store.remove(session_id).await;
+            }
             Ok(())
         } else {
             Err(WshError::SessionNotFound(session_id.to_string()))
@@ -289,6 +522,25 @@ impl SessionManager {
             true
         });
 
+        drop(sessions);
+
+        // Dormant sessions nobody reattached to within the persistence TTL
+        let now = unix_now();
+        self.dormant.write().await.retain(|id, s| {
+            if now.saturating_sub(s.saved_at) > self.persist_ttl {
+                warn!(session_id = %id, "persisted session expired");
+                removed.push(id.clone());
+                return false;
+            }
+            true
+        });
+
+        if let Some(store) = &self.store {
+            for id in &removed {
+                store.remove(id).await;
+            }
+        }
+
         if !removed.is_empty() {
             debug!(count = removed.len(), "GC removed sessions");
         }
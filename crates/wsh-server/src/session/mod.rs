@@ -1,6 +1,7 @@
-//! Session management: PTY lifecycle, ring buffer, recording.
+//! Session management: PTY lifecycle, ring buffer, recording, persistence.
 
 pub mod manager;
+pub mod persist;
 pub mod pty;
 pub mod recording;
 pub mod ring_buffer;
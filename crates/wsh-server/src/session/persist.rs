@@ -0,0 +1,232 @@
+//! On-disk session state for reattach across server restarts.
+//!
+//! A PTY cannot outlive the server process, but the session around it can:
+//! each session's metadata is saved as `<dir>/<session_id>.json`, with the
+//! ring-buffer scrollback alongside it in `<dir>/<session_id>.scrollback`.
+//! After a restart the saved sessions are listed as detached, and the first
+//! attach respawns the shell with the original command and size, replaying
+//! the saved scrollback before any new output.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{debug, warn};
+use wsh_core::WshResult;
+
+use crate::auth::permissions::KeyPermissions;
+
+/// Session metadata persisted across restarts.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PersistedSession {
+    pub id: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    pub username: String,
+    pub fingerprint: String,
+    pub permissions: KeyPermissions,
+    /// Command the PTY was started with (`None` = default shell).
+    #[serde(default)]
+    pub command: Option<String>,
+    pub cols: u16,
+    pub rows: u16,
+    /// Workspace the PTY was started in (`None` = not isolated).
+    #[serde(default)]
+    pub cwd: Option<PathBuf>,
+    /// System account the PTY ran as (`None` = the server's own).
+    #[serde(default)]
+    pub run_as: Option<String>,
+    /// Unix time the session was first created.
+    pub created_at: u64,
+    /// Unix time this snapshot was written.
+    pub saved_at: u64,
+}
+
+/// Directory-backed store for [`PersistedSession`]s.
+#[derive(Debug, Clone)]
+pub struct SessionStore {
+    dir: PathBuf,
+    /// Whether scrollback is saved along with the metadata.
+    scrollback: bool,
+}
+
+impl SessionStore {
+    /// Open (and create if needed) a store in `dir`, readable only by the
+    /// server account since snapshots hold session output.
+    pub fn new(dir: PathBuf, scrollback: bool) -> WshResult<Self> {
+        std::fs::create_dir_all(&dir)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o700))?;
+        }
+        Ok(Self { dir, scrollback })
+    }
+
+    /// Write a session snapshot, replacing any earlier one.
+    pub async fn save(&self, session: &PersistedSession, scrollback: &[u8]) -> WshResult<()> {
+        let json = serde_json::to_vec_pretty(session)
+            .map_err(|e| wsh_core::WshError::Codec(format!("session state: {e}")))?;
+        write_atomic(&self.meta_path(&session.id), &json).await?;
+        if self.scrollback {
+            write_atomic(&self.scrollback_path(&session.id), scrollback).await?;
+        }
+        Ok(())
+    }
+
+    /// Load every saved session no older than `ttl_secs`, deleting the rest.
+    pub async fn load_all(&self, ttl_secs: u64) -> Vec<PersistedSession> {
+        let now = unix_now();
+        let mut sessions = Vec::new();
+        let mut entries = match tokio::fs::read_dir(&self.dir).await {
+            Ok(entries) => entries,
+            Err(e) => {
+                warn!(path = %self.dir.display(), error = %e, "cannot read session state dir");
+                return sessions;
+            }
+        };
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            let parsed = tokio::fs::read(&path)
+                .await
+                .ok()
+                .and_then(|bytes| serde_json::from_slice::<PersistedSession>(&bytes).ok());
+            match parsed {
+                Some(session) if now.saturating_sub(session.saved_at) <= ttl_secs => {
+                    sessions.push(session);
+                }
+                Some(session) => {
+                    debug!(session_id = %session.id, "discarding expired session state");
+                    self.remove(&session.id).await;
+                }
+                None => warn!(path = %path.display(), "skipping unreadable session state"),
+            }
+        }
+        sessions
+    }
+
+    /// Read the saved scrollback for a session (empty if none).
+    pub async fn scrollback(&self, session_id: &str) -> Vec<u8> {
+        tokio::fs::read(self.scrollback_path(session_id))
+            .await
+            .unwrap_or_default()
+    }
+
+    /// Forget a session.
+    pub async fn remove(&self, session_id: &str) {
+        let _ = tokio::fs::remove_file(self.meta_path(session_id)).await;
+        let _ = tokio::fs::remove_file(self.scrollback_path(session_id)).await;
+    }
+
+    fn meta_path(&self, session_id: &str) -> PathBuf {
+        self.dir.join(format!("{session_id}.json"))
+    }
+
+    fn scrollback_path(&self, session_id: &str) -> PathBuf {
+        self.dir.join(format!("{session_id}.scrollback"))
+    }
+}
+
+/// Current Unix time in seconds.
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Write via a temp file + rename so a crash never leaves a torn snapshot.
+/// The file is created 0600 on Unix.
+async fn write_atomic(path: &Path, data: &[u8]) -> WshResult<()> {
+    use tokio::io::AsyncWriteExt;
+
+    let mut tmp = path.as_os_str().to_os_string();
+    tmp.push(".tmp");
+    let mut options = tokio::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let mut file = options.open(&tmp).await?;
+    #[cfg(unix)]
+    {
+        // A leftover temp file keeps its old mode; tighten it before writing.
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(std::fs::Permissions::from_mode(0o600))
+            .await?;
+    }
+    file.write_all(data).await?;
+    file.flush().await?;
+    drop(file);
+    tokio::fs::rename(&tmp, path).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(id: &str, saved_at: u64) -> PersistedSession {
+        PersistedSession {
+            id: id.into(),
+            name: Some("build".into()),
+            username: "alice".into(),
+            fingerprint: "SHA256:abc".into(),
+            permissions: KeyPermissions::full_access("SHA256:abc".into()),
+            command: None,
+            cols: 120,
+            rows: 40,
+            cwd: None,
+            run_as: None,
+            created_at: saved_at,
+            saved_at,
+        }
+    }
+
+    #[tokio::test]
+    async fn save_and_reload_drops_expired() {
+        let dir = std::env::temp_dir().join(format!("wsh-persist-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let store = SessionStore::new(dir.clone(), true).unwrap();
+
+        store
+            .save(&sample("fresh", unix_now()), b"$ ls\r\n")
+            .await
+            .unwrap();
+        store
+            .save(&sample("stale", unix_now() - 7200), b"")
+            .await
+            .unwrap();
+
+        let loaded = store.load_all(3600).await;
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].id, "fresh");
+        assert_eq!((loaded[0].cols, loaded[0].rows), (120, 40));
+        assert_eq!(store.scrollback("fresh").await, b"$ ls\r\n");
+        assert!(!dir.join("stale.json").exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn snapshots_are_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("wsh-persist-mode-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let store = SessionStore::new(dir.clone(), true).unwrap();
+        store
+            .save(&sample("s", unix_now()), b"secret")
+            .await
+            .unwrap();
+
+        let mode = |p: &Path| std::fs::metadata(p).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(&dir), 0o700);
+        assert_eq!(mode(&dir.join("s.json")), 0o600);
+        assert_eq!(mode(&dir.join("s.scrollback")), 0o600);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -4,12 +4,45 @@
 //! async read/write and resize operations.
 
 use portable_pty::{native_pty_system, CommandBuilder, MasterPty, PtySize};
+use std::collections::HashMap;
 use std::io::{Read, Write};
+use std::path::Path;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 use tracing::{debug, info};
 use wsh_core::{WshError, WshResult};
 
+/// How to start a PTY process.
+#[derive(Debug, Clone, Copy)]
+pub struct SpawnOptions<'a> {
+    /// Command to run; the user's default shell when `None`.
+    pub command: Option<&'a str>,
+    /// Initial terminal size.
+    pub cols: u16,
+    pub rows: u16,
+    /// Extra environment, e.g. `SSH_AUTH_SOCK` for a forwarded agent.
+    pub env: Option<&'a HashMap<String, String>>,
+    /// Isolated workspace: the process starts there and sees it as `$HOME`.
+    pub cwd: Option<&'a Path>,
+    /// System account to switch to (see [`crate::isolation::RUN_AS_ARG`]).
+    pub run_as: Option<&'a str>,
+}
+
+impl<'a> SpawnOptions<'a> {
+    /// Run `command` (or the default shell) at the given size, with no
+    /// extra environment or workspace.
+    pub fn new(command: Option<&'a str>, cols: u16, rows: u16) -> Self {
+        Self {
+            command,
+            cols,
+            rows,
+            env: None,
+            cwd: None,
+            run_as: None,
+        }
+    }
+}
+
 /// A managed PTY instance.
 pub struct PtyHandle {
     /// The master side of the PTY (read/write).
@@ -25,15 +58,16 @@ pub struct PtyHandle {
 }
 
 impl PtyHandle {
-    /// Spawn a new PTY with the given command and terminal size.
-    ///
-    /// If `command` is None, the user's default shell is used.
-    pub fn spawn(
-        command: Option<&str>,
-        cols: u16,
-        rows: u16,
-        env: Option<&std::collections::HashMap<String, String>>,
-    ) -> WshResult<Self> {
+    /// Spawn a new PTY as described by `options`.
+    pub fn spawn(options: &SpawnOptions<'_>) -> WshResult<Self> {
+        let SpawnOptions {
+            command,
+            cols,
+            rows,
+            env,
+            cwd,
+            run_as,
+        } = *options;
         let pty_system = native_pty_system();
 
         let size = PtySize {
@@ -47,7 +81,7 @@ impl PtyHandle {
             .openpty(size)
             .map_err(|e| WshError::Other(format!("failed to open PTY: {e}")))?;
 
-        let mut cmd = build_command_builder(command)?;
+        let mut cmd = build_command_builder(command, run_as)?;
 
         // Set environment variables
         if let Some(env_map) = env {
@@ -59,6 +93,12 @@ impl PtyHandle {
         // Set TERM if not already in env
         cmd.env("TERM", "xterm-256color");
 
+        // Start in (and treat as home) the user's workspace, when isolated
+        if let Some(dir) = cwd {
+            cmd.cwd(dir);
+            cmd.env("HOME", dir);
+        }
+
         let child = pair
             .slave
             .spawn_command(cmd)
@@ -177,9 +217,10 @@ impl PtyHandle {
     }
 }
 
-fn build_command_builder(command: Option<&str>) -> WshResult<CommandBuilder> {
+fn build_command_builder(command: Option<&str>, run_as: Option<&str>) -> WshResult<CommandBuilder> {
     let shell = default_shell();
     let (program, args) = command_spec(command, &shell)?;
+    let (program, args) = crate::isolation::run_as_command(run_as, program, args)?;
     let mut builder = CommandBuilder::new(program);
     for arg in args {
         builder.arg(arg);
@@ -1,13 +1,25 @@
 //! Structured session recording.
 //!
 //! Records timestamped events (input, output, resize, etc.) to a file
-//! for later replay. Format is newline-delimited JSON for simplicity.
+//! for later replay. Format is newline-delimited JSON for simplicity: one
+//! [`RecordingEntry`] per line, e.g.
+//!
+//! ```text
+//! {"timestamp_ms":0,"event":{"type":"Start","data":{"command":"bash"}}}
+//! {"timestamp_ms":12,"event":{"type":"Output","data":[36,32]}}
+//! {"timestamp_ms":900,"event":{"type":"Resize","data":{"cols":120,"rows":40}}}
+//! {"timestamp_ms":4100,"event":{"type":"Exit","data":{"code":0}}}
+//! ```
+//!
+//! [`to_asciicast`] converts a recording to asciicast v2 for `wsh play`.
 
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
 use tokio::fs::OpenOptions;
 use tokio::io::AsyncWriteExt;
-use tracing::{debug, error};
+use tracing::{debug, error, info};
+use wsh_core::cast::{CastHeader, CastWriter};
 
 /// Event types that can appear in a session recording.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -37,6 +49,10 @@ pub struct RecordingEntry {
 }
 
 /// Session recorder that writes events to a file.
+///
+/// Clones share the same file and start time, so background tasks (such as
+/// the PTY output pump) can record without holding the session lock.
+#[derive(Debug, Clone)]
 pub struct SessionRecorder {
     path: PathBuf,
     start_time: std::time::Instant,
@@ -91,11 +107,12 @@ impl SessionRecorder {
     }
 
     async fn append_line(&self, line: &str) -> std::io::Result<()> {
-        let mut file = OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(&self.path)
-            .await?;
+        let mut options = OpenOptions::new();
+        options.create(true).append(true);
+        // Recordings hold everything typed, passwords included.
+        #[cfg(unix)]
+        options.mode(0o600);
+        let mut file = options.open(&self.path).await?;
         file.write_all(line.as_bytes()).await?;
         file.flush().await?;
         Ok(())
@@ -119,3 +136,132 @@ pub async fn load_recording(path: &Path) -> std::io::Result<Vec<RecordingEntry>>
     }
     Ok(entries)
 }
+
+/// Convert a recording to asciicast v2 text.
+///
+/// The header size comes from the first resize event, falling back to
+/// `default_size` when the recording has none before the first output.
+pub fn to_asciicast(entries: &[RecordingEntry], default_size: (u16, u16)) -> String {
+    let (width, height) = entries
+        .iter()
+        .find_map(|e| match e.event {
+            RecordingEvent::Resize { cols, rows } => Some((cols, rows)),
+            _ => None,
+        })
+        .unwrap_or(default_size);
+    let mut header = CastHeader::new(width, height);
+    header.timestamp = None;
+    header.command = entries.iter().find_map(|e| match &e.event {
+        RecordingEvent::Start { command } => Some(command.clone()),
+        _ => None,
+    });
+
+    let mut out = Vec::new();
+    let mut writer = match CastWriter::new(&mut out, &header) {
+        Ok(writer) => writer,
+        Err(_) => return String::new(),
+    };
+    for entry in entries {
+        let at = Duration::from_millis(entry.timestamp_ms);
+        let result = match &entry.event {
+            RecordingEvent::Output(data) => writer.output_at(at, data),
+            RecordingEvent::Resize { cols, rows } => writer.resize_at(at, *cols, *rows),
+            _ => Ok(()),
+        };
+        if let Err(e) = result {
+            error!(error = %e, "asciicast conversion failed");
+            break;
+        }
+    }
+    drop(writer);
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Create the recordings directory, readable only by the server account.
+pub fn create_recording_dir(dir: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(dir)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o700))?;
+    }
+    Ok(())
+}
+
+/// Delete recordings in `dir` last modified more than `max_age` ago.
+///
+/// Returns the number of files removed.
+pub async fn prune_recordings(dir: &Path, max_age: Duration) -> std::io::Result<usize> {
+    let cutoff = SystemTime::now()
+        .checked_sub(max_age)
+        .unwrap_or(SystemTime::UNIX_EPOCH);
+    let mut removed = 0;
+    let mut entries = tokio::fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let metadata = match entry.metadata().await {
+            Ok(m) if m.is_file() => m,
+            _ => continue,
+        };
+        let modified = metadata.modified().unwrap_or(SystemTime::now());
+        if modified < cutoff && tokio::fs::remove_file(entry.path()).await.is_ok() {
+            removed += 1;
+        }
+    }
+    if removed > 0 {
+        info!(dir = %dir.display(), removed, "pruned expired recordings");
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn asciicast_export_uses_first_resize_and_timestamps() {
+        let entries = vec![
+            RecordingEntry {
+                timestamp_ms: 0,
+                event: RecordingEvent::Start {
+                    command: "bash".into(),
+                },
+            },
+            RecordingEntry {
+                timestamp_ms: 5,
+                event: RecordingEvent::Resize {
+                    cols: 100,
+                    rows: 30,
+                },
+            },
+            RecordingEntry {
+                timestamp_ms: 1500,
+                event: RecordingEvent::Output(b"$ ".to_vec()),
+            },
+        ];
+        let (header, events) = wsh_core::cast::parse(&to_asciicast(&entries, (80, 24))).unwrap();
+        assert_eq!((header.width, header.height), (100, 30));
+        assert_eq!(header.command.as_deref(), Some("bash"));
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[1].time(), 1.5);
+        assert_eq!(events[1].data(), "$ ");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn recordings_are_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("wsh-rec-mode-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        create_recording_dir(&dir).unwrap();
+        let recorder = SessionRecorder::new(dir.join("s.jsonl"));
+        recorder
+            .record(RecordingEvent::Input(b"hunter2\r".to_vec()))
+            .await;
+
+        let mode = |p: &Path| std::fs::metadata(p).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(&dir), 0o700);
+        assert_eq!(mode(recorder.path()), 0o600);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -61,6 +61,19 @@ impl RingBuffer {
         result
     }
 
+    /// Read the data written after stream position `offset` (a byte count
+    /// comparable to [`total_written`](Self::total_written)), as far back as
+    /// the buffer still reaches.
+    pub fn read_since(&self, offset: u64) -> Vec<u8> {
+        let mut data = self.read_all();
+        let missing = self
+            .total_written
+            .saturating_sub(offset)
+            .min(data.len() as u64);
+        data.drain(..data.len() - missing as usize);
+        data
+    }
+
     /// Number of valid bytes currently stored.
     pub fn len(&self) -> usize {
         if self.total_written >= self.capacity as u64 {
@@ -108,6 +121,19 @@ mod tests {
         assert_eq!(rb.len(), 5);
     }
 
+    #[test]
+    fn read_since_returns_unseen_tail() {
+        let mut rb = RingBuffer::new(5);
+        rb.write(b"abc");
+        assert_eq!(rb.read_since(1), b"bc");
+        assert_eq!(rb.read_since(3), b"");
+        rb.write(b"defg");
+        // Offset 0 reaches past the start of the buffer.
+        assert_eq!(rb.read_since(0), b"cdefg");
+        assert_eq!(rb.read_since(5), b"fg");
+        assert_eq!(rb.read_since(9), b"");
+    }
+
     #[test]
     fn empty_buffer() {
         let rb = RingBuffer::new(10);
@@ -0,0 +1,954 @@
+//! Resumable, chunked file transfers over the control channel.
+//!
+//! A client starts a transfer with `FILE_TRANSFER_START` and the server
+//! answers `FILE_TRANSFER_READY` with the offset it can resume from.
+//!
+//! - **Upload:** the client streams `FILE_CHUNK`s, each carrying a BLAKE3
+//!   hash. Verified chunks are appended to `<path>.wshpart`. After the final
+//!   chunk the whole-file hash is checked, the partial file is renamed into
+//!   place, and a `FILE_RESULT` reports the outcome.
+//! - **Download:** the server checks the client's partial prefix hash, then
+//!   streams hashed `FILE_CHUNK`s from the agreed offset through the
+//!   connection's `peer_tx`. The client verifies the whole-file hash itself.
+//!
+//! Chunk hashes always cover the uncompressed data; when the connection
+//! negotiated compression (see [`wsh_core::compress`]) chunks may travel
+//! compressed in either direction.
+//!
+//! Partial files survive disconnects, so a repeated transfer of the same
+//! file picks up after the last verified chunk.
+//!
+//! Both directions are flow controlled (see [`wsh_core::flow`]): the server
+//! advertises an upload window in `FILE_TRANSFER_READY` and returns credit
+//! with `WINDOW_UPDATE` as chunks reach disk; downloads stop once the
+//! client's advertised window is used up and resume on its updates.
+//!
+//! `wsh sync` adds `SYNC_MANIFEST_REQUEST` (list a directory tree with
+//! hashes) and `SYNC_DELETE` (remove files that no longer exist on the
+//! source); the changed files themselves travel as ordinary transfers.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use tokio::fs::{File, OpenOptions};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio::sync::{mpsc, watch, Mutex};
+use tracing::{debug, info, warn};
+use wsh_core::compress::{Codec, CompressionStats};
+use wsh_core::flow::{window_update, ReceiveWindow, SendWindow, DEFAULT_WINDOW};
+use wsh_core::messages::*;
+use wsh_core::transfer::{
+    build_manifest, chunk_hash, hash_file, join_relative, partial_path, resume_point,
+};
+
+use crate::isolation::Workspace;
+
+/// Smallest chunk size the server accepts.
+const MIN_CHUNK_SIZE: u32 = 4 * 1024;
+/// Largest chunk size the server accepts (keeps frames under the 1 MiB
+/// WebSocket control-frame limit once CBOR overhead is added).
+const MAX_CHUNK_SIZE: u32 = 512 * 1024;
+
+/// Identifies a transfer: `(session_id, transfer_id)`.
+type TransferKey = (String, u32);
+
+/// An upload in progress, keyed by [`TransferKey`].
+struct UploadState {
+    final_path: PathBuf,
+    partial_path: PathBuf,
+    file: File,
+    total_size: u64,
+    file_hash: String,
+    written: u64,
+    window: ReceiveWindow,
+}
+
+/// Where a connection's download chunks go.
+#[derive(Clone)]
+pub struct ChunkSink {
+    pub peer_tx: mpsc::Sender<Envelope>,
+    /// Codec negotiated by the connection, if any.
+    pub compression: Option<Codec>,
+    pub stats: Arc<CompressionStats>,
+}
+
+/// Tracks active transfers across all connections.
+#[derive(Default)]
+pub struct TransferManager {
+    /// Each upload has its own lock, so chunk I/O does not hold the map.
+    uploads: Mutex<HashMap<TransferKey, Arc<Mutex<UploadState>>>>,
+    /// Total window credit granted to running downloads.
+    download_grants: Mutex<HashMap<TransferKey, watch::Sender<u64>>>,
+}
+
+impl TransferManager {
+    /// Create an empty transfer manager.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Handle `FILE_TRANSFER_START`, returning the reply envelope.
+    ///
+    /// Downloads begin streaming chunks to `sink` after the reply is sent.
+    /// The requested path is resolved inside `workspace`.
+    pub async fn start(
+        &self,
+        owner: &str,
+        workspace: &Workspace,
+        request: FileTransferStartPayload,
+        sink: ChunkSink,
+    ) -> Envelope {
+        let transfer_id = request.transfer_id;
+        let chunk_size = request.chunk_size.clamp(MIN_CHUNK_SIZE, MAX_CHUNK_SIZE);
+        let path = match workspace.resolve(&request.path) {
+            Ok(path) => path,
+            Err(message) => {
+                warn!(transfer_id, "file transfer rejected: {message}");
+                return transfer_result(transfer_id, false, serde_json::json!({}), Some(message));
+            }
+        };
+        let result = match request.direction.as_str() {
+            "upload" => self.start_upload(owner, &request, &path, chunk_size).await,
+            "download" => {
+                let flow = match request.window {
+                    Some(window) => {
+                        let (grant_tx, grant_rx) = watch::channel(0);
+                        self.download_grants
+                            .lock()
+                            .await
+                            .insert((owner.to_string(), transfer_id), grant_tx);
+                        Some((SendWindow::new(window.max(chunk_size)), grant_rx))
+                    }
+                    None => None,
+                };
+                start_download(&request, &path, chunk_size, sink, flow).await
+            }
+            other => Err(format!("unknown transfer direction: {other}")),
+        };
+        match result {
+            Ok(ready) => Envelope {
+                msg_type: MsgType::FileTransferReady,
+                payload: Payload::FileTransferReady(ready),
+            },
+            Err(message) => {
+                warn!(transfer_id, path = %path.display(), "file transfer start failed: {message}");
+                transfer_result(transfer_id, false, serde_json::json!({}), Some(message))
+            }
+        }
+    }
+
+    async fn start_upload(
+        &self,
+        owner: &str,
+        request: &FileTransferStartPayload,
+        path: &Path,
+        chunk_size: u32,
+    ) -> Result<FileTransferReadyPayload, String> {
+        let file_hash = request
+            .file_hash
+            .clone()
+            .ok_or_else(|| "upload requires file_hash".to_string())?;
+        let partial = partial_path(path);
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .map_err(|e| format!("cannot create {}: {e}", parent.display()))?;
+        }
+
+        let existing = tokio::fs::metadata(&partial)
+            .await
+            .map(|m| m.len())
+            .unwrap_or(0);
+        let resume_offset = resume_point(existing, Some(request.total_size), chunk_size);
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&partial)
+            .await
+            .map_err(|e| format!("cannot open {}: {e}", partial.display()))?;
+        file.set_len(resume_offset)
+            .await
+            .map_err(|e| format!("cannot truncate {}: {e}", partial.display()))?;
+        file.seek(std::io::SeekFrom::Start(resume_offset))
+            .await
+            .map_err(|e| format!("cannot seek {}: {e}", partial.display()))?;
+
+        let prefix_hash = if resume_offset > 0 {
+            Some(hash_prefix(&partial, resume_offset).await?)
+        } else {
+            None
+        };
+        let window = ReceiveWindow::new(DEFAULT_WINDOW.max(chunk_size));
+
+        info!(
+            transfer_id = request.transfer_id,
+            path = %path.display(),
+            total_size = request.total_size,
+            resume_offset,
+            "upload started"
+        );
+        self.uploads.lock().await.insert(
+            (owner.to_string(), request.transfer_id),
+            Arc::new(Mutex::new(UploadState {
+                final_path: path.to_path_buf(),
+                partial_path: partial,
+                file,
+                total_size: request.total_size,
+                file_hash,
+                written: resume_offset,
+                window: window.clone(),
+            })),
+        );
+
+        Ok(FileTransferReadyPayload {
+            transfer_id: request.transfer_id,
+            total_size: request.total_size,
+            chunk_size,
+            file_hash: None,
+            resume_offset,
+            prefix_hash,
+            window: Some(window.size()),
+        })
+    }
+
+    /// Handle an uploaded `FILE_CHUNK`. Returns a `FILE_RESULT` on failure
+    /// or once the final chunk has been verified, and otherwise a
+    /// `WINDOW_UPDATE` whenever the client is due more credit.
+    ///
+    /// Compressed chunks are decoded (and counted in `stats`) first.
+    pub async fn handle_chunk(
+        &self,
+        owner: &str,
+        mut chunk: FileChunkPayload,
+        stats: &CompressionStats,
+    ) -> Option<Envelope> {
+        let key = (owner.to_string(), chunk.channel_id);
+        // Only look the upload up under the map lock; decoding, hashing and
+        // writing hold just this upload's own lock.
+        let upload = self.uploads.lock().await.get(&key).cloned()?;
+
+        let checked = match stats.decode(
+            chunk.compression.take().as_deref(),
+            std::mem::take(&mut chunk.data),
+        ) {
+            Ok(data) => {
+                chunk.data = data;
+                match &chunk.hash {
+                    Some(expected) if chunk_hash(&chunk.data) == *expected => Ok(()),
+                    Some(_) => Err(format!("chunk hash mismatch at offset {}", chunk.offset)),
+                    None => Err(format!("chunk at offset {} has no hash", chunk.offset)),
+                }
+            }
+            Err(e) => Err(format!(
+                "cannot decode chunk at offset {}: {e}",
+                chunk.offset
+            )),
+        };
+
+        let mut state = upload.lock().await;
+        let written = match checked {
+            Ok(()) if !state.window.admits(chunk.data.len()) => Err(format!(
+                "chunk at offset {} overruns the flow-control window",
+                chunk.offset
+            )),
+            Ok(()) => write_chunk(&mut state, &chunk).await,
+            Err(message) => Err(message),
+        };
+        if let Err(message) = written {
+            self.uploads.lock().await.remove(&key);
+            return Some(transfer_result(
+                chunk.channel_id,
+                false,
+                serde_json::json!({ "offset": chunk.offset }),
+                Some(message),
+            ));
+        }
+
+        if !chunk.is_final {
+            return state
+                .window
+                .consumed(chunk.data.len())
+                .map(|increment| window_update(chunk.channel_id, increment));
+        }
+
+        self.uploads.lock().await.remove(&key);
+        Some(finish_upload(chunk.channel_id, &state).await)
+    }
+
+    /// Handle `SYNC_MANIFEST_REQUEST`: list the tree under the requested path.
+    pub async fn manifest(
+        &self,
+        workspace: &Workspace,
+        request: &SyncManifestRequestPayload,
+    ) -> Envelope {
+        let transfer_id = request.transfer_id;
+        let root = match workspace.resolve(&request.path) {
+            Ok(root) => root,
+            Err(message) => {
+                return transfer_result(transfer_id, false, serde_json::json!({}), Some(message))
+            }
+        };
+        let listing = tokio::task::spawn_blocking(move || build_manifest(&root)).await;
+        match listing {
+            Ok(Ok(entries)) => {
+                debug!(transfer_id, count = entries.len(), "sync manifest built");
+                Envelope {
+                    msg_type: MsgType::SyncManifest,
+                    payload: Payload::SyncManifest(SyncManifestPayload {
+                        transfer_id,
+                        entries,
+                    }),
+                }
+            }
+            Ok(Err(e)) => transfer_result(
+                transfer_id,
+                false,
+                serde_json::json!({}),
+                Some(e.to_string()),
+            ),
+            Err(e) => transfer_result(
+                transfer_id,
+                false,
+                serde_json::json!({}),
+                Some(format!("manifest task failed: {e}")),
+            ),
+        }
+    }
+
+    /// Handle `SYNC_DELETE`: remove the listed files under the requested path,
+    /// then prune directories left empty.
+    pub async fn delete(&self, workspace: &Workspace, request: &SyncDeletePayload) -> Envelope {
+        let root = match workspace.resolve(&request.path) {
+            Ok(root) => root,
+            Err(message) => {
+                return transfer_result(
+                    request.transfer_id,
+                    false,
+                    serde_json::json!({}),
+                    Some(message),
+                )
+            }
+        };
+        let mut deleted = 0_u64;
+        let mut errors = Vec::new();
+        for rel in &request.paths {
+            let path = match join_relative(&root, rel) {
+                Ok(path) => path,
+                Err(e) => {
+                    errors.push(e.to_string());
+                    continue;
+                }
+            };
+            if !workspace.contains(&path) {
+                errors.push(format!("{rel}: escapes the workspace"));
+                continue;
+            }
+            match tokio::fs::remove_file(&path).await {
+                Ok(()) => {
+                    deleted += 1;
+                    prune_empty_dirs(&root, &path).await;
+                }
+                Err(e) => errors.push(format!("{rel}: {e}")),
+            }
+        }
+        info!(
+            transfer_id = request.transfer_id,
+            deleted,
+            failed = errors.len(),
+            "sync delete"
+        );
+        transfer_result(
+            request.transfer_id,
+            errors.is_empty(),
+            serde_json::json!({ "deleted": deleted }),
+            (!errors.is_empty()).then(|| errors.join("; ")),
+        )
+    }
+
+    /// Handle `WINDOW_UPDATE` for one of this connection's downloads.
+    pub async fn grant(&self, owner: &str, update: &WindowUpdatePayload) {
+        let key = (owner.to_string(), update.channel_id);
+        let mut grants = self.download_grants.lock().await;
+        let Some(grant_tx) = grants.get(&key) else {
+            return;
+        };
+        // A closed channel means the download already finished.
+        if grant_tx.is_closed() {
+            grants.remove(&key);
+            return;
+        }
+        // Credit adds up in place, so granting never waits on the download.
+        grant_tx.send_modify(|total| *total += u64::from(update.increment));
+    }
+
+    /// Uploads and flow-controlled downloads `owner` has in progress.
+    pub async fn active_for(&self, owner: &str) -> usize {
+        let uploads = self
+            .uploads
+            .lock()
+            .await
+            .keys()
+            .filter(|(o, _)| o == owner)
+            .count();
+        let downloads = self
+            .download_grants
+            .lock()
+            .await
+            .iter()
+            .filter(|((o, _), grant_tx)| o == owner && !grant_tx.is_closed())
+            .count();
+        uploads + downloads
+    }
+
+    /// Forget all transfers owned by a closed connection. Partial files are
+    /// left on disk so the transfer can be resumed.
+    pub async fn release_owner(&self, owner: &str) {
+        self.uploads.lock().await.retain(|(o, _), _| o != owner);
+        self.download_grants
+            .lock()
+            .await
+            .retain(|(o, _), _| o != owner);
+    }
+}
+
+/// Write a verified chunk at its offset, rewinding if the client restarted.
+async fn write_chunk(state: &mut UploadState, chunk: &FileChunkPayload) -> Result<(), String> {
+    if chunk.offset > state.written {
+        return Err(format!(
+            "out-of-order chunk at offset {} (expected {})",
+            chunk.offset, state.written
+        ));
+    }
+    if chunk.offset < state.written {
+        state
+            .file
+            .set_len(chunk.offset)
+            .await
+            .map_err(|e| format!("cannot rewind partial file: {e}"))?;
+        state
+            .file
+            .seek(std::io::SeekFrom::Start(chunk.offset))
+            .await
+            .map_err(|e| format!("cannot rewind partial file: {e}"))?;
+        state.written = chunk.offset;
+    }
+    if state.written + chunk.data.len() as u64 > state.total_size {
+        return Err("upload exceeds declared size".to_string());
+    }
+    state
+        .file
+        .write_all(&chunk.data)
+        .await
+        .map_err(|e| format!("write failed: {e}"))?;
+    // Flush per chunk so the partial file only ever holds verified data.
+    state
+        .file
+        .flush()
+        .await
+        .map_err(|e| format!("write failed: {e}"))?;
+    state.written += chunk.data.len() as u64;
+    Ok(())
+}
+
+async fn finish_upload(transfer_id: u32, state: &UploadState) -> Envelope {
+    if let Err(e) = state.file.sync_all().await {
+        return transfer_result(
+            transfer_id,
+            false,
+            serde_json::json!({}),
+            Some(format!("sync failed: {e}")),
+        );
+    }
+    if state.written != state.total_size {
+        return transfer_result(
+            transfer_id,
+            false,
+            serde_json::json!({ "bytes": state.written }),
+            Some(format!(
+                "upload ended at {} of {} bytes",
+                state.written, state.total_size
+            )),
+        );
+    }
+
+    let actual = match hash_prefix(&state.partial_path, state.total_size).await {
+        Ok(hash) => hash,
+        Err(message) => {
+            return transfer_result(transfer_id, false, serde_json::json!({}), Some(message))
+        }
+    };
+    if actual != state.file_hash {
+        // The partial file is internally consistent but not the file the
+        // client described; resuming from it would only repeat the failure.
+        let _ = tokio::fs::remove_file(&state.partial_path).await;
+        return transfer_result(
+            transfer_id,
+            false,
+            serde_json::json!({ "hash": actual }),
+            Some("file hash mismatch".to_string()),
+        );
+    }
+
+    if let Err(e) = tokio::fs::rename(&state.partial_path, &state.final_path).await {
+        return transfer_result(
+            transfer_id,
+            false,
+            serde_json::json!({}),
+            Some(format!("cannot move into place: {e}")),
+        );
+    }
+    info!(
+        transfer_id,
+        path = %state.final_path.display(),
+        bytes = state.total_size,
+        "upload complete"
+    );
+    transfer_result(
+        transfer_id,
+        true,
+        serde_json::json!({ "bytes": state.total_size, "hash": actual }),
+        None,
+    )
+}
+
+async fn start_download(
+    request: &FileTransferStartPayload,
+    path: &Path,
+    chunk_size: u32,
+    sink: ChunkSink,
+    flow: Option<(SendWindow, watch::Receiver<u64>)>,
+) -> Result<FileTransferReadyPayload, String> {
+    let metadata = tokio::fs::metadata(path)
+        .await
+        .map_err(|e| format!("cannot stat {}: {e}", path.display()))?;
+    if !metadata.is_file() {
+        return Err(format!("{} is not a regular file", path.display()));
+    }
+    let total_size = metadata.len();
+    let file_hash = hash_prefix(path, total_size).await?;
+
+    let mut resume_offset = 0;
+    if request.resume_offset > 0 && request.resume_offset <= total_size {
+        if let Some(client_prefix) = &request.prefix_hash {
+            if hash_prefix(path, request.resume_offset).await? == *client_prefix {
+                resume_offset = request.resume_offset;
+            }
+        }
+    }
+
+    let file = File::open(path)
+        .await
+        .map_err(|e| format!("cannot open {}: {e}", path.display()))?;
+    info!(
+        transfer_id = request.transfer_id,
+        path = %path.display(),
+        total_size,
+        resume_offset,
+        "download started"
+    );
+    tokio::spawn(stream_download(
+        request.transfer_id,
+        file,
+        resume_offset,
+        total_size,
+        chunk_size,
+        sink,
+        flow,
+    ));
+
+    Ok(FileTransferReadyPayload {
+        transfer_id: request.transfer_id,
+        total_size,
+        chunk_size,
+        file_hash: Some(file_hash),
+        resume_offset,
+        prefix_hash: None,
+        window: None,
+    })
+}
+
+/// Stream hashed chunks to the client, waiting for window credit when the
+/// client asked for flow control. Stops quietly if the connection goes away.
+async fn stream_download(
+    transfer_id: u32,
+    mut file: File,
+    mut offset: u64,
+    total_size: u64,
+    chunk_size: u32,
+    sink: ChunkSink,
+    mut flow: Option<(SendWindow, watch::Receiver<u64>)>,
+) {
+    let peer_tx = &sink.peer_tx;
+    if let Err(e) = file.seek(std::io::SeekFrom::Start(offset)).await {
+        let _ = peer_tx
+            .send(transfer_result(
+                transfer_id,
+                false,
+                serde_json::json!({}),
+                Some(format!("seek failed: {e}")),
+            ))
+            .await;
+        return;
+    }
+
+    let mut buf = vec![0_u8; chunk_size as usize];
+    let mut granted = 0_u64;
+    loop {
+        let want = (total_size - offset).min(u64::from(chunk_size)) as usize;
+        if let Err(e) = file.read_exact(&mut buf[..want]).await {
+            let _ = peer_tx
+                .send(transfer_result(
+                    transfer_id,
+                    false,
+                    serde_json::json!({ "offset": offset }),
+                    Some(format!("read failed: {e}")),
+                ))
+                .await;
+            return;
+        }
+        if let Some((window, grants)) = flow.as_mut() {
+            while !window.can_send(want) {
+                if grants.changed().await.is_err() {
+                    debug!(transfer_id, offset, "download abandoned awaiting window");
+                    return;
+                }
+                let total = *grants.borrow_and_update();
+                window.grant(u32::try_from(total - granted).unwrap_or(u32::MAX));
+                granted = total;
+            }
+            window.consume(want);
+        }
+        let hash = chunk_hash(&buf[..want]);
+        let (data, compression) = sink.stats.encode(sink.compression, buf[..want].to_vec());
+        let is_final = offset + want as u64 >= total_size;
+        let chunk = Envelope {
+            msg_type: MsgType::FileChunk,
+            payload: Payload::FileChunk(FileChunkPayload {
+                channel_id: transfer_id,
+                offset,
+                hash: Some(hash),
+                data,
+                is_final,
+                compression,
+            }),
+        };
+        if peer_tx.send(chunk).await.is_err() {
+            debug!(transfer_id, offset, "download receiver gone");
+            return;
+        }
+        offset += want as u64;
+        if is_final {
+            break;
+        }
+    }
+    debug!(transfer_id, total_size, "download stream finished");
+}
+
+/// BLAKE3 of the first `len` bytes of a file, computed off the async runtime.
+async fn hash_prefix(path: &Path, len: u64) -> Result<String, String> {
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || hash_file(&path, Some(len)))
+        .await
+        .map_err(|e| format!("hash task failed: {e}"))?
+        .map_err(|e| format!("hash failed: {e}"))
+}
+
+/// Remove now-empty parent directories of `path`, stopping at `root`.
+async fn prune_empty_dirs(root: &Path, path: &Path) {
+    let mut dir = path.parent();
+    while let Some(current) = dir {
+        if current == root || !current.starts_with(root) {
+            break;
+        }
+        // remove_dir fails on non-empty directories, which ends the walk.
+        if tokio::fs::remove_dir(current).await.is_err() {
+            break;
+        }
+        dir = current.parent();
+    }
+}
+
+fn transfer_result(
+    transfer_id: u32,
+    success: bool,
+    metadata: serde_json::Value,
+    error_message: Option<String>,
+) -> Envelope {
+    Envelope {
+        msg_type: MsgType::FileResult,
+        payload: Payload::FileResult(FileResultPayload {
+            channel_id: transfer_id,
+            success,
+            metadata,
+            error_message,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("wsh-transfer-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn start_payload(path: &Path, data: &[u8]) -> FileTransferStartPayload {
+        FileTransferStartPayload {
+            transfer_id: 7,
+            direction: "upload".into(),
+            path: path.to_string_lossy().into_owned(),
+            total_size: data.len() as u64,
+            chunk_size: MIN_CHUNK_SIZE,
+            file_hash: Some(chunk_hash(data)),
+            resume_offset: 0,
+            prefix_hash: None,
+            window: None,
+        }
+    }
+
+    fn sink(peer_tx: mpsc::Sender<Envelope>) -> ChunkSink {
+        ChunkSink {
+            peer_tx,
+            compression: None,
+            stats: Default::default(),
+        }
+    }
+
+    fn chunk(offset: usize, data: &[u8], is_final: bool) -> FileChunkPayload {
+        FileChunkPayload {
+            channel_id: 7,
+            offset: offset as u64,
+            data: data.to_vec(),
+            is_final,
+            hash: Some(chunk_hash(data)),
+            compression: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn upload_resumes_from_last_whole_chunk() {
+        let dir = temp_dir("resume");
+        let dest = dir.join("out.bin");
+        let data: Vec<u8> = (0..(MIN_CHUNK_SIZE as usize * 3 + 100))
+            .map(|i| (i % 251) as u8)
+            .collect();
+        let size = MIN_CHUNK_SIZE as usize;
+        let (tx, _rx) = mpsc::channel(4);
+
+        // First attempt: one whole chunk plus a torn write, then disconnect.
+        let manager = TransferManager::new();
+        let stats = CompressionStats::default();
+        manager
+            .start(
+                "s",
+                &Workspace::unconfined(),
+                start_payload(&dest, &data),
+                sink(tx.clone()),
+            )
+            .await;
+        assert!(manager
+            .handle_chunk("s", chunk(0, &data[..size], false), &stats)
+            .await
+            .is_none());
+        manager.release_owner("s").await;
+        let mut partial = std::fs::OpenOptions::new()
+            .append(true)
+            .open(partial_path(&dest))
+            .unwrap();
+        std::io::Write::write_all(&mut partial, &data[size..size + 10]).unwrap();
+
+        // Second attempt resumes at the chunk boundary.
+        let reply = manager
+            .start(
+                "s",
+                &Workspace::unconfined(),
+                start_payload(&dest, &data),
+                sink(tx),
+            )
+            .await;
+        let Payload::FileTransferReady(ready) = reply.payload else {
+            panic!("expected ready");
+        };
+        assert_eq!(ready.resume_offset, size as u64);
+        assert_eq!(
+            ready.prefix_hash.as_deref(),
+            Some(chunk_hash(&data[..size]).as_str())
+        );
+
+        let mut offset = size;
+        let mut result = None;
+        while offset < data.len() {
+            let end = (offset + size).min(data.len());
+            result = manager
+                .handle_chunk(
+                    "s",
+                    chunk(offset, &data[offset..end], end == data.len()),
+                    &stats,
+                )
+                .await;
+            offset = end;
+        }
+        let Some(Envelope {
+            payload: Payload::FileResult(result),
+            ..
+        }) = result
+        else {
+            panic!("expected file result");
+        };
+        assert!(result.success, "{:?}", result.error_message);
+        assert_eq!(std::fs::read(&dest).unwrap(), data);
+        assert!(!partial_path(&dest).exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn corrupted_chunk_is_rejected() {
+        let dir = temp_dir("corrupt");
+        let dest = dir.join("out.bin");
+        let data = vec![1_u8; 100];
+        let (tx, _rx) = mpsc::channel(4);
+        let manager = TransferManager::new();
+        let stats = CompressionStats::default();
+        manager
+            .start(
+                "s",
+                &Workspace::unconfined(),
+                start_payload(&dest, &data),
+                sink(tx),
+            )
+            .await;
+
+        let mut bad = chunk(0, &data, true);
+        bad.data[0] = 2;
+        let reply = manager.handle_chunk("s", bad, &stats).await.unwrap();
+        let Payload::FileResult(result) = reply.payload else {
+            panic!("expected file result");
+        };
+        assert!(!result.success);
+        assert!(!dest.exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn unhashed_chunk_is_rejected() {
+        let dir = temp_dir("unhashed");
+        let dest = dir.join("out.bin");
+        let data = vec![1_u8; 100];
+        let (tx, _rx) = mpsc::channel(4);
+        let manager = TransferManager::new();
+        let stats = CompressionStats::default();
+        manager
+            .start(
+                "s",
+                &Workspace::unconfined(),
+                start_payload(&dest, &data),
+                sink(tx),
+            )
+            .await;
+
+        let mut unhashed = chunk(0, &data, true);
+        unhashed.hash = None;
+        let reply = manager.handle_chunk("s", unhashed, &stats).await.unwrap();
+        let Payload::FileResult(result) = reply.payload else {
+            panic!("expected file result");
+        };
+        assert!(!result.success);
+        assert!(!dest.exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn upload_past_the_window_is_rejected() {
+        let dir = temp_dir("window");
+        let dest = dir.join("out.bin");
+        let data = vec![7_u8; DEFAULT_WINDOW as usize + 1];
+        let (tx, _rx) = mpsc::channel(4);
+        let manager = TransferManager::new();
+        let stats = CompressionStats::default();
+        manager
+            .start(
+                "s",
+                &Workspace::unconfined(),
+                start_payload(&dest, &data),
+                sink(tx),
+            )
+            .await;
+
+        let reply = manager
+            .handle_chunk("s", chunk(0, &data, true), &stats)
+            .await
+            .unwrap();
+        let Payload::FileResult(result) = reply.payload else {
+            panic!("expected file result");
+        };
+        assert!(!result.success);
+        assert_eq!(manager.active_for("s").await, 0);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn compressed_chunk_is_decoded() {
+        let dir = temp_dir("compressed");
+        let dest = dir.join("out.bin");
+        let data = b"hello wsh ".repeat(50);
+        let (tx, _rx) = mpsc::channel(4);
+        let manager = TransferManager::new();
+        let stats = CompressionStats::default();
+        manager
+            .start(
+                "s",
+                &Workspace::unconfined(),
+                start_payload(&dest, &data),
+                sink(tx),
+            )
+            .await;
+
+        let mut compressed = chunk(0, &data, true);
+        compressed.data = Codec::Deflate.compress(&data).unwrap();
+        compressed.compression = Some("deflate".into());
+        let reply = manager.handle_chunk("s", compressed, &stats).await.unwrap();
+        let Payload::FileResult(result) = reply.payload else {
+            panic!("expected file result");
+        };
+        assert!(result.success, "{:?}", result.error_message);
+        assert_eq!(std::fs::read(&dest).unwrap(), data);
+        assert_eq!(stats.raw_bytes(), data.len() as u64);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn sync_delete_does_not_follow_symlinks_out() {
+        let dir = temp_dir("delete");
+        let outside = dir.join("outside");
+        std::fs::create_dir_all(&outside).unwrap();
+        std::fs::write(outside.join("victim"), b"keep").unwrap();
+        let isolation =
+            crate::isolation::Isolation::new(dir.join("home"), &Default::default()).unwrap();
+        let workspace = isolation.workspace("alice", "").unwrap();
+        std::os::unix::fs::symlink(&outside, workspace.home().join("link")).unwrap();
+        std::fs::write(workspace.home().join("mine"), b"drop").unwrap();
+
+        let request = SyncDeletePayload {
+            transfer_id: 3,
+            path: "~".into(),
+            paths: vec!["link/victim".into(), "mine".into()],
+        };
+        let reply = TransferManager::new().delete(&workspace, &request).await;
+        let Payload::FileResult(result) = reply.payload else {
+            panic!("expected file result");
+        };
+        assert!(!result.success);
+        assert_eq!(result.metadata["deleted"], 1);
+        assert!(outside.join("victim").exists());
+        assert!(!workspace.home().join("mine").exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}